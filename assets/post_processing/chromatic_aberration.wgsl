@@ -0,0 +1,25 @@
+// Pulls red and blue apart, more towards the edges of the screen.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+struct ChromaticAberration {
+    intensity: f32,
+    max_offset: f32,
+    decay: f32,
+    rest: f32,
+}
+@group(0) @binding(2) var<uniform> settings: ChromaticAberration;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let offset = (in.uv - 0.5) * 2.0 * settings.intensity * settings.max_offset;
+
+    return vec4<f32>(
+        textureSample(screen_texture, texture_sampler, in.uv + offset).r,
+        textureSample(screen_texture, texture_sampler, in.uv).g,
+        textureSample(screen_texture, texture_sampler, in.uv - offset).b,
+        1.0
+    );
+}
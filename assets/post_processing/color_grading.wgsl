@@ -0,0 +1,33 @@
+// Exposure, contrast and saturation, then a lookup in a 3D colour table.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+struct ColorGrading {
+    strength: f32,
+    exposure: f32,
+    contrast: f32,
+    saturation: f32,
+}
+@group(0) @binding(2) var<uniform> settings: ColorGrading;
+@group(0) @binding(3) var lut_texture: texture_3d<f32>;
+@group(0) @binding(4) var lut_sampler: sampler;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    var color = textureSample(screen_texture, texture_sampler, in.uv).rgb * exp2(settings.exposure);
+    // around middle grey
+    color = max((color - 0.18) * settings.contrast + 0.18, vec3(0.0));
+    let luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+    color = clamp(mix(vec3(luminance), color, settings.saturation), vec3(0.0), vec3(1.0));
+
+    // the table is indexed by gamma encoded colour, and the lookup lands on the middle of its
+    // first and last entries
+    let size = f32(textureDimensions(lut_texture).x);
+    let encoded = pow(color, vec3(1.0 / 2.2));
+    let lut_uv = encoded * (size - 1.0) / size + 0.5 / size;
+    let graded = textureSample(lut_texture, lut_sampler, lut_uv).rgb;
+
+    return vec4<f32>(mix(color, graded, settings.strength), 1.0);
+}
@@ -0,0 +1,24 @@
+// Drains the colour out as the hermits run out of hearts, darkening a little on each heartbeat.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+struct LowHealthDesaturation {
+    amount: f32,
+    max_saturation_loss: f32,
+    darken: f32,
+    pulse: f32,
+}
+@group(0) @binding(2) var<uniform> settings: LowHealthDesaturation;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(screen_texture, texture_sampler, in.uv).rgb;
+    let luminance = dot(color, vec3(0.2126, 0.7152, 0.0722));
+
+    let grey = mix(color, vec3(luminance), settings.amount * settings.max_saturation_loss);
+    let shade = 1.0 - settings.amount * settings.darken * settings.pulse;
+
+    return vec4<f32>(grey * shade, 1.0);
+}
@@ -0,0 +1,25 @@
+// Darkens the corners.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+struct Vignette {
+    intensity: f32,
+    radius: f32,
+    softness: f32,
+    roundness: f32,
+}
+@group(0) @binding(2) var<uniform> settings: Vignette;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(screen_texture, texture_sampler, in.uv).rgb;
+
+    let size = vec2<f32>(textureDimensions(screen_texture));
+    let stretch = vec2(mix(1.0, size.x / size.y, settings.roundness), 1.0);
+    let from_center = length((in.uv - 0.5) * 2.0 * stretch);
+    let shade = smoothstep(settings.radius, settings.radius + settings.softness, from_center);
+
+    return vec4<f32>(color * (1.0 - shade * settings.intensity), 1.0);
+}
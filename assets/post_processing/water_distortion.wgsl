@@ -0,0 +1,29 @@
+// Wobbles the picture like it's seen through moving water, leaving the middle of the screen sharp.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader FullscreenVertexOutput
+
+@group(0) @binding(0) var screen_texture: texture_2d<f32>;
+@group(0) @binding(1) var texture_sampler: sampler;
+struct WaterDistortion {
+    time: f32,
+    strength: f32,
+    frequency: f32,
+    clear_radius: f32,
+}
+@group(0) @binding(2) var<uniform> settings: WaterDistortion;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    // https://www.shadertoy.com/view/4tG3WR
+    let x = in.uv.x * settings.frequency + settings.time;
+    let y = in.uv.y * settings.frequency + settings.time;
+    let water_uv = vec2(
+        in.uv.x + cos(x - y) * settings.strength * sin(y),
+        in.uv.y + cos(x + y) * settings.strength * cos(y)
+    );
+
+    let distance_from_center = clamp(length(2.0 * in.uv - 1.0) - settings.clear_radius, 0.0, 1.0);
+    let uv = mix(in.uv, water_uv, distance_from_center);
+
+    return vec4<f32>(textureSample(screen_texture, texture_sampler, uv).rgb, 1.0);
+}
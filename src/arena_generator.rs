@@ -0,0 +1,480 @@
+use std::collections::VecDeque;
+
+use bevy::{ecs::system::SystemState, log, prelude::*};
+use rand::Rng;
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::game::GameRng;
+use crate::game_state::GameState;
+use crate::item_mesh_generator::{FaceCell, QuadBuilder};
+use crate::level_loader::{
+    get_collider_from_mesh, terrain_collision_groups, wall_collision_groups,
+};
+use crate::player::combat::PlayerCombatState;
+use crate::player::PlayerControllerState;
+use crate::wave_manager::ARENA_DIMENSIONS_METERS;
+
+const OBSTACLE_HEIGHT: f32 = 1.5;
+const PIT_DEPTH: f32 = 3.0;
+const WALL_HEIGHT: f32 = 2.0;
+// players start around the origin, keep it clear
+const START_ZONE_RADIUS: f32 = 4.0;
+const MAX_GENERATION_ATTEMPTS: usize = 20;
+// how much of the floor has to be reachable from the start for a layout to be kept
+const MIN_REACHABLE_RATIO: f32 = 0.8;
+const FALL_RESCUE_HEIGHT: f32 = -5.0;
+
+const FLOOR_COLOR: Color = Color::rgba_linear(0.86, 0.78, 0.55, 1.0);
+const OBSTACLE_COLOR: Color = Color::rgba_linear(0.45, 0.42, 0.4, 1.0);
+const PIT_COLOR: Color = Color::rgba_linear(0.35, 0.3, 0.22, 1.0);
+const WALL_COLOR: Color = Color::rgba_linear(0.55, 0.5, 0.45, 1.0);
+
+pub struct ArenaGeneratorPlugin;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArenaCell {
+    Floor,
+    Pit,
+    Obstacle,
+}
+
+#[derive(Resource, Clone, Debug)]
+pub struct ArenaSettings {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    pub obstacle_count: usize,
+    pub pit_count: usize,
+}
+
+impl Default for ArenaSettings {
+    fn default() -> Self {
+        let cell_size = 1.5;
+        // the arena's dimensions are from the middle out to the walls
+        Self {
+            columns: (2.0 * ARENA_DIMENSIONS_METERS[0] / cell_size) as usize,
+            rows: (2.0 * ARENA_DIMENSIONS_METERS[1] / cell_size) as usize,
+            cell_size,
+            obstacle_count: 8,
+            pit_count: 4,
+        }
+    }
+}
+
+// A grid of cells centered on the origin, floor at y = 0.
+// Only there when the arena was generated, the glTF map has no layout.
+#[derive(Resource, Clone, Debug)]
+pub struct ArenaLayout {
+    pub columns: usize,
+    pub rows: usize,
+    pub cell_size: f32,
+    cells: Vec<ArenaCell>,
+    reachable: Vec<bool>,
+}
+
+// everything spawned for a generated arena, so it can be swapped for another one
+#[derive(Component)]
+pub struct ArenaGeometry;
+
+impl ArenaLayout {
+    pub fn cell(&self, column: usize, row: usize) -> ArenaCell {
+        self.cells[row * self.columns + column]
+    }
+
+    fn neighbour(&self, column: usize, row: usize, offset: IVec2) -> Option<(usize, usize)> {
+        let column = column as i32 + offset.x;
+        let row = row as i32 + offset.y;
+        (column >= 0 && row >= 0 && (column as usize) < self.columns && (row as usize) < self.rows)
+            .then_some((column as usize, row as usize))
+    }
+
+    pub fn cell_center(&self, column: usize, row: usize) -> Vec3 {
+        Vec3::new(
+            (column as f32 + 0.5 - self.columns as f32 / 2.0) * self.cell_size,
+            0.0,
+            (row as f32 + 0.5 - self.rows as f32 / 2.0) * self.cell_size,
+        )
+    }
+
+    pub fn cell_at(&self, position: Vec3) -> Option<(usize, usize)> {
+        let column = (position.x / self.cell_size + self.columns as f32 / 2.0).floor();
+        let row = (position.z / self.cell_size + self.rows as f32 / 2.0).floor();
+        (column >= 0.0
+            && row >= 0.0
+            && (column as usize) < self.columns
+            && (row as usize) < self.rows)
+            .then_some((column as usize, row as usize))
+    }
+
+    // on the floor and connected to where the players start
+    pub fn is_reachable(&self, position: Vec3) -> bool {
+        self.cell_at(position)
+            .is_some_and(|(column, row)| self.reachable[row * self.columns + column])
+    }
+
+    // A random spot on reachable floor, at least `min_distance` away from everything in `avoid`.
+    // The height is the floor's, callers lift it to whatever they spawn.
+    pub fn spawn_point(
+        &self,
+        rng: &mut impl Rng,
+        avoid: &[Vec3],
+        min_distance: f32,
+    ) -> Option<Vec3> {
+        let candidates: Vec<Vec3> = (0..self.rows)
+            .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+            .filter(|(column, row)| self.reachable[row * self.columns + column])
+            .map(|(column, row)| self.cell_center(column, row))
+            .filter(|center| {
+                avoid.iter().all(|other| {
+                    Vec2::new(other.x - center.x, other.z - center.z).length() >= min_distance
+                })
+            })
+            .collect();
+
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let jitter = self.cell_size * 0.25;
+        let center = candidates[rng.gen_range(0..candidates.len())];
+        Some(
+            center
+                + Vec3::new(
+                    rng.gen_range(-jitter..jitter),
+                    0.0,
+                    rng.gen_range(-jitter..jitter),
+                ),
+        )
+    }
+
+    fn is_in_start_zone(&self, column: usize, row: usize) -> bool {
+        self.cell_center(column, row).length() < START_ZONE_RADIUS
+    }
+
+    // fills a random rectangle of 1 to 3 cells a side, away from the border and the start zone
+    fn place_rectangle(&mut self, rng: &mut impl Rng, cell: ArenaCell) {
+        let width = rng.gen_range(1..=3);
+        let depth = rng.gen_range(1..=3);
+        if self.columns < width + 2 || self.rows < depth + 2 {
+            return;
+        }
+
+        let column = rng.gen_range(1..=self.columns - width - 1);
+        let row = rng.gen_range(1..=self.rows - depth - 1);
+        let cells: Vec<(usize, usize)> = (row..row + depth)
+            .flat_map(|row| (column..column + width).map(move |column| (column, row)))
+            .collect();
+
+        if cells
+            .iter()
+            .any(|(column, row)| self.is_in_start_zone(*column, *row))
+        {
+            return;
+        }
+        for (column, row) in cells {
+            self.cells[row * self.columns + column] = cell;
+        }
+    }
+
+    fn flood_reachable(&mut self) -> usize {
+        self.reachable = vec![false; self.cells.len()];
+        let Some(start) = self.cell_at(Vec3::ZERO) else {
+            return 0;
+        };
+
+        let mut count = 0;
+        let mut queue = VecDeque::from([start]);
+        while let Some((column, row)) = queue.pop_front() {
+            let index = row * self.columns + column;
+            if self.reachable[index] || self.cells[index] != ArenaCell::Floor {
+                continue;
+            }
+            self.reachable[index] = true;
+            count += 1;
+
+            for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                if let Some(next) = self.neighbour(column, row, offset) {
+                    queue.push_back(next);
+                }
+            }
+        }
+        count
+    }
+}
+
+pub fn generate_arena(settings: &ArenaSettings, rng: &mut impl Rng) -> ArenaLayout {
+    let mut layout = ArenaLayout {
+        columns: settings.columns,
+        rows: settings.rows,
+        cell_size: settings.cell_size,
+        cells: Vec::new(),
+        reachable: Vec::new(),
+    };
+
+    for attempt in 1..=MAX_GENERATION_ATTEMPTS {
+        layout.cells = vec![ArenaCell::Floor; settings.columns * settings.rows];
+        for _ in 0..settings.obstacle_count {
+            layout.place_rectangle(rng, ArenaCell::Obstacle);
+        }
+        for _ in 0..settings.pit_count {
+            layout.place_rectangle(rng, ArenaCell::Pit);
+        }
+
+        let floor = layout
+            .cells
+            .iter()
+            .filter(|cell| **cell == ArenaCell::Floor)
+            .count();
+        let reachable = layout.flood_reachable();
+        if reachable as f32 >= floor as f32 * MIN_REACHABLE_RATIO {
+            log::info!(
+                "Generated arena in {attempt} attempt(s), {reachable}/{floor} cells reachable"
+            );
+            return layout;
+        }
+    }
+
+    // nothing decent came out, an empty arena is still an arena
+    log::warn!("Could not generate a well connected arena, falling back to a flat one");
+    layout.cells = vec![ArenaCell::Floor; settings.columns * settings.rows];
+    layout.flood_reachable();
+    layout
+}
+
+impl QuadBuilder {
+    // corners go around the quad, the winding is fixed up to face `normal`
+    fn facing_quad(&mut self, corners: [Vec3; 4], normal: Vec3, color: Color) {
+        let facing = (corners[1] - corners[0])
+            .cross(corners[2] - corners[0])
+            .dot(normal);
+        let [c0, c1, c2, c3] = corners;
+        let (corners, uvs) = if facing >= 0.0 {
+            (corners, [[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [0.0, 1.0]])
+        } else {
+            (
+                [c0, c3, c2, c1],
+                [[0.0, 0.0], [0.0, 1.0], [1.0, 1.0], [1.0, 0.0]],
+            )
+        };
+        self.quad(corners, uvs, normal, FaceCell::lit(color));
+    }
+
+    // horizontal quad over a cell at `height`
+    fn top(&mut self, center: Vec3, half: f32, height: f32, color: Color) {
+        let y = Vec3::Y * height;
+        self.facing_quad(
+            [
+                center + y + Vec3::new(-half, 0.0, -half),
+                center + y + Vec3::new(-half, 0.0, half),
+                center + y + Vec3::new(half, 0.0, half),
+                center + y + Vec3::new(half, 0.0, -half),
+            ],
+            Vec3::Y,
+            color,
+        );
+    }
+
+    // vertical quad centered on `edge`, `half_length` to each side, from `bottom` to `top`
+    fn side(
+        &mut self,
+        edge: Vec3,
+        half_length: f32,
+        normal: Vec3,
+        bottom: f32,
+        top: f32,
+        color: Color,
+    ) {
+        let along = Vec3::new(normal.z, 0.0, -normal.x) * half_length;
+        self.facing_quad(
+            [
+                edge - along + Vec3::Y * bottom,
+                edge + along + Vec3::Y * bottom,
+                edge + along + Vec3::Y * top,
+                edge - along + Vec3::Y * top,
+            ],
+            normal,
+            color,
+        );
+    }
+}
+
+impl ArenaLayout {
+    // floor, obstacle blocks and the sides of the pits
+    pub fn terrain_mesh(&self) -> Mesh {
+        let mut builder = QuadBuilder::default();
+        let half = self.cell_size / 2.0;
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let center = self.cell_center(column, row);
+                let cell = self.cell(column, row);
+
+                match cell {
+                    ArenaCell::Floor => builder.top(center, half, 0.0, FLOOR_COLOR),
+                    ArenaCell::Obstacle => {
+                        builder.top(center, half, OBSTACLE_HEIGHT, OBSTACLE_COLOR)
+                    }
+                    ArenaCell::Pit => {}
+                }
+
+                for offset in [IVec2::X, IVec2::NEG_X, IVec2::Y, IVec2::NEG_Y] {
+                    let direction = Vec3::new(offset.x as f32, 0.0, offset.y as f32);
+                    let edge = center + direction * half;
+                    let neighbour = self
+                        .neighbour(column, row, offset)
+                        .map(|(column, row)| self.cell(column, row));
+
+                    match (cell, neighbour) {
+                        (ArenaCell::Obstacle, Some(ArenaCell::Obstacle)) => {}
+                        (ArenaCell::Obstacle, _) => builder.side(
+                            edge,
+                            half,
+                            direction,
+                            0.0,
+                            OBSTACLE_HEIGHT,
+                            OBSTACLE_COLOR,
+                        ),
+                        // the wall of a pit faces into the pit
+                        (ArenaCell::Pit, Some(ArenaCell::Floor | ArenaCell::Obstacle)) => {
+                            builder.side(edge, half, -direction, -PIT_DEPTH, 0.0, PIT_COLOR)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        builder.build()
+    }
+
+    // a rim around the whole arena, facing inwards
+    pub fn wall_mesh(&self) -> Mesh {
+        let mut builder = QuadBuilder::default();
+        let half_width = self.columns as f32 * self.cell_size / 2.0;
+        let half_depth = self.rows as f32 * self.cell_size / 2.0;
+
+        for (normal, half_length, distance) in [
+            (Vec3::X, half_depth, half_width),
+            (Vec3::NEG_X, half_depth, half_width),
+            (Vec3::Z, half_width, half_depth),
+            (Vec3::NEG_Z, half_width, half_depth),
+        ] {
+            builder.side(
+                -normal * distance,
+                half_length,
+                normal,
+                0.0,
+                WALL_HEIGHT,
+                WALL_COLOR,
+            );
+        }
+
+        builder.build()
+    }
+}
+
+// Spawns the meshes and colliders of a layout. Colliders use the same groups as the
+// terrain and walls of the glTF levels so players, enemies and projectiles behave the same.
+pub fn spawn_arena(
+    layout: &ArenaLayout,
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) {
+    let material = materials.add(StandardMaterial {
+        base_color: Color::WHITE,
+        perceptual_roughness: 0.9,
+        ..default()
+    });
+
+    for (mesh, collision_groups) in [
+        (layout.terrain_mesh(), terrain_collision_groups()),
+        (layout.wall_mesh(), wall_collision_groups()),
+    ] {
+        let collider = match get_collider_from_mesh(&mesh, &Transform::IDENTITY) {
+            Ok(collider) => collider,
+            Err(err) => {
+                log::error!("Could not build a collider for the arena: {err:?}");
+                continue;
+            }
+        };
+
+        commands
+            .spawn(PbrBundle {
+                mesh: meshes.add(mesh),
+                material: material.clone(),
+                ..default()
+            })
+            .insert(collider)
+            .insert(collision_groups)
+            .insert(ArenaGeometry);
+    }
+}
+
+impl Plugin for ArenaGeneratorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            rescue_fallen_players.run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("arena", arena_command)
+                .usage("[seed]")
+                .help("replace the generated arena with a new one"),
+        );
+    }
+}
+
+// whoever falls into a pit climbs back out somewhere safe, a heart lighter
+fn rescue_fallen_players(
+    arena: Option<Res<ArenaLayout>>,
+    mut rng: ResMut<GameRng>,
+    mut players: Query<(&mut Transform, &mut PlayerCombatState), With<PlayerControllerState>>,
+) {
+    let Some(arena) = arena else {
+        return;
+    };
+
+    for (mut transform, mut combat_state) in &mut players {
+        if transform.translation.y > FALL_RESCUE_HEIGHT {
+            continue;
+        }
+
+        if let Some(point) = arena.spawn_point(rng.as_mut(), &[], 0.0) {
+            transform.translation = point + Vec3::Y;
+            combat_state.current_hp = (combat_state.current_hp - 1).max(1);
+        }
+    }
+}
+
+fn arena_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let Some(settings) = world.get_resource::<ArenaSettings>().cloned() else {
+        return Err("this level is not generated".to_string());
+    };
+
+    if !args.is_empty() {
+        let seed: u64 = parse_arg(args, 0, "seed")?;
+        *world.resource_mut::<GameRng>() = GameRng::new(seed);
+    }
+
+    let layout = generate_arena(&settings, world.resource_mut::<GameRng>().as_mut());
+
+    let mut state: SystemState<(
+        Commands,
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<StandardMaterial>>,
+        Query<Entity, With<ArenaGeometry>>,
+    )> = SystemState::new(world);
+    let (mut commands, mut meshes, mut materials, old_geometry) = state.get_mut(world);
+
+    for entity in &old_geometry {
+        commands.entity(entity).despawn_recursive();
+    }
+    spawn_arena(&layout, &mut commands, &mut meshes, &mut materials);
+    commands.insert_resource(layout);
+
+    state.apply(world);
+
+    let seed = world.resource::<GameRng>().seed;
+    Ok(format!("new arena generated, seed {seed}"))
+}
@@ -1,5 +1,6 @@
 use crate::game_state::GameState;
 use crate::inventory::InventoryItem;
+use crate::network::is_simulation_authority;
 use crate::player::PlayerControllerState;
 use bevy::prelude::*;
 
@@ -10,14 +11,17 @@ type CollectedItems = InventoryItem;
 #[derive(Component)]
 pub struct Collectable(pub(crate) bool);
 
+// the collected item and the index of the hermit who picked it up
 #[derive(Event)]
-pub struct ItemCollectEvent(pub(crate) CollectedItems);
+pub struct ItemCollectEvent(pub(crate) CollectedItems, pub(crate) usize);
 
 impl Plugin for CollectablePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            detect_items.run_if(in_state(GameState::FightingInArena)),
+            detect_items
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
         );
         app.add_event::<ItemCollectEvent>();
     }
@@ -26,19 +30,28 @@ impl Plugin for CollectablePlugin {
 fn detect_items(
     mut commands: Commands,
     items: Query<(Entity, &Transform, &Collectable, &InventoryItem)>,
-    player_trans: Query<&Transform, With<PlayerControllerState>>,
+    players: Query<(&Transform, &PlayerControllerState)>,
     mut item_collected_event_writer: EventWriter<ItemCollectEvent>,
 ) {
     let detect_range = 2.0;
-    let current_location = player_trans.single().translation;
 
     for item in items.iter() {
-        if item.2 .0 {
-            let distance_squared = current_location.distance_squared(item.1.translation);
+        if !item.2 .0 {
+            continue;
+        }
+
+        for (player_transform, player_controller) in players.iter() {
+            let distance_squared = player_transform
+                .translation
+                .distance_squared(item.1.translation);
 
             if distance_squared < detect_range * detect_range {
-                item_collected_event_writer.send(ItemCollectEvent(item.3.clone()));
+                item_collected_event_writer.send(ItemCollectEvent(
+                    item.3.clone(),
+                    player_controller.player_index,
+                ));
                 commands.entity(item.0).despawn();
+                break;
             }
         }
     }
@@ -7,6 +7,7 @@ pub const DEFAULT_BAG_LOCATION: Vec3 = Vec3 {
     z: 0.0,
 };
 pub const SPAWN_ENEMIES: bool = true;
+pub const MAX_LOCAL_PLAYERS: usize = 2;
 
 pub const COLLISION_GROUP_PLAYER: Group = Group::GROUP_1;
 pub const COLLISION_GROUP_TERRAIN: Group = Group::GROUP_2;
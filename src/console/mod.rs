@@ -0,0 +1,430 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::str::FromStr;
+
+use bevy::input::keyboard::KeyboardInput;
+use bevy::input::InputSystem;
+use bevy::{log, prelude::*};
+
+pub struct ConsolePlugin;
+
+const MAX_OUTPUT_LINES: usize = 200;
+const VISIBLE_OUTPUT_LINES: usize = 14;
+const MAX_HISTORY: usize = 50;
+
+// Runs with full access to the world, gets the words after the command name.
+// Ok is printed as is, Err in red.
+pub type ConsoleHandler = fn(&mut World, &[&str]) -> Result<String, String>;
+
+#[derive(Clone)]
+pub struct ConsoleCommand {
+    pub name: &'static str,
+    pub usage: &'static str,
+    pub help: &'static str,
+    // offered by tab completion for the first argument
+    pub arguments: Vec<String>,
+    handler: ConsoleHandler,
+}
+
+impl ConsoleCommand {
+    pub fn new(name: &'static str, handler: ConsoleHandler) -> Self {
+        Self {
+            name,
+            usage: "",
+            help: "",
+            arguments: Vec::new(),
+            handler,
+        }
+    }
+
+    pub fn usage(mut self, usage: &'static str) -> Self {
+        self.usage = usage;
+        self
+    }
+
+    pub fn help(mut self, help: &'static str) -> Self {
+        self.help = help;
+        self
+    }
+
+    pub fn arguments<S: Into<String>>(mut self, arguments: impl IntoIterator<Item = S>) -> Self {
+        self.arguments = arguments.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleCommands(BTreeMap<&'static str, ConsoleCommand>);
+
+// lets every plugin bring its own commands: `app.add_console_command(ConsoleCommand::new(...))`
+pub trait AddConsoleCommand {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self;
+}
+
+impl AddConsoleCommand for App {
+    fn add_console_command(&mut self, command: ConsoleCommand) -> &mut Self {
+        self.init_resource::<ConsoleCommands>();
+        let name = command.name;
+        let mut commands = self.world.resource_mut::<ConsoleCommands>();
+        if commands.0.insert(name, command).is_some() {
+            log::warn!("Console command `{name}` registered twice");
+        }
+        self
+    }
+}
+
+// reads the argument at `index`, with an error message the console can print
+pub fn parse_arg<T: FromStr>(args: &[&str], index: usize, name: &str) -> Result<T, String> {
+    let Some(arg) = args.get(index) else {
+        return Err(format!("missing <{name}>"));
+    };
+    arg.parse()
+        .map_err(|_| format!("`{arg}` is not a valid <{name}>"))
+}
+
+#[derive(Resource, Default)]
+pub struct ConsoleState {
+    pub open: bool,
+    input: String,
+    output: VecDeque<(String, bool)>,
+    history: Vec<String>,
+    // where we are when browsing the history with up/down, None is the line being typed
+    history_cursor: Option<usize>,
+    submitted: Vec<String>,
+}
+
+impl ConsoleState {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.push_output(line.into(), false);
+    }
+
+    pub fn print_error(&mut self, line: impl Into<String>) {
+        self.push_output(line.into(), true);
+    }
+
+    pub fn clear(&mut self) {
+        self.output.clear();
+    }
+
+    fn push_output(&mut self, text: String, is_error: bool) {
+        for line in text.lines() {
+            self.output.push_back((line.to_string(), is_error));
+        }
+        while self.output.len() > MAX_OUTPUT_LINES {
+            self.output.pop_front();
+        }
+    }
+
+    fn submit(&mut self) {
+        let line = std::mem::take(&mut self.input);
+        self.history_cursor = None;
+        if line.trim().is_empty() {
+            return;
+        }
+
+        self.print(format!("> {line}"));
+        if self.history.last() != Some(&line) {
+            self.history.push(line.clone());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+        self.submitted.push(line);
+    }
+
+    fn browse_history(&mut self, older: bool) {
+        if self.history.is_empty() {
+            return;
+        }
+
+        self.history_cursor = match (self.history_cursor, older) {
+            (None, true) => Some(self.history.len() - 1),
+            (None, false) => None,
+            (Some(cursor), true) => Some(cursor.saturating_sub(1)),
+            (Some(cursor), false) if cursor + 1 < self.history.len() => Some(cursor + 1),
+            (Some(_), false) => None,
+        };
+        self.input = self
+            .history_cursor
+            .map(|cursor| self.history[cursor].clone())
+            .unwrap_or_default();
+    }
+
+    fn autocomplete(&mut self, commands: &ConsoleCommands) {
+        let input = self.input.clone();
+        let words: Vec<&str> = input.split(' ').collect();
+
+        let (prefix, candidates): (&str, Vec<&str>) = match words.as_slice() {
+            [name] => (*name, commands.0.keys().copied().collect()),
+            [name, argument] => match commands.0.get(name) {
+                Some(command) => (
+                    *argument,
+                    command.arguments.iter().map(String::as_str).collect(),
+                ),
+                None => return,
+            },
+            _ => return,
+        };
+
+        let matches: Vec<&str> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+
+        let completed = match matches.as_slice() {
+            [] => return,
+            [single] => format!("{single} "),
+            [first, rest @ ..] => {
+                // extend to what all the matches have in common and show them
+                let mut common = first.to_string();
+                for other in rest {
+                    while !other.starts_with(common.as_str()) {
+                        common.pop();
+                    }
+                }
+                self.print(matches.join("  "));
+                common
+            }
+        };
+
+        let mut words: Vec<String> = words.iter().map(|word| word.to_string()).collect();
+        *words.last_mut().unwrap() = completed;
+        self.input = words.join(" ");
+    }
+}
+
+#[derive(Component)]
+struct ConsoleUi;
+
+#[derive(Component)]
+struct ConsoleOutputText;
+
+#[derive(Component)]
+struct ConsoleInputText;
+
+#[derive(Resource)]
+struct ConsoleFont(Handle<Font>);
+
+impl Plugin for ConsolePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ConsoleState>();
+        app.init_resource::<ConsoleCommands>();
+        app.add_systems(Startup, setup);
+        app.add_systems(PreUpdate, handle_console_keys.after(InputSystem));
+        app.add_systems(Update, (run_submitted_commands, update_console_ui).chain());
+
+        app.add_console_command(
+            ConsoleCommand::new("help", help_command)
+                .usage("[command]")
+                .help("list the commands, or explain one"),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("clear", clear_command).help("clear the console output"),
+        );
+    }
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                display: Display::None,
+                position_type: PositionType::Absolute,
+                top: Val::Px(0.0),
+                left: Val::Px(0.0),
+                width: Val::Percent(100.0),
+                height: Val::Percent(40.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::FlexEnd,
+                padding: UiRect::all(Val::Px(10.0)),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            z_index: ZIndex::Global(100),
+            ..default()
+        })
+        .insert(ConsoleUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::default())
+                .insert(ConsoleOutputText);
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: font.clone(),
+                        font_size: 20.0,
+                        color: Color::rgb(1.0, 0.85, 0.3),
+                    },
+                ))
+                .insert(ConsoleInputText);
+        });
+
+    commands.insert_resource(ConsoleFont(font));
+}
+
+// While the console is open it eats the key presses, so typing `wave 7` doesn't walk the hermit.
+// Releases still go through, otherwise a key held while opening it would stay stuck.
+fn handle_console_keys(
+    mut state: ResMut<ConsoleState>,
+    commands: Res<ConsoleCommands>,
+    mut keyboard_events: ResMut<Events<KeyboardInput>>,
+    mut keys: ResMut<Input<KeyCode>>,
+    mut characters: EventReader<ReceivedCharacter>,
+) {
+    let toggled = keyboard_events
+        .iter_current_update_events()
+        .any(|event| event.key_code == Some(KeyCode::Grave) && event.state.is_pressed());
+    if toggled {
+        state.open = !state.open;
+        // the backtick is the console's either way, opening or closing it doesn't reach the game
+        keys.reset(KeyCode::Grave);
+        let events: Vec<KeyboardInput> = keyboard_events.drain().collect();
+        for event in events {
+            if event.key_code != Some(KeyCode::Grave) {
+                keyboard_events.send(event);
+            }
+        }
+    }
+
+    if !state.open {
+        characters.clear();
+        return;
+    }
+
+    let events: Vec<KeyboardInput> = keyboard_events.drain().collect();
+    for event in events {
+        if !event.state.is_pressed() {
+            keyboard_events.send(event);
+            continue;
+        }
+
+        match event.key_code {
+            Some(KeyCode::Return | KeyCode::NumpadEnter) => state.submit(),
+            Some(KeyCode::Back) => {
+                state.input.pop();
+            }
+            Some(KeyCode::Up) => state.browse_history(true),
+            Some(KeyCode::Down) => state.browse_history(false),
+            Some(KeyCode::Tab) => state.autocomplete(&commands),
+            Some(KeyCode::Escape) => state.open = false,
+            _ => {}
+        }
+    }
+    keys.reset_all();
+
+    for event in characters.iter() {
+        if !event.char.is_control() && !matches!(event.char, '`' | '~') {
+            state.input.push(event.char);
+        }
+    }
+}
+
+fn run_submitted_commands(world: &mut World) {
+    let lines = std::mem::take(&mut world.resource_mut::<ConsoleState>().submitted);
+
+    for line in lines {
+        let result = execute(world, &line);
+        let mut state = world.resource_mut::<ConsoleState>();
+        match result {
+            Ok(output) if output.is_empty() => {}
+            Ok(output) => state.print(output),
+            Err(error) => state.print_error(error),
+        }
+    }
+}
+
+fn execute(world: &mut World, line: &str) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((name, args)) = words.split_first() else {
+        return Ok(String::new());
+    };
+
+    let handler = world
+        .resource::<ConsoleCommands>()
+        .0
+        .get(name)
+        .map(|command| command.handler)
+        .ok_or_else(|| format!("unknown command `{name}`, try `help`"))?;
+
+    log::info!("Console: {line}");
+    handler(world, args)
+}
+
+fn update_console_ui(
+    state: Res<ConsoleState>,
+    font: Res<ConsoleFont>,
+    mut ui: Query<&mut Style, With<ConsoleUi>>,
+    mut output_text: Query<&mut Text, (With<ConsoleOutputText>, Without<ConsoleInputText>)>,
+    mut input_text: Query<&mut Text, (With<ConsoleInputText>, Without<ConsoleOutputText>)>,
+) {
+    for mut style in &mut ui {
+        let display = if state.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+        if style.display != display {
+            style.display = display;
+        }
+    }
+
+    if !state.open || !state.is_changed() {
+        return;
+    }
+
+    for mut text in &mut output_text {
+        let skip = state.output.len().saturating_sub(VISIBLE_OUTPUT_LINES);
+        text.sections = state
+            .output
+            .iter()
+            .skip(skip)
+            .map(|(line, is_error)| {
+                TextSection::new(
+                    format!("{line}\n"),
+                    TextStyle {
+                        font: font.0.clone(),
+                        font_size: 18.0,
+                        color: if *is_error {
+                            Color::rgb(1.0, 0.4, 0.4)
+                        } else {
+                            Color::rgb(0.9, 0.9, 0.9)
+                        },
+                    },
+                )
+            })
+            .collect();
+    }
+
+    for mut text in &mut input_text {
+        text.sections[0].value = format!("> {}_", state.input);
+    }
+}
+
+fn help_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let commands = world.resource::<ConsoleCommands>();
+
+    if let Some(name) = args.first() {
+        let command = commands
+            .0
+            .get(name)
+            .ok_or_else(|| format!("unknown command `{name}`"))?;
+        return Ok(format!(
+            "{} {} - {}",
+            command.name, command.usage, command.help
+        ));
+    }
+
+    Ok(commands
+        .0
+        .values()
+        .map(|command| format!("{} {}", command.name, command.usage))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+fn clear_command(world: &mut World, _: &[&str]) -> Result<String, String> {
+    world.resource_mut::<ConsoleState>().clear();
+    Ok(String::new())
+}
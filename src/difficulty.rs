@@ -0,0 +1,358 @@
+use bevy::utils::HashMap;
+use bevy::{log, prelude::*};
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::endless::EndlessRun;
+use crate::enemy::{detect_enemy_hit, Enemy};
+use crate::game_state::GameState;
+use crate::network::is_simulation_authority;
+use crate::player::combat::{process_hit, PlayerCombatState};
+use crate::player::consumables::use_consumables;
+use crate::status_effect::tick_status_effects;
+use crate::wave_manager::{Wave, WaveDefinition, WaveState};
+
+// how far the director goes either way, and how much it moves after a wave
+const MIN_INTENSITY: f32 = 0.6;
+const MAX_INTENSITY: f32 = 1.5;
+const INTENSITY_STEP: f32 = 0.1;
+
+// A wave is expected to take this long per creature, or the spawn interval if that's longer.
+// Taking half as long again is struggling, three quarters of it is cruising.
+const SECONDS_PER_CREATURE: f32 = 1.5;
+const SLOW_CLEAR: f32 = 1.5;
+const FAST_CLEAR: f32 = 0.75;
+// share of the hermits' hearts lost over a wave
+const HEAVY_LOSS: f32 = 0.5;
+const LIGHT_LOSS: f32 = 0.1;
+
+pub struct DifficultyPlugin;
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Difficulty>();
+        app.init_resource::<DifficultyDirector>();
+        app.init_resource::<DifficultyModifiers>();
+        app.add_systems(Update, update_modifiers);
+        app.add_systems(
+            Update,
+            (
+                scale_new_enemies
+                    .before(process_hit)
+                    .before(detect_enemy_hit)
+                    .before(use_consumables)
+                    .before(tick_status_effects),
+                track_hp_loss,
+            )
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(
+            OnEnter(WaveState::ACTIVE_WAVE_SPAWNING),
+            start_wave_stats.run_if(is_simulation_authority),
+        );
+        app.add_systems(
+            OnEnter(WaveState::WAVE_END),
+            judge_wave.run_if(is_simulation_authority),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("difficulty", difficulty_command)
+                .usage("[easy|normal|hard|nightmare]")
+                .help("show or change the difficulty, it applies from the next wave")
+                .arguments(Difficulty::ALL.map(Difficulty::name)),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("director", director_command)
+                .usage("[on|off]")
+                .help("show or toggle the director that adapts the waves to how the run is going")
+                .arguments(["on", "off"]),
+        );
+    }
+}
+
+#[derive(Resource, Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Nightmare,
+}
+
+// Multipliers on top of the creatures' own numbers. As a resource it's the difficulty and the
+// endless run's modifiers together, which is what the game should read.
+#[derive(Resource, Copy, Clone)]
+pub struct DifficultyModifiers {
+    pub enemy_speed: f32,
+    pub enemy_damage: f32,
+    pub enemy_hp: f32,
+    // above 1 the creatures of a wave come in quicker
+    pub spawn_rate: f32,
+}
+
+impl Default for DifficultyModifiers {
+    fn default() -> Self {
+        Difficulty::Normal.modifiers()
+    }
+}
+
+impl DifficultyModifiers {
+    pub fn stack(self, other: DifficultyModifiers) -> Self {
+        Self {
+            enemy_speed: self.enemy_speed * other.enemy_speed,
+            enemy_damage: self.enemy_damage * other.enemy_damage,
+            enemy_hp: self.enemy_hp * other.enemy_hp,
+            spawn_rate: self.spawn_rate * other.spawn_rate,
+        }
+    }
+
+    // a hit always takes at least one heart
+    pub fn damage(&self, damage: i32) -> i32 {
+        ((damage as f32 * self.enemy_damage).round() as i32).max(1)
+    }
+
+    pub fn hp(&self, hp: i32) -> i32 {
+        ((hp as f32 * self.enemy_hp).round() as i32).max(1)
+    }
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Nightmare,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+            Difficulty::Nightmare => "nightmare",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name() == name)
+    }
+
+    // the one after, wrapping around
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|other| *other == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn modifiers(self) -> DifficultyModifiers {
+        match self {
+            Difficulty::Easy => DifficultyModifiers {
+                enemy_speed: 0.8,
+                enemy_damage: 0.5,
+                enemy_hp: 0.75,
+                spawn_rate: 0.75,
+            },
+            Difficulty::Normal => DifficultyModifiers {
+                enemy_speed: 1.0,
+                enemy_damage: 1.0,
+                enemy_hp: 1.0,
+                spawn_rate: 1.0,
+            },
+            Difficulty::Hard => DifficultyModifiers {
+                enemy_speed: 1.2,
+                enemy_damage: 1.5,
+                enemy_hp: 1.5,
+                spawn_rate: 1.3,
+            },
+            Difficulty::Nightmare => DifficultyModifiers {
+                enemy_speed: 1.4,
+                enemy_damage: 2.0,
+                enemy_hp: 2.0,
+                spawn_rate: 1.6,
+            },
+        }
+    }
+}
+
+// how the wave being fought is going
+struct WaveStats {
+    wave: i32,
+    started: f32,
+    enemies: i32,
+    spawn_interval: f32,
+    hp_lost: i32,
+    max_hp: i32,
+}
+
+// Watches how much the hermits get hurt and how long they take over each wave, and turns the
+// waves after it up or down. Every call it makes goes to the log.
+#[derive(Resource)]
+pub struct DifficultyDirector {
+    pub enabled: bool,
+    // 1 leaves the waves as they are, above it they get more creatures coming in quicker
+    pub intensity: f32,
+    current: Option<WaveStats>,
+}
+
+impl Default for DifficultyDirector {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            intensity: 1.0,
+            current: None,
+        }
+    }
+}
+
+impl DifficultyDirector {
+    pub fn nudge(&mut self, wave: i32, definition: &mut WaveDefinition) {
+        // a new run starts from the waves as they were made
+        if wave == 0 {
+            self.intensity = 1.0;
+        }
+        if !self.enabled || self.intensity == 1.0 {
+            return;
+        }
+
+        let before = definition.enemy_count();
+        definition.scale_enemies(self.intensity);
+        definition.scale_spawn_rate(self.intensity);
+        log::info!(
+            "Director: wave {} gets {} creatures instead of {} at intensity {:.2}",
+            wave + 1,
+            definition.enemy_count(),
+            before,
+            self.intensity
+        );
+    }
+}
+
+fn update_modifiers(
+    difficulty: Res<Difficulty>,
+    endless: Res<EndlessRun>,
+    mut modifiers: ResMut<DifficultyModifiers>,
+) {
+    if difficulty.is_changed() || endless.is_changed() {
+        *modifiers = difficulty
+            .modifiers()
+            .stack(endless.effects().enemy_modifiers);
+    }
+}
+
+// runs before anything can hurt them, so a fresh creature has its full scaled health
+fn scale_new_enemies(
+    mut enemies: Query<&mut Enemy, Added<Enemy>>,
+    modifiers: Res<DifficultyModifiers>,
+) {
+    for mut enemy in &mut enemies {
+        enemy.hp = modifiers.hp(enemy.hp);
+    }
+}
+
+fn track_hp_loss(
+    players: Query<(Entity, &PlayerCombatState)>,
+    mut last_hp: Local<HashMap<Entity, i32>>,
+    mut director: ResMut<DifficultyDirector>,
+) {
+    let mut lost = 0;
+    for (entity, state) in &players {
+        if let Some(last) = last_hp.insert(entity, state.current_hp) {
+            lost += (last - state.current_hp).max(0);
+        }
+    }
+
+    if lost > 0 {
+        if let Some(stats) = director.current.as_mut() {
+            stats.hp_lost += lost;
+        }
+    }
+}
+
+fn start_wave_stats(
+    mut director: ResMut<DifficultyDirector>,
+    wave: Res<Wave>,
+    players: Query<&PlayerCombatState>,
+    time: Res<Time>,
+) {
+    director.current = Some(WaveStats {
+        wave: wave.count,
+        started: time.elapsed_seconds(),
+        enemies: wave.wave_definition.enemy_count(),
+        spawn_interval: wave.wave_definition.spawn_interval(),
+        hp_lost: 0,
+        max_hp: players.iter().map(|state| state.max_hp).sum(),
+    });
+}
+
+// Runs as soon as the last creature of a wave is gone, before the next wave is picked.
+fn judge_wave(mut director: ResMut<DifficultyDirector>, time: Res<Time>) {
+    let Some(stats) = director.current.take() else {
+        return;
+    };
+    if !director.enabled {
+        return;
+    }
+
+    let clear_secs = time.elapsed_seconds() - stats.started;
+    let expected_secs = stats.enemies as f32 * stats.spawn_interval.max(SECONDS_PER_CREATURE);
+    let loss = stats.hp_lost as f32 / stats.max_hp.max(1) as f32;
+
+    let before = director.intensity;
+    let verdict = if loss >= HEAVY_LOSS || clear_secs > expected_secs * SLOW_CLEAR {
+        director.intensity = (before - INTENSITY_STEP).max(MIN_INTENSITY);
+        "struggling, easing off"
+    } else if loss <= LIGHT_LOSS && clear_secs < expected_secs * FAST_CLEAR {
+        director.intensity = (before + INTENSITY_STEP).min(MAX_INTENSITY);
+        "cruising, pushing harder"
+    } else {
+        "holding steady"
+    };
+
+    log::info!(
+        "Director: wave {} cost {}/{} hearts and took {:.1}s (expected {:.1}s), {}, intensity {:.2} -> {:.2}",
+        stats.wave + 1,
+        stats.hp_lost,
+        stats.max_hp,
+        clear_secs,
+        expected_secs,
+        verdict,
+        before,
+        director.intensity
+    );
+}
+
+fn difficulty_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return Ok(format!(
+            "difficulty is {}",
+            world.resource::<Difficulty>().name()
+        ));
+    }
+
+    let name: String = parse_arg(args, 0, "difficulty")?;
+    let difficulty =
+        Difficulty::from_name(&name).ok_or_else(|| format!("no difficulty called `{name}`"))?;
+    *world.resource_mut::<Difficulty>() = difficulty;
+    Ok(format!("difficulty set to {name}"))
+}
+
+fn director_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut director = world.resource_mut::<DifficultyDirector>();
+    let Some(setting) = args.first() else {
+        return Ok(format!(
+            "director {}, intensity {:.2}",
+            if director.enabled { "on" } else { "off" },
+            director.intensity
+        ));
+    };
+
+    director.enabled = match *setting {
+        "on" => true,
+        "off" => false,
+        other => return Err(format!("expected on or off, got `{other}`")),
+    };
+    Ok(format!("director {setting}"))
+}
@@ -0,0 +1,102 @@
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+
+use crate::storage::{read_text, write_text};
+
+// one run per line: modifier set, waves cleared, difficulty and level, separated by tabs
+const LEADERBOARD_FILE: &str = "leaderboard.txt";
+// the best runs kept for every modifier set
+const RUNS_PER_SET: usize = 5;
+
+#[derive(Clone)]
+pub struct LeaderboardEntry {
+    pub waves: i32,
+    pub difficulty: String,
+    pub level: String,
+}
+
+impl LeaderboardEntry {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} waves, {} on {}",
+            self.waves, self.level, self.difficulty
+        )
+    }
+}
+
+// The best endless runs played on this machine. Runs only compete with runs that had the same
+// modifiers.
+#[derive(Resource, Default)]
+pub struct Leaderboard {
+    sets: BTreeMap<String, Vec<LeaderboardEntry>>,
+}
+
+impl Leaderboard {
+    // no file yet is an empty leaderboard, lines that don't make sense are skipped
+    pub fn load() -> Self {
+        let mut leaderboard = Self::default();
+        let Some(text) = read_text(LEADERBOARD_FILE) else {
+            return leaderboard;
+        };
+
+        for line in text.lines() {
+            let [set, waves, difficulty, level] = line.split('\t').collect::<Vec<_>>()[..] else {
+                continue;
+            };
+            let Ok(waves) = waves.parse() else {
+                continue;
+            };
+            leaderboard.record(
+                set,
+                LeaderboardEntry {
+                    waves,
+                    difficulty: difficulty.to_string(),
+                    level: level.to_string(),
+                },
+            );
+        }
+        leaderboard
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let text: String = self
+            .sets
+            .iter()
+            .flat_map(|(set, runs)| {
+                runs.iter().map(move |run| {
+                    format!("{set}\t{}\t{}\t{}\n", run.waves, run.difficulty, run.level)
+                })
+            })
+            .collect();
+        write_text(LEADERBOARD_FILE, &text)
+    }
+
+    // where the run landed among its set's best starting at 0, None if it didn't make it
+    pub fn record(&mut self, set: &str, run: LeaderboardEntry) -> Option<usize> {
+        let runs = self.sets.entry(set.to_string()).or_default();
+        // a tie goes behind the run that got there first
+        let rank = runs
+            .iter()
+            .position(|other| other.waves < run.waves)
+            .unwrap_or(runs.len());
+        if rank >= RUNS_PER_SET {
+            return None;
+        }
+
+        runs.insert(rank, run);
+        runs.truncate(RUNS_PER_SET);
+        Some(rank)
+    }
+
+    pub fn runs(&self, set: &str) -> &[LeaderboardEntry] {
+        self.sets.get(set).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    // the top run of every set
+    pub fn best_runs(&self) -> impl Iterator<Item = (&str, &LeaderboardEntry)> {
+        self.sets
+            .iter()
+            .filter_map(|(set, runs)| Some((set.as_str(), runs.first()?)))
+    }
+}
@@ -0,0 +1,385 @@
+use bevy::{log, prelude::*};
+use rand::seq::SliceRandom;
+
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+use crate::difficulty::{Difficulty, DifficultyModifiers};
+use crate::endless::leaderboard::{Leaderboard, LeaderboardEntry};
+use crate::game::GameRng;
+use crate::levels::{CurrentLevel, LevelRegistry};
+use crate::network::is_simulation_authority;
+use crate::player::combat::PlayerCombatState;
+use crate::player::PlayerState;
+use crate::wave_manager::{Wave, WaveState};
+
+mod leaderboard;
+mod ui;
+
+// the hermits get to pick a modifier after clearing every this many waves
+const MODIFIER_EVERY: i32 = 3;
+const MODIFIERS_OFFERED: usize = 3;
+
+pub struct EndlessPlugin;
+
+impl Plugin for EndlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<EndlessRun>();
+        app.insert_resource(Leaderboard::load());
+        app.add_systems(
+            OnEnter(WaveState::WAVE_END),
+            offer_modifiers.run_if(is_simulation_authority),
+        );
+        app.add_systems(Update, apply_player_effects.run_if(is_simulation_authority));
+        app.add_systems(
+            OnEnter(PlayerState::Dying),
+            record_run.run_if(is_simulation_authority),
+        );
+        app.add_plugins(ui::EndlessUiPlugin);
+        app.add_console_command(
+            ConsoleCommand::new("endless", endless_command)
+                .usage("[on|off]")
+                .help("show or toggle endless mode, it takes over from the next wave")
+                .arguments(["on", "off"]),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("modifier", modifier_command)
+                .usage("[modifier]")
+                .help("list the run modifiers, or add one to the endless run")
+                .arguments(RunModifier::ALL.map(RunModifier::name)),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("leaderboard", leaderboard_command)
+                .usage("[modifier+modifier|none]")
+                .help("show the best endless run of every modifier set, or the runs of one set"),
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RunModifier {
+    Frenzy,
+    GlassCannon,
+    Swarm,
+    ThickShells,
+    Riptide,
+    Venom,
+}
+
+impl RunModifier {
+    pub const ALL: [RunModifier; 6] = [
+        RunModifier::Frenzy,
+        RunModifier::GlassCannon,
+        RunModifier::Swarm,
+        RunModifier::ThickShells,
+        RunModifier::Riptide,
+        RunModifier::Venom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RunModifier::Frenzy => "frenzy",
+            RunModifier::GlassCannon => "glasscannon",
+            RunModifier::Swarm => "swarm",
+            RunModifier::ThickShells => "thickshells",
+            RunModifier::Riptide => "riptide",
+            RunModifier::Venom => "venom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|modifier| modifier.name() == name)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            RunModifier::Frenzy => "Frenzy",
+            RunModifier::GlassCannon => "Glass Cannon",
+            RunModifier::Swarm => "Swarm",
+            RunModifier::ThickShells => "Thick Shells",
+            RunModifier::Riptide => "Riptide",
+            RunModifier::Venom => "Venom",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            RunModifier::Frenzy => "Creatures are faster,\nmore loot",
+            RunModifier::GlassCannon => "No healing,\ndouble damage",
+            RunModifier::Swarm => "More creatures,\nluckier loot",
+            RunModifier::ThickShells => "Creatures are tougher,\nyou hit harder",
+            RunModifier::Riptide => "Creatures come in quicker,\nmore loot",
+            RunModifier::Venom => "Creatures hit harder,\nmuch luckier loot",
+        }
+    }
+
+    fn apply(self, effects: &mut RunEffects) {
+        match self {
+            RunModifier::Frenzy => {
+                effects.enemy_modifiers.enemy_speed *= 1.3;
+                effects.extra_drops += 1;
+            }
+            RunModifier::GlassCannon => {
+                effects.no_healing = true;
+                effects.player_damage *= 2.0;
+            }
+            RunModifier::Swarm => {
+                effects.enemy_count *= 1.5;
+                effects.luck += 1;
+            }
+            RunModifier::ThickShells => {
+                effects.enemy_modifiers.enemy_hp *= 2.0;
+                effects.player_damage *= 1.5;
+            }
+            RunModifier::Riptide => {
+                effects.enemy_modifiers.spawn_rate *= 2.0;
+                effects.extra_drops += 1;
+            }
+            RunModifier::Venom => {
+                effects.enemy_modifiers.enemy_damage *= 2.0;
+                effects.luck += 2;
+            }
+        }
+    }
+}
+
+// what the modifiers picked so far add up to
+#[derive(Copy, Clone)]
+pub struct RunEffects {
+    // on top of the difficulty's
+    pub enemy_modifiers: DifficultyModifiers,
+    pub enemy_count: f32,
+    pub player_damage: f32,
+    pub no_healing: bool,
+    pub luck: i32,
+    // items dropped at the end of a wave besides the usual one
+    pub extra_drops: i32,
+}
+
+impl Default for RunEffects {
+    fn default() -> Self {
+        Self {
+            enemy_modifiers: DifficultyModifiers::default(),
+            enemy_count: 1.0,
+            player_damage: 1.0,
+            no_healing: false,
+            luck: 0,
+            extra_drops: 0,
+        }
+    }
+}
+
+// Endless mode plays the picked level with its own ever growing waves instead of the level's,
+// until every hermit is down. The modifiers stack up over the run and reset with the next one.
+#[derive(Resource, Default)]
+pub struct EndlessRun {
+    pub enabled: bool,
+    // in the order they were picked
+    modifiers: Vec<RunModifier>,
+    // waiting for the hermits to take one, the next wave doesn't start meanwhile
+    offer: Vec<RunModifier>,
+}
+
+impl EndlessRun {
+    pub fn modifiers(&self) -> &[RunModifier] {
+        &self.modifiers
+    }
+
+    pub fn offer(&self) -> &[RunModifier] {
+        &self.offer
+    }
+
+    // nothing at all outside endless mode
+    pub fn effects(&self) -> RunEffects {
+        let mut effects = RunEffects::default();
+        if self.enabled {
+            for modifier in &self.modifiers {
+                modifier.apply(&mut effects);
+            }
+        }
+        effects
+    }
+
+    pub fn pick(&mut self, modifier: RunModifier) {
+        self.offer.clear();
+        if !self.modifiers.contains(&modifier) {
+            log::info!("Endless run picked {}", modifier.title());
+            self.modifiers.push(modifier);
+        }
+    }
+
+    // what the leaderboard files the run under
+    pub fn modifier_set(&self) -> String {
+        modifier_set(&self.modifiers)
+    }
+}
+
+// the same modifiers make the same set whatever order they were picked in
+fn modifier_set(modifiers: &[RunModifier]) -> String {
+    let mut modifiers = modifiers.to_vec();
+    modifiers.sort();
+    if modifiers.is_empty() {
+        return "none".to_string();
+    }
+    modifiers
+        .into_iter()
+        .map(RunModifier::name)
+        .collect::<Vec<_>>()
+        .join("+")
+}
+
+pub fn is_choosing_modifier(endless: Res<EndlessRun>) -> bool {
+    !endless.offer.is_empty()
+}
+
+// A new run starts without modifiers, after that every few waves cleared three of the ones not
+// taken yet come up.
+fn offer_modifiers(mut endless: ResMut<EndlessRun>, wave: Res<Wave>, mut rng: ResMut<GameRng>) {
+    if wave.count == 0 {
+        if !endless.modifiers.is_empty() || !endless.offer.is_empty() {
+            endless.modifiers.clear();
+            endless.offer.clear();
+        }
+        return;
+    }
+    if !endless.enabled || wave.count % MODIFIER_EVERY != 0 {
+        return;
+    }
+
+    let left: Vec<RunModifier> = RunModifier::ALL
+        .into_iter()
+        .filter(|modifier| !endless.modifiers.contains(modifier))
+        .collect();
+    endless.offer = left
+        .choose_multiple(rng.as_mut(), MODIFIERS_OFFERED)
+        .copied()
+        .collect();
+}
+
+fn apply_player_effects(endless: Res<EndlessRun>, mut players: Query<&mut PlayerCombatState>) {
+    let effects = endless.effects();
+    for mut state in &mut players {
+        if state.damage_multiplier != effects.player_damage {
+            state.damage_multiplier = effects.player_damage;
+        }
+        if state.no_healing != effects.no_healing {
+            state.no_healing = effects.no_healing;
+        }
+    }
+}
+
+// the run is over once every hermit is down, `count` is the waves cleared
+fn record_run(
+    endless: Res<EndlessRun>,
+    wave: Res<Wave>,
+    difficulty: Res<Difficulty>,
+    levels: Res<LevelRegistry>,
+    current_level: Res<CurrentLevel>,
+    mut leaderboard: ResMut<Leaderboard>,
+) {
+    if !endless.enabled {
+        return;
+    }
+
+    let set = endless.modifier_set();
+    let rank = leaderboard.record(
+        &set,
+        LeaderboardEntry {
+            waves: wave.count,
+            difficulty: difficulty.name().to_string(),
+            level: levels.level(current_level.0).name.to_string(),
+        },
+    );
+    match rank {
+        Some(rank) => log::info!(
+            "Endless run cleared {} waves, #{} with {set}",
+            wave.count,
+            rank + 1
+        ),
+        None => log::info!("Endless run cleared {} waves", wave.count),
+    }
+    if let Err(error) = leaderboard.save() {
+        log::warn!("{error}");
+    }
+}
+
+fn endless_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut endless = world.resource_mut::<EndlessRun>();
+    let Some(setting) = args.first() else {
+        return Ok(format!(
+            "endless mode {}, modifiers {}",
+            if endless.enabled { "on" } else { "off" },
+            endless.modifier_set()
+        ));
+    };
+
+    endless.enabled = match *setting {
+        "on" => true,
+        "off" => false,
+        other => return Err(format!("expected on or off, got `{other}`")),
+    };
+    Ok(format!("endless mode {setting}"))
+}
+
+fn modifier_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut endless = world.resource_mut::<EndlessRun>();
+    let Some(name) = args.first() else {
+        return Ok(RunModifier::ALL
+            .into_iter()
+            .map(|modifier| {
+                let picked = if endless.modifiers.contains(&modifier) {
+                    " (picked)"
+                } else {
+                    ""
+                };
+                format!(
+                    "{}{picked}: {}",
+                    modifier.name(),
+                    modifier.description().replace('\n', " ")
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n"));
+    };
+
+    let modifier =
+        RunModifier::from_name(name).ok_or_else(|| format!("no modifier called `{name}`"))?;
+    endless.pick(modifier);
+    Ok(format!("modifiers are now {}", endless.modifier_set()))
+}
+
+fn leaderboard_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let leaderboard = world.resource::<Leaderboard>();
+    let Some(query) = args.first() else {
+        let lines: Vec<String> = leaderboard
+            .best_runs()
+            .map(|(set, run)| format!("{set}: {}", run.describe()))
+            .collect();
+        if lines.is_empty() {
+            return Ok("no endless runs yet".to_string());
+        }
+        return Ok(lines.join("\n"));
+    };
+
+    // written in any order, `swarm+frenzy` is the same set as `frenzy+swarm`
+    let modifiers = match *query {
+        "none" => vec![],
+        query => query
+            .split('+')
+            .map(|name| {
+                RunModifier::from_name(name).ok_or_else(|| format!("no modifier called `{name}`"))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let set = modifier_set(&modifiers);
+    let runs = leaderboard.runs(&set);
+    if runs.is_empty() {
+        return Ok(format!("no endless runs with {set} yet"));
+    }
+    Ok(runs
+        .iter()
+        .enumerate()
+        .map(|(rank, run)| format!("{} {}", rank + 1, run.describe()))
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
@@ -0,0 +1,248 @@
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+
+use crate::endless::leaderboard::Leaderboard;
+use crate::endless::EndlessRun;
+use crate::game_state::GameState;
+
+pub struct EndlessUiPlugin;
+
+impl Plugin for EndlessUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighlightedCard>();
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            (
+                update_modifier_list,
+                show_offer,
+                pick_modifier,
+                update_card_highlight,
+            )
+                .chain()
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+    }
+}
+
+#[derive(Component)]
+struct ModifierList;
+
+#[derive(Component)]
+struct OfferPanel;
+
+// which one of the offer it is
+#[derive(Component)]
+struct ModifierCard(usize);
+
+#[derive(Resource, Default)]
+struct HighlightedCard(usize);
+
+// under the wave counter, empty outside endless mode
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(
+            TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 24.0,
+                    color: Color::BLACK,
+                },
+            )
+            .with_text_alignment(TextAlignment::Right)
+            .with_style(Style {
+                position_type: Absolute,
+                right: Val::Px(16.0),
+                top: Val::Px(70.0),
+                ..default()
+            }),
+        )
+        .insert(ModifierList);
+}
+
+fn update_modifier_list(
+    endless: Res<EndlessRun>,
+    leaderboard: Res<Leaderboard>,
+    mut texts: Query<&mut Text, With<ModifierList>>,
+) {
+    let label = if !endless.enabled {
+        String::new()
+    } else {
+        let modifiers = if endless.modifiers().is_empty() {
+            "No modifiers".to_string()
+        } else {
+            endless
+                .modifiers()
+                .iter()
+                .map(|modifier| modifier.title())
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        match leaderboard.runs(&endless.modifier_set()).first() {
+            Some(best) => format!("{modifiers}\nBest: {} waves", best.waves),
+            None => modifiers,
+        }
+    };
+
+    for mut text in &mut texts {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+// The cards come up as soon as there's an offer and go once one is picked. Leaving for the
+// inventory takes them down, coming back brings them up again.
+fn show_offer(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    endless: Res<EndlessRun>,
+    panels: Query<Entity, With<OfferPanel>>,
+    mut highlighted: ResMut<HighlightedCard>,
+) {
+    if endless.offer().is_empty() {
+        for entity in &panels {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !panels.is_empty() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+    highlighted.0 = 0;
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Percent(25.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(15.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(OfferPanel)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Pick a modifier",
+                TextStyle {
+                    color: Color::BLACK,
+                    ..text_style(40.0)
+                },
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (index, modifier) in endless.offer().iter().enumerate() {
+                        parent
+                            .spawn(ButtonBundle {
+                                style: Style {
+                                    width: Val::Px(260.0),
+                                    height: Val::Px(130.0),
+                                    border: UiRect::all(Val::Px(5.0)),
+                                    flex_direction: FlexDirection::Column,
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    row_gap: Val::Px(8.0),
+                                    ..default()
+                                },
+                                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                                ..default()
+                            })
+                            .insert(ModifierCard(index))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(
+                                    modifier.title(),
+                                    text_style(30.0),
+                                ));
+                                parent.spawn(
+                                    TextBundle::from_section(
+                                        modifier.description(),
+                                        text_style(20.0),
+                                    )
+                                    .with_text_alignment(TextAlignment::Center),
+                                );
+                            });
+                    }
+                });
+        });
+}
+
+// clicking a card, or left and right then enter, the left trigger and east on a gamepad
+fn pick_modifier(
+    cards: Query<(&Interaction, &ModifierCard), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut highlighted: ResMut<HighlightedCard>,
+    mut endless: ResMut<EndlessRun>,
+) {
+    let offered = endless.offer().len();
+    if offered == 0 {
+        return;
+    }
+    let pressed = |key: KeyCode, button_type: GamepadButtonType| {
+        keys.just_pressed(key)
+            || gamepads.iter().any(|gamepad| {
+                buttons.just_pressed(GamepadButton {
+                    gamepad,
+                    button_type,
+                })
+            })
+    };
+
+    if keys.just_pressed(KeyCode::Left) {
+        highlighted.0 = (highlighted.0 + offered - 1) % offered;
+    }
+    if pressed(KeyCode::Right, GamepadButtonType::LeftTrigger2) {
+        highlighted.0 = (highlighted.0 + 1) % offered;
+    }
+
+    let clicked = cards
+        .iter()
+        .find(|(interaction, _)| **interaction == Interaction::Pressed)
+        .map(|(_, card)| card.0);
+    let picked = clicked
+        .or_else(|| pressed(KeyCode::Return, GamepadButtonType::East).then_some(highlighted.0));
+    if let Some(modifier) = picked.and_then(|index| endless.offer().get(index).copied()) {
+        endless.pick(modifier);
+    }
+}
+
+fn update_card_highlight(
+    highlighted: Res<HighlightedCard>,
+    mut cards: Query<(&ModifierCard, &Interaction, &mut BorderColor)>,
+) {
+    for (card, interaction, mut border) in &mut cards {
+        let lit = card.0 == highlighted.0 || *interaction == Interaction::Hovered;
+        border.0 = if lit {
+            Color::rgb(1.0, 0.85, 0.2)
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 0.0)
+        };
+    }
+}
+
+fn clean(mut commands: Commands, query: Query<Entity, Or<(With<ModifierList>, With<OfferPanel>)>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,13 +1,24 @@
+use std::f32::consts::TAU;
+
+use bevy::ecs::system::SystemState;
 use bevy::prelude::*;
+use bevy::utils::HashSet;
 use bevy_rapier3d::prelude::*;
 
 use crate::asset_loader::GameAssets;
 use crate::config::{
     COLLISION_GROUP_ENEMIES, COLLISION_GROUP_PROJECTILES, COLLISION_GROUP_TERRAIN,
 };
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::difficulty::DifficultyModifiers;
+use crate::game::GameRng;
 use crate::game_state::GameState;
+use crate::hit_feedback::{EnemyHit, HitSource};
+use crate::network::is_simulation_authority;
+use crate::player::combat::{roll_critical, PlayerCombatState};
 use crate::player::PlayerControllerState;
 use crate::projectile::Projectile;
+use crate::status_effect::{ApplyStatusEffect, StatusEffectKind, StatusEffects};
 
 pub const ENEMY_COLLIDER_RADIUS: f32 = 0.25;
 
@@ -19,33 +30,114 @@ pub struct EnemyBundle {
     pbr: PbrBundle,
     controller: KinematicCharacterController,
     enemy: Enemy,
+    status_effects: StatusEffects,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum EnemyType {
     Jellyfish,
     Urchin,
     Shrimp,
 }
 
+impl EnemyType {
+    pub const ALL: [EnemyType; 3] = [EnemyType::Jellyfish, EnemyType::Urchin, EnemyType::Shrimp];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EnemyType::Jellyfish => "jellyfish",
+            EnemyType::Urchin => "urchin",
+            EnemyType::Shrimp => "shrimp",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        EnemyType::ALL
+            .into_iter()
+            .find(|enemy_type| enemy_type.name() == name)
+    }
+
+    // every creature still goes down in one hit
+    pub fn max_hp(&self) -> i32 {
+        1
+    }
+
+    // hearts a hit takes, before the difficulty
+    pub fn damage(&self) -> i32 {
+        match self {
+            EnemyType::Jellyfish => 1,
+            EnemyType::Urchin => 2,
+            EnemyType::Shrimp => 3,
+        }
+    }
+
+    // what sticks to a hermit after being hit, with how long it lasts
+    pub fn attack_effect(&self) -> (StatusEffectKind, f32) {
+        match self {
+            // sticky tentacles
+            EnemyType::Jellyfish => (StatusEffectKind::Slow, 1.5),
+            EnemyType::Urchin => (StatusEffectKind::Poison, 1.0),
+            // the snap of the claw leaves you dazed
+            EnemyType::Shrimp => (StatusEffectKind::Stun, 0.5),
+        }
+    }
+
+    // what it bursts into when it dies
+    pub fn color(&self) -> Color {
+        match self {
+            EnemyType::Jellyfish => Color::rgb(0.95, 0.55, 0.95),
+            EnemyType::Urchin => Color::rgb(0.35, 0.15, 0.45),
+            EnemyType::Shrimp => Color::rgb(1.0, 0.45, 0.3),
+        }
+    }
+}
+
 #[derive(Component, Clone)]
 pub struct Enemy {
     pub enemy_type: EnemyType,
+    pub hp: i32,
+}
+
+impl Enemy {
+    pub fn new(enemy_type: EnemyType) -> Self {
+        Self {
+            enemy_type,
+            hp: enemy_type.max_hp(),
+        }
+    }
+
+    // true once it's dead
+    pub fn take_damage(&mut self, damage: i32) -> bool {
+        self.hp -= damage;
+        self.hp <= 0
+    }
 }
 
 impl Plugin for EnemyPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            move_enemies.run_if(in_state(GameState::FightingInArena)),
+            move_enemies
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
         );
         app.add_systems(
             Update,
-            detect_enemy_hit.run_if(in_state(GameState::FightingInArena)),
+            detect_enemy_hit
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
         );
         app.add_systems(
             Update,
-            remove_lost_enemies.run_if(in_state(GameState::FightingInArena)),
+            remove_lost_enemies
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("spawn", spawn_command)
+                .usage("<enemy> [count]")
+                .help("spawn enemies in a ring around player one")
+                .arguments(EnemyType::ALL.map(|enemy_type| enemy_type.name())),
         );
     }
 }
@@ -90,26 +182,90 @@ impl EnemyBundle {
                 }),
                 ..default()
             },
-            enemy: Enemy { enemy_type },
+            enemy: Enemy::new(enemy_type),
+            status_effects: StatusEffects::default(),
         }
     }
 }
 
+fn spawn_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "enemy")?;
+    let enemy_type =
+        EnemyType::from_name(&name).ok_or_else(|| format!("no enemy called `{name}`"))?;
+    let count: usize = if args.len() > 1 {
+        parse_arg(args, 1, "count")?
+    } else {
+        1
+    };
+
+    let mut state: SystemState<(
+        Commands,
+        Res<GameAssets>,
+        Query<(&Transform, &PlayerControllerState)>,
+    )> = SystemState::new(world);
+    let (mut commands, game_assets, players) = state.get_mut(world);
+
+    if !game_assets.are_all_assets_loaded() {
+        return Err("still loading the enemy models".to_string());
+    }
+
+    let center = players
+        .iter()
+        .find(|(_, controller)| controller.player_index == 0)
+        .map(|(transform, _)| transform.translation)
+        .unwrap_or(Vec3::ZERO);
+
+    for i in 0..count {
+        let angle = TAU * i as f32 / count as f32;
+        let position = center + Vec3::new(angle.cos() * 5.0, 1.0, angle.sin() * 5.0);
+        commands.spawn(EnemyBundle::new(position, &game_assets, enemy_type));
+    }
+
+    state.apply(world);
+    Ok(format!("spawned {count} {name}"))
+}
+
 fn move_enemies(
     mut param_set: ParamSet<(
-        Query<&Transform, With<PlayerControllerState>>,
-        Query<(&mut KinematicCharacterController, &mut Transform), With<Enemy>>,
+        Query<(&Transform, &PlayerCombatState), With<PlayerControllerState>>,
+        Query<
+            (
+                &mut KinematicCharacterController,
+                &mut Transform,
+                Option<&StatusEffects>,
+            ),
+            With<Enemy>,
+        >,
     )>,
+    modifiers: Res<DifficultyModifiers>,
     time: Res<Time>,
 ) {
     let player_query = param_set.p0();
-    let player_position = player_query.single().translation;
+    let player_positions: Vec<Vec3> = player_query
+        .iter()
+        .filter(|(_, combat_state)| !combat_state.is_downed())
+        .map(|(transform, _)| transform.translation)
+        .collect();
+
+    if player_positions.is_empty() {
+        return;
+    }
 
     let mut enemy_query = param_set.p1();
-    for (mut k_controller, mut transform) in &mut enemy_query {
+    for (mut k_controller, mut transform, effects) in &mut enemy_query {
+        // chase whichever hermit is closest
+        let player_position = *player_positions
+            .iter()
+            .min_by(|a, b| {
+                a.distance_squared(transform.translation)
+                    .total_cmp(&b.distance_squared(transform.translation))
+            })
+            .unwrap();
+
         // looked kinda cool without normalize tho :eyes:
         let to_player_unit_vector = (player_position - transform.translation).normalize();
-        let speed = 4.0;
+        let speed =
+            4.0 * modifiers.enemy_speed * effects.map_or(1.0, StatusEffects::speed_multiplier);
 
         let mut current_frame_movement = Vec3::ZERO;
         current_frame_movement.y -= 9.81 * time.delta_seconds();
@@ -120,19 +276,50 @@ fn move_enemies(
     }
 }
 
-fn detect_enemy_hit(
+pub fn detect_enemy_hit(
     mut commands: Commands,
-    enemy_controller_output_query: Query<
-        (Entity, &KinematicCharacterControllerOutput),
-        With<Enemy>,
-    >,
+    mut enemy_controller_output_query: Query<(
+        Entity,
+        &Transform,
+        &KinematicCharacterControllerOutput,
+        &mut Enemy,
+    )>,
     projectile_entity_query: Query<(Entity, &Projectile)>,
+    mut status_effect_writer: EventWriter<ApplyStatusEffect>,
+    mut hit_writer: EventWriter<EnemyHit>,
+    mut rng: ResMut<GameRng>,
 ) {
-    for (enemy_entity, enemy_controller) in &enemy_controller_output_query {
+    // a bubble can touch a creature more than once in a frame, it only hurts the first time
+    let mut spent = HashSet::new();
+
+    for (enemy_entity, transform, enemy_controller, mut enemy) in &mut enemy_controller_output_query
+    {
         for collision in &enemy_controller.collisions {
-            if projectile_entity_query.contains(collision.entity) {
+            let Ok((projectile_entity, projectile)) = projectile_entity_query.get(collision.entity)
+            else {
+                continue;
+            };
+            if !spent.insert(projectile_entity) {
+                continue;
+            }
+            commands.entity(projectile_entity).despawn();
+
+            let (damage, critical) = roll_critical(projectile.damage, rng.as_mut());
+            let killed = enemy.take_damage(damage);
+            hit_writer.send(EnemyHit {
+                position: transform.translation,
+                damage,
+                critical,
+                enemy_type: enemy.enemy_type,
+                killed,
+                source: HitSource::Projectile,
+            });
+            if killed {
                 commands.entity(enemy_entity).despawn();
-                commands.entity(collision.entity).despawn();
+                break;
+            }
+            if let Some(kind) = projectile.source_weapon.status_effect {
+                status_effect_writer.send(ApplyStatusEffect::new(enemy_entity, kind));
             }
         }
     }
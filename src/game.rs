@@ -8,18 +8,22 @@ use bevy::pbr::{CascadeShadowConfigBuilder, DirectionalLightShadowMap};
 use bevy::prelude::Projection::Perspective;
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use rand::rngs::StdRng;
+use rand::{random, RngCore, SeedableRng};
 
+use crate::arena_generator::ArenaGeneratorPlugin;
 use crate::collectable::CollectablePlugin;
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
 use crate::debug_camera_controller::DebugCameraControllerPlugin;
 use crate::enemy::EnemyPlugin;
 use crate::game_state::GameState;
 use crate::item_spawner::ItemSpawner;
-use crate::level_loader::{load_level, LevelLoaderPlugin};
+use crate::level_loader::LevelLoaderPlugin;
+use crate::levels::{CurrentLevel, LevelRegistry, LevelsPlugin};
 use crate::player::PlayerPlugin;
-use crate::post_processing::PostProcessSettings;
+use crate::post_processing::PostProcessBundle;
 use crate::projectile::ProjectilePlugin;
-use crate::ui::health_bar::HealthBarPlugin;
-use crate::ui::weapon_selector::WeaponSelectorPlugin;
+use crate::ui::HudPlugin;
 use crate::wave_manager::WaveManagerPlugin;
 use crate::world_item::ItemAttachmentPlugin;
 
@@ -28,12 +32,79 @@ pub struct GamePlugin;
 #[derive(Component)]
 pub struct HolyCam;
 
+// every random roll of a run goes through this, so a run can be replayed with `seed`
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    rng: StdRng,
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+// Everything that bends time multiplies in here, the console's factor, the weapon wheel and
+// hit-stop, so one ending doesn't undo another.
+#[derive(Resource)]
+pub struct TimeScale {
+    pub console: f32,
+    pub weapon_wheel: f32,
+    pub hit_stop: f32,
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self {
+            console: 1.0,
+            weapon_wheel: 1.0,
+            hit_stop: 1.0,
+        }
+    }
+}
+
+impl TimeScale {
+    fn factor(&self) -> f32 {
+        self.console * self.weapon_wheel * self.hit_stop
+    }
+}
+
+fn apply_time_scale(scale: Res<TimeScale>, mut time: ResMut<Time>) {
+    if scale.is_changed() {
+        time.set_relative_speed(scale.factor());
+    }
+}
+
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup);
         app.add_systems(OnEnter(GameState::FightingInArena), reset_camera);
         app.add_plugins((
             LevelLoaderPlugin,
+            LevelsPlugin,
+            ArenaGeneratorPlugin,
             RapierPhysicsPlugin::<NoUserData>::default(),
             RapierDebugRenderPlugin::default().disabled(),
             PlayerPlugin,
@@ -43,16 +114,28 @@ impl Plugin for GamePlugin {
             ItemAttachmentPlugin,
             CollectablePlugin,
             ProjectilePlugin,
-            WeaponSelectorPlugin,
-            HealthBarPlugin,
+            HudPlugin,
         ))
-        .add_systems(Update, debug_render_toggle)
+        .add_systems(Update, (debug_render_toggle, apply_time_scale))
         .insert_resource(AmbientLight {
             color: Color::WHITE,
             brightness: 1.0 / 5.0f32,
         })
         .insert_resource(ClearColor(Color::rgb(0.3, 0.6, 0.9)))
-        .insert_resource(DirectionalLightShadowMap { size: 4096 });
+        .insert_resource(DirectionalLightShadowMap { size: 4096 })
+        .insert_resource(GameRng::new(random()))
+        .init_resource::<TimeScale>();
+
+        app.add_console_command(
+            ConsoleCommand::new("timescale", timescale_command)
+                .usage("<factor>")
+                .help("speed up or slow down the game, 1 is normal speed"),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("seed", seed_command)
+                .usage("[seed]")
+                .help("show the seed of the random rolls, or restart them from a new one"),
+        );
 
         app.add_plugins(DebugCameraControllerPlugin);
     }
@@ -64,6 +147,26 @@ fn debug_render_toggle(mut context: ResMut<DebugRenderContext>, keys: Res<Input<
     }
 }
 
+fn timescale_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let factor: f32 = parse_arg(args, 0, "factor")?;
+    if factor < 0.0 {
+        return Err("time doesn't go backwards".to_string());
+    }
+
+    world.resource_mut::<TimeScale>().console = factor;
+    Ok(format!("time scale set to {factor}"))
+}
+
+fn seed_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    if args.is_empty() {
+        return Ok(format!("seed is {}", world.resource::<GameRng>().seed));
+    }
+
+    let seed: u64 = parse_arg(args, 0, "seed")?;
+    *world.resource_mut::<GameRng>() = GameRng::new(seed);
+    Ok(format!("seed set to {seed}"))
+}
+
 /// set up a simple 3D scene
 fn setup(
     mut commands: Commands,
@@ -71,8 +174,7 @@ fn setup(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
 ) {
-    load_level("map.glb#Scene0", &mut commands, &asset_server);
-
+    // the level itself is loaded by LevelsPlugin
     commands.spawn(AudioBundle {
         source: asset_server.load("song.ogg"),
         settings: PlaybackSettings {
@@ -115,11 +217,7 @@ fn setup(
             }),
             ..default()
         })
-        .insert(PostProcessSettings {
-            time: 0.0,
-            enable: 1.0,
-            ..default()
-        })
+        .insert(PostProcessBundle::default())
         .insert(HolyCam);
 
     // commands.spawn(SceneBundle {
@@ -136,8 +234,10 @@ fn get_camera_position() -> Transform {
 }
 
 fn reset_camera(
-    mut camera_query: Query<(&mut Transform, &mut Projection)>,
+    mut camera_query: Query<(&mut Transform, &mut Projection), With<HolyCam>>,
     mut color: ResMut<ClearColor>,
+    levels: Res<LevelRegistry>,
+    current_level: Res<CurrentLevel>,
 ) {
     let mut a = camera_query.single_mut();
     (*a.0) = get_camera_position();
@@ -145,5 +245,5 @@ fn reset_camera(
     if let Perspective(pers_proj) = a.1.as_mut() {
         pers_proj.fov = 10.0f32.to_radians();
     }
-    color.0 = Color::rgb(0.3, 0.6, 0.9);
+    color.0 = levels.level(current_level.0).clear_color;
 }
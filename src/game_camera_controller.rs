@@ -3,43 +3,127 @@ use bevy::prelude::*;
 
 use crate::game::HolyCam;
 use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::combat::PlayerCombatState;
 use crate::player::PlayerControllerState;
 
+const MAX_CAMERA_ZOOM: f32 = 3.0;
+
+// how far the camera moves at full trauma, and how much trauma wears off per second
+const MAX_SHAKE: f32 = 0.6;
+const SHAKE_DECAY: f32 = 1.5;
+
 pub struct GameCameraControllerPlugin;
 
 impl Plugin for GameCameraControllerPlugin {
     fn build(&self, app: &mut App) {
+        app.init_resource::<CameraShake>();
         app.add_systems(
             Update,
             set_camera.run_if(in_state(GameState::FightingInArena)),
         );
+        // the camera is put back in place when the next fight starts
+        app.add_systems(OnExit(GameState::FightingInArena), stop_shaking);
     }
 }
 
 #[derive(Resource)]
 struct GameCameraState {}
 
+// Trauma goes from 0 to 1 and the shake grows with its square, so small hits barely move the
+// camera and big ones rattle it.
+#[derive(Resource, Default)]
+pub struct CameraShake {
+    trauma: f32,
+    offset: Vec3,
+}
+
+impl CameraShake {
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).min(1.0);
+    }
+
+    // a few sines that don't line up, smooth enough to not look like noise
+    fn sample(&self, t: f32) -> Vec3 {
+        let shake = self.trauma * self.trauma * MAX_SHAKE;
+        vec3(
+            (t * 37.0).sin() + (t * 23.0).sin() * 0.5,
+            0.0,
+            (t * 41.0).cos() + (t * 29.0).cos() * 0.5,
+        ) / 1.5
+            * shake
+    }
+}
+
 pub fn set_camera(
     mut camera_transform_query: Query<
         (&mut Transform, &Camera, &HolyCam),
         Without<PlayerControllerState>,
     >,
-    player: Query<(&Transform, &PlayerControllerState)>,
+    // online hermits have their own camera on their own machine
+    player: Query<
+        (&Transform, &PlayerCombatState),
+        (With<PlayerControllerState>, Without<RemotePlayer>),
+    >,
+    mut shake: ResMut<CameraShake>,
     time: Res<Time>,
 ) {
     let mut camera_transform = camera_transform_query.single_mut().0;
-    let (player_transform, player_controller) = player.single();
+
+    // last frame's shake comes off first, so it doesn't get smoothed into the follow
+    camera_transform.translation -= shake.offset;
+    shake.offset = Vec3::ZERO;
+
+    // frame every hermit still standing, or everyone if they're all down
+    let mut framed: Vec<&Transform> = player
+        .iter()
+        .filter(|(_, combat_state)| !combat_state.is_downed())
+        .map(|(transform, _)| transform)
+        .collect();
+    if framed.is_empty() {
+        framed = player.iter().map(|(transform, _)| transform).collect();
+    }
+    if framed.is_empty() {
+        return;
+    }
+
+    let mut target = Vec3::ZERO;
+    for transform in &framed {
+        target += transform.translation;
+    }
+    target /= framed.len() as f32;
+
+    let mut spread: f32 = 0.0;
+    for transform in &framed {
+        spread = spread.max(transform.translation.distance(target));
+    }
+
+    // solo play looks a bit ahead of the hermit
+    if framed.len() == 1 {
+        target += framed[0].forward() * 1.0;
+    }
+
+    // back the camera off along its view axis so both hermits stay in the narrow fov
+    let zoom = (1.0 + spread / 6.0).min(MAX_CAMERA_ZOOM);
 
     //camera_transform.look_at(player_transform.single().translation, Vec3::Y);
-    let new_pos = player_transform.translation
-        + player_transform.forward() * 1.0
-        + vec3(0.0, 60.0, 30.0)
-        + vec3(0.0, 0.0, -1.0);
+    let new_pos = target + vec3(0.0, 60.0, 30.0) * zoom + vec3(0.0, 0.0, -1.0);
     let mut rate = 0.0025f32.powf(time.delta_seconds());
 
-    if new_pos.distance_squared(camera_transform.translation) > 10.0 * 10.0 {
+    if new_pos.distance_squared(camera_transform.translation) > 10.0 * 10.0 * zoom * zoom {
         rate = 0.0;
     }
 
     camera_transform.translation = camera_transform.translation * rate + new_pos * (1.0 - rate);
+
+    // real time, so the camera still shakes during hit-stop
+    if shake.trauma > 0.0 {
+        shake.offset = shake.sample(time.raw_elapsed_seconds());
+        shake.trauma = (shake.trauma - SHAKE_DECAY * time.raw_delta_seconds()).max(0.0);
+        camera_transform.translation += shake.offset;
+    }
+}
+
+fn stop_shaking(mut shake: ResMut<CameraShake>) {
+    *shake = CameraShake::default();
 }
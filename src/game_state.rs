@@ -1,5 +1,7 @@
 use bevy::{input::keyboard::KeyboardInput, log, prelude::*};
 
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+
 pub struct GameStatePlugin;
 
 #[derive(Debug, Clone, Default, Eq, PartialEq, Hash, States)]
@@ -8,15 +10,47 @@ pub enum GameState {
     TitleScreen,
     FightingInArena,
     ManagingInventory,
+    Lobby,
+    LevelSelect,
+    ItemEditor,
 }
 
 impl Plugin for GameStatePlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_state::<GameState>();
         app.add_systems(Update, process_inputs);
+        app.add_console_command(
+            ConsoleCommand::new("state", state_command)
+                .usage("<state>")
+                .help("switch the game state")
+                .arguments(["title", "arena", "inventory", "lobby", "levels", "editor"]),
+        );
     }
 }
 
+fn state_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let Some(name) = args.first() else {
+        let current = world.resource::<State<GameState>>().get();
+        return Ok(format!("state is {current:?}"));
+    };
+
+    let new_state = match *name {
+        "title" => GameState::TitleScreen,
+        "arena" => GameState::FightingInArena,
+        "inventory" => GameState::ManagingInventory,
+        "lobby" => GameState::Lobby,
+        "levels" => GameState::LevelSelect,
+        "editor" => GameState::ItemEditor,
+        other => return Err(format!("no state called `{other}`")),
+    };
+
+    log::info!("Changing game state to: {new_state:?}");
+    world
+        .resource_mut::<NextState<GameState>>()
+        .set(new_state.clone());
+    Ok(format!("state set to {new_state:?}"))
+}
+
 fn process_inputs(
     mut keyboard_input_events: EventReader<KeyboardInput>,
     current_state: ResMut<State<GameState>>,
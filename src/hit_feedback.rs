@@ -0,0 +1,353 @@
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::enemy::EnemyType;
+use crate::game::{HolyCam, TimeScale};
+use crate::game_camera_controller::CameraShake;
+use crate::game_state::GameState;
+use crate::network::NetworkRole;
+use crate::particles::{Particles, DEATH_BURST};
+
+// The numbers are spawned once per fight and reused round robin, the oldest one gets taken
+// over when they're all busy.
+const DAMAGE_NUMBER_POOL: usize = 32;
+
+const DAMAGE_NUMBER_LIFETIME: f32 = 0.8;
+// world units a number floats up over its lifetime
+const DAMAGE_NUMBER_RISE: f32 = 1.5;
+const DAMAGE_NUMBER_SIZE: f32 = 28.0;
+const CRITICAL_NUMBER_SIZE: f32 = 44.0;
+
+const DAMAGE_COLOR: Color = Color::WHITE;
+const CRITICAL_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+const STATUS_EFFECT_DAMAGE_COLOR: Color = Color::rgb(0.7, 1.0, 0.6);
+
+const BURST_PARTICLES: usize = 12;
+
+// real seconds the fight freezes for after a melee kill, and how slow it goes meanwhile
+const HIT_STOP_SECS: f32 = 0.06;
+const HIT_STOP_TIME_SCALE: f32 = 0.05;
+
+const HIT_TRAUMA: f32 = 0.15;
+const KILL_TRAUMA: f32 = 0.3;
+const CRITICAL_TRAUMA: f32 = 0.4;
+
+pub struct HitFeedbackPlugin;
+
+impl Plugin for HitFeedbackPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EnemyHit>();
+        app.init_resource::<HitFeedbackSettings>();
+        app.init_resource::<HitStop>();
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            (react_to_hits, float_damage_numbers, end_hit_stop)
+                .chain()
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+        app.add_console_command(
+            ConsoleCommand::new("feedback", feedback_command)
+                .usage("[numbers|bursts|hitstop|shake] [on|off]")
+                .help("show or toggle the hit feedback")
+                .arguments(FEEDBACK_SETTINGS),
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum HitSource {
+    Melee,
+    Projectile,
+    StatusEffect,
+    Bomb,
+}
+
+// Sent wherever a creature takes damage. Only the simulation authority resolves hits, so online
+// clients don't get any of this yet.
+#[derive(Event)]
+pub struct EnemyHit {
+    pub position: Vec3,
+    pub damage: i32,
+    pub critical: bool,
+    pub enemy_type: EnemyType,
+    pub killed: bool,
+    pub source: HitSource,
+}
+
+#[derive(Resource)]
+pub struct HitFeedbackSettings {
+    pub damage_numbers: bool,
+    pub death_bursts: bool,
+    pub hit_stop: bool,
+    pub camera_shake: bool,
+}
+
+impl Default for HitFeedbackSettings {
+    fn default() -> Self {
+        Self {
+            damage_numbers: true,
+            death_bursts: true,
+            hit_stop: true,
+            camera_shake: true,
+        }
+    }
+}
+
+const FEEDBACK_SETTINGS: [&str; 4] = ["numbers", "bursts", "hitstop", "shake"];
+
+impl HitFeedbackSettings {
+    fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "numbers" => Some(&mut self.damage_numbers),
+            "bursts" => Some(&mut self.death_bursts),
+            "hitstop" => Some(&mut self.hit_stop),
+            "shake" => Some(&mut self.camera_shake),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Resource, Default)]
+struct HitStop {
+    remaining: f32,
+}
+
+#[derive(Resource)]
+struct DamageNumberPool {
+    entities: Vec<Entity>,
+    next: usize,
+}
+
+impl DamageNumberPool {
+    fn take(&mut self) -> Option<Entity> {
+        let entity = *self.entities.get(self.next)?;
+        self.next = (self.next + 1) % self.entities.len();
+        Some(entity)
+    }
+}
+
+#[derive(Component)]
+struct DamageNumber {
+    position: Vec3,
+    color: Color,
+    age: Timer,
+}
+
+// the whole pool is spawned hidden with finished timers, so it all counts as free
+fn finished_timer(seconds: f32) -> Timer {
+    let mut timer = Timer::from_seconds(seconds, TimerMode::Once);
+    timer.tick(timer.duration());
+    timer
+}
+
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let numbers = (0..DAMAGE_NUMBER_POOL)
+        .map(|_| {
+            commands
+                .spawn(TextBundle {
+                    text: Text::from_section(
+                        "",
+                        TextStyle {
+                            font: font.clone(),
+                            font_size: DAMAGE_NUMBER_SIZE,
+                            color: DAMAGE_COLOR,
+                        },
+                    ),
+                    style: Style {
+                        position_type: Absolute,
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                })
+                .insert(DamageNumber {
+                    position: Vec3::ZERO,
+                    color: DAMAGE_COLOR,
+                    age: finished_timer(DAMAGE_NUMBER_LIFETIME),
+                })
+                .id()
+        })
+        .collect();
+
+    commands.insert_resource(DamageNumberPool {
+        entities: numbers,
+        next: 0,
+    });
+}
+
+#[allow(clippy::too_many_arguments)]
+fn react_to_hits(
+    mut hits: EventReader<EnemyHit>,
+    settings: Res<HitFeedbackSettings>,
+    pool: Option<ResMut<DamageNumberPool>>,
+    mut numbers: Query<(&mut DamageNumber, &mut Text)>,
+    mut particles: ResMut<Particles>,
+    mut shake: ResMut<CameraShake>,
+    mut hit_stop: ResMut<HitStop>,
+    mut time_scale: ResMut<TimeScale>,
+    role: Res<NetworkRole>,
+) {
+    let Some(mut pool) = pool else {
+        return;
+    };
+    // purely cosmetic, so it doesn't touch the game's seeded rolls
+    let mut rng = rand::thread_rng();
+
+    for hit in &mut hits {
+        if settings.damage_numbers {
+            if let Some(Ok((mut number, mut text))) =
+                pool.take().map(|entity| numbers.get_mut(entity))
+            {
+                let (color, size) = match (hit.critical, hit.source) {
+                    (true, _) => (CRITICAL_COLOR, CRITICAL_NUMBER_SIZE),
+                    (false, HitSource::StatusEffect) => {
+                        (STATUS_EFFECT_DAMAGE_COLOR, DAMAGE_NUMBER_SIZE)
+                    }
+                    _ => (DAMAGE_COLOR, DAMAGE_NUMBER_SIZE),
+                };
+                number.position = hit.position + Vec3::Y;
+                number.color = color;
+                number.age.reset();
+                text.sections[0].value = if hit.critical {
+                    format!("{}!", hit.damage)
+                } else {
+                    hit.damage.to_string()
+                };
+                text.sections[0].style.font_size = size;
+            }
+        }
+
+        if hit.killed && settings.death_bursts {
+            particles.burst(
+                &DEATH_BURST,
+                hit.position,
+                Vec3::splat(0.2),
+                BURST_PARTICLES,
+                hit.enemy_type.color(),
+                &mut rng,
+            );
+        }
+
+        if settings.camera_shake {
+            shake.add_trauma(if hit.critical {
+                CRITICAL_TRAUMA
+            } else if hit.killed {
+                KILL_TRAUMA
+            } else {
+                HIT_TRAUMA
+            });
+        }
+
+        // freezing the host would freeze everyone else with it
+        if hit.killed
+            && hit.source == HitSource::Melee
+            && settings.hit_stop
+            && matches!(*role, NetworkRole::Offline)
+        {
+            hit_stop.remaining = HIT_STOP_SECS;
+            time_scale.hit_stop = HIT_STOP_TIME_SCALE;
+        }
+    }
+}
+
+// Numbers float up from where the creature was, fading out as they go. They live in the UI and
+// follow their spot in the world through the camera.
+fn float_damage_numbers(
+    mut numbers: Query<(
+        &mut DamageNumber,
+        &mut Style,
+        &mut Text,
+        &mut Visibility,
+        &Node,
+    )>,
+    camera: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+    time: Res<Time>,
+) {
+    let Ok((camera, camera_transform)) = camera.get_single() else {
+        return;
+    };
+
+    for (mut number, mut style, mut text, mut visibility, node) in &mut numbers {
+        if number.age.finished() {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        }
+        number.age.tick(time.delta());
+
+        let progress = number.age.percent();
+        let position = number.position + Vec3::Y * DAMAGE_NUMBER_RISE * progress;
+        let Some(screen) = camera.world_to_viewport(camera_transform, position) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        // centred on its spot
+        let size = node.size();
+        style.left = Val::Px(screen.x - size.x / 2.0);
+        style.top = Val::Px(screen.y - size.y / 2.0);
+        text.sections[0].style.color = number.color.with_a(1.0 - progress * progress);
+        if *visibility != Visibility::Visible {
+            *visibility = Visibility::Visible;
+        }
+    }
+}
+
+// counted in real time, the game clock is the thing that's stopped
+fn end_hit_stop(mut hit_stop: ResMut<HitStop>, mut time_scale: ResMut<TimeScale>, time: Res<Time>) {
+    if hit_stop.remaining <= 0.0 {
+        return;
+    }
+
+    hit_stop.remaining -= time.raw_delta_seconds();
+    if hit_stop.remaining <= 0.0 {
+        time_scale.hit_stop = 1.0;
+    }
+}
+
+fn clean(
+    mut commands: Commands,
+    pool: Option<Res<DamageNumberPool>>,
+    mut hit_stop: ResMut<HitStop>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    if let Some(pool) = pool {
+        for entity in &pool.entities {
+            commands.entity(*entity).despawn_recursive();
+        }
+        commands.remove_resource::<DamageNumberPool>();
+    }
+
+    hit_stop.remaining = 0.0;
+    time_scale.hit_stop = 1.0;
+}
+
+fn feedback_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut settings = world.resource_mut::<HitFeedbackSettings>();
+    if args.is_empty() {
+        return Ok(format!(
+            "numbers {}, bursts {}, hitstop {}, shake {}",
+            settings.damage_numbers,
+            settings.death_bursts,
+            settings.hit_stop,
+            settings.camera_shake
+        ));
+    }
+
+    let name: String = parse_arg(args, 0, "setting")?;
+    let setting = settings
+        .get_mut(&name)
+        .ok_or_else(|| format!("no setting called `{name}`"))?;
+    *setting = match args.get(1) {
+        Some(&"on") => true,
+        Some(&"off") => false,
+        Some(other) => return Err(format!("expected on or off, got `{other}`")),
+        None => !*setting,
+    };
+    Ok(format!("{name} {}", if *setting { "on" } else { "off" }))
+}
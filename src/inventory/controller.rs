@@ -3,12 +3,12 @@ use std::time::Duration;
 use bevy::prelude::Projection::Perspective;
 use bevy::prelude::*;
 
-use crate::config::{DEFAULT_BAG_LOCATION, INVENTORY_GRID_DIMENSIONS};
+use crate::config::DEFAULT_BAG_LOCATION;
 use crate::game::HolyCam;
 use crate::game_state::GameState;
 use crate::inventory::gizmo::update_gizmo_position;
 use crate::inventory::selection::SelectedItem;
-use crate::inventory::{InventoryData, InventoryItem, PackedInventoryItem};
+use crate::inventory::{BagSize, InventoryData, InventoryItem, PackedInventoryItem};
 
 use super::gizmo::highlight_gizmo;
 
@@ -19,13 +19,16 @@ impl Plugin for InventoryControllerPlugin {
         app.add_systems(Startup, setup);
         app.add_systems(
             Update,
-            update_gizmo_position
-                .after(update_cube_rotation)
-                .run_if(in_state(GameState::ManagingInventory)),
+            update_gizmo_position.after(update_cube_rotation).run_if(
+                in_state(GameState::ManagingInventory).or_else(in_state(GameState::ItemEditor)),
+            ),
         );
         app.add_systems(
             Update,
-            update_cube_rotation.run_if(in_state(GameState::ManagingInventory)),
+            // the item editor looks at the bag the same way
+            update_cube_rotation.run_if(
+                in_state(GameState::ManagingInventory).or_else(in_state(GameState::ItemEditor)),
+            ),
         );
         app.add_systems(
             Update,
@@ -37,6 +40,7 @@ impl Plugin for InventoryControllerPlugin {
         );
         app.add_systems(Update, highlight_gizmo);
         app.add_systems(OnEnter(GameState::ManagingInventory), set_fov);
+        app.add_systems(OnEnter(GameState::ItemEditor), set_fov);
         app.insert_resource(InventoryControllerState::new());
         app.insert_resource(CubeRotationAnime::new());
     }
@@ -158,12 +162,16 @@ fn update_cube_rotation(
     camera_transform.rotation = look_at_my_balls.rotation;
 }
 
-pub fn update_inventory_data(query: Query<&PackedInventoryItem>, mut inv: ResMut<InventoryData>) {
+pub fn update_inventory_data(
+    query: Query<&PackedInventoryItem>,
+    mut inv: ResMut<InventoryData>,
+    bag: Res<BagSize>,
+) {
     let mut items: Vec<InventoryItem> = Vec::new();
     for p in query.iter() {
         items.push(p.data.clone())
     }
-    inv.grid = InventoryData::grid_from_items(items, IVec3::from_array(INVENTORY_GRID_DIMENSIONS))
+    inv.grid = InventoryData::grid_from_items(items, bag.0)
 }
 
 #[derive(Debug, Copy, Clone)]
@@ -231,6 +239,27 @@ pub fn move_item(item: &mut PackedInventoryItem, item_dir: ItemDirection, view_i
     }
 }
 
+// where the camera looks from, for each view_index
+fn view_forward(view_index: usize) -> IVec3 {
+    [IVec3::NEG_X, IVec3::NEG_Z, IVec3::X, IVec3::Z][view_index % 4]
+}
+
+// turns are relative to the camera, pitching forward always tips the top away from it
+pub fn rotate_item(item: &mut PackedInventoryItem, item_dir: ItemDirection, view_index: usize) {
+    let forward = view_forward(view_index);
+    let axis = match item_dir {
+        ItemDirection::YAW_LEFT => IVec3::Y,
+        ItemDirection::YAW_RIGHT => IVec3::NEG_Y,
+        ItemDirection::PITCH_FORWARD => IVec3::Y.cross(forward),
+        ItemDirection::PITCH_BACKWARDS => forward.cross(IVec3::Y),
+        // tips the top to the right of the screen
+        ItemDirection::ROLL_RIGHT => forward,
+        ItemDirection::ROLL_LEFT => -forward,
+        _ => return,
+    };
+    item.data.turn(axis);
+}
+
 fn move_inventory_items(
     state: Res<InventoryControllerState>,
     key_codes: Res<Input<KeyCode>>,
@@ -264,25 +293,41 @@ fn move_inventory_items(
     } else if key_codes.just_pressed(KeyCode::Q) {
         for mut item in query_items.iter_mut() {
             if Some(item.0) == selected.selected_entity {
-                item.1.data.rotate_y(true);
+                rotate_item(&mut item.1, ItemDirection::YAW_LEFT, state.view_index);
             }
         }
     } else if key_codes.just_pressed(KeyCode::E) {
         for mut item in query_items.iter_mut() {
             if Some(item.0) == selected.selected_entity {
-                item.1.data.rotate_y(false);
+                rotate_item(&mut item.1, ItemDirection::YAW_RIGHT, state.view_index);
             }
         }
     } else if key_codes.just_pressed(KeyCode::R) {
         for mut item in query_items.iter_mut() {
             if Some(item.0) == selected.selected_entity {
-                item.1.data.rotate_z(true);
+                rotate_item(&mut item.1, ItemDirection::PITCH_FORWARD, state.view_index);
             }
         }
     } else if key_codes.just_pressed(KeyCode::F) {
         for mut item in query_items.iter_mut() {
             if Some(item.0) == selected.selected_entity {
-                item.1.data.rotate_z(false);
+                rotate_item(
+                    &mut item.1,
+                    ItemDirection::PITCH_BACKWARDS,
+                    state.view_index,
+                );
+            }
+        }
+    } else if key_codes.just_pressed(KeyCode::T) {
+        for mut item in query_items.iter_mut() {
+            if Some(item.0) == selected.selected_entity {
+                rotate_item(&mut item.1, ItemDirection::ROLL_LEFT, state.view_index);
+            }
+        }
+    } else if key_codes.just_pressed(KeyCode::G) {
+        for mut item in query_items.iter_mut() {
+            if Some(item.0) == selected.selected_entity {
+                rotate_item(&mut item.1, ItemDirection::ROLL_RIGHT, state.view_index);
             }
         }
     } else if key_codes.just_pressed(KeyCode::Z) {
@@ -300,7 +345,7 @@ fn move_inventory_items(
     }
 }
 
-fn set_fov(mut camera_query: Query<(&mut Transform, &mut Projection)>) {
+fn set_fov(mut camera_query: Query<(&mut Transform, &mut Projection), With<HolyCam>>) {
     let mut proj = camera_query.single_mut().1;
 
     if let Perspective(pers_proj) = proj.as_mut() {
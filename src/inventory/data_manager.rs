@@ -1,8 +1,11 @@
 use bevy::log;
 use bevy::prelude::*;
+use std::collections::VecDeque;
+
 use crate::collectable::ItemCollectEvent;
 use crate::game_state::GameState;
 use crate::inventory::Inventory;
+use crate::network::NetworkRole;
 
 pub struct InventoryDataPlugin;
 
@@ -12,21 +15,38 @@ impl Plugin for InventoryDataPlugin {
             Update,
             handle_item_collect.run_if(in_state(GameState::FightingInArena)),
         );
+        app.insert_resource(PackingTurns(VecDeque::new()));
     }
 }
 
+// hermits waiting for their turn in the packing screen, in pickup order
+#[derive(Resource)]
+pub struct PackingTurns(pub VecDeque<usize>);
+
 pub fn handle_item_collect(
     mut item_collect_event_reader: EventReader<ItemCollectEvent>,
     mut next_state: ResMut<NextState<GameState>>,
     mut inventory: ResMut<Inventory>,
+    mut packing_turns: ResMut<PackingTurns>,
+    network_role: Res<NetworkRole>,
 ) {
-    if item_collect_event_reader.len() > 0 {
-        for item in &mut item_collect_event_reader {
-            log::info!("Player collected item: {:?}", item.0);
-            inventory.content.push(item.0.clone());
+    for item in &mut item_collect_event_reader {
+        log::info!("Player {} collected item: {:?}", item.1 + 1, item.0);
+        inventory.items_of_mut(item.1).push(item.0.clone());
+
+        // online hermits pack on their own screen, the host forwards the item to them
+        if network_role.is_remote_player(item.1) {
+            continue;
         }
 
-        let new_state = GameState::ManagingInventory;
-        next_state.set(new_state);
+        if !packing_turns.0.contains(&item.1) {
+            packing_turns.0.push_back(item.1);
+        }
     }
-}
\ No newline at end of file
+
+    // one hermit packs at a time, the next one gets the screen once we're back in the arena
+    if let Some(player_index) = packing_turns.0.pop_front() {
+        inventory.set_owner(player_index);
+        next_state.set(GameState::ManagingInventory);
+    }
+}
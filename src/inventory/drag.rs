@@ -0,0 +1,257 @@
+use bevy::input::mouse::MouseWheel;
+use bevy::math::vec3;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+use bevy::window::PrimaryWindow;
+use bevy_mod_raycast::{ray_intersection_over_mesh, Backfaces, Ray3d};
+
+use crate::config::{DEFAULT_BAG_LOCATION, INVENTORY_GRID_DIMENSIONS};
+use crate::game::HolyCam;
+use crate::game_state::GameState;
+use crate::inventory::gizmo::{is_hovered, Gizmo};
+use crate::inventory::selection::SelectedItem;
+use crate::inventory::{BagSize, InventoryItem, PackedInventoryItem};
+
+const GHOST_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.45);
+const BLOCKED_GHOST_COLOR: Color = Color::rgba(1.0, 0.15, 0.15, 0.55);
+
+pub struct InventoryDragPlugin;
+
+impl Plugin for InventoryDragPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DraggedItem>();
+        app.add_systems(
+            Update,
+            (grab_item, drag_ghost, drop_item)
+                .chain()
+                .run_if(in_state(GameState::ManagingInventory)),
+        );
+        app.add_systems(OnExit(GameState::ManagingInventory), clean);
+    }
+}
+
+// The item stays where it is while a ghost follows the mouse, it only moves on a valid drop.
+struct Drag {
+    entity: Entity,
+    // where the item would go, with the turns done while dragging
+    item: InventoryItem,
+    // the voxel that was clicked, it stays under the cursor
+    grab_index: usize,
+    // grid height of the grabbed voxel, the scroll wheel changes it
+    layer: i32,
+    ghost: Entity,
+    fits: bool,
+}
+
+#[derive(Resource, Default)]
+struct DraggedItem(Option<Drag>);
+
+// world position of grid cell (0, 0, 0), same offset update_packed_items uses
+fn grid_origin() -> Vec3 {
+    DEFAULT_BAG_LOCATION
+        - vec3(
+            (INVENTORY_GRID_DIMENSIONS[0] / 2) as f32,
+            (INVENTORY_GRID_DIMENSIONS[1] / 2) as f32,
+            (INVENTORY_GRID_DIMENSIONS[2] / 2) as f32,
+        )
+}
+
+fn cursor_ray(
+    window: &Window,
+    camera_query: &Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+) -> Option<Ray3d> {
+    let (camera, camera_transform) = camera_query.get_single().ok()?;
+    Ray3d::from_screenspace(window.cursor_position()?, camera, camera_transform)
+}
+
+// every voxel inside the bag and none on top of another item
+pub(super) fn fits(item: &InventoryItem, occupied: &HashSet<IVec3>, size: IVec3) -> bool {
+    item.local_points.iter().all(|point| {
+        let cell = item.location + *point;
+        cell.cmpge(IVec3::ZERO).all() && cell.cmplt(size).all() && !occupied.contains(&cell)
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn grab_item(
+    mut commands: Commands,
+    mouse_input: Res<Input<MouseButton>>,
+    query_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+    gizmos: Query<&Transform, With<Gizmo>>,
+    buttons: Query<&Interaction, With<Button>>,
+    items: Query<(Entity, &Transform, &Handle<Mesh>, &PackedInventoryItem)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut dragged: ResMut<DraggedItem>,
+    mut selected: ResMut<SelectedItem>,
+) {
+    if dragged.0.is_some() || !mouse_input.just_pressed(MouseButton::Left) {
+        return;
+    }
+    // the gizmos and the buttons have their own use for that click
+    if gizmos.iter().any(is_hovered)
+        || buttons
+            .iter()
+            .any(|interaction| *interaction != Interaction::None)
+    {
+        return;
+    }
+    let Some(ray) = cursor_ray(query_window.single(), &camera_query) else {
+        return;
+    };
+
+    let closest = items
+        .iter()
+        .filter_map(|(entity, transform, mesh, packed)| {
+            let hit = ray_intersection_over_mesh(
+                meshes.get(mesh)?,
+                &transform.compute_matrix(),
+                &ray,
+                Backfaces::Cull,
+            )?;
+            Some((entity, transform, packed, hit))
+        })
+        .min_by(|a, b| a.3.distance().total_cmp(&b.3.distance()));
+    let Some((entity, transform, packed, hit)) = closest else {
+        return;
+    };
+
+    let grabbed = (hit.position() - transform.translation - hit.normal() * 0.5)
+        .round()
+        .as_ivec3();
+    let Some(grab_index) = packed.data.local_points.iter().position(|p| *p == grabbed) else {
+        return;
+    };
+
+    let ghost = commands
+        .spawn(PbrBundle {
+            mesh: meshes.add(packed.data.generate_mesh(false)),
+            material: materials.add(StandardMaterial {
+                base_color: GHOST_COLOR,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            transform: *transform,
+            ..default()
+        })
+        .insert(NotShadowCaster)
+        .id();
+
+    selected.selected_entity = Some(entity);
+    dragged.0 = Some(Drag {
+        entity,
+        item: packed.data.clone(),
+        grab_index,
+        layer: packed.data.location.y + grabbed.y,
+        ghost,
+        fits: true,
+    });
+}
+
+// Snaps the ghost to the cell under the cursor on the current layer. Scrolling changes the layer
+// and right click turns the item.
+#[allow(clippy::too_many_arguments)]
+fn drag_ghost(
+    mouse_input: Res<Input<MouseButton>>,
+    mut scroll_events: EventReader<MouseWheel>,
+    query_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+    items: Query<(Entity, &PackedInventoryItem)>,
+    mut ghosts: Query<(&mut Transform, &mut Handle<Mesh>, &Handle<StandardMaterial>)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut dragged: ResMut<DraggedItem>,
+    bag: Res<BagSize>,
+) {
+    let Some(drag) = dragged.0.as_mut() else {
+        scroll_events.clear();
+        return;
+    };
+
+    let scroll: f32 = scroll_events.iter().map(|event| event.y).sum();
+    if scroll != 0.0 {
+        drag.layer = (drag.layer + scroll.signum() as i32).clamp(0, bag.0.y - 1);
+    }
+
+    let Ok((mut transform, mut mesh, material)) = ghosts.get_mut(drag.ghost) else {
+        return;
+    };
+
+    if mouse_input.just_pressed(MouseButton::Right) {
+        drag.item.rotate_y(true);
+        *mesh = meshes.add(drag.item.generate_mesh(false));
+    }
+
+    let grabbed = drag.item.local_points[drag.grab_index];
+    if let Some(ray) = cursor_ray(query_window.single(), &camera_query) {
+        let origin = grid_origin();
+        let plane_height = origin.y + drag.layer as f32;
+        let distance = (plane_height - ray.origin().y) / ray.direction().y;
+        if distance.is_finite() && distance > 0.0 {
+            let cursor = ray.origin() + ray.direction() * distance;
+            let cell = (cursor - origin).round().as_ivec3();
+            drag.item.location = IVec3::new(cell.x, drag.layer, cell.z) - grabbed;
+        }
+    }
+    // keeps the grabbed voxel on the layer even when the cursor is off the plane
+    drag.item.location.y = drag.layer - grabbed.y;
+
+    let occupied: HashSet<IVec3> = items
+        .iter()
+        .filter(|(entity, _)| *entity != drag.entity)
+        .flat_map(|(_, packed)| {
+            let location = packed.data.location;
+            packed
+                .data
+                .local_points
+                .iter()
+                .map(move |point| location + *point)
+        })
+        .collect();
+    drag.fits = fits(&drag.item, &occupied, bag.0);
+
+    transform.translation = grid_origin() + drag.item.location.as_vec3();
+    let color = if drag.fits {
+        GHOST_COLOR
+    } else {
+        BLOCKED_GHOST_COLOR
+    };
+    if materials.get(material).map(|m| m.base_color) != Some(color) {
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color = color;
+        }
+    }
+}
+
+// a drop that doesn't fit leaves the item where it was picked up
+fn drop_item(
+    mut commands: Commands,
+    mouse_input: Res<Input<MouseButton>>,
+    mut items: Query<&mut PackedInventoryItem>,
+    mut dragged: ResMut<DraggedItem>,
+) {
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(drag) = dragged.0.take() else {
+        return;
+    };
+
+    commands.entity(drag.ghost).despawn_recursive();
+    if !drag.fits {
+        return;
+    }
+    if let Ok(mut packed) = items.get_mut(drag.entity) {
+        packed.data = drag.item;
+        packed.data.changed = true;
+    }
+}
+
+fn clean(mut commands: Commands, mut dragged: ResMut<DraggedItem>) {
+    if let Some(drag) = dragged.0.take() {
+        commands.entity(drag.ghost).despawn_recursive();
+    }
+}
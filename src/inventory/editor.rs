@@ -0,0 +1,611 @@
+use std::fs;
+use std::path::PathBuf;
+
+use bevy::log;
+use bevy::math::vec3;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_mod_raycast::{ray_intersection_over_mesh, Backfaces, Ray3d};
+
+use crate::asset_loader::GameAssets;
+use crate::config::DEFAULT_BAG_LOCATION;
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+use crate::game::HolyCam;
+use crate::game_state::GameState;
+use crate::inventory::gizmo::{is_hovered, Gizmo};
+use crate::inventory::orientation::Orientation;
+use crate::inventory::selection::SelectedItem;
+use crate::inventory::ItemType::{CONSUMABLE, MELEE_WEAPON, NON_WEAPON, RANGED_WEAPON};
+use crate::inventory::{
+    spawn_gizmos, update_packed_items, InventoryItem, ItemTypeId, PackedInventoryItem,
+};
+use crate::item_spawner::catalog_item;
+use crate::status_effect::StatusEffectKind;
+
+// where `Return` writes the items, one file per item type id
+const EXPORT_DIRECTORY: &str = "exported_items";
+
+const PALETTE: [Color; 10] = [
+    Color::WHITE,
+    Color::rgb(0.5, 0.5, 0.5),
+    Color::rgb(0.1, 0.1, 0.1),
+    Color::rgb(0.9, 0.2, 0.2),
+    Color::rgb(1.0, 0.55, 0.1),
+    Color::rgb(0.85, 0.65, 0.15),
+    Color::rgb(0.2, 0.8, 0.2),
+    Color::rgb(0.2, 0.5, 0.95),
+    Color::rgb(0.6, 0.3, 0.8),
+    Color::rgb(0.45, 0.28, 0.12),
+];
+
+const STATS: [&str; 8] = [
+    "hp_gain",
+    "attack_damage_gain",
+    "attack_speed_gain",
+    "weapon_damage",
+    "weapon_attack_speed",
+    "weapon_is_auto",
+    "projectile_speed",
+    "status_effect",
+];
+
+pub struct ItemEditorPlugin;
+
+// the item on the bench, kept between visits so the editor opens where it was left
+#[derive(Resource)]
+pub struct ItemEditor {
+    pub item: InventoryItem,
+    palette_index: usize,
+    preview: bool,
+    // last thing that happened, shown under the stats
+    status: String,
+}
+
+impl Default for ItemEditor {
+    fn default() -> Self {
+        Self {
+            item: blank_item(),
+            palette_index: 0,
+            preview: true,
+            status: String::new(),
+        }
+    }
+}
+
+// while the editor is open the item lives on this entity, so the gizmos can move and turn it
+#[derive(Component)]
+struct EditedItem;
+
+#[derive(Component)]
+struct ItemPreview;
+
+#[derive(Component)]
+struct ItemEditorUi;
+
+#[derive(Component)]
+struct ItemEditorText;
+
+impl Plugin for ItemEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ItemEditor>();
+        app.add_console_command(
+            ConsoleCommand::new("editor", editor_command)
+                .usage("[item|new|export|set <stat> <value>]")
+                .help("open the item editor on a catalog item, or change the one being edited")
+                .arguments(
+                    ItemTypeId::ALL
+                        .iter()
+                        .map(|item_type_id| item_type_id.name())
+                        .chain(["new", "export", "set"].map(String::from)),
+                ),
+        );
+
+        app.add_systems(OnEnter(GameState::ItemEditor), on_enter);
+        app.add_systems(OnExit(GameState::ItemEditor), clean);
+        app.add_systems(
+            Update,
+            (
+                keep_colors_on_rotation,
+                edit_voxels,
+                editor_keys,
+                refresh_edited_item,
+                spin_preview,
+                update_editor_text,
+            )
+                .chain()
+                .before(update_packed_items)
+                .run_if(in_state(GameState::ItemEditor)),
+        );
+    }
+}
+
+fn blank_item() -> InventoryItem {
+    InventoryItem::from((
+        (3, 0, 3),
+        vec![(0, 0, 0)],
+        Color::WHITE,
+        NON_WEAPON,
+        ItemTypeId::Heart,
+    ))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn on_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    game_assets: Res<GameAssets>,
+    editor: Res<ItemEditor>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut selection: ResMut<SelectedItem>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    spawn_gizmos(&mut commands, &game_assets);
+
+    let mut item = editor.item.clone();
+    // builds the preview, update_packed_items puts it on the grid
+    item.changed = true;
+    selection.selected_entity = Some(
+        commands
+            .spawn((EditedItem, PackedInventoryItem { data: item.clone() }))
+            .insert(PbrBundle {
+                mesh: meshes.add(item.generate_mesh(false)),
+                material: materials.add(item.material()),
+                transform: Transform::from_translation(DEFAULT_BAG_LOCATION),
+                ..default()
+            })
+            .id(),
+    );
+    clear_color.0 = Color::rgb(0.3, 0.6, 0.9);
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                position_type: PositionType::Absolute,
+                padding: UiRect::all(Val::Px(20.0)),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(ItemEditorUi)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 24.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                ))
+                .insert(ItemEditorText);
+        });
+}
+
+fn clean(
+    mut commands: Commands,
+    mut editor: ResMut<ItemEditor>,
+    edited: Query<(Entity, &PackedInventoryItem), With<EditedItem>>,
+    leftovers: Query<Entity, Or<(With<Gizmo>, With<ItemPreview>, With<ItemEditorUi>)>>,
+    mut selection: ResMut<SelectedItem>,
+) {
+    for (entity, packed) in &edited {
+        editor.item = packed.data.clone();
+        commands.entity(entity).despawn_recursive();
+    }
+    for entity in &leftovers {
+        commands.entity(entity).despawn_recursive();
+    }
+    selection.selected_entity = None;
+}
+
+// Left click adds a voxel on the face under the cursor, right click takes that voxel away.
+// With shift held they paint the voxel with the palette colour, or wipe its paint.
+fn edit_voxels(
+    mouse_input: Res<Input<MouseButton>>,
+    keys: Res<Input<KeyCode>>,
+    meshes: Res<Assets<Mesh>>,
+    query_window: Query<&Window, With<PrimaryWindow>>,
+    camera_query: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+    gizmos: Query<&Transform, With<Gizmo>>,
+    mut edited: Query<(&Transform, &Handle<Mesh>, &mut PackedInventoryItem), With<EditedItem>>,
+    mut editor: ResMut<ItemEditor>,
+) {
+    let left = mouse_input.just_pressed(MouseButton::Left);
+    let right = mouse_input.just_pressed(MouseButton::Right);
+    if !left && !right {
+        return;
+    }
+
+    if gizmos.iter().any(is_hovered) {
+        return;
+    }
+
+    let Some(cursor) = query_window.single().cursor_position() else {
+        return;
+    };
+    let (camera, camera_transform) = camera_query.single();
+    let Some(ray) = Ray3d::from_screenspace(cursor, camera, camera_transform) else {
+        return;
+    };
+    let Ok((transform, mesh, mut packed)) = edited.get_single_mut() else {
+        return;
+    };
+    let Some(hit) = meshes.get(mesh).and_then(|mesh| {
+        ray_intersection_over_mesh(mesh, &transform.compute_matrix(), &ray, Backfaces::Cull)
+    }) else {
+        return;
+    };
+
+    // voxels sit on whole numbers around the item's origin, the faces half a voxel away
+    let local = hit.position() - transform.translation;
+    let inside = (local - hit.normal() * 0.5).round().as_ivec3();
+    let outside = (local + hit.normal() * 0.5).round().as_ivec3();
+    let painting = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    let item = &mut packed.data;
+    match (left, painting) {
+        (true, false) => {
+            if !item.local_points.contains(&outside) {
+                item.local_points.push(outside);
+                item.original_points.push(outside);
+            }
+        }
+        (false, false) => {
+            if item.local_points.len() == 1 {
+                editor.status = "an item needs at least one voxel".to_string();
+                return;
+            }
+            if let Some(index) = item.local_points.iter().position(|p| *p == inside) {
+                item.local_points.remove(index);
+                item.original_points.remove(index);
+                item.voxel_colors.remove(&inside);
+            }
+        }
+        (true, true) => {
+            item.voxel_colors
+                .insert(inside, PALETTE[editor.palette_index]);
+        }
+        (false, true) => {
+            item.voxel_colors.remove(&inside);
+        }
+    }
+    item.changed = true;
+}
+
+// Tab picks the next palette colour and B makes it the item colour, T and I go through the item
+// types and ids, P shows the item at world scale, Return exports it and Escape leaves
+fn editor_keys(
+    keys: Res<Input<KeyCode>>,
+    mut editor: ResMut<ItemEditor>,
+    mut edited: Query<&mut PackedInventoryItem, With<EditedItem>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Ok(mut packed) = edited.get_single_mut() else {
+        return;
+    };
+
+    if keys.just_pressed(KeyCode::Tab) {
+        editor.palette_index = (editor.palette_index + 1) % PALETTE.len();
+    }
+    if keys.just_pressed(KeyCode::B) {
+        packed.data.color = PALETTE[editor.palette_index];
+        packed.data.changed = true;
+    }
+    if keys.just_pressed(KeyCode::T) {
+        packed.data.item_type = match packed.data.item_type {
+            MELEE_WEAPON => RANGED_WEAPON,
+            RANGED_WEAPON => NON_WEAPON,
+            NON_WEAPON => CONSUMABLE,
+            CONSUMABLE => MELEE_WEAPON,
+        };
+    }
+    if keys.just_pressed(KeyCode::I) {
+        let index = ItemTypeId::ALL
+            .iter()
+            .position(|id| *id == packed.data.item_type_id)
+            .unwrap_or(0);
+        packed.data.item_type_id = ItemTypeId::ALL[(index + 1) % ItemTypeId::ALL.len()];
+    }
+    if keys.just_pressed(KeyCode::P) {
+        editor.preview = !editor.preview;
+        // gets the preview rebuilt or removed
+        packed.data.changed = true;
+    }
+    if keys.just_pressed(KeyCode::Return) {
+        editor.status = match export(&packed.data) {
+            Ok(path) => format!("exported to {}", path.display()),
+            Err(error) => error,
+        };
+    }
+    if keys.just_pressed(KeyCode::Escape) {
+        next_state.set(GameState::TitleScreen);
+    }
+}
+
+// The gizmos turn the local points, in the editor that's turning the shape itself. The paint is
+// keyed by original point, so it has to follow.
+fn keep_colors_on_rotation(mut edited: Query<&mut PackedInventoryItem, With<EditedItem>>) {
+    for mut packed in &mut edited {
+        if packed.data.local_points == packed.data.original_points {
+            continue;
+        }
+
+        let item = &mut packed.data;
+        item.voxel_colors = item
+            .original_points
+            .iter()
+            .zip(&item.local_points)
+            .filter_map(|(original, local)| {
+                item.voxel_colors
+                    .get(original)
+                    .map(|color| (*local, *color))
+            })
+            .collect();
+        item.original_points = item.local_points.clone();
+        item.orientation = Orientation::IDENTITY;
+        item.changed = true;
+    }
+}
+
+// update_packed_items rebuilds the mesh, this does the material and the world scale preview
+fn refresh_edited_item(
+    mut commands: Commands,
+    editor: Res<ItemEditor>,
+    edited: Query<(&PackedInventoryItem, &Handle<StandardMaterial>), With<EditedItem>>,
+    previews: Query<Entity, With<ItemPreview>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Ok((packed, material)) = edited.get_single() else {
+        return;
+    };
+    if !packed.data.changed {
+        return;
+    }
+
+    if let Some(material) = materials.get_mut(material) {
+        *material = packed.data.material();
+    }
+
+    for entity in &previews {
+        commands.entity(entity).despawn_recursive();
+    }
+    if editor.preview {
+        // floats over the bag, next to the voxels it's made of
+        let preview = packed.data.create_world_entity(
+            DEFAULT_BAG_LOCATION + vec3(0.0, 4.0, 0.0),
+            false,
+            false,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
+        commands.entity(preview).insert(ItemPreview);
+    }
+}
+
+fn spin_preview(time: Res<Time>, mut previews: Query<&mut Transform, With<ItemPreview>>) {
+    for mut transform in &mut previews {
+        transform.rotate_y(time.delta_seconds());
+    }
+}
+
+fn update_editor_text(
+    editor: Res<ItemEditor>,
+    edited: Query<&PackedInventoryItem, With<EditedItem>>,
+    mut text_query: Query<&mut Text, With<ItemEditorText>>,
+) {
+    let Ok(packed) = edited.get_single() else {
+        return;
+    };
+    let Ok(mut text) = text_query.get_single_mut() else {
+        return;
+    };
+
+    let item = &packed.data;
+    let mut lines = vec![
+        format!("{:?} ({:?})", item.item_type_id, item.item_type),
+        format!("{} voxels at {}", item.local_points.len(), item.location),
+    ];
+    lines.extend(
+        STATS
+            .iter()
+            .map(|stat| format!("{stat}: {}", stat_value(item, stat))),
+    );
+    lines.push(String::new());
+    lines.push("click: add, right click: remove, shift: paint".to_string());
+    lines.push("tab: colour, b: item colour, t: type, i: id".to_string());
+    lines.push("p: preview, enter: export, esc: leave".to_string());
+    lines.push("`editor set <stat> <value>` in the console".to_string());
+    lines.push(editor.status.clone());
+
+    let section = &mut text.sections[0];
+    section.value = lines.join("\n");
+    // so the palette colour can be seen without painting
+    section.style.color = PALETTE[editor.palette_index];
+}
+
+fn stat_value(item: &InventoryItem, stat: &str) -> String {
+    match stat {
+        "hp_gain" => item.hp_gain.to_string(),
+        "attack_damage_gain" => item.attack_damage_gain.to_string(),
+        "attack_speed_gain" => float(item.attack_speed_gain),
+        "weapon_damage" => item.weapon_damage.to_string(),
+        "weapon_attack_speed" => float(item.weapon_attack_speed),
+        "weapon_is_auto" => item.weapon_is_auto.to_string(),
+        "projectile_speed" => float(item.projectile_speed),
+        // written the way the catalog sets it
+        "status_effect" => match item.status_effect {
+            Some(kind) => format!("Some(StatusEffectKind::{kind:?})"),
+            None => "None".to_string(),
+        },
+        _ => unreachable!(),
+    }
+}
+
+fn set_stat(item: &mut InventoryItem, stat: &str, value: &str) -> Result<(), String> {
+    let invalid = |_| format!("`{value}` is not a valid {stat}");
+    match stat {
+        "hp_gain" => item.hp_gain = value.parse().map_err(invalid)?,
+        "attack_damage_gain" => item.attack_damage_gain = value.parse().map_err(invalid)?,
+        "weapon_damage" => item.weapon_damage = value.parse().map_err(invalid)?,
+        "attack_speed_gain" => item.attack_speed_gain = parse_float(stat, value)?,
+        "weapon_attack_speed" => item.weapon_attack_speed = parse_float(stat, value)?,
+        "projectile_speed" => item.projectile_speed = parse_float(stat, value)?,
+        "weapon_is_auto" => {
+            item.weapon_is_auto = value
+                .parse()
+                .map_err(|_| format!("`{value}` is not a valid {stat}, true or false"))?
+        }
+        "status_effect" => {
+            item.status_effect = match value {
+                "none" => None,
+                name => Some(StatusEffectKind::from_name(name).ok_or_else(|| {
+                    format!("`{value}` is not a valid {stat}, try none or an effect name")
+                })?),
+            }
+        }
+        other => {
+            return Err(format!(
+                "no stat called `{other}`, try {}",
+                STATS.join(", ")
+            ))
+        }
+    }
+    Ok(())
+}
+
+fn parse_float(stat: &str, value: &str) -> Result<f32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{value}` is not a valid {stat}"))
+}
+
+// `1.0` rather than `1`, the snippet has to compile
+fn float(value: f32) -> String {
+    format!("{value:?}")
+}
+
+fn color_literal(color: Color) -> String {
+    let [r, g, b, a] = color.as_rgba_f32();
+    format!(
+        "Color::rgba({}, {}, {}, {})",
+        float(r),
+        float(g),
+        float(b),
+        float(a)
+    )
+}
+
+fn tuple(point: IVec3) -> String {
+    format!("({}, {}, {})", point.x, point.y, point.z)
+}
+
+// the item as a match arm of catalog_item, only the stats that differ from the defaults are set
+pub fn catalog_snippet(item: &InventoryItem) -> String {
+    let defaults = InventoryItem::from((
+        (0, 0, 0),
+        Vec::new(),
+        item.color,
+        item.item_type.clone(),
+        item.item_type_id,
+    ));
+
+    let mut out = format!("        ItemTypeId::{:?} => {{\n", item.item_type_id);
+    out += "            let mut item = InventoryItem::from((\n";
+    out += &format!("                {},\n", tuple(item.location));
+    out += "                vec![\n";
+    for point in &item.original_points {
+        out += &format!("                    {},\n", tuple(*point));
+    }
+    out += "                ],\n";
+    out += &format!("                {},\n", color_literal(item.color));
+    out += &format!("                {:?},\n", item.item_type);
+    out += &format!("                ItemTypeId::{:?},\n", item.item_type_id);
+    out += "            ));\n";
+
+    for stat in STATS {
+        let value = stat_value(item, stat);
+        if value != stat_value(&defaults, stat) {
+            out += &format!("            item.{stat} = {value};\n");
+        }
+    }
+
+    // one paint call per colour, in the order the voxels were placed
+    let mut paint: Vec<(Color, Vec<IVec3>)> = Vec::new();
+    for point in &item.original_points {
+        let Some(color) = item.voxel_colors.get(point) else {
+            continue;
+        };
+        match paint.iter_mut().find(|(c, _)| c == color) {
+            Some((_, points)) => points.push(*point),
+            None => paint.push((*color, vec![*point])),
+        }
+    }
+    for (color, points) in paint {
+        let points: Vec<String> = points.into_iter().map(tuple).collect();
+        out += &format!(
+            "            item.paint(&[{}], {});\n",
+            points.join(", "),
+            color_literal(color)
+        );
+    }
+
+    out += "            item\n";
+    out += "        }\n";
+    out
+}
+
+fn export(item: &InventoryItem) -> Result<PathBuf, String> {
+    let path = PathBuf::from(EXPORT_DIRECTORY).join(format!("{}.rs", item.item_type_id.name()));
+    fs::create_dir_all(EXPORT_DIRECTORY)
+        .and_then(|_| fs::write(&path, catalog_snippet(item)))
+        .map_err(|error| format!("could not write {}: {error}", path.display()))?;
+    log::info!("Exported {:?} to {}", item.item_type_id, path.display());
+    Ok(path)
+}
+
+// the open editor's item if there is one, the one waiting for it otherwise
+fn with_edited_item<R>(world: &mut World, edit: impl FnOnce(&mut InventoryItem) -> R) -> R {
+    let mut query = world.query_filtered::<&mut PackedInventoryItem, With<EditedItem>>();
+    if let Ok(mut packed) = query.get_single_mut(world) {
+        let result = edit(&mut packed.data);
+        packed.data.changed = true;
+        return result;
+    }
+    edit(&mut world.resource_mut::<ItemEditor>().item)
+}
+
+fn editor_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let message = match args {
+        [] => "editing".to_string(),
+        ["new"] => {
+            with_edited_item(world, |item| *item = blank_item());
+            "editing a new item".to_string()
+        }
+        ["export"] => {
+            let path = with_edited_item(world, |item| export(item))?;
+            return Ok(format!("exported to {}", path.display()));
+        }
+        ["set", stat, value] => {
+            with_edited_item(world, |item| set_stat(item, stat, value))?;
+            return Ok(format!("{stat} set to {value}"));
+        }
+        ["set", ..] => return Err(format!("usage: editor set <{}> <value>", STATS.join("|"))),
+        [name] => {
+            let item_type_id =
+                ItemTypeId::from_name(name).ok_or_else(|| format!("no item called `{name}`"))?;
+            with_edited_item(world, |item| *item = catalog_item(item_type_id));
+            format!("editing {name}")
+        }
+        _ => return Err("too many arguments".to_string()),
+    };
+
+    world
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::ItemEditor);
+    Ok(message)
+}
@@ -5,6 +5,7 @@ use bevy_mod_raycast::Ray3d;
 
 use crate::game::HolyCam;
 use crate::inventory::controller::move_item;
+use crate::inventory::controller::rotate_item;
 use crate::inventory::controller::CubeRotationAnime;
 use crate::inventory::controller::InventoryControllerState;
 use crate::inventory::controller::ItemDirection;
@@ -73,6 +74,11 @@ pub fn update_gizmo_position(
     }
 }
 
+// highlight_gizmo scales up the gizmo under the cursor, clicks there are for the gizmo
+pub fn is_hovered(gizmo_transform: &Transform) -> bool {
+    gizmo_transform.scale.x > 1.0
+}
+
 pub fn highlight_gizmo(
     mut param_set: ParamSet<(
         Query<(&mut Transform, &Gizmo, &Handle<Mesh>)>,
@@ -138,33 +144,13 @@ pub fn highlight_gizmo(
                             | ItemDirection::BACKWARDS => {
                                 move_item(&mut item.1, g.item_dir, state.view_index)
                             }
-                            ItemDirection::YAW_LEFT => {
-                                item.1.data.rotate_y(true);
-                            }
-                            ItemDirection::YAW_RIGHT => {
-                                item.1.data.rotate_y(false);
-                            }
-                            ItemDirection::PITCH_BACKWARDS => {
-                                if state.view_index == 3 {
-                                    item.1.data.rotate_x(false);
-                                } else if (state.view_index == 2) {
-                                    item.1.data.rotate_z(true);
-                                } else if state.view_index % 2 == 0 {
-                                    item.1.data.rotate_z(false);
-                                } else {
-                                    item.1.data.rotate_x(true);
-                                }
-                            }
-                            ItemDirection::PITCH_FORWARD => {
-                                if state.view_index == 3 {
-                                    item.1.data.rotate_x(true);
-                                } else if (state.view_index == 2) {
-                                    item.1.data.rotate_z(false);
-                                } else if state.view_index % 2 == 0 {
-                                    item.1.data.rotate_z(true);
-                                } else {
-                                    item.1.data.rotate_x(false);
-                                }
+                            ItemDirection::YAW_LEFT
+                            | ItemDirection::YAW_RIGHT
+                            | ItemDirection::PITCH_FORWARD
+                            | ItemDirection::PITCH_BACKWARDS
+                            | ItemDirection::ROLL_LEFT
+                            | ItemDirection::ROLL_RIGHT => {
+                                rotate_item(&mut item.1, g.item_dir, state.view_index)
                             }
                             ItemDirection::ROTATE_VIEW_LEFT => {
                                 if !rotation_anime.enabled {
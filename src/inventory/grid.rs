@@ -1,27 +1,38 @@
 use bevy::math::vec2;
 use bevy::prelude::*;
 
+use crate::inventory::BagSize;
+
 pub struct GridDisplayPlugin;
 
 impl Plugin for GridDisplayPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup);
+        app.add_systems(Update, build_grid.run_if(resource_changed::<BagSize>()));
     }
 }
 
 #[derive(Component)]
 pub struct Grid;
 
-fn setup(
+// built again whenever the bag grows, the walls go up with the layers
+fn build_grid(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    bag: Res<BagSize>,
+    old_grid: Query<Entity, With<Grid>>,
 ) {
+    for entity in &old_grid {
+        commands.entity(entity).despawn_recursive();
+    }
+
     let texture_handle = asset_server.load("grid.png");
     let texture_handle_selected = asset_server.load("grid_selected.png");
 
     let grid_size = 7;
+    let layers = bag.0.y as f32;
+    let wall_center = -1.5 + layers / 2.0;
 
     commands
         .spawn(PbrBundle {
@@ -49,7 +60,7 @@ fn setup(
                 cull_mode: None,
                 ..default()
             }),
-            transform: Transform::from_translation(Vec3::new(500.0, 0.5 + 0.01, 0.0)),
+            transform: Transform::from_translation(Vec3::new(500.0, -1.5 + layers + 0.01, 0.0)),
             ..default()
         })
         .insert(Grid);
@@ -71,7 +82,7 @@ fn setup(
 
     commands
         .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, 2.0)))),
+            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, layers)))),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(texture_handle_selected.clone()),
                 alpha_mode: AlphaMode::Blend,
@@ -79,14 +90,14 @@ fn setup(
                 cull_mode: None,
                 ..default()
             }),
-            transform: Transform::from_translation(Vec3::new(500.0, -0.5, 3.5)),
+            transform: Transform::from_translation(Vec3::new(500.0, wall_center, 3.5)),
             ..default()
         })
         .insert(Grid);
 
     commands
         .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, 2.0)))),
+            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, layers)))),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(texture_handle_selected.clone()),
                 alpha_mode: AlphaMode::Blend,
@@ -94,14 +105,14 @@ fn setup(
                 cull_mode: None,
                 ..default()
             }),
-            transform: Transform::from_translation(Vec3::new(500.0, -0.5, -3.5)),
+            transform: Transform::from_translation(Vec3::new(500.0, wall_center, -3.5)),
             ..default()
         })
         .insert(Grid);
 
     commands
         .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, 2.0)))),
+            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, layers)))),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(texture_handle_selected.clone()),
                 alpha_mode: AlphaMode::Blend,
@@ -109,7 +120,7 @@ fn setup(
                 cull_mode: None,
                 ..default()
             }),
-            transform: Transform::from_translation(Vec3::new(503.5, -0.5, 0.0))
+            transform: Transform::from_translation(Vec3::new(503.5, wall_center, 0.0))
                 .with_rotation(Quat::from_rotation_y(-90.0f32.to_radians())),
             ..default()
         })
@@ -117,7 +128,7 @@ fn setup(
 
     commands
         .spawn(PbrBundle {
-            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, 2.0)))),
+            mesh: meshes.add(Mesh::from(shape::Quad::new(vec2(grid_size as f32, layers)))),
             material: materials.add(StandardMaterial {
                 base_color_texture: Some(texture_handle_selected.clone()),
                 alpha_mode: AlphaMode::Blend,
@@ -125,7 +136,7 @@ fn setup(
                 cull_mode: None,
                 ..default()
             }),
-            transform: Transform::from_translation(Vec3::new(496.5, -0.5, 0.0))
+            transform: Transform::from_translation(Vec3::new(496.5, wall_center, 0.0))
                 .with_rotation(Quat::from_rotation_y(90.0f32.to_radians())),
             ..default()
         })
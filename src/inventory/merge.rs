@@ -0,0 +1,184 @@
+use bevy::log;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::game_state::GameState;
+use crate::inventory::drag::fits;
+use crate::inventory::selection::SelectedItem;
+use crate::inventory::{BagSize, InventoryItem, ItemTypeId, PackedInventoryItem};
+use crate::item_spawner::catalog_item;
+
+pub const MAX_WEAPON_LEVEL: u32 = 3;
+
+// what a duplicate turns into when it can't be merged
+const CONVERTED_ITEM: ItemTypeId = ItemTypeId::HealPotion;
+
+pub struct InventoryMergePlugin;
+
+impl Plugin for InventoryMergePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            merge_adjacent_duplicates.run_if(in_state(GameState::ManagingInventory)),
+        );
+    }
+}
+
+// two copies of the same weapon at the same level, below the cap
+pub fn can_merge(a: &InventoryItem, b: &InventoryItem) -> bool {
+    a.item_type.is_weapon()
+        && a.item_type_id == b.item_type_id
+        && a.level == b.level
+        && a.level < MAX_WEAPON_LEVEL
+}
+
+// a second copy of a weapon that has nothing to merge with, the packing screen offers to convert it
+pub fn is_stuck_duplicate<'a>(
+    item: &InventoryItem,
+    others: impl IntoIterator<Item = &'a InventoryItem>,
+) -> bool {
+    let mut copies = others
+        .into_iter()
+        .filter(|other| item.item_type.is_weapon() && other.item_type_id == item.item_type_id)
+        .peekable();
+    copies.peek().is_some() && !copies.any(|other| can_merge(item, other))
+}
+
+// the duplicate becomes a consumable in the same spot
+pub fn convert_to_consumable(item: &InventoryItem) -> InventoryItem {
+    let mut converted = catalog_item(CONVERTED_ITEM);
+    converted.location = item.location;
+    converted.changed = true;
+    converted
+}
+
+// some voxel of one is right next to a voxel of the other, in the shell
+fn touching(a: &InventoryItem, b: &InventoryItem) -> bool {
+    let b_cells: HashSet<IVec3> = b
+        .local_points
+        .iter()
+        .map(|point| b.location + *point)
+        .collect();
+    a.local_points.iter().any(|point| {
+        let cell = a.location + *point;
+        IVec3::AXES
+            .into_iter()
+            .any(|axis| b_cells.contains(&(cell + axis)) || b_cells.contains(&(cell - axis)))
+    })
+}
+
+impl InventoryItem {
+    // one level up, hits harder and faster and grows by a voxel
+    pub fn upgraded(&self) -> InventoryItem {
+        let mut item = self.clone();
+        item.level += 1;
+        item.weapon_damage += 1;
+        item.weapon_attack_speed *= 1.25;
+        item.projectile_speed *= 1.1;
+        item.grow();
+        item.changed = true;
+        item
+    }
+
+    // adds a voxel past the far end of the longest side, swords get longer and guns get a barrel
+    fn grow(&mut self) {
+        let min = self.original_points.iter().copied().reduce(IVec3::min);
+        let max = self.original_points.iter().copied().reduce(IVec3::max);
+        let (Some(min), Some(max)) = (min, max) else {
+            return;
+        };
+
+        let extent = max - min;
+        let axis = (0..3).max_by_key(|axis| extent[*axis]).unwrap();
+        let (end, direction) = if max[axis].abs() >= min[axis].abs() {
+            (max[axis], IVec3::AXES[axis])
+        } else {
+            (min[axis], -IVec3::AXES[axis])
+        };
+
+        let tip = *self
+            .original_points
+            .iter()
+            .find(|point| point[axis] == end)
+            .unwrap();
+        let grown = tip + direction;
+        self.original_points.push(grown);
+        self.local_points.push(self.orientation.apply(grown));
+        if let Some(color) = self.voxel_colors.get(&tip).copied() {
+            self.voxel_colors.insert(grown, color);
+        }
+    }
+}
+
+// Where the grown weapon goes, right where it was when it still fits, otherwise the closest spot
+// that's free. None when the shell has no room for it anywhere.
+fn grown_location(item: &InventoryItem, occupied: &HashSet<IVec3>, size: IVec3) -> Option<IVec3> {
+    let mut offsets: Vec<IVec3> = (-size.x..=size.x)
+        .flat_map(|x| {
+            (-size.y..=size.y)
+                .flat_map(move |y| (-size.z..=size.z).map(move |z| IVec3::new(x, y, z)))
+        })
+        .collect();
+    offsets.sort_by_key(|offset| offset.length_squared());
+
+    let mut moved = item.clone();
+    offsets.into_iter().find_map(|offset| {
+        moved.location = item.location + offset;
+        fits(&moved, occupied, size).then_some(moved.location)
+    })
+}
+
+// Two copies of the same weapon put side by side become one of a higher level. The one being
+// moved stays, the other one goes. The extra voxel has to fit in the shell, the pair stays
+// apart if it doesn't fit anywhere.
+fn merge_adjacent_duplicates(
+    mut commands: Commands,
+    mut items: Query<(Entity, &mut PackedInventoryItem)>,
+    mut selected: ResMut<SelectedItem>,
+    bag: Res<BagSize>,
+) {
+    let placed: Vec<(Entity, InventoryItem)> = items
+        .iter()
+        .map(|(entity, packed)| (entity, packed.data.clone()))
+        .collect();
+
+    for (index, (a_entity, a)) in placed.iter().enumerate() {
+        for (b_entity, b) in &placed[index + 1..] {
+            if !can_merge(a, b) || !touching(a, b) {
+                continue;
+            }
+
+            let (kept, merged) = if selected.selected_entity == Some(*b_entity) {
+                (*b_entity, *a_entity)
+            } else {
+                (*a_entity, *b_entity)
+            };
+
+            let kept_item = if kept == *a_entity { a } else { b };
+            let mut grown = kept_item.upgraded();
+            let occupied: HashSet<IVec3> = placed
+                .iter()
+                .filter(|(entity, _)| *entity != kept && *entity != merged)
+                .flat_map(|(_, item)| item.local_points.iter().map(|point| item.location + *point))
+                .collect();
+            let Some(location) = grown_location(&grown, &occupied, bag.0) else {
+                continue;
+            };
+            grown.location = location;
+
+            commands.entity(merged).despawn_recursive();
+            if let Ok((_, mut packed)) = items.get_mut(kept) {
+                packed.data = grown;
+                log::info!(
+                    "Merged two {:?} into level {}",
+                    packed.data.item_type_id,
+                    packed.data.level
+                );
+            }
+            selected.selected_entity = Some(kept);
+
+            // the despawn goes through before the next merge is looked for
+            return;
+        }
+    }
+}
@@ -2,7 +2,7 @@ use bevy::math::vec3;
 use bevy::pbr::NotShadowReceiver;
 use bevy::prelude::*;
 use bevy::transform::components::Transform;
-use bevy::utils::HashSet;
+use bevy::utils::HashMap;
 
 use crate::asset_loader::GameAssets;
 use crate::config::{DEFAULT_BAG_LOCATION, INVENTORY_GRID_DIMENSIONS};
@@ -10,17 +10,25 @@ use crate::game_state::GameState;
 use crate::inventory::controller::InventoryControllerPlugin;
 use crate::inventory::controller::ItemDirection;
 use crate::inventory::data_manager::InventoryDataPlugin;
+use crate::inventory::drag::InventoryDragPlugin;
+use crate::inventory::editor::ItemEditorPlugin;
 use crate::inventory::gizmo::Gizmo;
 use crate::inventory::grid::GridDisplayPlugin;
+use crate::inventory::merge::InventoryMergePlugin;
+pub use crate::inventory::orientation::Orientation;
 use crate::inventory::selection::{SelectedItem, SelectionPlugin};
 use crate::inventory::ui::InventoryUIPlugin;
 use crate::inventory::validation::InventoryValidationPlugin;
-use crate::inventory::ItemType::NON_WEAPON;
+use crate::status_effect::StatusEffectKind;
 
 mod controller;
 mod data_manager;
+mod drag;
+mod editor;
 mod gizmo;
 mod grid;
+mod merge;
+mod orientation;
 mod selection;
 pub mod ui;
 mod validation;
@@ -32,9 +40,10 @@ impl Plugin for InventoryPlugin {
         app.add_systems(OnEnter(GameState::ManagingInventory), setup);
         app.add_systems(
             Update,
-            update_packed_items.run_if(in_state(GameState::ManagingInventory)),
+            update_packed_items.run_if(
+                in_state(GameState::ManagingInventory).or_else(in_state(GameState::ItemEditor)),
+            ),
         );
-        app.add_systems(Update, make_sure_no_weapon_duplicates);
         app.add_plugins((
             InventoryControllerPlugin,
             InventoryDataPlugin,
@@ -42,24 +51,37 @@ impl Plugin for InventoryPlugin {
             InventoryValidationPlugin,
             InventoryUIPlugin,
             SelectionPlugin,
+            ItemEditorPlugin,
+            InventoryDragPlugin,
+            InventoryMergePlugin,
         ))
-        .insert_resource(Inventory {
-            content: Vec::new(),
-        })
-        .insert_resource(InventoryData { grid: Vec::new() });
+        .insert_resource(Inventory::new())
+        .insert_resource(InventoryData { grid: Vec::new() })
+        .init_resource::<BagSize>()
+        .add_event::<PackClosed>();
     }
 }
 
-/// set up a simple 3D scene
-pub fn setup(
-    mut commands: Commands,
-    assets: Res<AssetServer>,
-    mut inventory: ResMut<Inventory>,
-    game_assets: Res<GameAssets>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut selection: ResMut<SelectedItem>,
-) {
+// Cells of the shell along x, y (the layers) and z. Extra layers go on top, the bag's centre stays
+// where the default one has it.
+#[derive(Resource)]
+pub struct BagSize(pub IVec3);
+
+impl Default for BagSize {
+    fn default() -> Self {
+        Self(IVec3::from_array(INVENTORY_GRID_DIMENSIONS))
+    }
+}
+
+// sent when a hermit is done packing, the items that didn't fit are thrown out
+#[derive(Event)]
+pub struct PackClosed {
+    pub items: usize,
+    pub kept: usize,
+}
+
+// the arrows around the bag, the item editor uses them too
+pub(crate) fn spawn_gizmos(commands: &mut Commands, game_assets: &GameAssets) {
     let mut up_transform =
         Transform::from_translation(DEFAULT_BAG_LOCATION + Vec3::from((0.0, 0.0, 0.0)));
     up_transform.rotation = Quat::from_euler(
@@ -238,31 +260,31 @@ pub fn setup(
             ..default()
         })
         .insert(NotShadowReceiver);
-    // right_transform.translation = Vec3::from((0.0, 0.0, 0.2));
-    // commands
-    //     .spawn(Gizmo {
-    //         relative: right_transform,
-    //         item_dir: ItemDirection::ROLL_LEFT,
-    //     })
-    //     .insert(PbrBundle {
-    //         mesh: game_assets.arrow_rotated().mesh_handle,
-    //         material: game_assets.arrow_rotated().material_handle,
-    //         ..default()
-    //     })
-    //     .insert(NotShadowReceiver);
-    // left_transform.rotate_x(180.0f32.to_radians());
-    // left_transform.translation = Vec3::from((0.0, 0.0, -0.2));
-    // commands
-    //     .spawn(Gizmo {
-    //         relative: left_transform,
-    //         item_dir: ItemDirection::ROLL_RIGHT,
-    //     })
-    //     .insert(PbrBundle {
-    //         mesh: game_assets.arrow_rotated().mesh_handle,
-    //         material: game_assets.arrow_rotated().material_handle,
-    //         ..default()
-    //     })
-    //     .insert(NotShadowReceiver);
+    right_transform.translation = Vec3::from((0.0, 0.0, 0.2));
+    commands
+        .spawn(Gizmo {
+            relative: right_transform,
+            item_dir: ItemDirection::ROLL_LEFT,
+        })
+        .insert(PbrBundle {
+            mesh: game_assets.arrow_rotated().mesh_handle,
+            material: game_assets.arrow_rotated().material_handle,
+            ..default()
+        })
+        .insert(NotShadowReceiver);
+    left_transform.rotate_x(180.0f32.to_radians());
+    left_transform.translation = Vec3::from((0.0, 0.0, -0.2));
+    commands
+        .spawn(Gizmo {
+            relative: left_transform,
+            item_dir: ItemDirection::ROLL_RIGHT,
+        })
+        .insert(PbrBundle {
+            mesh: game_assets.arrow_rotated().mesh_handle,
+            material: game_assets.arrow_rotated().material_handle,
+            ..default()
+        })
+        .insert(NotShadowReceiver);
 
     up_transform.translation = Vec3::from((0.0, 0.0, 0.1));
     up_transform.rotate_z(90.0f32.to_radians());
@@ -292,6 +314,20 @@ pub fn setup(
             ..default()
         })
         .insert(NotShadowReceiver);
+}
+
+/// set up a simple 3D scene
+pub fn setup(
+    mut commands: Commands,
+    assets: Res<AssetServer>,
+    mut inventory: ResMut<Inventory>,
+    game_assets: Res<GameAssets>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut selection: ResMut<SelectedItem>,
+) {
+    spawn_gizmos(&mut commands, &game_assets);
+
     // Render current inventory data
 
     let mut id = None;
@@ -302,7 +338,7 @@ pub fn setup(
                 .spawn(PackedInventoryItem { data: item.clone() })
                 .insert(PbrBundle {
                     mesh: meshes.add(item.generate_mesh(false)),
-                    material: materials.add(item.color.clone().into()),
+                    material: materials.add(item.material()),
                     transform: Transform::from_translation(
                         DEFAULT_BAG_LOCATION + item.location.as_vec3()
                             - vec3(
@@ -361,6 +397,13 @@ pub enum ItemType {
     MELEE_WEAPON,
     RANGED_WEAPON,
     NON_WEAPON,
+    CONSUMABLE,
+}
+
+impl ItemType {
+    pub fn is_weapon(&self) -> bool {
+        matches!(self, ItemType::MELEE_WEAPON | ItemType::RANGED_WEAPON)
+    }
 }
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -373,6 +416,38 @@ pub enum ItemTypeId {
     MidSword,
     AlexSword,
     Heart,
+    HealPotion,
+    SpeedPotion,
+    Bomb,
+    Shield,
+}
+
+impl ItemTypeId {
+    pub const ALL: [ItemTypeId; 12] = [
+        ItemTypeId::Boomerang,
+        ItemTypeId::AlexBoomerang,
+        ItemTypeId::HandGun,
+        ItemTypeId::SuperGun,
+        ItemTypeId::WillSword,
+        ItemTypeId::MidSword,
+        ItemTypeId::AlexSword,
+        ItemTypeId::Heart,
+        ItemTypeId::HealPotion,
+        ItemTypeId::SpeedPotion,
+        ItemTypeId::Bomb,
+        ItemTypeId::Shield,
+    ];
+
+    // what the console calls it, `SuperGun` is `supergun`
+    pub fn name(&self) -> String {
+        format!("{self:?}").to_lowercase()
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        ItemTypeId::ALL
+            .into_iter()
+            .find(|item_type_id| item_type_id.name() == name)
+    }
 }
 
 #[derive(Clone, Debug, Component, PartialEq)]
@@ -380,8 +455,11 @@ pub struct InventoryItem {
     pub location: IVec3, // grid location
     pub original_points: Vec<IVec3>,
     pub local_points: Vec<IVec3>, // relative coordinate, center is the first point
+    pub orientation: Orientation, // how the original points were turned into the local ones
     pub changed: bool,
     pub color: Color,
+    // voxels painted another colour than `color`, keyed by their original point
+    pub voxel_colors: HashMap<IVec3, Color>,
 
     pub hp_gain: i32,            // how much HP this item gives you for having it
     pub attack_damage_gain: i32, // how much attack damage this item gives you for having it
@@ -392,24 +470,78 @@ pub struct InventoryItem {
     pub weapon_is_auto: bool, // whether holding click auto attacks for this weapon
 
     pub projectile_speed: f32, // how fast the ranged weapon's 'bullets' travel
+    pub status_effect: Option<StatusEffectKind>, // what sticks to the creatures this weapon hits
 
     pub item_type: ItemType,
     pub item_type_id: ItemTypeId,
+    pub level: u32, // goes up when two of the same weapon are merged in the shell
 }
 
 // inventory of what the user owns currently
 // query the resource to get it
+//
+// in co-op every hermit has its own shell, `content` is the one of `owner` (the player packing
+// their shell right now), the others wait in `stashed` until it's their turn
 #[derive(Resource)]
 pub struct Inventory {
     pub content: Vec<InventoryItem>,
+    pub owner: usize,
+    stashed: Vec<Vec<InventoryItem>>,
 }
 
 impl Inventory {
+    pub fn new() -> Self {
+        Self {
+            content: Vec::new(),
+            owner: 0,
+            stashed: Vec::new(),
+        }
+    }
+
+    pub fn items_of(&self, player_index: usize) -> &[InventoryItem] {
+        if player_index == self.owner {
+            &self.content
+        } else {
+            self.stashed
+                .get(player_index)
+                .map(|items| items.as_slice())
+                .unwrap_or(&[])
+        }
+    }
+
+    pub fn items_of_mut(&mut self, player_index: usize) -> &mut Vec<InventoryItem> {
+        if player_index == self.owner {
+            return &mut self.content;
+        }
+
+        if self.stashed.len() <= player_index {
+            self.stashed.resize(player_index + 1, Vec::new());
+        }
+        &mut self.stashed[player_index]
+    }
+
+    // hands the packing screen over to another hermit
+    pub fn set_owner(&mut self, player_index: usize) {
+        if player_index == self.owner {
+            return;
+        }
+
+        let previous_owner = self.owner;
+        let previous_content = std::mem::take(&mut self.content);
+        self.content = std::mem::take(self.items_of_mut(player_index));
+        self.owner = player_index;
+        *self.items_of_mut(previous_owner) = previous_content;
+    }
+
+    pub fn player_count(&self) -> usize {
+        self.stashed.len().max(self.owner + 1)
+    }
+
     pub fn first_weapon(&self) -> Option<&InventoryItem> {
         return self
             .content
             .iter()
-            .filter(|i| i.item_type != NON_WEAPON)
+            .filter(|i| i.item_type.is_weapon())
             .next();
     }
 }
@@ -434,44 +566,40 @@ impl InventoryItem {
         self.location += translation;
     }
 
-    pub fn rotate_y(&mut self, ccw: bool) {
-        let rot_angle = ((if ccw { 90 } else { -90 }) as f32).to_radians();
-
-        let rot_mat = Mat3::from_rotation_y(rot_angle);
-        for p in self.local_points.iter_mut() {
-            let vec3 = Vec3::new(p.x as f32, p.y as f32, p.z as f32);
-            let new_p: Vec3 = rot_mat.mul_vec3(vec3);
-            p.x = new_p.x.round() as i32;
-            p.y = new_p.y.round() as i32;
-            p.z = new_p.z.round() as i32;
-        }
-        self.changed = true;
+    // a quarter turn around one of the grid directions, the points are turned from the original
+    // ones every time so they stay exact
+    pub fn turn(&mut self, axis: IVec3) {
+        self.set_orientation(self.orientation.turned(axis));
     }
-    pub fn rotate_x(&mut self, ccw: bool) {
-        let rot_angle = ((if ccw { 90 } else { -90 }) as f32).to_radians();
-
-        let rot_mat = Mat3::from_rotation_x(rot_angle);
-        for p in self.local_points.iter_mut() {
-            let vec3 = Vec3::new(p.x as f32, p.y as f32, p.z as f32);
-            let new_p: Vec3 = rot_mat.mul_vec3(vec3);
-            p.x = new_p.x.round() as i32;
-            p.y = new_p.y.round() as i32;
-            p.z = new_p.z.round() as i32;
-        }
+
+    // puts the item in a given orientation, as if turned there from the way it was made
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation;
+        self.local_points = self
+            .original_points
+            .iter()
+            .map(|point| self.orientation.apply(*point))
+            .collect();
         self.changed = true;
     }
-    pub fn rotate_z(&mut self, ccw: bool) {
-        let rot_angle = ((if ccw { 90 } else { -90 }) as f32).to_radians();
-
-        let rot_mat = Mat3::from_rotation_z(rot_angle);
-        for p in self.local_points.iter_mut() {
-            let vec3 = Vec3::new(p.x as f32, p.y as f32, p.z as f32);
-            let new_p: Vec3 = rot_mat.mul_vec3(vec3);
-            p.x = new_p.x.round() as i32;
-            p.y = new_p.y.round() as i32;
-            p.z = new_p.z.round() as i32;
+
+    // the same copy out of the shell, two of one weapon are packed in different spots at least
+    pub fn is_same_item(&self, other: &InventoryItem) -> bool {
+        self.item_type_id == other.item_type_id
+            && self.level == other.level
+            && self.orientation == other.orientation
+            && self.original_points == other.original_points
+            && self.location == other.location
+    }
+
+    pub fn rotate_y(&mut self, ccw: bool) {
+        self.turn(if ccw { IVec3::Y } else { IVec3::NEG_Y });
+    }
+
+    pub fn paint(&mut self, original_points: &[(i32, i32, i32)], color: Color) {
+        for point in original_points {
+            self.voxel_colors.insert((*point).into(), color);
         }
-        self.changed = true;
     }
 
     #[allow(dead_code)]
@@ -502,7 +630,9 @@ impl
             location: value.0.into(),
             local_points: value.1.iter().map(|tup| (*tup).into()).collect(),
             original_points: value.1.iter().map(|tup| (*tup).into()).collect(),
+            orientation: Orientation::IDENTITY,
             color: value.2,
+            voxel_colors: HashMap::default(),
             hp_gain: 0,
             attack_damage_gain: 0,
             attack_speed_gain: 1.0,
@@ -511,8 +641,10 @@ impl
             weapon_attack_speed: 1.0,
             item_type: value.3,
             projectile_speed: 1.0,
+            status_effect: None,
             changed: false,
             item_type_id: value.4,
+            level: 1,
         }
     }
 }
@@ -553,20 +685,3 @@ impl InventoryData {
         item_grid
     }
 }
-
-fn make_sure_no_weapon_duplicates(inventory: Res<Inventory>) {
-    let mut type_id_set: HashSet<ItemTypeId> = HashSet::new();
-
-    for item in &inventory.content {
-        if item.item_type == ItemType::NON_WEAPON {
-            continue;
-        }
-
-        assert!(
-            !type_id_set.contains(&item.item_type_id),
-            "Not allowed two non-weapons with the same item type id in the inventory, fuck you"
-        );
-
-        type_id_set.insert(item.item_type_id);
-    }
-}
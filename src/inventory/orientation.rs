@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+
+// One of the 24 ways a voxel item can sit in the bag, as an integer rotation matrix. The columns
+// are where the item's own x, y and z axes point, so turning never rounds and never drifts.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Orientation {
+    x: IVec3,
+    y: IVec3,
+    z: IVec3,
+}
+
+impl Orientation {
+    pub const IDENTITY: Orientation = Orientation {
+        x: IVec3::X,
+        y: IVec3::Y,
+        z: IVec3::Z,
+    };
+
+    pub fn apply(&self, point: IVec3) -> IVec3 {
+        self.x * point.x + self.y * point.y + self.z * point.z
+    }
+
+    // a quarter turn around `axis`, counter-clockwise looking down it like Quat::from_axis_angle,
+    // `axis` has to be one of the six grid directions
+    pub fn turned(&self, axis: IVec3) -> Orientation {
+        Orientation {
+            x: quarter_turn(self.x, axis),
+            y: quarter_turn(self.y, axis),
+            z: quarter_turn(self.z, axis),
+        }
+    }
+
+    // where the item's own x, y and z axes point
+    pub fn axes(&self) -> [IVec3; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    // None unless the axes are grid directions making a proper turn, mirror images aren't one
+    pub fn from_axes([x, y, z]: [IVec3; 3]) -> Option<Orientation> {
+        let unit = |axis: IVec3| axis.abs().element_sum() == 1;
+        (unit(x) && unit(y) && x.cross(y) == z).then_some(Orientation { x, y, z })
+    }
+
+    pub fn quat(&self) -> Quat {
+        Quat::from_mat3(&Mat3::from_cols(
+            self.x.as_vec3(),
+            self.y.as_vec3(),
+            self.z.as_vec3(),
+        ))
+    }
+}
+
+// for a unit axis, the cross product is exactly the 90 degree turn of what's perpendicular to it
+fn quarter_turn(v: IVec3, axis: IVec3) -> IVec3 {
+    let along = axis * v.dot(axis);
+    along + axis.cross(v - along)
+}
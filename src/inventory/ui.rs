@@ -3,8 +3,10 @@ use bevy::prelude::*;
 use bevy::ui::PositionType::Absolute;
 
 use crate::game_state::GameState;
+use crate::inventory::merge::{convert_to_consumable, is_stuck_duplicate};
 use crate::inventory::selection::SelectedItem;
 use crate::inventory::{selection, Inventory, PackedInventoryItem};
+use crate::player::LocalPlayerCount;
 
 pub struct InventoryUIPlugin;
 
@@ -18,6 +20,8 @@ pub struct InventoryUI;
 pub struct ValidationButton;
 #[derive(Component)]
 pub struct ItemSwitch;
+#[derive(Component)]
+pub struct ConvertButton;
 
 impl Plugin for InventoryUIPlugin {
     fn build(&self, app: &mut App) {
@@ -29,6 +33,12 @@ impl Plugin for InventoryUIPlugin {
             Update,
             select_next_button.run_if(in_state(GameState::ManagingInventory)),
         );
+        app.add_systems(
+            Update,
+            (update_convert_button, convert_button)
+                .chain()
+                .run_if(in_state(GameState::ManagingInventory)),
+        );
         app.add_systems(OnEnter(GameState::ManagingInventory), build_ui);
         app.add_systems(OnExit(GameState::ManagingInventory), clean);
         app.add_systems(Startup, setup);
@@ -43,8 +53,21 @@ fn setup(mut commands: Commands) {
     // });
 }
 
-fn build_ui(mut commands: Commands, asset_server: Res<AssetServer>, inventory: Res<Inventory>) {
+fn build_ui(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    inventory: Res<Inventory>,
+    player_count: Res<LocalPlayerCount>,
+) {
     let multiple_items = inventory.content.iter().count() > 1;
+    let hint = if player_count.0 > 1 {
+        format!(
+            "Player {}: Space is limited! Fit your items or lose them.",
+            inventory.owner + 1
+        )
+    } else {
+        "Space is limited! Fit your items or lose them.".to_string()
+    };
 
     commands
         .spawn(NodeBundle {
@@ -62,7 +85,7 @@ fn build_ui(mut commands: Commands, asset_server: Res<AssetServer>, inventory: R
         .with_children(|parent| {
             parent.spawn(TextBundle::from_section(
                 // "fit your cock in your ass" - Cedric Martens
-                "Space is limited! Fit your items or lose them.",
+                hint,
                 TextStyle {
                     font: asset_server.load("fonts/FiraSans-Bold.ttf"),
                     font_size: 40.0,
@@ -172,6 +195,48 @@ fn build_ui(mut commands: Commands, asset_server: Res<AssetServer>, inventory: R
                         });
                 });
         });
+
+    // only shown while the selected item is a weapon we already have and can't merge
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                position_type: Absolute,
+                top: Val::Percent(5.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(InventoryUI)
+        .with_children(|parent| {
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(320.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    border_color: BorderColor(Color::BLACK),
+                    background_color: NORMAL_BUTTON.into(),
+                    visibility: Hidden,
+                    ..default()
+                })
+                .insert(ConvertButton)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Duplicate! Convert (C)",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 40.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ));
+                });
+        });
 }
 
 fn validation_button(
@@ -259,8 +324,71 @@ fn select_next_button(
     }
 }
 
+fn update_convert_button(
+    selected: Res<SelectedItem>,
+    query_items: Query<(Entity, &PackedInventoryItem)>,
+    mut button_query: Query<&mut Visibility, With<ConvertButton>>,
+) {
+    let stuck = selected
+        .selected_entity
+        .and_then(|selected_entity| query_items.get(selected_entity).ok())
+        .is_some_and(|(selected_entity, packed)| {
+            is_stuck_duplicate(
+                &packed.data,
+                query_items
+                    .iter()
+                    .filter(|(entity, _)| *entity != selected_entity)
+                    .map(|(_, other)| &other.data),
+            )
+        });
+
+    for mut visibility in &mut button_query {
+        *visibility = if stuck { Visible } else { Hidden };
+    }
+}
+
+// turns the selected duplicate into a consumable, with the button or C
+fn convert_button(
+    mut interaction_query: Query<
+        (&Interaction, &mut BackgroundColor, &Visibility),
+        (Changed<Interaction>, With<ConvertButton>),
+    >,
+    button_query: Query<&Visibility, With<ConvertButton>>,
+    key_codes: Res<Input<KeyCode>>,
+    selected: Res<SelectedItem>,
+    mut query_items: Query<&mut PackedInventoryItem>,
+) {
+    let mut pressed = false;
+    for (interaction, mut color, visibility) in &mut interaction_query {
+        match *interaction {
+            Interaction::Pressed => {
+                *color = PRESSED_BUTTON.into();
+                pressed = *visibility == Visible;
+            }
+            Interaction::Hovered => {
+                *color = HOVERED_BUTTON.into();
+            }
+            Interaction::None => {
+                *color = NORMAL_BUTTON.into();
+            }
+        }
+    }
+
+    let shown = button_query.iter().any(|visibility| *visibility == Visible);
+    if !pressed && !(shown && key_codes.just_pressed(KeyCode::C)) {
+        return;
+    }
+
+    if let Some(mut packed) = selected
+        .selected_entity
+        .and_then(|entity| query_items.get_mut(entity).ok())
+    {
+        packed.data = convert_to_consumable(&packed.data);
+    }
+}
+
 fn clean(mut commands: Commands, query: Query<Entity, With<InventoryUI>>) {
     for ui_element in query.iter() {
-        commands.entity(ui_element).despawn();
+        commands.entity(ui_element).despawn_recursive();
     }
 }
@@ -1,9 +1,8 @@
 use bevy::prelude::*;
 use bevy::utils::{HashMap, HashSet};
 
-use crate::config::INVENTORY_GRID_DIMENSIONS;
 use crate::game_state::GameState;
-use crate::inventory::{Inventory, InventoryItem, PackedInventoryItem};
+use crate::inventory::{BagSize, Inventory, InventoryItem, PackClosed, PackedInventoryItem};
 
 pub struct InventoryValidationPlugin;
 
@@ -17,7 +16,11 @@ impl Plugin for InventoryValidationPlugin {
     }
 }
 
-fn update_background(mut color: ResMut<ClearColor>, query: Query<&PackedInventoryItem>) {
+fn update_background(
+    mut color: ResMut<ClearColor>,
+    query: Query<&PackedInventoryItem>,
+    bag: Res<BagSize>,
+) {
     let mut set: HashSet<IVec3> = HashSet::new();
 
     let mut overlap = false;
@@ -27,11 +30,11 @@ fn update_background(mut color: ResMut<ClearColor>, query: Query<&PackedInventor
             let vec = *point + element.data.location;
 
             if vec.x < 0
-                || vec.x >= INVENTORY_GRID_DIMENSIONS[0]
+                || vec.x >= bag.0.x
                 || vec.y < 0
-                || vec.y >= INVENTORY_GRID_DIMENSIONS[1]
+                || vec.y >= bag.0.y
                 || vec.z < 0
-                || vec.z >= INVENTORY_GRID_DIMENSIONS[2]
+                || vec.z >= bag.0.z
             {
                 overlap = true;
                 break;
@@ -57,6 +60,8 @@ fn save_and_clear_render(
     mut commands: Commands,
     rendered_inventory: Query<(Entity, &PackedInventoryItem)>,
     mut inventory: ResMut<Inventory>,
+    bag: Res<BagSize>,
+    mut pack_closed_writer: EventWriter<PackClosed>,
 ) {
     inventory.content.clear();
 
@@ -79,11 +84,11 @@ fn save_and_clear_render(
             let vec = *point + element.location;
 
             if vec.x < 0
-                || vec.x >= INVENTORY_GRID_DIMENSIONS[0]
+                || vec.x >= bag.0.x
                 || vec.y < 0
-                || vec.y >= INVENTORY_GRID_DIMENSIONS[1]
+                || vec.y >= bag.0.y
                 || vec.z < 0
-                || vec.z >= INVENTORY_GRID_DIMENSIONS[2]
+                || vec.z >= bag.0.z
             {
                 non_overlapping.remove(&i);
                 continue;
@@ -106,6 +111,11 @@ fn save_and_clear_render(
             .push(all.get(index as usize).unwrap().clone());
     }
 
+    pack_closed_writer.send(PackClosed {
+        items: all.len(),
+        kept: inventory.content.len(),
+    });
+
     for item in rendered_inventory.iter() {
         commands.entity(item.0).despawn();
     }
@@ -1,97 +1,234 @@
 use crate::inventory::InventoryItem;
-use bevy::math::vec3;
-use bevy::prelude::{Mesh, Vec3};
+use bevy::math::{ivec2, IVec2, IVec3};
+use bevy::prelude::{Color, Mesh, StandardMaterial, Vec3};
 use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::utils::{HashMap, HashSet};
 
-struct VoxelFace {
-    position: Vec3,
-    normal: Vec3,
+// how bright a face corner stays with 3, 2, 1 or 0 of its neighbours letting light in
+const AMBIENT_OCCLUSION: [f32; 4] = [0.55, 0.7, 0.85, 1.0];
+
+// one exposed voxel face, only faces that look exactly the same get merged
+#[derive(Clone, Copy, PartialEq)]
+pub(crate) struct FaceCell {
+    pub color: Color,
+    // per corner, in quad order, 3 is fully lit
+    pub occlusion: [u8; 4],
 }
 
-impl InventoryItem {
-    pub fn generate_mesh(&self, original: bool) -> Mesh {
-        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+impl FaceCell {
+    // nothing next to it shades it
+    pub fn lit(color: Color) -> Self {
+        Self {
+            color,
+            occlusion: [3; 4],
+        }
+    }
+}
+
+// also builds the generated arena's terrain and walls
+#[derive(Default)]
+pub(crate) struct QuadBuilder {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<[f32; 2]>,
+    colors: Vec<[f32; 4]>,
+    indices: Vec<u32>,
+}
+
+impl QuadBuilder {
+    // corners go counter-clockwise around `normal`
+    pub fn quad(&mut self, corners: [Vec3; 4], uvs: [[f32; 2]; 4], normal: Vec3, cell: FaceCell) {
+        let i = self.positions.len() as u32;
+        let [r, g, b, a] = cell.color.as_linear_rgba_f32();
+
+        for corner in 0..4 {
+            let light = AMBIENT_OCCLUSION[cell.occlusion[corner] as usize];
+            self.positions.push(corners[corner]);
+            self.normals.push(normal);
+            self.uvs.push(uvs[corner]);
+            self.colors.push([r * light, g * light, b * light, a]);
+        }
 
-        let mut faces: Vec<VoxelFace> = Vec::new();
+        // split along the brighter diagonal so the shading doesn't look creased
+        let [o0, o1, o2, o3] = cell.occlusion;
+        if o0 + o2 >= o1 + o3 {
+            self.indices.extend([i, i + 1, i + 2, i, i + 2, i + 3]);
+        } else {
+            self.indices.extend([i, i + 1, i + 3, i + 1, i + 2, i + 3]);
+        }
+    }
 
-        let dirs = vec![
-            vec3(0.0, 1.0, 0.0),
-            vec3(0.0, -1.0, 0.0),
-            vec3(1.0, 0.0, 0.0),
-            vec3(-1.0, 0.0, 0.0),
-            vec3(0.0, 0.0, 1.0),
-            vec3(0.0, 0.0, -1.0),
-        ];
+    pub fn build(self) -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
 
-        for point in if original {
+impl InventoryItem {
+    // Only faces between a voxel and air are kept, then the faces of each layer are merged into
+    // as few rectangles as possible. UVs are in voxels, one texture tile per voxel.
+    pub fn generate_mesh(&self, original: bool) -> Mesh {
+        let points = if original {
             &self.original_points
         } else {
             &self.local_points
-        } {
-            'dirs: for dir in &dirs {
-                let new_pos = vec3(
-                    point.x as f32 + dir.x * 0.5,
-                    point.y as f32 + dir.y * 0.5,
-                    point.z as f32 + dir.z * 0.5,
-                );
-
-                for face in &faces {
-                    if face.position == new_pos {
-                        continue 'dirs;
+        };
+
+        // without painted voxels the colour comes from the material, see `material`
+        let painted = !self.voxel_colors.is_empty();
+        let voxels: HashMap<IVec3, Color> = points
+            .iter()
+            .zip(&self.original_points)
+            .map(|(point, original_point)| {
+                let color = if painted {
+                    self.voxel_colors
+                        .get(original_point)
+                        .copied()
+                        .unwrap_or(self.color)
+                } else {
+                    Color::WHITE
+                };
+                (*point, color)
+            })
+            .collect();
+
+        let mut builder = QuadBuilder::default();
+        for axis in 0..3 {
+            for sign in [1, -1] {
+                let normal = IVec3::AXES[axis] * sign;
+                let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+
+                let mut layers: HashMap<i32, HashMap<IVec2, FaceCell>> = HashMap::new();
+                for (&voxel, &color) in &voxels {
+                    if voxels.contains_key(&(voxel + normal)) {
+                        continue;
                     }
+                    layers.entry(voxel[axis]).or_default().insert(
+                        ivec2(voxel[u], voxel[v]),
+                        FaceCell {
+                            color,
+                            occlusion: corner_occlusion(&voxels, voxel + normal, u, v),
+                        },
+                    );
                 }
 
-                faces.push(VoxelFace {
-                    position: new_pos,
-                    normal: *dir,
-                });
+                for (layer, cells) in layers {
+                    for (start, size, cell) in greedy_rectangles(&cells) {
+                        let plane = layer as f32 + sign as f32 * 0.5;
+                        let low = start.as_vec2() - 0.5;
+                        let high = low + size.as_vec2();
+                        let corner_uvs = [
+                            [low.x, low.y],
+                            [high.x, low.y],
+                            [high.x, high.y],
+                            [low.x, high.y],
+                        ];
+                        let corners = corner_uvs.map(|[cu, cv]| {
+                            let mut corner = Vec3::ZERO;
+                            corner[axis] = plane;
+                            corner[u] = cu;
+                            corner[v] = cv;
+                            corner
+                        });
+
+                        // u x v points along +axis, the back faces go the other way round
+                        if sign > 0 {
+                            builder.quad(corners, corner_uvs, normal.as_vec3(), cell);
+                        } else {
+                            let [c0, c1, c2, c3] = corners;
+                            let [uv0, uv1, uv2, uv3] = corner_uvs;
+                            let [o0, o1, o2, o3] = cell.occlusion;
+                            builder.quad(
+                                [c0, c3, c2, c1],
+                                [uv0, uv3, uv2, uv1],
+                                normal.as_vec3(),
+                                FaceCell {
+                                    occlusion: [o0, o3, o2, o1],
+                                    ..cell
+                                },
+                            );
+                        }
+                    }
+                }
             }
         }
 
-        let mut vertices = Vec::new();
-        let mut normals = Vec::new();
-        let mut indices = Vec::new();
-
-        let mut i = 0;
-        for face in &faces {
-            let orth1 = vec3(face.normal.y, face.normal.z, face.normal.x);
-            let orth2 = vec3(face.normal.z, face.normal.x, face.normal.y);
-            vertices.push(face.position + orth1 * 0.5 + orth2 * 0.5);
-            vertices.push(face.position - orth1 * 0.5 + orth2 * 0.5);
-            vertices.push(face.position + orth1 * 0.5 - orth2 * 0.5);
-            vertices.push(face.position - orth1 * 0.5 - orth2 * 0.5);
-
-            normals.push(face.normal);
-            normals.push(face.normal);
-            normals.push(face.normal);
-            normals.push(face.normal);
-
-            if face.normal.x + face.normal.y + face.normal.z < 0.0 {
-                indices.push(i);
-                indices.push(i + 1);
-                indices.push(i + 2);
-
-                indices.push(i + 3);
-                indices.push(i + 2);
-                indices.push(i + 1);
-            } else {
-                indices.push(i + 2);
-                indices.push(i + 1);
-                indices.push(i);
-
-                indices.push(i + 1);
-                indices.push(i + 2);
-                indices.push(i + 3);
-            }
+        builder.build()
+    }
+
+    // painted items carry their colours in the mesh, the material only has to let them through
+    pub fn material(&self) -> StandardMaterial {
+        if self.voxel_colors.is_empty() {
+            self.color.into()
+        } else {
+            Color::WHITE.into()
+        }
+    }
+}
 
-            i += 4;
+// Classic voxel AO: looks at the two sides and the diagonal of each corner in the layer of air
+// in front of the face. Corners in quad order, (-u, -v), (+u, -v), (+u, +v), (-u, +v).
+fn corner_occlusion(voxels: &HashMap<IVec3, Color>, air: IVec3, u: usize, v: usize) -> [u8; 4] {
+    let (along_u, along_v) = (IVec3::AXES[u], IVec3::AXES[v]);
+    [(-1, -1), (1, -1), (1, 1), (-1, 1)].map(|(su, sv)| {
+        let side_u = voxels.contains_key(&(air + along_u * su));
+        let side_v = voxels.contains_key(&(air + along_v * sv));
+        let corner = voxels.contains_key(&(air + along_u * su + along_v * sv));
+        if side_u && side_v {
+            0
+        } else {
+            3 - (side_u as u8 + side_v as u8 + corner as u8)
         }
+    })
+}
 
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+// Greedy meshing of one layer: grows each rectangle along u as far as it can, then along v
+// while the whole row still matches. Returns (lowest cell, size in cells, face).
+fn greedy_rectangles(cells: &HashMap<IVec2, FaceCell>) -> Vec<(IVec2, IVec2, FaceCell)> {
+    let Some(min) = cells.keys().copied().reduce(IVec2::min) else {
+        return Vec::new();
+    };
+    let max = cells.keys().copied().reduce(IVec2::max).unwrap();
+
+    let mut used: HashSet<IVec2> = HashSet::new();
+    let mut rectangles = Vec::new();
+    let matches = |position: IVec2, cell: &FaceCell, used: &HashSet<IVec2>| {
+        !used.contains(&position) && cells.get(&position) == Some(cell)
+    };
+
+    for y in min.y..=max.y {
+        for x in min.x..=max.x {
+            let start = ivec2(x, y);
+            let Some(cell) = cells.get(&start) else {
+                continue;
+            };
+            if used.contains(&start) {
+                continue;
+            }
 
-        mesh.set_indices(Some(Indices::U32(indices)));
+            let mut width = 1;
+            while matches(start + ivec2(width, 0), cell, &used) {
+                width += 1;
+            }
 
-        mesh
+            let mut height = 1;
+            while (0..width).all(|dx| matches(start + ivec2(dx, height), cell, &used)) {
+                height += 1;
+            }
+
+            for dy in 0..height {
+                for dx in 0..width {
+                    used.insert(start + ivec2(dx, dy));
+                }
+            }
+            rectangles.push((start, ivec2(width, height), *cell));
+        }
     }
+
+    rectangles
 }
@@ -1,45 +1,292 @@
+use bevy::ecs::system::SystemState;
 use bevy::prelude::*;
 use bevy::prelude::*;
 use queues::{IsQueue, Queue};
-use rand::{random, Rng};
+use rand::Rng;
 
-use crate::inventory::ItemType::{MELEE_WEAPON, NON_WEAPON, RANGED_WEAPON};
+use crate::arena_generator::ArenaLayout;
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::game::GameRng;
+use crate::game_state::GameState;
+use crate::inventory::ItemType::{CONSUMABLE, MELEE_WEAPON, NON_WEAPON, RANGED_WEAPON};
 use crate::inventory::{InventoryItem, ItemTypeId};
+use crate::level_loader::LevelMetadata;
+use crate::player::PlayerControllerState;
+use crate::profile::Profile;
+use crate::status_effect::StatusEffectKind;
 use crate::wave_manager::ARENA_DIMENSIONS_METERS;
 
 pub struct ItemSpawner;
 
+// what can show up next to the hearts between waves
+const CONSUMABLES: [ItemTypeId; 4] = [
+    ItemTypeId::HealPotion,
+    ItemTypeId::SpeedPotion,
+    ItemTypeId::Bomb,
+    ItemTypeId::Shield,
+];
+
+// chance for a lucky drop to also bring one of the weapons the profile unlocked
+const UNLOCKED_DROP_CHANCE: f64 = 0.25;
+
+// Set when a run starts from scratch, the starting items drop into the arena as the fight begins,
+// wherever the level drops its items. That way whatever was unlocked on the title screen is in
+// the next run already.
+#[derive(Resource)]
+pub struct StartingItems {
+    pub pending: bool,
+}
+
 impl Plugin for ItemSpawner {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, setup);
+        app.insert_resource(StartingItems { pending: true });
+        app.add_systems(
+            OnEnter(GameState::FightingInArena),
+            drop_starting_items.run_if(|starting_items: Res<StartingItems>| starting_items.pending),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("give", give_command)
+                .usage("<item>")
+                .help("drop an item at player one's feet")
+                .arguments(ItemTypeId::ALL.map(|item_type_id| item_type_id.name())),
+        );
     }
 }
 
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-    create_sword(
-        &mut commands,
-        &mut meshes,
-        &mut materials,
-        Vec3 {
-            x: -3.0,
-            y: 0.5,
-            z: 0.0,
-        },
-    );
-    create_heart(
+fn give_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "item")?;
+    let item_type_id =
+        ItemTypeId::from_name(&name).ok_or_else(|| format!("no item called `{name}`"))?;
+
+    let mut state: SystemState<(
+        Commands,
+        ResMut<Assets<Mesh>>,
+        ResMut<Assets<StandardMaterial>>,
+        Query<(&Transform, &PlayerControllerState)>,
+    )> = SystemState::new(world);
+    let (mut commands, mut meshes, mut materials, players) = state.get_mut(world);
+
+    let location = players
+        .iter()
+        .find(|(_, controller)| controller.player_index == 0)
+        .map(|(transform, _)| transform.translation)
+        .ok_or("no hermit to give it to")?;
+
+    catalog_item(item_type_id).create_world_entity(
+        location,
+        false,
+        true,
         &mut commands,
         &mut meshes,
         &mut materials,
-        Vec3 {
-            x: 5.0,
-            y: 0.5,
-            z: -8.0,
-        },
     );
+
+    state.apply(world);
+    Ok(format!("gave {name}"))
+}
+
+#[allow(clippy::too_many_arguments)]
+fn drop_starting_items(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut starting_items: ResMut<StartingItems>,
+    profile: Res<Profile>,
+    mut rng: ResMut<GameRng>,
+    arena: Option<Res<ArenaLayout>>,
+    level: Res<LevelMetadata>,
+) {
+    starting_items.pending = false;
+
+    let mut position = || random_item_position(rng.as_mut(), arena.as_deref(), &level);
+
+    create_sword(&mut commands, &mut meshes, &mut materials, position());
+    create_heart(&mut commands, &mut meshes, &mut materials, position());
+    for item_type_id in profile.starting_items() {
+        catalog_item(item_type_id).create_world_entity(
+            position(),
+            false,
+            true,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
+    }
+}
+
+// every item the game knows about, keyed by type id, in the spot it first lands in the shell
+pub fn catalog_item(item_type_id: ItemTypeId) -> InventoryItem {
+    match item_type_id {
+        ItemTypeId::AlexBoomerang => {
+            let mut gun = InventoryItem::from((
+                (1, 0, 3),
+                vec![(0, 0, 0), (0, 0, 1), (0, 0, 2), (-1, 0, 2), (-2, 0, 2)],
+                Color::rgba(1.0, 1.0, 1.0, 1.0),
+                RANGED_WEAPON,
+                ItemTypeId::AlexBoomerang,
+            ));
+            gun.weapon_attack_speed = 10.0;
+            gun.projectile_speed = 30.0;
+            gun.status_effect = Some(StatusEffectKind::Poison);
+            gun
+        }
+        ItemTypeId::WillSword => {
+            let mut sword = InventoryItem::from((
+                (5, 0, 2),
+                vec![
+                    (0, 0, 0),
+                    (0, 0, 1),
+                    (0, 0, 2),
+                    (1, 0, 0),
+                    (-1, 0, 0),
+                    (0, 0, -1),
+                ],
+                Color::rgba(0.0, 1.0, 0.0, 1.0),
+                MELEE_WEAPON,
+                ItemTypeId::WillSword,
+            ));
+            // wooden hilt
+            sword.paint(
+                &[(1, 0, 0), (-1, 0, 0), (0, 0, -1)],
+                Color::rgb(0.45, 0.28, 0.12),
+            );
+            sword
+        }
+        ItemTypeId::MidSword => {
+            let mut sword = InventoryItem::from((
+                (0, 0, 0),
+                vec![
+                    (0, 0, 0),
+                    (0, 0, 1),
+                    (0, 0, 2),
+                    (0, 0, 3),
+                    (0, 0, 4),
+                    (0, 0, 5),
+                    (1, 0, 0),
+                    (-1, 0, 0),
+                    (0, 0, -1),
+                ],
+                Color::rgba(0.5, 0.5, 0.5, 1.0),
+                MELEE_WEAPON,
+                ItemTypeId::MidSword,
+            ));
+            sword.weapon_attack_speed = 2.0;
+            sword.weapon_is_auto = true;
+            sword.status_effect = Some(StatusEffectKind::Burn);
+            // golden hilt
+            sword.paint(
+                &[(1, 0, 0), (-1, 0, 0), (0, 0, -1)],
+                Color::rgb(0.85, 0.65, 0.15),
+            );
+            sword
+        }
+        ItemTypeId::HandGun => {
+            let mut gun = InventoryItem::from((
+                (0, 0, 0),
+                vec![(0, 0, 0), (0, 0, 1), (0, -1, 0), (0, 0, 2)],
+                Color::rgba(0.1, 0.1, 0.1, 1.0),
+                RANGED_WEAPON,
+                ItemTypeId::HandGun,
+            ));
+            gun.weapon_attack_speed = 2.0;
+            gun.projectile_speed = 30.0;
+            gun.status_effect = Some(StatusEffectKind::Stun);
+            gun
+        }
+        ItemTypeId::SuperGun => {
+            let mut gun = InventoryItem::from((
+                (0, 1, 0),
+                vec![
+                    (0, 0, 0),
+                    (0, 0, 1),
+                    (0, -1, 0),
+                    (0, 0, 2),
+                    (0, 0, 3),
+                    (0, 0, 4),
+                    (0, -1, 4),
+                    (0, 0, 5),
+                ],
+                Color::rgba(0.1, 0.1, 0.1, 1.0),
+                RANGED_WEAPON,
+                ItemTypeId::SuperGun,
+            ));
+            gun.weapon_attack_speed = 10.0;
+            gun.projectile_speed = 30.0;
+            gun.status_effect = Some(StatusEffectKind::Slow);
+            gun
+        }
+        ItemTypeId::Heart => {
+            let mut heart = InventoryItem::from((
+                (0, 0, 0),
+                vec![
+                    (0, 0, 0),
+                    (0, 0, 1),
+                    (-1, 0, 0),
+                    (1, 0, 0),
+                    (-1, 0, -1),
+                    (1, 0, -1),
+                ],
+                Color::rgba(1.0, 0.1, 0.1, 1.0),
+                NON_WEAPON,
+                ItemTypeId::Heart,
+            ));
+            heart.hp_gain = 1;
+            heart
+        }
+        ItemTypeId::HealPotion => {
+            let mut potion = InventoryItem::from((
+                (0, 0, 0),
+                vec![(0, 0, 0), (0, 1, 0)],
+                Color::rgba(1.0, 0.4, 0.7, 1.0),
+                CONSUMABLE,
+                ItemTypeId::HealPotion,
+            ));
+            // cork
+            potion.paint(&[(0, 1, 0)], Color::rgb(0.45, 0.28, 0.12));
+            potion
+        }
+        ItemTypeId::SpeedPotion => {
+            let mut potion = InventoryItem::from((
+                (0, 0, 0),
+                vec![(0, 0, 0), (0, 1, 0)],
+                Color::rgba(0.3, 0.9, 1.0, 1.0),
+                CONSUMABLE,
+                ItemTypeId::SpeedPotion,
+            ));
+            potion.paint(&[(0, 1, 0)], Color::rgb(0.45, 0.28, 0.12));
+            potion
+        }
+        ItemTypeId::Bomb => {
+            let mut bomb = InventoryItem::from((
+                (0, 0, 0),
+                vec![(0, 0, 0), (1, 0, 0), (0, 0, 1), (1, 0, 1), (0, 1, 0)],
+                Color::rgba(0.15, 0.15, 0.2, 1.0),
+                CONSUMABLE,
+                ItemTypeId::Bomb,
+            ));
+            // fuse
+            bomb.paint(&[(0, 1, 0)], Color::rgb(1.0, 0.55, 0.1));
+            bomb
+        }
+        ItemTypeId::Shield => {
+            let mut shield = InventoryItem::from((
+                (0, 0, 0),
+                vec![(0, 0, 0), (1, 0, 0), (-1, 0, 0), (0, 0, 1), (0, 0, -1)],
+                Color::rgba(0.6, 0.7, 0.85, 1.0),
+                CONSUMABLE,
+                ItemTypeId::Shield,
+            ));
+            // golden boss
+            shield.paint(&[(0, 0, 0)], Color::rgb(0.85, 0.65, 0.15));
+            shield
+        }
+        // never got a model of its own, falls back to Alex's
+        ItemTypeId::Boomerang | ItemTypeId::AlexSword => {
+            let mut item = catalog_item(ItemTypeId::AlexBoomerang);
+            item.item_type_id = item_type_id;
+            item
+        }
+    }
 }
 
 fn create_alex_boomerang_copyrighted_you_need_permissions_to_use(
@@ -48,17 +295,8 @@ fn create_alex_boomerang_copyrighted_you_need_permissions_to_use(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let mut gun = InventoryItem::from((
-        (1, 0, 3),
-        vec![(0, 0, 0), (0, 0, 1), (0, 0, 2), (-1, 0, 2), (-2, 0, 2)],
-        Color::rgba(1.0, 1.0, 1.0, 1.0),
-        RANGED_WEAPON,
-        ItemTypeId::AlexBoomerang,
-    ));
-    gun.weapon_attack_speed = 10.0;
-    gun.projectile_speed = 30.0;
-
-    gun.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::AlexBoomerang)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
 
 pub fn create_sword(
@@ -67,22 +305,8 @@ pub fn create_sword(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let sword = InventoryItem::from((
-        (5, 0, 2),
-        vec![
-            (0, 0, 0),
-            (0, 0, 1),
-            (0, 0, 2),
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 0, -1),
-        ],
-        Color::rgba(0.0, 1.0, 0.0, 1.0),
-        MELEE_WEAPON,
-        ItemTypeId::WillSword,
-    ));
-
-    sword.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::WillSword)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
 
 fn create_better_sword(
@@ -91,26 +315,8 @@ fn create_better_sword(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let mut sword = InventoryItem::from((
-        (0, 0, 0),
-        vec![
-            (0, 0, 0),
-            (0, 0, 1),
-            (0, 0, 2),
-            (0, 0, 3),
-            (0, 0, 4),
-            (0, 0, 5),
-            (1, 0, 0),
-            (-1, 0, 0),
-            (0, 0, -1),
-        ],
-        Color::rgba(0.5, 0.5, 0.5, 1.0),
-        MELEE_WEAPON,
-        ItemTypeId::MidSword,
-    ));
-    sword.weapon_attack_speed = 2.0;
-    sword.weapon_is_auto = true;
-    sword.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::MidSword)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
 
 fn create_handgun(
@@ -119,17 +325,8 @@ fn create_handgun(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let mut gun = InventoryItem::from((
-        (0, 0, 0),
-        vec![(0, 0, 0), (0, 0, 1), (0, -1, 0), (0, 0, 2)],
-        Color::rgba(0.1, 0.1, 0.1, 1.0),
-        RANGED_WEAPON,
-        ItemTypeId::HandGun,
-    ));
-    gun.weapon_attack_speed = 2.0;
-    gun.projectile_speed = 30.0;
-
-    gun.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::HandGun)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
 
 fn create_supergun(
@@ -138,26 +335,8 @@ fn create_supergun(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let mut gun = InventoryItem::from((
-        (0, 1, 0),
-        vec![
-            (0, 0, 0),
-            (0, 0, 1),
-            (0, -1, 0),
-            (0, 0, 2),
-            (0, 0, 3),
-            (0, 0, 4),
-            (0, -1, 4),
-            (0, 0, 5),
-        ],
-        Color::rgba(0.1, 0.1, 0.1, 1.0),
-        RANGED_WEAPON,
-        ItemTypeId::SuperGun,
-    ));
-    gun.weapon_attack_speed = 10.0;
-    gun.projectile_speed = 30.0;
-
-    gun.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::SuperGun)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
 
 pub fn create_heart(
@@ -166,53 +345,62 @@ pub fn create_heart(
     materials: &mut ResMut<Assets<StandardMaterial>>,
     location: Vec3,
 ) {
-    let mut heart = InventoryItem::from((
-        (0, 0, 0),
-        vec![
-            (0, 0, 0),
-            (0, 0, 1),
-            (-1, 0, 0),
-            (1, 0, 0),
-            (-1, 0, -1),
-            (1, 0, -1),
-        ],
-        Color::rgba(1.0, 0.1, 0.1, 1.0),
-        NON_WEAPON,
-        ItemTypeId::Heart,
-    ));
-    heart.hp_gain = 1;
-
-    heart.create_world_entity(location, false, true, commands, meshes, materials);
+    catalog_item(ItemTypeId::Heart)
+        .create_world_entity(location, false, true, commands, meshes, materials);
 }
+
+fn random_item_position(
+    rng: &mut impl Rng,
+    arena: Option<&ArenaLayout>,
+    level: &LevelMetadata,
+) -> Vec3 {
+    if let Some(point) = level.item_spawn_point(rng) {
+        return point + Vec3::Y * 0.5;
+    }
+
+    if let Some(point) = arena.and_then(|arena| arena.spawn_point(rng, &[], 0.0)) {
+        return point + Vec3::Y * 0.5;
+    }
+
+    Vec3::new(
+        (rng.gen::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[0] / 2.0),
+        0.5,
+        (rng.gen::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[1] / 2.0),
+    )
+}
+
+// `unlocked` are the weapons the profile added to the drops
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_random_item(
     luck: i32,
+    unlocked: &[ItemTypeId],
+    rng: &mut impl Rng,
+    arena: Option<&ArenaLayout>,
+    level: &LevelMetadata,
     mut commands: &mut Commands,
     mut meshes: &mut ResMut<Assets<Mesh>>,
     mut materials: &mut ResMut<Assets<StandardMaterial>>,
 ) {
-    let mut rng = rand::thread_rng();
     let spawn_id = rng.gen_range(0..20);
 
-    let mut position = Vec3::new(
-        (random::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[0] / 2.0),
-        0.5,
-        (random::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[1] / 2.0),
-    );
+    let mut position = random_item_position(rng, arena, level);
 
     match spawn_id {
         0..=6 => {
             create_heart(commands, meshes, materials, position);
         }
+        7..=10 => {
+            let item_type_id = CONSUMABLES[rng.gen_range(0..CONSUMABLES.len())];
+            catalog_item(item_type_id)
+                .create_world_entity(position, false, true, commands, meshes, materials);
+        }
         _ => {}
     }
 
-    position = Vec3::new(
-        (random::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[0] / 2.0),
-        0.5,
-        (random::<f32>() - 0.5) * (ARENA_DIMENSIONS_METERS[1] / 2.0),
-    );
+    position = random_item_position(rng, arena, level);
 
-    match luck {
+    // luck past the super gun still drops it
+    match luck.min(3) {
         1 => {
             create_better_sword(commands, meshes, materials, position);
         }
@@ -224,6 +412,13 @@ pub fn spawn_random_item(
         }
         _ => {}
     }
+
+    if luck > 0 && !unlocked.is_empty() && rng.gen_bool(UNLOCKED_DROP_CHANCE) {
+        let item_type_id = unlocked[rng.gen_range(0..unlocked.len())];
+        position = random_item_position(rng, arena, level);
+        catalog_item(item_type_id)
+            .create_world_entity(position, false, true, commands, meshes, materials);
+    }
 }
 
 fn monitor_spawns() {}
@@ -1,142 +1,487 @@
 use bevy::{
-    asset::LoadState,
-    gltf::{Gltf, GltfMesh, GltfNode},
+    gltf::GltfExtras,
     log,
     prelude::*,
     render::mesh::{Indices, VertexAttributeValues},
+    transform::TransformSystem,
+    utils::HashMap,
 };
-use bevy_rapier3d::prelude::{Collider, CollisionGroups, Group};
+use bevy_rapier3d::prelude::{Collider, CollisionGroups};
+use rand::Rng;
 
 use crate::config::{
     COLLISION_GROUP_ENEMIES, COLLISION_GROUP_PLAYER, COLLISION_GROUP_TERRAIN, COLLISION_GROUP_WALLS,
 };
+use crate::enemy::EnemyType;
+use crate::game_state::GameState;
+use crate::inventory::ItemTypeId;
+use crate::network::is_simulation_authority;
+use crate::player::combat::{PlayerCombatState, PLAYER_INVICIBILITY_COOLDOWN};
+use crate::player::{GodMode, PlayerControllerState};
+
+const DEFAULT_MARKER_RADIUS: f32 = 1.5;
 
 pub struct LevelLoaderPlugin;
 
 impl Plugin for LevelLoaderPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_gltf_load_event);
+        app.add_systems(Update, build_level_colliders);
+        app.add_systems(
+            PostUpdate,
+            collect_level_markers.after(TransformSystem::TransformPropagate),
+        );
+        app.add_systems(
+            Update,
+            (
+                hurt_players_in_hazards.run_if(is_simulation_authority),
+                detect_triggers,
+            )
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.insert_resource(LevelMetadata::default());
+        app.add_event::<LevelTriggerEvent>();
+    }
+}
+
+// Root of the scene spawned by `load_level`, markers and colliders are only looked for below it.
+// Colliders live on the scene's own node entities, so despawning the root unloads it all.
+#[derive(Component)]
+pub struct LevelRoot;
+
+// An empty (or mesh) placed in Blender, named `spawn_enemy_*`, `spawn_item_*`, `player_start*`,
+// `hazard_*` or `trigger_*`. Custom properties come along as `extras`.
+#[derive(Clone, Debug)]
+pub struct LevelMarker {
+    pub name: String,
+    pub position: Vec3,
+    pub extras: HashMap<String, String>,
+}
+
+impl LevelMarker {
+    pub fn number(&self, key: &str) -> Option<f32> {
+        self.extras.get(key).and_then(|value| value.parse().ok())
+    }
+
+    pub fn radius(&self) -> f32 {
+        self.number("radius").unwrap_or(DEFAULT_MARKER_RADIUS)
+    }
+
+    // `"enemy": "urchin"` in the extras, or the type in the name like `spawn_enemy_urchin_2`
+    pub fn enemy_type(&self) -> Option<EnemyType> {
+        self.extras
+            .get("enemy")
+            .and_then(|name| EnemyType::from_name(name))
+            .or_else(|| {
+                EnemyType::ALL
+                    .into_iter()
+                    .find(|enemy_type| self.name.contains(enemy_type.name()))
+            })
+    }
+
+    // `"item": "supergun"` in the extras, or the item in the name like `spawn_item_heart`
+    pub fn item_type_id(&self) -> Option<ItemTypeId> {
+        self.extras
+            .get("item")
+            .and_then(|name| ItemTypeId::from_name(name))
+            .or_else(|| {
+                ItemTypeId::ALL
+                    .into_iter()
+                    .find(|item_type_id| self.name.contains(&item_type_id.name()))
+            })
+    }
+}
+
+// Everything the level designer placed in the current level. Empty for levels without markers,
+// in which case the spawners fall back to random spots in the arena.
+#[derive(Resource, Default, Debug)]
+pub struct LevelMetadata {
+    // sorted by name, player one takes the first
+    pub player_starts: Vec<LevelMarker>,
+    pub enemy_spawns: Vec<LevelMarker>,
+    pub item_spawns: Vec<LevelMarker>,
+    pub hazards: Vec<LevelMarker>,
+    pub triggers: Vec<LevelMarker>,
+}
+
+impl LevelMetadata {
+    pub fn player_start(&self, player_index: usize) -> Option<Vec3> {
+        if self.player_starts.is_empty() {
+            return None;
+        }
+        Some(self.player_starts[player_index % self.player_starts.len()].position)
+    }
+
+    // a random spot inside one of the enemy spawns that takes this type (untyped ones take any)
+    pub fn enemy_spawn_point(&self, rng: &mut impl Rng, enemy_type: EnemyType) -> Option<Vec3> {
+        let candidates: Vec<&LevelMarker> = self
+            .enemy_spawns
+            .iter()
+            .filter(|marker| {
+                marker
+                    .enemy_type()
+                    .map_or(true, |wanted| wanted == enemy_type)
+            })
+            .collect();
+        random_point_in(rng, &candidates)
+    }
+
+    pub fn item_spawn_point(&self, rng: &mut impl Rng) -> Option<Vec3> {
+        let candidates: Vec<&LevelMarker> = self.item_spawns.iter().collect();
+        random_point_in(rng, &candidates)
     }
+
+    fn add(&mut self, marker: LevelMarker) -> bool {
+        let name = marker.name.to_lowercase();
+        let list = if name.starts_with("spawn_enemy") {
+            &mut self.enemy_spawns
+        } else if name.starts_with("spawn_item") {
+            &mut self.item_spawns
+        } else if name.starts_with("player_start") {
+            &mut self.player_starts
+        } else if name.starts_with("hazard") {
+            &mut self.hazards
+        } else if name.starts_with("trigger") {
+            &mut self.triggers
+        } else {
+            return false;
+        };
+
+        list.push(marker);
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        true
+    }
+}
+
+fn random_point_in(rng: &mut impl Rng, markers: &[&LevelMarker]) -> Option<Vec3> {
+    if markers.is_empty() {
+        return None;
+    }
+
+    let marker = markers[rng.gen_range(0..markers.len())];
+    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+    let distance = marker.radius() * rng.gen::<f32>().sqrt();
+    Some(marker.position + vec3(angle.cos(), 0.0, angle.sin()) * distance)
 }
 
-pub fn load_level(asset_path: &str, commands: &mut Commands, asset_server: &ResMut<AssetServer>) {
-    commands.spawn(SceneBundle {
-        scene: asset_server.load(asset_path),
-        ..default()
-    });
+// a player walked into a `trigger_*` marker
+#[derive(Event, Debug)]
+pub struct LevelTriggerEvent {
+    pub name: String,
+    pub player_index: usize,
+}
+
+pub fn load_level(asset_path: &str, commands: &mut Commands, asset_server: &AssetServer) {
+    commands
+        .spawn(SceneBundle {
+            scene: asset_server.load(asset_path),
+            ..default()
+        })
+        .insert(LevelRoot);
+}
+
+// Runs once the scene's nodes have been spawned and their transforms propagated, so the markers
+// get their world positions.
+fn collect_level_markers(
+    new_nodes: Query<
+        (Entity, &Name, &GlobalTransform, Option<&GltfExtras>),
+        (Added<Name>, Without<Handle<Mesh>>),
+    >,
+    new_levels: Query<(), Added<LevelRoot>>,
+    parents: Query<&Parent>,
+    level_roots: Query<(), With<LevelRoot>>,
+    mut visibilities: Query<&mut Visibility>,
+    mut metadata: ResMut<LevelMetadata>,
+) {
+    if !new_levels.is_empty() {
+        *metadata = LevelMetadata::default();
+    }
+
+    for (entity, name, transform, extras) in &new_nodes {
+        let in_level = parents
+            .iter_ancestors(entity)
+            .any(|ancestor| level_roots.contains(ancestor));
+        if !in_level {
+            continue;
+        }
+
+        let marker = LevelMarker {
+            name: name.as_str().to_string(),
+            position: transform.translation(),
+            extras: extras
+                .map(|extras| parse_extras(&extras.value))
+                .unwrap_or_default(),
+        };
+
+        let name = marker.name.clone();
+        if metadata.add(marker) {
+            log::info!("Found level marker: {name:?}");
+            // markers are for the game, not for the player
+            if let Ok(mut visibility) = visibilities.get_mut(entity) {
+                *visibility = Visibility::Hidden;
+            }
+        }
+    }
+}
+
+// Blender exports custom properties as a flat json object like `{"enemy": "urchin", "radius": 2}`.
+// Values are kept as text, strings without their quotes.
+pub fn parse_extras(json: &str) -> HashMap<String, String> {
+    let body = json.trim().trim_start_matches('{').trim_end_matches('}');
+
+    // split on the commas that are not inside a string or a nested value
+    let mut pairs = Vec::new();
+    let (mut depth, mut in_string, mut start) = (0, false, 0);
+    for (i, c) in body.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '[' | '{' if !in_string => depth += 1,
+            ']' | '}' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                pairs.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    pairs.push(&body[start..]);
+
+    pairs
+        .into_iter()
+        .filter_map(|pair| pair.split_once(':'))
+        .map(|(key, value)| {
+            (
+                key.trim().trim_matches('"').to_string(),
+                value.trim().trim_matches('"').to_string(),
+            )
+        })
+        .filter(|(key, _)| !key.is_empty())
+        .collect()
+}
+
+// `hazard_*` markers hurt whoever stands in them, `"damage"` per hit, every invincibility window
+fn hurt_players_in_hazards(
+    metadata: Res<LevelMetadata>,
+    god_mode: Res<GodMode>,
+    mut players: Query<(&Transform, &mut PlayerCombatState), With<PlayerControllerState>>,
+    time: Res<Time>,
+) {
+    if metadata.hazards.is_empty() || god_mode.0 {
+        return;
+    }
+
+    for (transform, mut combat_state) in &mut players {
+        if combat_state.is_downed()
+            || combat_state.last_hit + PLAYER_INVICIBILITY_COOLDOWN > time.elapsed_seconds()
+        {
+            continue;
+        }
+
+        let hazard = metadata
+            .hazards
+            .iter()
+            .find(|hazard| hazard.position.distance(transform.translation) < hazard.radius());
+        if let Some(hazard) = hazard {
+            combat_state.current_hp -= hazard.number("damage").unwrap_or(1.0) as i32;
+            combat_state.last_hit = time.elapsed_seconds();
+            combat_state.last_heal = time.elapsed_seconds();
+        }
+    }
 }
 
-#[allow(clippy::too_many_arguments)]
-fn handle_gltf_load_event(
+#[derive(Default)]
+struct PlayersInTriggers(Vec<(usize, String)>);
+
+fn detect_triggers(
+    metadata: Res<LevelMetadata>,
+    players: Query<(&Transform, &PlayerControllerState)>,
+    mut inside: Local<PlayersInTriggers>,
+    mut trigger_event_writer: EventWriter<LevelTriggerEvent>,
+) {
+    if metadata.triggers.is_empty() {
+        return;
+    }
+
+    let mut now_inside = Vec::new();
+    for (transform, controller) in &players {
+        for trigger in &metadata.triggers {
+            if trigger.position.distance(transform.translation) < trigger.radius() {
+                now_inside.push((controller.player_index, trigger.name.clone()));
+            }
+        }
+    }
+
+    // only fire when walking in, not every frame spent inside
+    for (player_index, name) in &now_inside {
+        if !inside.0.contains(&(*player_index, name.clone())) {
+            log::info!("Player {} entered trigger {name:?}", player_index + 1);
+            trigger_event_writer.send(LevelTriggerEvent {
+                name: name.clone(),
+                player_index: *player_index,
+            });
+        }
+    }
+    inside.0 = now_inside;
+}
+
+// How a level node's collider is shaped, from `"collider": "convex"` in its extras or a suffix in
+// its name like `rock_convex`. Static terrain and walls default to a trimesh.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColliderShape {
+    Trimesh,
+    ConvexHull,
+    ConvexDecomposition,
+    Box,
+    Sphere,
+    Capsule,
+}
+
+impl ColliderShape {
+    // the longer name first, `rock_convex_decomposition` also has `_convex` in it
+    const NAMES: [(&'static str, ColliderShape); 7] = [
+        ("trimesh", ColliderShape::Trimesh),
+        ("convex_decomposition", ColliderShape::ConvexDecomposition),
+        ("convex", ColliderShape::ConvexHull),
+        ("decomposition", ColliderShape::ConvexDecomposition),
+        ("box", ColliderShape::Box),
+        ("sphere", ColliderShape::Sphere),
+        ("capsule", ColliderShape::Capsule),
+    ];
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .into_iter()
+            .find(|(shape_name, _)| *shape_name == name)
+            .map(|(_, shape)| shape)
+    }
+
+    // None when the node should not collide at all
+    fn for_node(name: &str, extras: &HashMap<String, String>) -> Result<Option<Self>, String> {
+        let name = name.to_lowercase();
+
+        if let Some(value) = extras.get("collider") {
+            return match value.to_lowercase().as_str() {
+                "none" => Ok(None),
+                value => Self::from_name(value)
+                    .map(Some)
+                    .ok_or_else(|| format!("unknown collider {value:?}")),
+            };
+        }
+
+        let suffixed = Self::NAMES
+            .into_iter()
+            .find(|(shape_name, _)| name.contains(&format!("_{shape_name}")))
+            .map(|(_, shape)| shape);
+        if suffixed.is_some() {
+            return Ok(suffixed);
+        }
+
+        if name.contains("terrain") || name.contains("wall") {
+            Ok(Some(ColliderShape::Trimesh))
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+// Runs on the frame after the level scene is spawned. Every primitive of a node's mesh gets a shape,
+// several primitives end up in one compound collider on the node.
+fn build_level_colliders(
     mut commands: Commands,
-    mut load_events: EventReader<AssetEvent<Gltf>>,
-    _mesh_handle_query: Query<&Handle<Mesh>>,
+    // the mesh primitives below a node can be named too, they are not nodes themselves
+    new_nodes: Query<
+        (Entity, &Name, Option<&GltfExtras>, Option<&Children>),
+        (Added<Name>, Without<Handle<Mesh>>),
+    >,
+    parents: Query<&Parent>,
+    level_roots: Query<(), With<LevelRoot>>,
+    primitives: Query<(&Handle<Mesh>, Option<&Handle<StandardMaterial>>)>,
     meshes: Res<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    gltf_meshes: Res<Assets<GltfMesh>>,
-    nodes: Res<Assets<GltfNode>>,
-    assets: Res<Assets<Gltf>>,
-    asset_server: Res<AssetServer>,
 ) {
-    for event in load_events.iter() {
-        if let AssetEvent::Created { handle } = event {
-            match asset_server.get_load_state(handle) {
-                LoadState::Loaded => {
-                    if let Some(scene) = assets.get(handle) {
-                        for (name, node_handle) in &scene.named_nodes {
-                            if name.to_lowercase().contains("terrain")
-                                || name.to_lowercase().contains("wall")
-                            {
-                                log::info!("Generating collider from level object: {name:?}");
-                                if let (Some(mesh), Some(material_handle), Some(transform)) = (
-                                    get_mesh_from_gltf_node(
-                                        node_handle,
-                                        &meshes,
-                                        &gltf_meshes,
-                                        &nodes,
-                                    ),
-                                    get_material_from_gltf_node(node_handle, &gltf_meshes, &nodes),
-                                    nodes.get(node_handle).map(|node| node.transform),
-                                ) {
-                                    if name.to_lowercase().contains("wall") {
-                                        materials.get_mut(&material_handle).unwrap().base_color =
-                                            Color::rgba(0.0, 0.0, 0.0, 0.0);
-                                        materials.get_mut(&material_handle).unwrap().alpha_mode =
-                                            AlphaMode::Blend;
-                                    }
-                                    match get_collider_from_mesh(mesh, &transform) {
-                                        Ok(collider) => {
-                                            commands.spawn(collider).insert(
-                                                if name.to_lowercase().contains("wall") {
-                                                    CollisionGroups {
-                                                        memberships: COLLISION_GROUP_WALLS,
-                                                        filters: COLLISION_GROUP_PLAYER,
-                                                        // memberships: Group::ALL,
-                                                        // filters: Group::ALL,
-                                                    }
-                                                } else if name.to_lowercase().contains("terrain") {
-                                                    CollisionGroups {
-                                                        memberships: COLLISION_GROUP_TERRAIN,
-                                                        filters: COLLISION_GROUP_PLAYER
-                                                            | COLLISION_GROUP_ENEMIES,
-                                                        // memberships: Group::ALL,
-                                                        // filters: Group::ALL,
-                                                    }
-                                                } else {
-                                                    CollisionGroups {
-                                                        memberships: Group::ALL,
-                                                        filters: Group::ALL,
-                                                    }
-                                                },
-                                            );
-                                        }
-                                        Err(err) => {
-                                            log::error!("{err:?}");
-                                        }
-                                    }
-                                } else {
-                                    log::error!(
-                                        "Node {name:?} was missing either a mesh or a transform"
-                                    );
-                                }
-                            }
-                        }
-                    }
-                }
-                LoadState::Failed => {
-                    log::error!("gltf failed to load dog");
+    for (entity, name, extras, children) in &new_nodes {
+        let in_level = parents
+            .iter_ancestors(entity)
+            .any(|ancestor| level_roots.contains(ancestor));
+        if !in_level {
+            continue;
+        }
+
+        let extras = extras
+            .map(|extras| parse_extras(&extras.value))
+            .unwrap_or_default();
+        let shape = match ColliderShape::for_node(name, &extras) {
+            Ok(Some(shape)) => shape,
+            Ok(None) => continue,
+            Err(err) => {
+                log::error!("Level node {name:?}: {err}");
+                continue;
+            }
+        };
+        let is_wall = name.to_lowercase().contains("wall");
+
+        let node_primitives: Vec<_> = children
+            .into_iter()
+            .flat_map(|children| children.iter())
+            .filter_map(|child| primitives.get(*child).ok())
+            .collect();
+        if node_primitives.is_empty() {
+            log::error!("Level node {name:?} wants a collider but has no mesh");
+            continue;
+        }
+
+        let mut shapes = Vec::new();
+        for (index, (mesh_handle, material_handle)) in node_primitives.into_iter().enumerate() {
+            if is_wall {
+                if let Some(material) = material_handle.and_then(|handle| materials.get_mut(handle))
+                {
+                    material.base_color = Color::rgba(0.0, 0.0, 0.0, 0.0);
+                    material.alpha_mode = AlphaMode::Blend;
                 }
-                _ => {}
+            }
+
+            let Some(mesh) = meshes.get(mesh_handle) else {
+                log::error!("Level node {name:?}: primitive {index} has no mesh loaded");
+                continue;
+            };
+            match get_shaped_collider_from_mesh(mesh, shape) {
+                Ok(collider) => shapes.push(collider),
+                Err(err) => log::error!("Level node {name:?}: primitive {index}: {err:?}"),
             }
         }
+
+        let collider = match shapes.len() {
+            0 => continue,
+            1 => shapes.pop().unwrap(),
+            _ => Collider::compound(
+                shapes
+                    .into_iter()
+                    .map(|shape| (Vec3::ZERO, Quat::IDENTITY, shape))
+                    .collect(),
+            ),
+        };
+
+        log::info!("Generating {shape:?} collider from level object: {name:?}");
+        commands.entity(entity).insert(collider).insert(if is_wall {
+            wall_collision_groups()
+        } else {
+            terrain_collision_groups()
+        });
     }
 }
 
-fn get_mesh_from_gltf_node<'a>(
-    node_handle: &Handle<GltfNode>,
-    meshes: &'a Res<Assets<Mesh>>,
-    gltf_meshes: &Res<Assets<GltfMesh>>,
-    nodes: &Res<Assets<GltfNode>>,
-) -> Option<&'a Mesh> {
-    nodes
-        .get(node_handle)
-        .and_then(|node| node.mesh.as_ref())
-        .and_then(|mesh_handle| gltf_meshes.get(mesh_handle))
-        .and_then(|gltf_mesh| gltf_mesh.primitives.get(0))
-        .and_then(|first_primitive| meshes.get(&first_primitive.mesh))
+// walls only stop the hermits
+pub fn wall_collision_groups() -> CollisionGroups {
+    CollisionGroups {
+        memberships: COLLISION_GROUP_WALLS,
+        filters: COLLISION_GROUP_PLAYER,
+    }
 }
 
-fn get_material_from_gltf_node<'a>(
-    node_handle: &Handle<GltfNode>,
-    gltf_meshes: &Res<Assets<GltfMesh>>,
-    nodes: &Res<Assets<GltfNode>>,
-) -> Option<Handle<StandardMaterial>> {
-    nodes
-        .get(node_handle)
-        .and_then(|node| node.mesh.as_ref())
-        .and_then(|mesh_handle| gltf_meshes.get(mesh_handle))
-        .and_then(|gltf_mesh| gltf_mesh.primitives.get(0))
-        .and_then(|first_primitive| first_primitive.material.clone())
+pub fn terrain_collision_groups() -> CollisionGroups {
+    CollisionGroups {
+        memberships: COLLISION_GROUP_TERRAIN,
+        filters: COLLISION_GROUP_PLAYER | COLLISION_GROUP_ENEMIES,
+    }
 }
 
 // taken from https://github.com/Defernus/bevy_gltf_collider/blob/9f27253e6d2e645c3570bebead34a493e4da1deb/src/mesh_collider.rs
@@ -146,12 +491,11 @@ pub enum ColliderFromMeshError {
     MissingIndices,
     InvalidIndicesCount(usize),
     InvalidPositionsType(&'static str),
+    Empty,
+    DegenerateHull,
 }
 
-fn get_collider_from_mesh(
-    mesh: &Mesh,
-    transform: &Transform,
-) -> Result<Collider, ColliderFromMeshError> {
+fn mesh_triangles(mesh: &Mesh) -> Result<(Vec<Vec3>, Vec<[u32; 3]>), ColliderFromMeshError> {
     let positions = mesh
         .attribute(Mesh::ATTRIBUTE_POSITION)
         .map_or(Err(ColliderFromMeshError::MissingPositions), Ok)?;
@@ -179,20 +523,68 @@ fn get_collider_from_mesh(
     }
 
     let triple_indices = indices.chunks(3).map(|v| [v[0], v[1], v[2]]).collect();
-    let vertices = positions
-        .iter()
-        .map(|v| {
-            let p = Vec4::new(v[0], v[1], v[2], 1.0);
-            let p_transformed = transform.compute_matrix() * p;
-            Vec3::new(
-                p_transformed.x / p_transformed.w,
-                p_transformed.y / p_transformed.w,
-                p_transformed.z / p_transformed.w,
-            )
-        })
+    let vertices = positions.iter().map(|v| Vec3::from_array(*v)).collect();
+
+    Ok((vertices, triple_indices))
+}
+
+pub fn get_collider_from_mesh(
+    mesh: &Mesh,
+    transform: &Transform,
+) -> Result<Collider, ColliderFromMeshError> {
+    let (vertices, indices) = mesh_triangles(mesh)?;
+    let matrix = transform.compute_matrix();
+    let vertices = vertices
+        .into_iter()
+        .map(|vertex| matrix.transform_point3(vertex))
         .collect();
 
-    let collider = Collider::trimesh(vertices, triple_indices);
+    Ok(Collider::trimesh(vertices, indices))
+}
+
+// in the mesh's own space, the collider goes on the entity that holds the mesh
+pub fn get_shaped_collider_from_mesh(
+    mesh: &Mesh,
+    shape: ColliderShape,
+) -> Result<Collider, ColliderFromMeshError> {
+    let (vertices, indices) = mesh_triangles(mesh)?;
+    if vertices.is_empty() {
+        return Err(ColliderFromMeshError::Empty);
+    }
 
-    Ok(collider)
+    // the primitive shapes wrap the mesh's bounding box
+    let min = vertices.iter().copied().reduce(Vec3::min).unwrap();
+    let max = vertices.iter().copied().reduce(Vec3::max).unwrap();
+    let center = (min + max) / 2.0;
+    let half_extents = (max - min) / 2.0;
+    let around_center = |collider: Collider| {
+        if center == Vec3::ZERO {
+            collider
+        } else {
+            Collider::compound(vec![(center, Quat::IDENTITY, collider)])
+        }
+    };
+
+    match shape {
+        ColliderShape::Trimesh => Ok(Collider::trimesh(vertices, indices)),
+        ColliderShape::ConvexHull => {
+            Collider::convex_hull(&vertices).ok_or(ColliderFromMeshError::DegenerateHull)
+        }
+        ColliderShape::ConvexDecomposition => {
+            Ok(Collider::convex_decomposition(&vertices, &indices))
+        }
+        ColliderShape::Box => Ok(around_center(Collider::cuboid(
+            half_extents.x,
+            half_extents.y,
+            half_extents.z,
+        ))),
+        ColliderShape::Sphere => Ok(around_center(Collider::ball(half_extents.max_element()))),
+        ColliderShape::Capsule => {
+            let radius = half_extents.x.max(half_extents.z);
+            Ok(around_center(Collider::capsule_y(
+                (half_extents.y - radius).max(0.0),
+                radius,
+            )))
+        }
+    }
 }
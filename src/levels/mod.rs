@@ -0,0 +1,328 @@
+use bevy::ecs::system::SystemState;
+use bevy::{log, prelude::*};
+
+use crate::arena_generator::{
+    generate_arena, spawn_arena, ArenaGeometry, ArenaLayout, ArenaSettings,
+};
+use crate::collectable::Collectable;
+use crate::console::{AddConsoleCommand, ConsoleCommand};
+use crate::enemy::{Enemy, EnemyType};
+use crate::game::GameRng;
+use crate::game_state::GameState;
+use crate::level_loader::{load_level, LevelMetadata, LevelRoot};
+use crate::player::{start_position, PlayerControllerState};
+use crate::wave_manager::{
+    LineOfSight, SpawnRules, SpawnTelegraph, SpawnZone, Wave, WaveDefinition, WaveState,
+    CORAL_MAZE_WAVES, DEFINED_WAVES, TRENCH_WAVES,
+};
+
+mod select;
+
+pub struct LevelsPlugin;
+
+#[derive(Clone)]
+pub enum LevelSource {
+    // a glTF scene, see level_loader for what it can contain
+    Scene(&'static str),
+    Generated(ArenaSettings),
+}
+
+#[derive(Clone)]
+pub struct LevelDefinition {
+    pub name: &'static str,
+    pub source: LevelSource,
+    // the level is cleared after the last one
+    pub waves: Vec<WaveDefinition>,
+    pub spawn_rules: SpawnRules,
+    // empty lets creatures spawn anywhere on the floor, or on the scene's spawn markers
+    pub spawn_zones: Vec<SpawnZone>,
+    pub ambient_light: AmbientLight,
+    pub clear_color: Color,
+}
+
+// every level, in the order they unlock
+#[derive(Resource)]
+pub struct LevelRegistry(Vec<LevelDefinition>);
+
+impl Default for LevelRegistry {
+    fn default() -> Self {
+        Self(vec![
+            LevelDefinition {
+                name: "Tide Pool",
+                source: LevelSource::Scene("map.glb#Scene0"),
+                waves: DEFINED_WAVES.to_vec(),
+                spawn_rules: SpawnRules::default(),
+                spawn_zones: vec![],
+                ambient_light: AmbientLight {
+                    color: Color::WHITE,
+                    brightness: 1.0 / 5.0f32,
+                },
+                clear_color: Color::rgb(0.3, 0.6, 0.9),
+            },
+            LevelDefinition {
+                name: "Coral Maze",
+                source: LevelSource::Generated(ArenaSettings {
+                    obstacle_count: 16,
+                    pit_count: 2,
+                    ..default()
+                }),
+                waves: CORAL_MAZE_WAVES.to_vec(),
+                // nothing should wait behind the coral where nobody can reach it
+                spawn_rules: SpawnRules {
+                    line_of_sight: LineOfSight::Visible,
+                    ..default()
+                },
+                spawn_zones: vec![],
+                ambient_light: AmbientLight {
+                    color: Color::rgb(1.0, 0.85, 0.8),
+                    brightness: 1.0 / 4.0f32,
+                },
+                clear_color: Color::rgb(0.25, 0.5, 0.75),
+            },
+            LevelDefinition {
+                name: "The Trench",
+                source: LevelSource::Generated(ArenaSettings {
+                    obstacle_count: 6,
+                    pit_count: 10,
+                    ..default()
+                }),
+                waves: TRENCH_WAVES.to_vec(),
+                // the creatures climb out of both ends of the trench, the shrimps only the far one
+                spawn_rules: SpawnRules {
+                    min_player_distance: 5.0,
+                    ..default()
+                },
+                spawn_zones: vec![
+                    SpawnZone {
+                        center: Vec3::new(0.0, 0.0, -13.0),
+                        half_extents: Vec2::new(10.0, 2.0),
+                        enemy_type: None,
+                    },
+                    SpawnZone {
+                        center: Vec3::new(0.0, 0.0, 13.0),
+                        half_extents: Vec2::new(10.0, 2.0),
+                        enemy_type: Some(EnemyType::Jellyfish),
+                    },
+                    SpawnZone {
+                        center: Vec3::new(0.0, 0.0, 13.0),
+                        half_extents: Vec2::new(10.0, 2.0),
+                        enemy_type: Some(EnemyType::Urchin),
+                    },
+                ],
+                ambient_light: AmbientLight {
+                    color: Color::rgb(0.6, 0.7, 1.0),
+                    brightness: 1.0 / 10.0f32,
+                },
+                clear_color: Color::rgb(0.05, 0.15, 0.3),
+            },
+        ])
+    }
+}
+
+impl LevelRegistry {
+    pub fn level(&self, index: usize) -> &LevelDefinition {
+        &self.0[index.min(self.0.len() - 1)]
+    }
+
+    pub fn count(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LevelDefinition> {
+        self.0.iter()
+    }
+
+    // by number, starting at 1, or by name without spaces: `2`, `coralmaze`
+    pub fn find(&self, query: &str) -> Option<usize> {
+        if let Ok(number) = query.parse::<usize>() {
+            return number.checked_sub(1).filter(|index| *index < self.count());
+        }
+        self.0
+            .iter()
+            .position(|level| level_argument(level.name) == query.to_lowercase())
+    }
+}
+
+fn level_argument(name: &str) -> String {
+    name.to_lowercase().replace(' ', "")
+}
+
+// the level being played, or the one picked on the level select
+#[derive(Resource, Default)]
+pub struct CurrentLevel(pub usize);
+
+// the one actually in the world, switch_level catches up when they differ
+#[derive(Resource, Default)]
+struct LoadedLevel(Option<usize>);
+
+#[derive(Resource, Default)]
+pub struct LevelProgress {
+    // every level up to this one can be picked
+    pub unlocked: usize,
+    // shown on the level select when we come back to it
+    pub just_cleared: Option<usize>,
+}
+
+// sent by the wave manager once the last wave of the level is beaten
+#[derive(Event)]
+pub struct LevelCleared {
+    pub level: usize,
+}
+
+impl Plugin for LevelsPlugin {
+    fn build(&self, app: &mut App) {
+        let levels = LevelRegistry::default();
+        app.add_console_command(
+            ConsoleCommand::new("level", level_command)
+                .usage("[level]")
+                .help("list the levels, or restart the waves in another one, locked or not")
+                .arguments(levels.iter().map(|level| level_argument(level.name))),
+        );
+
+        app.insert_resource(levels);
+        app.init_resource::<CurrentLevel>();
+        app.init_resource::<LoadedLevel>();
+        app.init_resource::<LevelProgress>();
+        app.add_event::<LevelCleared>();
+        app.add_plugins(select::LevelSelectPlugin);
+
+        app.add_systems(Update, (switch_level, unlock_next_level));
+    }
+}
+
+// unloads whatever was there, scene, colliders, generated arena and the items lying around in it
+// alike, then loads the new one
+#[allow(clippy::too_many_arguments)]
+fn switch_level(
+    mut commands: Commands,
+    current_level: Res<CurrentLevel>,
+    mut loaded_level: ResMut<LoadedLevel>,
+    levels: Res<LevelRegistry>,
+    old_level: Query<Entity, Or<(With<LevelRoot>, With<ArenaGeometry>)>>,
+    old_items: Query<(Entity, &Collectable)>,
+    mut players: Query<(&mut Transform, &PlayerControllerState)>,
+    mut metadata: ResMut<LevelMetadata>,
+    asset_server: Res<AssetServer>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut rng: ResMut<GameRng>,
+    mut ambient_light: ResMut<AmbientLight>,
+    mut clear_color: ResMut<ClearColor>,
+) {
+    if loaded_level.0 == Some(current_level.0) {
+        return;
+    }
+    loaded_level.0 = Some(current_level.0);
+
+    let level = levels.level(current_level.0);
+    log::info!("Loading level: {}", level.name);
+
+    for entity in &old_level {
+        commands.entity(entity).despawn_recursive();
+    }
+    // the held weapons go along with their hermits
+    for (entity, collectable) in &old_items {
+        if collectable.0 {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+    commands.remove_resource::<ArenaLayout>();
+    commands.remove_resource::<ArenaSettings>();
+    *metadata = LevelMetadata::default();
+
+    match &level.source {
+        LevelSource::Scene(asset_path) => {
+            load_level(asset_path, &mut commands, &asset_server);
+            // the markers move them again once the scene is in
+            for (mut transform, controller) in &mut players {
+                transform.translation = start_position(&metadata, controller.player_index);
+            }
+        }
+        LevelSource::Generated(settings) => {
+            let layout = generate_arena(settings, rng.as_mut());
+            spawn_arena(&layout, &mut commands, &mut meshes, &mut materials);
+
+            let mut placed = Vec::new();
+            for (mut transform, _) in &mut players {
+                if let Some(point) = layout.spawn_point(rng.as_mut(), &placed, 2.0) {
+                    transform.translation = point + Vec3::Y;
+                    placed.push(point);
+                }
+            }
+
+            commands.insert_resource(layout);
+            commands.insert_resource(settings.clone());
+        }
+    }
+
+    *ambient_light = level.ambient_light.clone();
+    clear_color.0 = level.clear_color;
+}
+
+fn unlock_next_level(
+    mut level_cleared_reader: EventReader<LevelCleared>,
+    levels: Res<LevelRegistry>,
+    mut progress: ResMut<LevelProgress>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for event in level_cleared_reader.iter() {
+        log::info!("Cleared level: {}", levels.level(event.level).name);
+        progress.unlocked = progress
+            .unlocked
+            .max((event.level + 1).min(levels.count() - 1));
+        progress.just_cleared = Some(event.level);
+        next_state.set(GameState::LevelSelect);
+    }
+}
+
+// puts the waves back at the start of the level, used when a level is picked
+pub fn restart_waves(wave: &mut Wave, next_wave_state: &mut NextState<WaveState>) {
+    wave.count = 0;
+    next_wave_state.set(WaveState::WAVE_END);
+}
+
+fn level_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let levels = world.resource::<LevelRegistry>();
+    let current = world.resource::<CurrentLevel>().0;
+    let unlocked = world.resource::<LevelProgress>().unlocked;
+
+    let Some(query) = args.first() else {
+        return Ok(levels
+            .iter()
+            .enumerate()
+            .map(|(index, level)| {
+                let status = match index {
+                    _ if index == current => " (current)",
+                    _ if index > unlocked => " (locked)",
+                    _ => "",
+                };
+                format!("{} {}{status}", index + 1, level.name)
+            })
+            .collect::<Vec<_>>()
+            .join("\n"));
+    };
+
+    let index = levels
+        .find(query)
+        .ok_or_else(|| format!("no level called `{query}`"))?;
+    let name = levels.level(index).name;
+
+    let mut state: SystemState<(
+        Commands,
+        ResMut<CurrentLevel>,
+        ResMut<Wave>,
+        ResMut<NextState<WaveState>>,
+        Query<Entity, Or<(With<Enemy>, With<SpawnTelegraph>)>>,
+    )> = SystemState::new(world);
+    let (mut commands, mut current_level, mut wave, mut next_wave_state, enemies) =
+        state.get_mut(world);
+
+    for entity in &enemies {
+        commands.entity(entity).despawn_recursive();
+    }
+    current_level.0 = index;
+    restart_waves(&mut wave, &mut next_wave_state);
+
+    state.apply(world);
+    Ok(format!("switched to {name}"))
+}
@@ -0,0 +1,386 @@
+use bevy::prelude::*;
+
+use crate::asset_loader::GameAssets;
+use crate::difficulty::{Difficulty, DifficultyDirector};
+use crate::endless::EndlessRun;
+use crate::game_state::GameState;
+use crate::levels::{restart_waves, CurrentLevel, LevelProgress, LevelRegistry};
+use crate::shop::Merchant;
+use crate::wave_manager::{Wave, WaveState};
+
+pub struct LevelSelectPlugin;
+
+#[derive(Component)]
+struct LevelSelectUi;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum LevelSelectButton {
+    Level(usize),
+    Difficulty,
+    Director,
+    Endless,
+    Shop,
+    Back,
+}
+
+// the level a gamepad or the arrow keys would start
+#[derive(Resource, Default)]
+struct HighlightedLevel(usize);
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HighlightedLevel>();
+        app.add_systems(OnEnter(GameState::LevelSelect), on_enter);
+        app.add_systems(OnExit(GameState::LevelSelect), clean);
+        app.add_systems(
+            Update,
+            (
+                level_select_click_handler,
+                level_select_pad_handler,
+                change_run_options,
+                update_option_labels,
+                update_highlight,
+            )
+                .run_if(in_state(GameState::LevelSelect)),
+        );
+    }
+}
+
+fn on_enter(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    levels: Res<LevelRegistry>,
+    progress: Res<LevelProgress>,
+    current_level: Res<CurrentLevel>,
+    mut highlighted: ResMut<HighlightedLevel>,
+) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+
+    // straight onto the new level after clearing one
+    highlighted.0 = match progress.just_cleared {
+        Some(_) => progress.unlocked,
+        None => current_level.0.min(progress.unlocked),
+    };
+
+    let message = match progress.just_cleared {
+        Some(cleared) if cleared < progress.unlocked => format!(
+            "{} cleared! {} is open",
+            levels.level(cleared).name,
+            levels.level(progress.unlocked).name
+        ),
+        Some(cleared) => format!("{} cleared!", levels.level(cleared).name),
+        None => String::new(),
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(15.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(LevelSelectUi)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Choose a level",
+                text_style(56.0, Color::rgb(0.9, 0.9, 0.9)),
+            ));
+            parent.spawn(TextBundle::from_section(
+                message,
+                text_style(32.0, Color::ORANGE_RED),
+            ));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (index, level) in levels.iter().enumerate() {
+                        let (label, color) = if index > progress.unlocked {
+                            (format!("{}\nlocked", level.name), Color::rgb(0.4, 0.4, 0.4))
+                        } else {
+                            (level.name.to_string(), Color::rgb(0.9, 0.9, 0.9))
+                        };
+                        spawn_button(
+                            parent,
+                            LevelSelectButton::Level(index),
+                            label,
+                            text_style(32.0, color),
+                        );
+                    }
+                });
+
+            // labelled by update_option_labels
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for button in [
+                        LevelSelectButton::Difficulty,
+                        LevelSelectButton::Director,
+                        LevelSelectButton::Endless,
+                        LevelSelectButton::Shop,
+                    ] {
+                        spawn_button(
+                            parent,
+                            button,
+                            String::new(),
+                            text_style(28.0, Color::rgb(0.9, 0.9, 0.9)),
+                        );
+                    }
+                });
+
+            spawn_button(
+                parent,
+                LevelSelectButton::Back,
+                "Back".to_string(),
+                text_style(32.0, Color::rgb(0.9, 0.9, 0.9)),
+            );
+        });
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: LevelSelectButton,
+    label: String,
+    style: TextStyle,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(220.0),
+                height: Val::Px(90.0),
+                border: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(label, style).with_text_alignment(TextAlignment::Center),
+            );
+        });
+}
+
+fn start_level(
+    index: usize,
+    current_level: &mut CurrentLevel,
+    wave: &mut Wave,
+    next_wave_state: &mut NextState<WaveState>,
+    next_state: &mut NextState<GameState>,
+) {
+    current_level.0 = index;
+    restart_waves(wave, next_wave_state);
+    next_state.set(GameState::FightingInArena);
+}
+
+fn level_select_click_handler(
+    interaction_query: Query<(&Interaction, &LevelSelectButton), Changed<Interaction>>,
+    progress: Res<LevelProgress>,
+    game_assets: Res<GameAssets>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut wave: ResMut<Wave>,
+    mut next_wave_state: ResMut<NextState<WaveState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match *button {
+            LevelSelectButton::Level(index) => {
+                if index <= progress.unlocked && game_assets.are_all_assets_loaded() {
+                    start_level(
+                        index,
+                        &mut current_level,
+                        &mut wave,
+                        &mut next_wave_state,
+                        &mut next_state,
+                    );
+                }
+            }
+            LevelSelectButton::Back => next_state.set(GameState::TitleScreen),
+            // change_run_options takes care of those
+            LevelSelectButton::Difficulty
+            | LevelSelectButton::Director
+            | LevelSelectButton::Endless
+            | LevelSelectButton::Shop => {}
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn level_select_pad_handler(
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    progress: Res<LevelProgress>,
+    game_assets: Res<GameAssets>,
+    mut highlighted: ResMut<HighlightedLevel>,
+    mut current_level: ResMut<CurrentLevel>,
+    mut wave: ResMut<Wave>,
+    mut next_wave_state: ResMut<NextState<WaveState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let pressed = |key: KeyCode, button_type: GamepadButtonType| {
+        keys.just_pressed(key)
+            || gamepads.iter().any(|gamepad| {
+                buttons.just_pressed(GamepadButton {
+                    gamepad,
+                    button_type,
+                })
+            })
+    };
+
+    if pressed(KeyCode::Left, GamepadButtonType::DPadLeft) {
+        highlighted.0 = highlighted.0.saturating_sub(1);
+    }
+    if pressed(KeyCode::Right, GamepadButtonType::DPadRight) {
+        highlighted.0 = (highlighted.0 + 1).min(progress.unlocked);
+    }
+
+    if pressed(KeyCode::Return, GamepadButtonType::South)
+        || pressed(KeyCode::Space, GamepadButtonType::Start)
+    {
+        if game_assets.are_all_assets_loaded() {
+            start_level(
+                highlighted.0,
+                &mut current_level,
+                &mut wave,
+                &mut next_wave_state,
+                &mut next_state,
+            );
+        }
+    } else if pressed(KeyCode::Escape, GamepadButtonType::East) {
+        next_state.set(GameState::TitleScreen);
+    }
+}
+
+// Clicking the buttons, or up for the next difficulty, down to toggle the director, E for
+// endless mode and S for the shop between waves.
+#[allow(clippy::too_many_arguments)]
+fn change_run_options(
+    interaction_query: Query<(&Interaction, &LevelSelectButton), Changed<Interaction>>,
+    keys: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    buttons: Res<Input<GamepadButton>>,
+    mut difficulty: ResMut<Difficulty>,
+    mut director: ResMut<DifficultyDirector>,
+    mut endless: ResMut<EndlessRun>,
+    mut merchant: ResMut<Merchant>,
+) {
+    let pressed = |key: KeyCode, button_type: GamepadButtonType| {
+        keys.just_pressed(key)
+            || gamepads.iter().any(|gamepad| {
+                buttons.just_pressed(GamepadButton {
+                    gamepad,
+                    button_type,
+                })
+            })
+    };
+    let clicked = |wanted: LevelSelectButton| {
+        interaction_query
+            .iter()
+            .any(|(interaction, button)| *interaction == Interaction::Pressed && *button == wanted)
+    };
+
+    if clicked(LevelSelectButton::Difficulty) || pressed(KeyCode::Up, GamepadButtonType::DPadUp) {
+        *difficulty = difficulty.next();
+    }
+    if clicked(LevelSelectButton::Director) || pressed(KeyCode::Down, GamepadButtonType::DPadDown) {
+        director.enabled = !director.enabled;
+    }
+    if clicked(LevelSelectButton::Endless) || pressed(KeyCode::E, GamepadButtonType::West) {
+        endless.enabled = !endless.enabled;
+    }
+    if clicked(LevelSelectButton::Shop) || pressed(KeyCode::S, GamepadButtonType::North) {
+        merchant.enabled = !merchant.enabled;
+    }
+}
+
+fn update_option_labels(
+    difficulty: Res<Difficulty>,
+    director: Res<DifficultyDirector>,
+    endless: Res<EndlessRun>,
+    merchant: Res<Merchant>,
+    buttons: Query<(&LevelSelectButton, &Children)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, children) in &buttons {
+        let label = match button {
+            LevelSelectButton::Difficulty => format!("Difficulty\n{:?}", *difficulty),
+            LevelSelectButton::Director => {
+                format!("Director\n{}", if director.enabled { "on" } else { "off" })
+            }
+            LevelSelectButton::Endless => {
+                format!(
+                    "Mode\n{}",
+                    if endless.enabled { "Endless" } else { "Levels" }
+                )
+            }
+            LevelSelectButton::Shop => {
+                format!("Shop\n{}", if merchant.enabled { "on" } else { "off" })
+            }
+            _ => continue,
+        };
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.clone();
+                }
+            }
+        }
+    }
+}
+
+fn update_highlight(
+    highlighted: Res<HighlightedLevel>,
+    mut buttons: Query<(&LevelSelectButton, &Interaction, &mut BackgroundColor)>,
+) {
+    for (button, interaction, mut color) in &mut buttons {
+        let lit = *button == LevelSelectButton::Level(highlighted.0)
+            || *interaction == Interaction::Hovered;
+        color.0 = if lit {
+            Color::rgb(0.2, 0.2, 0.2)
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 1.0)
+        };
+    }
+}
+
+fn clean(
+    mut commands: Commands,
+    query: Query<Entity, With<LevelSelectUi>>,
+    mut progress: ResMut<LevelProgress>,
+) {
+    for ui_element in query.iter() {
+        commands.entity(ui_element).despawn_recursive();
+    }
+    progress.just_cleared = None;
+}
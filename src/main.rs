@@ -3,28 +3,49 @@ use bevy::render::render_resource::{AddressMode, FilterMode, SamplerDescriptor};
 use bevy::render::settings::{WgpuFeatures, WgpuSettings};
 use bevy::render::RenderPlugin;
 
+use console::ConsolePlugin;
+use difficulty::DifficultyPlugin;
+use endless::EndlessPlugin;
 use game_state::GameStatePlugin;
+use hit_feedback::HitFeedbackPlugin;
+use network::NetworkPlugin;
+use particles::ParticlePlugin;
 use post_processing::PostProcessingPlugin;
+use profile::ProfilePlugin;
+use shop::ShopPlugin;
+use status_effect::StatusEffectPlugin;
 use title_screen::TitleScreenPlugin;
 
 use crate::game::GamePlugin;
 use crate::inventory::InventoryPlugin;
 
+mod arena_generator;
 mod asset_loader;
 mod collectable;
 mod config;
+mod console;
 mod debug_camera_controller;
+mod difficulty;
+mod endless;
 mod enemy;
 mod game;
 mod game_camera_controller;
 mod game_state;
+mod hit_feedback;
 mod inventory;
 mod item_mesh_generator;
 mod item_spawner;
 mod level_loader;
+mod levels;
+mod network;
+mod particles;
 mod player;
 mod post_processing;
+mod profile;
 mod projectile;
+mod shop;
+mod status_effect;
+mod storage;
 mod title_screen;
 mod ui;
 pub mod wave_manager;
@@ -67,11 +88,20 @@ fn main() {
         );
     }
 
+    app.add_plugins(ConsolePlugin);
     app.add_plugins(TitleScreenPlugin);
     app.add_plugins(PostProcessingPlugin);
     app.add_plugins(GamePlugin);
     app.add_plugins(InventoryPlugin);
+    app.add_plugins(StatusEffectPlugin);
+    app.add_plugins(DifficultyPlugin);
+    app.add_plugins(EndlessPlugin);
+    app.add_plugins(ProfilePlugin);
+    app.add_plugins(ShopPlugin);
+    app.add_plugins(HitFeedbackPlugin);
+    app.add_plugins(ParticlePlugin);
     app.add_plugins(GameStatePlugin);
+    app.add_plugins(NetworkPlugin);
     app.add_systems(Update, bevy::window::close_on_esc);
 
     app.run();
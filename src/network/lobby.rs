@@ -0,0 +1,217 @@
+use bevy::prelude::*;
+
+use crate::asset_loader::GameAssets;
+use crate::game_state::GameState;
+use crate::network::{NetworkRole, NetworkStatus, DEFAULT_PORT};
+use crate::player::LocalPlayerCount;
+
+pub struct LobbyPlugin;
+
+#[derive(Component)]
+struct LobbyUi;
+
+#[derive(Component, Clone, Copy, PartialEq)]
+enum LobbyButton {
+    Host,
+    Join,
+    Start,
+    Back,
+}
+
+#[derive(Component)]
+struct AddressText;
+
+#[derive(Component)]
+struct StatusText;
+
+// what the player typed in the address field
+#[derive(Resource)]
+struct JoinAddress(String);
+
+impl Plugin for LobbyPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(JoinAddress(format!("127.0.0.1:{DEFAULT_PORT}")));
+        app.add_systems(OnEnter(GameState::Lobby), on_enter);
+        app.add_systems(OnExit(GameState::Lobby), clean);
+        app.add_systems(
+            Update,
+            (type_address, lobby_click_handler, update_lobby_text)
+                .run_if(in_state(GameState::Lobby)),
+        );
+    }
+}
+
+fn on_enter(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32| TextStyle {
+        font: font.clone(),
+        font_size,
+        color: Color::rgb(0.9, 0.9, 0.9),
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(15.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.6).into(),
+            ..default()
+        })
+        .insert(LobbyUi)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Online co-op", text_style(56.0)));
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, LobbyButton::Host, "Host", text_style(32.0));
+                    spawn_button(parent, LobbyButton::Join, "Join", text_style(32.0));
+                });
+
+            parent
+                .spawn(TextBundle::from_section("", text_style(32.0)))
+                .insert(AddressText);
+
+            parent
+                .spawn(
+                    TextBundle::from_section("", text_style(24.0))
+                        .with_text_alignment(TextAlignment::Center),
+                )
+                .insert(StatusText);
+
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(20.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    spawn_button(parent, LobbyButton::Start, "Start", text_style(32.0));
+                    spawn_button(parent, LobbyButton::Back, "Back", text_style(32.0));
+                });
+        });
+}
+
+fn spawn_button(parent: &mut ChildBuilder, button: LobbyButton, label: &str, style: TextStyle) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(200.0),
+                height: Val::Px(65.0),
+                border: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(label, style));
+        });
+}
+
+fn type_address(
+    mut characters: EventReader<ReceivedCharacter>,
+    keys: Res<Input<KeyCode>>,
+    mut address: ResMut<JoinAddress>,
+) {
+    for event in characters.iter() {
+        if event.char.is_ascii_alphanumeric() || matches!(event.char, '.' | ':' | '-') {
+            address.0.push(event.char);
+        }
+    }
+    if keys.just_pressed(KeyCode::Back) {
+        address.0.pop();
+    }
+}
+
+fn lobby_click_handler(
+    interaction_query: Query<(&Interaction, &LobbyButton), Changed<Interaction>>,
+    mut role: ResMut<NetworkRole>,
+    mut status: ResMut<NetworkStatus>,
+    mut player_count: ResMut<LocalPlayerCount>,
+    address: Res<JoinAddress>,
+    game_assets: Res<GameAssets>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        match button {
+            LobbyButton::Host => {
+                role.shutdown();
+                match NetworkRole::host(DEFAULT_PORT) {
+                    Ok(new_role) => *role = new_role,
+                    Err(err) => status.0 = format!("Could not host: {err}"),
+                }
+            }
+            LobbyButton::Join => {
+                role.shutdown();
+                match NetworkRole::join(&address.0) {
+                    Ok(new_role) => {
+                        // the host decides who is who, we only bring our own hermit
+                        player_count.0 = 1;
+                        *role = new_role;
+                    }
+                    Err(err) => status.0 = format!("Could not join {}: {err}", address.0),
+                }
+            }
+            LobbyButton::Start => {
+                // clients wait for the host's first snapshot to get in
+                if matches!(*role, NetworkRole::Host(_)) && game_assets.are_all_assets_loaded() {
+                    next_state.set(GameState::FightingInArena);
+                }
+            }
+            LobbyButton::Back => {
+                role.shutdown();
+                status.0.clear();
+                next_state.set(GameState::TitleScreen);
+            }
+        }
+    }
+}
+
+fn update_lobby_text(
+    address: Res<JoinAddress>,
+    status: Res<NetworkStatus>,
+    role: Res<NetworkRole>,
+    mut address_text: Query<&mut Text, (With<AddressText>, Without<StatusText>)>,
+    mut status_text: Query<&mut Text, (With<StatusText>, Without<AddressText>)>,
+) {
+    for mut text in &mut address_text {
+        text.sections[0].value = format!("Join address: {}", address.0);
+    }
+
+    for mut text in &mut status_text {
+        text.sections[0].value = match (&*role, status.0.is_empty()) {
+            (NetworkRole::Offline, true) => {
+                "Host a game, or type the host's address and join".to_string()
+            }
+            _ => status.0.clone(),
+        };
+    }
+}
+
+fn clean(mut commands: Commands, query: Query<Entity, With<LobbyUi>>) {
+    for ui_element in query.iter() {
+        commands.entity(ui_element).despawn_recursive();
+    }
+}
@@ -0,0 +1,1156 @@
+use std::collections::VecDeque;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+use bevy::utils::HashMap;
+use bevy::{log, prelude::*};
+
+use crate::asset_loader::GameAssets;
+use crate::collectable::{Collectable, ItemCollectEvent};
+use crate::enemy::{Enemy, EnemyType};
+use crate::game_state::GameState;
+use crate::inventory::{Inventory, InventoryItem, ItemTypeId};
+use crate::item_spawner::catalog_item;
+use crate::level_loader::LevelMetadata;
+use crate::levels::CurrentLevel;
+use crate::network::lobby::LobbyPlugin;
+use crate::network::protocol::{
+    EnemySnapshot, InputFrame, Message, PackedItem, PlayerSnapshot, Snapshot, SnapshotParts,
+    MAX_DATAGRAM_SIZE,
+};
+use crate::player::combat::PlayerCombatState;
+use crate::player::consumables::{ConsumableEffect, ConsumablePressed, ConsumablesUsed};
+use crate::player::{
+    spawn_player, start_position, LocalPlayerCount, PlayerControllerState, PlayerState,
+};
+use crate::projectile::Projectile;
+use crate::shop::ItemSold;
+use crate::wave_manager::Wave;
+use crate::world_item::{refresh_held_weapon, WeaponHolder};
+
+mod lobby;
+mod protocol;
+
+pub const DEFAULT_PORT: u16 = 7777;
+pub const MAX_NETWORK_PLAYERS: usize = 4;
+
+const SNAPSHOT_RATE: f32 = 1.0 / 20.0;
+const CONNECTION_TIMEOUT: f32 = 5.0;
+const HELLO_RETRY: f32 = 0.5;
+// the inventory sync goes out again this often, in case the last one got lost
+const INVENTORY_SYNC_RATE: f32 = 1.0;
+// how far the host may disagree with our prediction before we snap to it
+const RECONCILIATION_TOLERANCE: f32 = 0.3;
+
+pub struct NetworkPlugin;
+
+// The host runs the whole simulation, exactly like single player, and streams snapshots to the
+// clients. Clients only simulate their own hermit (so it reacts instantly) and mirror the rest.
+#[derive(Resource, Default)]
+pub enum NetworkRole {
+    #[default]
+    Offline,
+    Host(HostSession),
+    Client(ClientSession),
+}
+
+pub struct HostSession {
+    socket: UdpSocket,
+    clients: Vec<RemoteClient>,
+    tick: u32,
+    snapshot_timer: Timer,
+}
+
+struct RemoteClient {
+    address: SocketAddr,
+    player_index: usize,
+    hermit: Entity,
+    last_input_sequence: u32,
+    // the counts from their last input, a higher one is a consumable used since
+    consumables_used: [u8; 4],
+    // sold at the shop and resent until an inventory sync comes back without them
+    sold: Vec<PackedItem>,
+    last_heard: f32,
+}
+
+pub struct ClientSession {
+    socket: UdpSocket,
+    server: SocketAddr,
+    player_index: Option<usize>,
+    input_sequence: u32,
+    last_snapshot_tick: u32,
+    snapshot_parts: SnapshotParts,
+    // the level the host is playing, followed by follow_host_level
+    host_level: usize,
+    predictions: VecDeque<(u32, Vec3)>,
+    pending_grants: Vec<ItemTypeId>,
+    pending_sales: Vec<PackedItem>,
+    last_heard: f32,
+    hello_timer: Timer,
+    inventory_sync_timer: Timer,
+}
+
+// host side: a hermit driven by a remote client's inputs
+#[derive(Component)]
+pub struct RemotePlayer;
+
+// host side: ids shared with the clients for everything that gets mirrored
+#[derive(Component)]
+pub struct NetId(pub u32);
+
+// client side: an entity that only mirrors what the host simulates
+#[derive(Component)]
+pub struct NetworkProxy;
+
+#[derive(Resource, Default)]
+struct NetIdAllocator(u32);
+
+#[derive(Resource, Default)]
+struct Proxies {
+    players: HashMap<usize, Entity>,
+    enemies: HashMap<u32, Entity>,
+    projectiles: HashMap<u32, Entity>,
+    items: HashMap<u32, Entity>,
+}
+
+#[derive(Resource)]
+struct ProxyBubble(Handle<Mesh>, Handle<StandardMaterial>);
+
+// what the lobby shows under the buttons
+#[derive(Resource, Default)]
+pub struct NetworkStatus(pub String);
+
+impl NetworkRole {
+    pub fn host(port: u16) -> std::io::Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(NetworkRole::Host(HostSession {
+            socket,
+            clients: Vec::new(),
+            tick: 0,
+            snapshot_timer: Timer::from_seconds(SNAPSHOT_RATE, TimerMode::Repeating),
+        }))
+    }
+
+    pub fn join(address: &str) -> std::io::Result<Self> {
+        let server = address.to_socket_addrs()?.next().ok_or_else(|| {
+            std::io::Error::new(std::io::ErrorKind::NotFound, address.to_string())
+        })?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_nonblocking(true)?;
+
+        Ok(NetworkRole::Client(ClientSession {
+            socket,
+            server,
+            player_index: None,
+            input_sequence: 0,
+            last_snapshot_tick: 0,
+            snapshot_parts: SnapshotParts::default(),
+            host_level: 0,
+            predictions: VecDeque::new(),
+            pending_grants: Vec::new(),
+            pending_sales: Vec::new(),
+            last_heard: 0.0,
+            hello_timer: Timer::from_seconds(HELLO_RETRY, TimerMode::Repeating),
+            inventory_sync_timer: Timer::from_seconds(INVENTORY_SYNC_RATE, TimerMode::Repeating),
+        }))
+    }
+
+    pub fn is_remote_player(&self, player_index: usize) -> bool {
+        match self {
+            NetworkRole::Host(host) => host
+                .clients
+                .iter()
+                .any(|client| client.player_index == player_index),
+            _ => false,
+        }
+    }
+
+    pub fn connected_clients(&self) -> usize {
+        match self {
+            NetworkRole::Host(host) => host.clients.len(),
+            _ => 0,
+        }
+    }
+
+    // tells the other side we're leaving, then goes back to local play
+    pub fn shutdown(&mut self) {
+        match self {
+            NetworkRole::Host(host) => {
+                for client in &host.clients {
+                    send(&host.socket, client.address, &Message::Disconnect);
+                }
+            }
+            NetworkRole::Client(client) => {
+                send(&client.socket, client.server, &Message::Disconnect);
+            }
+            NetworkRole::Offline => {}
+        }
+        *self = NetworkRole::Offline;
+    }
+}
+
+// only the host (or a local game) spawns enemies, resolves hits and hands out items
+pub fn is_simulation_authority(role: Res<NetworkRole>) -> bool {
+    !matches!(*role, NetworkRole::Client(_))
+}
+
+pub fn is_host(role: Res<NetworkRole>) -> bool {
+    matches!(*role, NetworkRole::Host(_))
+}
+
+pub fn is_client(role: Res<NetworkRole>) -> bool {
+    matches!(*role, NetworkRole::Client(_))
+}
+
+impl Plugin for NetworkPlugin {
+    fn build(&self, app: &mut App) {
+        let mut status = NetworkStatus::default();
+        let role = match role_from_args(std::env::args().skip(1).collect()) {
+            Some(Ok(role)) => role,
+            Some(Err(err)) => {
+                status.0 = format!("Could not open connection: {err}");
+                NetworkRole::Offline
+            }
+            None => NetworkRole::Offline,
+        };
+        let open_lobby = !matches!(role, NetworkRole::Offline) || !status.0.is_empty();
+
+        app.insert_resource(role)
+            .insert_resource(status)
+            .insert_resource(NetIdAllocator::default())
+            .insert_resource(Proxies::default());
+
+        if open_lobby {
+            app.add_systems(Startup, open_lobby_from_args);
+        }
+
+        app.add_systems(Startup, setup);
+        app.add_systems(
+            Update,
+            (
+                host_receive,
+                assign_net_ids,
+                forward_remote_pickups,
+                forward_remote_sales,
+                host_send_snapshots,
+            )
+                .chain()
+                .run_if(is_host),
+        );
+        app.add_systems(
+            Update,
+            (
+                client_handshake,
+                client_receive,
+                apply_remote_sales,
+                follow_host_level,
+                client_send_input,
+                client_resync_inventory.run_if(in_state(GameState::FightingInArena)),
+                despawn_local_drops,
+            )
+                .chain()
+                .run_if(is_client),
+        );
+        app.add_systems(
+            OnEnter(GameState::FightingInArena),
+            client_sync_inventory.run_if(is_client),
+        );
+        app.add_plugins(LobbyPlugin);
+    }
+}
+
+// `--host [port]` or `--join <address>`, handy to test two processes over loopback
+fn role_from_args(args: Vec<String>) -> Option<std::io::Result<NetworkRole>> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--host" => {
+                let port = args
+                    .next()
+                    .and_then(|port| port.parse().ok())
+                    .unwrap_or(DEFAULT_PORT);
+                return Some(NetworkRole::host(port));
+            }
+            "--join" => {
+                let address = args
+                    .next()
+                    .unwrap_or_else(|| format!("127.0.0.1:{DEFAULT_PORT}"));
+                return Some(NetworkRole::join(&address));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn open_lobby_from_args(mut next_state: ResMut<NextState<GameState>>) {
+    next_state.set(GameState::Lobby);
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let bubble: Mesh = Mesh::try_from(shape::Icosphere {
+        radius: 0.2,
+        subdivisions: 3,
+    })
+    .unwrap();
+    commands.insert_resource(ProxyBubble(
+        meshes.add(bubble),
+        materials.add(Color::rgb(0.05, 0.4, 0.9).into()),
+    ));
+}
+
+fn send(socket: &UdpSocket, address: SocketAddr, message: &Message) {
+    if let Err(err) = socket.send_to(&message.encode(), address) {
+        log::warn!("Failed to send to {address}: {err}");
+    }
+}
+
+fn receive_all(socket: &UdpSocket) -> Vec<(SocketAddr, Message)> {
+    let mut messages = Vec::new();
+    let mut buffer = [0u8; MAX_DATAGRAM_SIZE];
+
+    loop {
+        match socket.recv_from(&mut buffer) {
+            Ok((size, address)) => match Message::decode(&buffer[..size]) {
+                Ok(message) => messages.push((address, message)),
+                Err(err) => log::warn!("Dropping datagram from {address}: {err:?}"),
+            },
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => break,
+            Err(err) => {
+                log::warn!("Socket error: {err}");
+                break;
+            }
+        }
+    }
+
+    messages
+}
+
+#[allow(clippy::too_many_arguments)]
+fn host_receive(
+    mut commands: Commands,
+    mut role: ResMut<NetworkRole>,
+    mut status: ResMut<NetworkStatus>,
+    asset_server: Res<AssetServer>,
+    local_players: Res<LocalPlayerCount>,
+    mut remote_players: Query<
+        (&Transform, &mut PlayerControllerState, &mut WeaponHolder),
+        With<RemotePlayer>,
+    >,
+    mut inventory: ResMut<Inventory>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut pressed_writer: EventWriter<ConsumablePressed>,
+    level: Res<LevelMetadata>,
+    time: Res<Time>,
+) {
+    let NetworkRole::Host(host) = role.as_mut() else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+
+    for (address, message) in receive_all(&host.socket) {
+        let known_client = host.clients.iter().position(|c| c.address == address);
+        if let Some(index) = known_client {
+            host.clients[index].last_heard = now;
+        }
+
+        match (message, known_client) {
+            (Message::Hello, Some(index)) => {
+                // our welcome got lost, say it again
+                let player_index = host.clients[index].player_index as u8;
+                send(&host.socket, address, &Message::Welcome { player_index });
+            }
+            (Message::Hello, None) => {
+                let player_index = (local_players.0..MAX_NETWORK_PLAYERS)
+                    .find(|i| !host.clients.iter().any(|c| c.player_index == *i));
+                let Some(player_index) = player_index else {
+                    log::warn!("Refusing {address}, the arena is full");
+                    continue;
+                };
+
+                let position = start_position(&level, player_index);
+                let hermit = spawn_player(&mut commands, &asset_server, player_index, position);
+                commands.entity(hermit).insert(RemotePlayer);
+                host.clients.push(RemoteClient {
+                    address,
+                    player_index,
+                    hermit,
+                    last_input_sequence: 0,
+                    consumables_used: [0; 4],
+                    sold: Vec::new(),
+                    last_heard: now,
+                });
+
+                log::info!("{address} joined as player {}", player_index + 1);
+                send(
+                    &host.socket,
+                    address,
+                    &Message::Welcome {
+                        player_index: player_index as u8,
+                    },
+                );
+            }
+            (Message::Input(input), Some(index)) => {
+                let client = &mut host.clients[index];
+                if input.sequence <= client.last_input_sequence {
+                    continue;
+                }
+                // counts from an earlier game only tell where this client's start
+                let first_input = client.last_input_sequence == 0;
+                client.last_input_sequence = input.sequence;
+
+                if !first_input {
+                    for (slot, effect) in ConsumableEffect::ALL.into_iter().enumerate() {
+                        let used = input.consumables_used[slot];
+                        for _ in 0..used.wrapping_sub(client.consumables_used[slot]) {
+                            pressed_writer.send(ConsumablePressed {
+                                player_index: client.player_index,
+                                effect,
+                            });
+                        }
+                    }
+                }
+                client.consumables_used = input.consumables_used;
+
+                let Ok((transform, mut controller, mut weapon_holder)) =
+                    remote_players.get_mut(client.hermit)
+                else {
+                    continue;
+                };
+                controller.movement = input.movement;
+                controller.aim = input.aim;
+                controller.is_shoot_pressed = input.shoot;
+
+                // a weapon we sold stays sold while the client catches up
+                let weapon = input.weapon.filter(|weapon| !client.sold.contains(weapon));
+                let held = weapon_holder
+                    .current_weapon
+                    .as_ref()
+                    .map(|(_, item)| PackedItem::of(item));
+                if held != weapon {
+                    if let Some((entity, _)) = weapon_holder.current_weapon.take() {
+                        commands.entity(entity).despawn();
+                    }
+                    if let Some(weapon) = &weapon {
+                        let item = weapon.rebuild();
+                        let entity = item.create_world_entity(
+                            transform.translation,
+                            true,
+                            false,
+                            &mut commands,
+                            &mut meshes,
+                            &mut materials,
+                        );
+                        weapon_holder.current_weapon = Some((entity, item));
+                    }
+                }
+            }
+            (Message::InventorySync(items), Some(index)) => {
+                // a sale missing from the shell made it over, the rest are still on their way
+                let client = &mut host.clients[index];
+                client.sold.retain(|sold| items.contains(sold));
+                *inventory.items_of_mut(client.player_index) = items
+                    .iter()
+                    .filter(|item| !client.sold.contains(item))
+                    .map(PackedItem::rebuild)
+                    .collect();
+            }
+            (Message::Disconnect, Some(index)) => {
+                let client = host.clients.remove(index);
+                log::info!("Player {} left", client.player_index + 1);
+                commands.entity(client.hermit).despawn_recursive();
+            }
+            (message, None) => {
+                log::warn!("Ignoring {message:?} from unknown peer {address}");
+            }
+            _ => {}
+        }
+    }
+
+    let mut timed_out = Vec::new();
+    host.clients.retain(|client| {
+        let alive = now - client.last_heard < CONNECTION_TIMEOUT;
+        if !alive {
+            timed_out.push(client.hermit);
+        }
+        alive
+    });
+    for hermit in timed_out {
+        log::info!("A remote player timed out");
+        commands.entity(hermit).despawn_recursive();
+    }
+
+    status.0 = format!(
+        "Hosting on port {} - {} player(s) connected",
+        host.socket.local_addr().map(|a| a.port()).unwrap_or(0),
+        host.clients.len()
+    );
+}
+
+fn assign_net_ids(
+    mut commands: Commands,
+    mut allocator: ResMut<NetIdAllocator>,
+    query: Query<
+        Entity,
+        (
+            Without<NetId>,
+            Or<(With<Enemy>, With<Projectile>, With<Collectable>)>,
+        ),
+    >,
+) {
+    for entity in &query {
+        allocator.0 += 1;
+        commands.entity(entity).insert(NetId(allocator.0));
+    }
+}
+
+// items picked up by a remote hermit get packed on the client's screen, not ours
+fn forward_remote_pickups(
+    role: Res<NetworkRole>,
+    mut item_collect_event_reader: EventReader<ItemCollectEvent>,
+) {
+    let NetworkRole::Host(host) = role.as_ref() else {
+        return;
+    };
+
+    for event in &mut item_collect_event_reader {
+        if let Some(client) = host.clients.iter().find(|c| c.player_index == event.1) {
+            send(
+                &host.socket,
+                client.address,
+                &Message::ItemGranted(event.0.item_type_id),
+            );
+        }
+    }
+}
+
+// items sold out of a remote hermit's shell come off the client's copy too
+fn forward_remote_sales(
+    mut role: ResMut<NetworkRole>,
+    mut item_sold_reader: EventReader<ItemSold>,
+) {
+    let NetworkRole::Host(host) = role.as_mut() else {
+        return;
+    };
+
+    for event in &mut item_sold_reader {
+        if let Some(client) = host
+            .clients
+            .iter_mut()
+            .find(|c| c.player_index == event.player_index)
+        {
+            client.sold.push(PackedItem::of(&event.item));
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn host_send_snapshots(
+    mut role: ResMut<NetworkRole>,
+    time: Res<Time>,
+    game_state: Res<State<GameState>>,
+    player_state: Res<State<PlayerState>>,
+    wave: Res<Wave>,
+    current_level: Res<CurrentLevel>,
+    players: Query<(
+        &Transform,
+        &PlayerControllerState,
+        &PlayerCombatState,
+        &WeaponHolder,
+    )>,
+    enemies: Query<(&NetId, &Transform, &Enemy)>,
+    projectiles: Query<(&NetId, &Transform), With<Projectile>>,
+    items: Query<(&NetId, &Transform, &Collectable, &InventoryItem)>,
+) {
+    let NetworkRole::Host(host) = role.as_mut() else {
+        return;
+    };
+
+    if !host.snapshot_timer.tick(time.delta()).just_finished() || host.clients.is_empty() {
+        return;
+    }
+    host.tick += 1;
+
+    let mut snapshot = Snapshot {
+        tick: host.tick,
+        run_active: !matches!(
+            game_state.get(),
+            GameState::TitleScreen
+                | GameState::Lobby
+                | GameState::LevelSelect
+                | GameState::ItemEditor
+        ),
+        all_down: *player_state.get() == PlayerState::Dying,
+        level: current_level.0 as u8,
+        wave: wave.count,
+        last_input_sequence: 0,
+        part: 0,
+        parts: 1,
+        players: players
+            .iter()
+            .map(
+                |(transform, controller, combat_state, weapon_holder)| PlayerSnapshot {
+                    player_index: controller.player_index as u8,
+                    position: transform.translation,
+                    yaw: transform.rotation.to_euler(EulerRot::YXZ).0,
+                    hp: combat_state.current_hp,
+                    max_hp: combat_state.max_hp,
+                    weapon: weapon_holder
+                        .current_weapon
+                        .as_ref()
+                        .map(|(_, item)| item.item_type_id),
+                },
+            )
+            .collect(),
+        enemies: enemies
+            .iter()
+            .map(|(net_id, transform, enemy)| EnemySnapshot {
+                net_id: net_id.0,
+                enemy_type: enemy.enemy_type,
+                position: transform.translation,
+            })
+            .collect(),
+        projectiles: projectiles
+            .iter()
+            .map(|(net_id, transform)| (net_id.0, transform.translation))
+            .collect(),
+        items: items
+            .iter()
+            .filter(|(_, _, collectable, _)| collectable.0)
+            .map(|(net_id, transform, _, item)| {
+                (net_id.0, item.item_type_id, transform.translation)
+            })
+            .collect(),
+    };
+
+    for client in &host.clients {
+        snapshot.last_input_sequence = client.last_input_sequence;
+        for part in snapshot.clone().split() {
+            send(&host.socket, client.address, &Message::Snapshot(part));
+        }
+        for sold in &client.sold {
+            send(
+                &host.socket,
+                client.address,
+                &Message::ItemSold(sold.clone()),
+            );
+        }
+    }
+}
+
+fn client_handshake(
+    mut role: ResMut<NetworkRole>,
+    mut status: ResMut<NetworkStatus>,
+    time: Res<Time>,
+) {
+    let NetworkRole::Client(client) = role.as_mut() else {
+        return;
+    };
+
+    if client.player_index.is_some() {
+        return;
+    }
+
+    if client.hello_timer.tick(time.delta()).just_finished() {
+        send(&client.socket, client.server, &Message::Hello);
+        status.0 = format!("Joining {}...", client.server);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn client_receive(
+    mut commands: Commands,
+    mut role: ResMut<NetworkRole>,
+    mut status: ResMut<NetworkStatus>,
+    mut proxies: ResMut<Proxies>,
+    proxy_bubble: Res<ProxyBubble>,
+    game_assets: Res<GameAssets>,
+    asset_server: Res<AssetServer>,
+    game_state: Res<State<GameState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut next_player_state: ResMut<NextState<PlayerState>>,
+    mut local_player: Query<
+        (
+            &mut Transform,
+            &mut PlayerCombatState,
+            &PlayerControllerState,
+        ),
+        Without<NetworkProxy>,
+    >,
+    mut proxy_transforms: Query<&mut Transform, With<NetworkProxy>>,
+    mut inventory: ResMut<Inventory>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    time: Res<Time>,
+) {
+    let NetworkRole::Client(client) = role.as_mut() else {
+        return;
+    };
+    let now = time.elapsed_seconds();
+    if client.last_heard == 0.0 {
+        client.last_heard = now;
+    }
+
+    for (address, message) in receive_all(&client.socket) {
+        if address != client.server {
+            continue;
+        }
+        if client.last_heard != f32::MIN {
+            client.last_heard = now;
+        }
+
+        match message {
+            Message::Welcome { player_index } => {
+                client.player_index = Some(player_index as usize);
+                status.0 = format!(
+                    "Connected to {} as player {}, waiting for the host",
+                    client.server,
+                    player_index + 1
+                );
+            }
+            Message::ItemGranted(item_type_id) => {
+                client.pending_grants.push(item_type_id);
+            }
+            Message::ItemSold(item) => {
+                if !client.pending_sales.contains(&item) {
+                    client.pending_sales.push(item);
+                }
+            }
+            Message::Disconnect => {
+                client.last_heard = f32::MIN;
+            }
+            Message::Snapshot(snapshot) => {
+                if snapshot.tick <= client.last_snapshot_tick {
+                    continue;
+                }
+                let Some(snapshot) = client.snapshot_parts.add(snapshot) else {
+                    continue;
+                };
+                client.last_snapshot_tick = snapshot.tick;
+                client.host_level = snapshot.level as usize;
+
+                if snapshot.run_active
+                    && matches!(
+                        game_state.get(),
+                        GameState::TitleScreen
+                            | GameState::Lobby
+                            | GameState::LevelSelect
+                            | GameState::ItemEditor
+                    )
+                {
+                    next_game_state.set(GameState::FightingInArena);
+                }
+                if snapshot.all_down {
+                    next_player_state.set(PlayerState::Dying);
+                }
+
+                apply_snapshot(
+                    &snapshot,
+                    client,
+                    &mut commands,
+                    &mut proxies,
+                    &proxy_bubble,
+                    &game_assets,
+                    &asset_server,
+                    &mut local_player,
+                    &mut proxy_transforms,
+                    &mut meshes,
+                    &mut materials,
+                );
+            }
+            _ => {}
+        }
+    }
+
+    if *game_state.get() == GameState::FightingInArena && !client.pending_grants.is_empty() {
+        for item_type_id in client.pending_grants.drain(..) {
+            inventory.content.push(catalog_item(item_type_id));
+        }
+        next_game_state.set(GameState::ManagingInventory);
+    }
+
+    if now - client.last_heard > CONNECTION_TIMEOUT {
+        status.0 = match (client.player_index, client.last_heard == f32::MIN) {
+            (None, _) => format!("Nobody answered at {}", client.server),
+            (Some(_), true) => "The host closed the game".to_string(),
+            (Some(_), false) => "Lost connection to the host".to_string(),
+        };
+        log::info!("{}", status.0);
+
+        let Proxies {
+            players,
+            enemies,
+            projectiles,
+            items,
+        } = proxies.as_mut();
+        for (_, entity) in players
+            .drain()
+            .chain(enemies.drain())
+            .chain(projectiles.drain())
+            .chain(items.drain())
+        {
+            commands.entity(entity).despawn_recursive();
+        }
+
+        *role = NetworkRole::Offline;
+        if *game_state.get() != GameState::Lobby {
+            next_game_state.set(GameState::TitleScreen);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_snapshot(
+    snapshot: &Snapshot,
+    client: &mut ClientSession,
+    commands: &mut Commands,
+    proxies: &mut Proxies,
+    proxy_bubble: &ProxyBubble,
+    game_assets: &GameAssets,
+    asset_server: &AssetServer,
+    local_player: &mut Query<
+        (
+            &mut Transform,
+            &mut PlayerCombatState,
+            &PlayerControllerState,
+        ),
+        Without<NetworkProxy>,
+    >,
+    proxy_transforms: &mut Query<&mut Transform, With<NetworkProxy>>,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+) {
+    let mut seen_players = Vec::new();
+    for player in &snapshot.players {
+        let player_index = player.player_index as usize;
+
+        if Some(player_index) == client.player_index {
+            let Some((mut transform, mut combat_state, _)) = local_player
+                .iter_mut()
+                .find(|(_, _, controller)| controller.player_index == 0)
+            else {
+                continue;
+            };
+            combat_state.current_hp = player.hp;
+            combat_state.max_hp = player.max_hp;
+
+            // compare where the host put us with where we thought we were at that input,
+            // and shift by the difference if it drifted too far
+            while client
+                .predictions
+                .front()
+                .is_some_and(|(sequence, _)| *sequence < snapshot.last_input_sequence)
+            {
+                client.predictions.pop_front();
+            }
+            if let Some((_, predicted)) = client.predictions.pop_front() {
+                let error = player.position - predicted;
+                if error.length() > RECONCILIATION_TOLERANCE {
+                    transform.translation += error;
+                    for (_, prediction) in client.predictions.iter_mut() {
+                        *prediction += error;
+                    }
+                }
+            }
+            continue;
+        }
+
+        seen_players.push(player_index);
+        let rotation = Quat::from_rotation_y(player.yaw);
+        match proxies.players.get(&player_index) {
+            Some(entity) => {
+                if let Ok(mut transform) = proxy_transforms.get_mut(*entity) {
+                    transform.translation = player.position;
+                    transform.rotation = rotation;
+                }
+            }
+            None => {
+                let entity = commands
+                    .spawn(SceneBundle {
+                        scene: asset_server.load("hermit.glb#Scene0"),
+                        transform: Transform::from_translation(player.position)
+                            .with_rotation(rotation),
+                        ..default()
+                    })
+                    .insert(NetworkProxy)
+                    .id();
+                proxies.players.insert(player_index, entity);
+            }
+        }
+    }
+    proxies.players.retain(|player_index, entity| {
+        let keep = seen_players.contains(player_index);
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+
+    sync_proxies(
+        &mut proxies.enemies,
+        snapshot
+            .enemies
+            .iter()
+            .map(|enemy| (enemy.net_id, enemy.position)),
+        commands,
+        proxy_transforms,
+        |commands, net_id, position| {
+            let enemy_type = snapshot
+                .enemies
+                .iter()
+                .find(|enemy| enemy.net_id == net_id)
+                .map(|enemy| enemy.enemy_type)
+                .unwrap_or(EnemyType::Jellyfish);
+            let model = match enemy_type {
+                EnemyType::Jellyfish => game_assets.jelly(),
+                EnemyType::Urchin => game_assets.urchin(),
+                EnemyType::Shrimp => game_assets.shrimp(),
+            };
+            commands
+                .spawn(PbrBundle {
+                    mesh: model.mesh_handle,
+                    material: model.material_handle,
+                    transform: Transform::from_translation(position),
+                    ..default()
+                })
+                .insert(NetworkProxy)
+                .id()
+        },
+    );
+
+    sync_proxies(
+        &mut proxies.projectiles,
+        snapshot.projectiles.iter().copied(),
+        commands,
+        proxy_transforms,
+        |commands, _, position| {
+            commands
+                .spawn(PbrBundle {
+                    mesh: proxy_bubble.0.clone(),
+                    material: proxy_bubble.1.clone(),
+                    transform: Transform::from_translation(position),
+                    ..default()
+                })
+                .insert(NetworkProxy)
+                .id()
+        },
+    );
+
+    sync_proxies(
+        &mut proxies.items,
+        snapshot
+            .items
+            .iter()
+            .map(|(net_id, _, position)| (*net_id, *position)),
+        commands,
+        proxy_transforms,
+        |commands, net_id, position| {
+            let item_type_id = snapshot
+                .items
+                .iter()
+                .find(|(id, _, _)| *id == net_id)
+                .map(|(_, item_type_id, _)| *item_type_id)
+                .unwrap();
+            let entity = catalog_item(item_type_id)
+                .create_world_entity(position, false, false, commands, meshes, materials);
+            commands.entity(entity).insert(NetworkProxy);
+            entity
+        },
+    );
+}
+
+// moves mirrored entities, spawns the new ones and drops the ones the host no longer has
+fn sync_proxies(
+    known: &mut HashMap<u32, Entity>,
+    current: impl Iterator<Item = (u32, Vec3)>,
+    commands: &mut Commands,
+    transforms: &mut Query<&mut Transform, With<NetworkProxy>>,
+    mut spawn: impl FnMut(&mut Commands, u32, Vec3) -> Entity,
+) {
+    let mut seen = Vec::new();
+    for (net_id, position) in current {
+        seen.push(net_id);
+        match known.get(&net_id) {
+            Some(entity) => {
+                if let Ok(mut transform) = transforms.get_mut(*entity) {
+                    transform.translation = position;
+                }
+            }
+            None => {
+                let entity = spawn(commands, net_id, position);
+                known.insert(net_id, entity);
+            }
+        }
+    }
+
+    known.retain(|net_id, entity| {
+        let keep = seen.contains(net_id);
+        if !keep {
+            commands.entity(*entity).despawn_recursive();
+        }
+        keep
+    });
+}
+
+fn client_send_input(
+    mut role: ResMut<NetworkRole>,
+    game_state: Res<State<GameState>>,
+    consumables_used: Res<ConsumablesUsed>,
+    local_player: Query<(&Transform, &PlayerControllerState, &WeaponHolder), Without<NetworkProxy>>,
+) {
+    let NetworkRole::Client(client) = role.as_mut() else {
+        return;
+    };
+
+    if client.player_index.is_none() || *game_state.get() != GameState::FightingInArena {
+        return;
+    }
+
+    let Some((transform, controller, weapon_holder)) = local_player
+        .iter()
+        .find(|(_, controller, _)| controller.player_index == 0)
+    else {
+        return;
+    };
+
+    client.input_sequence += 1;
+    client
+        .predictions
+        .push_back((client.input_sequence, transform.translation));
+    if client.predictions.len() > 240 {
+        client.predictions.pop_front();
+    }
+
+    send(
+        &client.socket,
+        client.server,
+        &Message::Input(InputFrame {
+            sequence: client.input_sequence,
+            movement: controller.movement,
+            aim: controller.aim,
+            shoot: controller.is_shoot_pressed,
+            weapon: weapon_holder
+                .current_weapon
+                .as_ref()
+                .map(|(_, item)| PackedItem::of(item)),
+            consumables_used: consumables_used.0,
+        }),
+    );
+}
+
+// let the host know what survived packing so our hermit gets the right stats over there
+fn client_sync_inventory(role: Res<NetworkRole>, inventory: Res<Inventory>) {
+    let NetworkRole::Client(client) = role.as_ref() else {
+        return;
+    };
+
+    sync_inventory(client, &inventory);
+}
+
+// it's only sent once per packing otherwise, and a lost one would leave the host with a stale shell
+fn client_resync_inventory(
+    mut role: ResMut<NetworkRole>,
+    inventory: Res<Inventory>,
+    time: Res<Time>,
+) {
+    let NetworkRole::Client(client) = role.as_mut() else {
+        return;
+    };
+
+    if client.player_index.is_some()
+        && client
+            .inventory_sync_timer
+            .tick(time.delta())
+            .just_finished()
+    {
+        sync_inventory(client, &inventory);
+    }
+}
+
+fn sync_inventory(client: &ClientSession, inventory: &Inventory) {
+    send(
+        &client.socket,
+        client.server,
+        &Message::InventorySync(inventory.content.iter().map(PackedItem::of).collect()),
+    );
+}
+
+// The host sold these out of our shell. They wait while we're packing, and the sync sent back
+// afterwards is what stops the host repeating them.
+fn apply_remote_sales(
+    mut commands: Commands,
+    mut role: ResMut<NetworkRole>,
+    game_state: Res<State<GameState>>,
+    mut inventory: ResMut<Inventory>,
+    mut local_player: Query<
+        (
+            &mut WeaponHolder,
+            &Transform,
+            &mut PlayerCombatState,
+            &PlayerControllerState,
+        ),
+        Without<NetworkProxy>,
+    >,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let NetworkRole::Client(client) = role.as_mut() else {
+        return;
+    };
+
+    if client.pending_sales.is_empty() || *game_state.get() != GameState::FightingInArena {
+        return;
+    }
+
+    for sold in client.pending_sales.drain(..) {
+        if let Some(index) = inventory
+            .content
+            .iter()
+            .position(|item| PackedItem::of(item) == sold)
+        {
+            inventory.content.remove(index);
+        }
+    }
+    sync_inventory(client, &inventory);
+
+    if let Some((mut weapon_holder, transform, mut combat_state, _)) = local_player
+        .iter_mut()
+        .find(|(_, _, _, controller)| controller.player_index == 0)
+    {
+        refresh_held_weapon(
+            &inventory.content,
+            &mut weapon_holder,
+            transform,
+            &mut combat_state,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
+    }
+}
+
+fn follow_host_level(role: Res<NetworkRole>, mut current_level: ResMut<CurrentLevel>) {
+    if let NetworkRole::Client(client) = role.as_ref() {
+        if current_level.0 != client.host_level {
+            current_level.0 = client.host_level;
+        }
+    }
+}
+
+// the starting items every game spawns belong to the host's world, clients see the host's copies
+fn despawn_local_drops(
+    mut commands: Commands,
+    drops: Query<(Entity, &Collectable), Without<NetworkProxy>>,
+) {
+    for (entity, collectable) in &drops {
+        if collectable.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
@@ -0,0 +1,528 @@
+use bevy::prelude::*;
+
+use crate::enemy::EnemyType;
+use crate::inventory::{InventoryItem, ItemTypeId, Orientation};
+use crate::item_spawner::catalog_item;
+
+// first byte of every datagram, anything else on the port gets dropped
+const PROTOCOL_MAGIC: u8 = 0x5c;
+
+pub const MAX_DATAGRAM_SIZE: usize = 8192;
+
+// encoded sizes, for splitting a snapshot into datagrams that fit
+const SNAPSHOT_HEADER_SIZE: usize = 27;
+const PLAYER_SNAPSHOT_SIZE: usize = 26;
+const ENEMY_SNAPSHOT_SIZE: usize = 17;
+const PROJECTILE_SNAPSHOT_SIZE: usize = 16;
+const ITEM_SNAPSHOT_SIZE: usize = 17;
+
+const GRID_DIRECTIONS: [IVec3; 6] = [
+    IVec3::X,
+    IVec3::NEG_X,
+    IVec3::Y,
+    IVec3::NEG_Y,
+    IVec3::Z,
+    IVec3::NEG_Z,
+];
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Hello,
+    Welcome { player_index: u8 },
+    Input(InputFrame),
+    InventorySync(Vec<PackedItem>),
+    ItemGranted(ItemTypeId),
+    Snapshot(Snapshot),
+    Disconnect,
+    // the host sold this out of the client's shell at the shop
+    ItemSold(PackedItem),
+}
+
+// what a client's hermit wants to do, sent every frame and tagged with a sequence number
+// so the client can tell which of its predictions the host has already applied
+#[derive(Debug, Clone)]
+pub struct InputFrame {
+    pub sequence: u32,
+    pub movement: Vec3,
+    pub aim: Option<Vec3>,
+    pub shoot: bool,
+    pub weapon: Option<PackedItem>,
+    // running counts of the consumables used, see ConsumablesUsed
+    pub consumables_used: [u8; 4],
+}
+
+// An item the way it sits in a hermit's shell. The catalog has the rest, so the host rebuilds the
+// same merged, turned item the client packed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PackedItem {
+    pub item_type_id: ItemTypeId,
+    pub level: u32,
+    pub orientation: Orientation,
+    pub location: IVec3,
+}
+
+impl PackedItem {
+    pub fn of(item: &InventoryItem) -> Self {
+        Self {
+            item_type_id: item.item_type_id,
+            level: item.level,
+            orientation: item.orientation,
+            location: item.location,
+        }
+    }
+
+    // the levels are grown on the catalog item before it's turned, like in the shell
+    pub fn rebuild(&self) -> InventoryItem {
+        let mut item = catalog_item(self.item_type_id);
+        for _ in 1..self.level {
+            item = item.upgraded();
+        }
+        item.set_orientation(self.orientation);
+        item.location = self.location;
+        item
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerSnapshot {
+    pub player_index: u8,
+    pub position: Vec3,
+    pub yaw: f32,
+    pub hp: i32,
+    pub max_hp: i32,
+    pub weapon: Option<ItemTypeId>,
+}
+
+#[derive(Debug, Clone)]
+pub struct EnemySnapshot {
+    pub net_id: u32,
+    pub enemy_type: EnemyType,
+    pub position: Vec3,
+}
+
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    pub tick: u32,
+    pub run_active: bool,
+    pub all_down: bool,
+    pub level: u8,
+    pub wave: i32,
+    // last input of the receiving client the host simulated
+    pub last_input_sequence: u32,
+    // a busy wave doesn't fit in one datagram, every part has the players and some of the rest
+    pub part: u8,
+    pub parts: u8,
+    pub players: Vec<PlayerSnapshot>,
+    pub enemies: Vec<EnemySnapshot>,
+    pub projectiles: Vec<(u32, Vec3)>,
+    pub items: Vec<(u32, ItemTypeId, Vec3)>,
+}
+
+impl Snapshot {
+    pub fn split(mut self) -> Vec<Snapshot> {
+        let room =
+            MAX_DATAGRAM_SIZE - SNAPSHOT_HEADER_SIZE - self.players.len() * PLAYER_SNAPSHOT_SIZE;
+        let mut enemies = std::mem::take(&mut self.enemies).into_iter();
+        let mut projectiles = std::mem::take(&mut self.projectiles).into_iter();
+        let mut items = std::mem::take(&mut self.items).into_iter();
+
+        let mut parts = Vec::new();
+        loop {
+            let mut part = self.clone();
+            let mut room = room;
+            part.enemies
+                .extend(enemies.by_ref().take(room / ENEMY_SNAPSHOT_SIZE));
+            room -= part.enemies.len() * ENEMY_SNAPSHOT_SIZE;
+            part.projectiles
+                .extend(projectiles.by_ref().take(room / PROJECTILE_SNAPSHOT_SIZE));
+            room -= part.projectiles.len() * PROJECTILE_SNAPSHOT_SIZE;
+            part.items
+                .extend(items.by_ref().take(room / ITEM_SNAPSHOT_SIZE));
+            parts.push(part);
+
+            if enemies.len() == 0 && projectiles.len() == 0 && items.len() == 0 {
+                break;
+            }
+        }
+
+        let count = parts.len() as u8;
+        for (index, part) in parts.iter_mut().enumerate() {
+            part.part = index as u8;
+            part.parts = count;
+        }
+        parts
+    }
+}
+
+// Puts a split snapshot back together. A tick missing a part is dropped once a newer one starts
+// coming in, the next whole one replaces it anyway. A part that arrives twice only counts once.
+#[derive(Default)]
+pub struct SnapshotParts {
+    gathered: Option<Snapshot>,
+    received: Vec<bool>,
+}
+
+impl SnapshotParts {
+    pub fn add(&mut self, part: Snapshot) -> Option<Snapshot> {
+        let index = part.part as usize;
+        match &mut self.gathered {
+            Some(gathered) if gathered.tick == part.tick => {
+                if self.received.get(index) != Some(&false) {
+                    return None;
+                }
+                gathered.enemies.extend(part.enemies);
+                gathered.projectiles.extend(part.projectiles);
+                gathered.items.extend(part.items);
+            }
+            Some(gathered) if gathered.tick > part.tick => return None,
+            _ => {
+                if index >= part.parts as usize {
+                    return None;
+                }
+                self.received = vec![false; part.parts as usize];
+                self.gathered = Some(part);
+            }
+        }
+
+        self.received[index] = true;
+        if self.received.iter().all(|received| *received) {
+            return self.gathered.take();
+        }
+        None
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    BadMagic,
+    UnknownMessage(u8),
+    UnknownItemType(u8),
+    UnknownEnemyType(u8),
+    BadOrientation,
+    Truncated,
+}
+
+impl Message {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = Writer(vec![PROTOCOL_MAGIC]);
+        match self {
+            Message::Hello => writer.u8(0),
+            Message::Welcome { player_index } => {
+                writer.u8(1);
+                writer.u8(*player_index);
+            }
+            Message::Input(input) => {
+                writer.u8(2);
+                writer.u32(input.sequence);
+                writer.vec3(input.movement);
+                writer.bool(input.aim.is_some());
+                writer.vec3(input.aim.unwrap_or(Vec3::ZERO));
+                writer.bool(input.shoot);
+                writer.bool(input.weapon.is_some());
+                if let Some(weapon) = &input.weapon {
+                    writer.packed_item(weapon);
+                }
+                for used in input.consumables_used {
+                    writer.u8(used);
+                }
+            }
+            Message::InventorySync(items) => {
+                writer.u8(3);
+                writer.u16(items.len() as u16);
+                for item in items {
+                    writer.packed_item(item);
+                }
+            }
+            Message::ItemGranted(item) => {
+                writer.u8(4);
+                writer.item(*item);
+            }
+            Message::Snapshot(snapshot) => {
+                writer.u8(5);
+                writer.u32(snapshot.tick);
+                writer.bool(snapshot.run_active);
+                writer.bool(snapshot.all_down);
+                writer.u8(snapshot.level);
+                writer.i32(snapshot.wave);
+                writer.u32(snapshot.last_input_sequence);
+                writer.u8(snapshot.part);
+                writer.u8(snapshot.parts);
+
+                writer.u16(snapshot.players.len() as u16);
+                for player in &snapshot.players {
+                    writer.u8(player.player_index);
+                    writer.vec3(player.position);
+                    writer.f32(player.yaw);
+                    writer.i32(player.hp);
+                    writer.i32(player.max_hp);
+                    writer.optional_item(player.weapon);
+                }
+
+                writer.u16(snapshot.enemies.len() as u16);
+                for enemy in &snapshot.enemies {
+                    writer.u32(enemy.net_id);
+                    writer.u8(match enemy.enemy_type {
+                        EnemyType::Jellyfish => 0,
+                        EnemyType::Urchin => 1,
+                        EnemyType::Shrimp => 2,
+                    });
+                    writer.vec3(enemy.position);
+                }
+
+                writer.u16(snapshot.projectiles.len() as u16);
+                for (net_id, position) in &snapshot.projectiles {
+                    writer.u32(*net_id);
+                    writer.vec3(*position);
+                }
+
+                writer.u16(snapshot.items.len() as u16);
+                for (net_id, item, position) in &snapshot.items {
+                    writer.u32(*net_id);
+                    writer.item(*item);
+                    writer.vec3(*position);
+                }
+            }
+            Message::Disconnect => writer.u8(6),
+            Message::ItemSold(item) => {
+                writer.u8(7);
+                writer.packed_item(item);
+            }
+        }
+        writer.0
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Message, DecodeError> {
+        let mut reader = Reader { bytes, cursor: 0 };
+        if reader.u8()? != PROTOCOL_MAGIC {
+            return Err(DecodeError::BadMagic);
+        }
+
+        let message = match reader.u8()? {
+            0 => Message::Hello,
+            1 => Message::Welcome {
+                player_index: reader.u8()?,
+            },
+            2 => {
+                let sequence = reader.u32()?;
+                let movement = reader.vec3()?;
+                let has_aim = reader.bool()?;
+                let aim = reader.vec3()?;
+                let shoot = reader.bool()?;
+                let weapon = match reader.bool()? {
+                    true => Some(reader.packed_item()?),
+                    false => None,
+                };
+                let mut consumables_used = [0; 4];
+                for used in &mut consumables_used {
+                    *used = reader.u8()?;
+                }
+                Message::Input(InputFrame {
+                    sequence,
+                    movement,
+                    aim: has_aim.then_some(aim),
+                    shoot,
+                    weapon,
+                    consumables_used,
+                })
+            }
+            3 => {
+                let count = reader.u16()?;
+                let mut items = Vec::new();
+                for _ in 0..count {
+                    items.push(reader.packed_item()?);
+                }
+                Message::InventorySync(items)
+            }
+            4 => Message::ItemGranted(reader.item()?),
+            5 => {
+                let tick = reader.u32()?;
+                let run_active = reader.bool()?;
+                let all_down = reader.bool()?;
+                let level = reader.u8()?;
+                let wave = reader.i32()?;
+                let last_input_sequence = reader.u32()?;
+                let part = reader.u8()?;
+                let parts = reader.u8()?;
+
+                let mut players = Vec::new();
+                for _ in 0..reader.u16()? {
+                    players.push(PlayerSnapshot {
+                        player_index: reader.u8()?,
+                        position: reader.vec3()?,
+                        yaw: reader.f32()?,
+                        hp: reader.i32()?,
+                        max_hp: reader.i32()?,
+                        weapon: reader.optional_item()?,
+                    });
+                }
+
+                let mut enemies = Vec::new();
+                for _ in 0..reader.u16()? {
+                    let net_id = reader.u32()?;
+                    let enemy_type = match reader.u8()? {
+                        0 => EnemyType::Jellyfish,
+                        1 => EnemyType::Urchin,
+                        2 => EnemyType::Shrimp,
+                        other => return Err(DecodeError::UnknownEnemyType(other)),
+                    };
+                    enemies.push(EnemySnapshot {
+                        net_id,
+                        enemy_type,
+                        position: reader.vec3()?,
+                    });
+                }
+
+                let mut projectiles = Vec::new();
+                for _ in 0..reader.u16()? {
+                    projectiles.push((reader.u32()?, reader.vec3()?));
+                }
+
+                let mut items = Vec::new();
+                for _ in 0..reader.u16()? {
+                    items.push((reader.u32()?, reader.item()?, reader.vec3()?));
+                }
+
+                Message::Snapshot(Snapshot {
+                    tick,
+                    run_active,
+                    all_down,
+                    level,
+                    wave,
+                    last_input_sequence,
+                    part,
+                    parts,
+                    players,
+                    enemies,
+                    projectiles,
+                    items,
+                })
+            }
+            6 => Message::Disconnect,
+            7 => Message::ItemSold(reader.packed_item()?),
+            other => return Err(DecodeError::UnknownMessage(other)),
+        };
+
+        Ok(message)
+    }
+}
+
+struct Writer(Vec<u8>);
+
+impl Writer {
+    fn u8(&mut self, value: u8) {
+        self.0.push(value);
+    }
+    fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+    fn u16(&mut self, value: u16) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn i32(&mut self, value: i32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn f32(&mut self, value: f32) {
+        self.0.extend_from_slice(&value.to_le_bytes());
+    }
+    fn vec3(&mut self, value: Vec3) {
+        self.f32(value.x);
+        self.f32(value.y);
+        self.f32(value.z);
+    }
+    fn item(&mut self, item: ItemTypeId) {
+        let index = ItemTypeId::ALL.iter().position(|i| *i == item).unwrap();
+        self.u8(index as u8);
+    }
+    fn optional_item(&mut self, item: Option<ItemTypeId>) {
+        match item {
+            Some(item) => self.item(item),
+            None => self.u8(u8::MAX),
+        }
+    }
+    // every axis is one of the six grid directions
+    fn orientation(&mut self, orientation: Orientation) {
+        for axis in orientation.axes() {
+            let index = GRID_DIRECTIONS.iter().position(|d| *d == axis).unwrap();
+            self.u8(index as u8);
+        }
+    }
+    fn packed_item(&mut self, item: &PackedItem) {
+        self.item(item.item_type_id);
+        self.u8(item.level as u8);
+        self.orientation(item.orientation);
+        self.i32(item.location.x);
+        self.i32(item.location.y);
+        self.i32(item.location.z);
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], DecodeError> {
+        let slice = self
+            .bytes
+            .get(self.cursor..self.cursor + N)
+            .ok_or(DecodeError::Truncated)?;
+        self.cursor += N;
+        Ok(slice.try_into().unwrap())
+    }
+    fn u8(&mut self) -> Result<u8, DecodeError> {
+        Ok(self.take::<1>()?[0])
+    }
+    fn bool(&mut self) -> Result<bool, DecodeError> {
+        Ok(self.u8()? != 0)
+    }
+    fn u16(&mut self) -> Result<u16, DecodeError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+    fn u32(&mut self) -> Result<u32, DecodeError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+    fn i32(&mut self) -> Result<i32, DecodeError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+    fn f32(&mut self) -> Result<f32, DecodeError> {
+        Ok(f32::from_le_bytes(self.take()?))
+    }
+    fn vec3(&mut self) -> Result<Vec3, DecodeError> {
+        Ok(Vec3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+    fn item(&mut self) -> Result<ItemTypeId, DecodeError> {
+        let index = self.u8()?;
+        ItemTypeId::ALL
+            .get(index as usize)
+            .copied()
+            .ok_or(DecodeError::UnknownItemType(index))
+    }
+    fn optional_item(&mut self) -> Result<Option<ItemTypeId>, DecodeError> {
+        if self.bytes.get(self.cursor) == Some(&u8::MAX) {
+            self.cursor += 1;
+            return Ok(None);
+        }
+        self.item().map(Some)
+    }
+    fn orientation(&mut self) -> Result<Orientation, DecodeError> {
+        let mut axes = [IVec3::ZERO; 3];
+        for axis in &mut axes {
+            *axis = *GRID_DIRECTIONS
+                .get(self.u8()? as usize)
+                .ok_or(DecodeError::BadOrientation)?;
+        }
+        Orientation::from_axes(axes).ok_or(DecodeError::BadOrientation)
+    }
+    fn packed_item(&mut self) -> Result<PackedItem, DecodeError> {
+        Ok(PackedItem {
+            item_type_id: self.item()?,
+            level: self.u8()? as u32,
+            orientation: self.orientation()?,
+            location: IVec3::new(self.i32()?, self.i32()?, self.i32()?),
+        })
+    }
+}
@@ -0,0 +1,441 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::view::NoFrustumCulling;
+use rand::Rng;
+
+use crate::game::HolyCam;
+use crate::game_state::GameState;
+use crate::wave_manager::ARENA_DIMENSIONS_METERS;
+
+// new particles are dropped past this, the arena never needs nearly as many
+const MAX_PARTICLES: usize = 4096;
+
+const PARTICLE_TEXTURE_SIZE: u32 = 32;
+
+pub struct ParticlePlugin;
+
+impl Plugin for ParticlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Particles>();
+        app.add_systems(Startup, setup);
+        app.add_systems(OnEnter(GameState::FightingInArena), spawn_ambient_bubbles);
+        app.add_systems(
+            Update,
+            (emit_particles, simulate_particles, build_particle_mesh)
+                .chain()
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clear_particles);
+    }
+}
+
+// values spread evenly over a particle's life, with a straight line between neighbours
+pub struct Curve<T: 'static>(pub &'static [T]);
+
+impl<T: Copy> Curve<T> {
+    fn sample(&self, t: f32, lerp: impl Fn(T, T, f32) -> T) -> T {
+        let keys = self.0;
+        if keys.len() < 2 {
+            return keys[0];
+        }
+        let scaled = t.clamp(0.0, 1.0) * (keys.len() - 1) as f32;
+        let index = (scaled as usize).min(keys.len() - 2);
+        lerp(keys[index], keys[index + 1], scaled - index as f32)
+    }
+}
+
+impl Curve<f32> {
+    fn at(&self, t: f32) -> f32 {
+        self.sample(t, |a, b, t| a + (b - a) * t)
+    }
+}
+
+impl Curve<Color> {
+    fn at(&self, t: f32) -> Color {
+        self.sample(t, |a, b, t| {
+            let a = Vec4::from(a.as_rgba_f32());
+            let b = Vec4::from(b.as_rgba_f32());
+            Color::from(a.lerp(b, t))
+        })
+    }
+}
+
+// how one kind of particle starts out and changes over its life
+pub struct ParticleEffect {
+    pub lifetime: Range<f32>,
+    pub speed: Range<f32>,
+    pub direction: Vec3,
+    // 0 keeps to the direction, 1 goes about anywhere
+    pub spread: f32,
+    pub acceleration: Vec3,
+    // share of the velocity lost per second
+    pub drag: f32,
+    pub size: Curve<f32>,
+    pub color: Curve<Color>,
+}
+
+// rising slowly from the sand all over the arena
+pub const AMBIENT_BUBBLES: ParticleEffect = ParticleEffect {
+    lifetime: 4.0..7.0,
+    speed: 0.3..0.8,
+    direction: Vec3::Y,
+    spread: 0.2,
+    acceleration: Vec3::new(0.0, 0.3, 0.0),
+    drag: 0.0,
+    size: Curve(&[0.06, 0.12, 0.1]),
+    color: Curve(&[
+        Color::rgba(0.85, 0.95, 1.0, 0.0),
+        Color::rgba(0.85, 0.95, 1.0, 0.5),
+        Color::rgba(0.85, 0.95, 1.0, 0.0),
+    ]),
+};
+
+// little bubbles left behind by the bubble projectiles
+pub const PROJECTILE_TRAIL: ParticleEffect = ParticleEffect {
+    lifetime: 0.3..0.6,
+    speed: 0.0..0.3,
+    direction: Vec3::Y,
+    spread: 1.0,
+    acceleration: Vec3::new(0.0, 1.5, 0.0),
+    drag: 1.0,
+    size: Curve(&[0.1, 0.03]),
+    color: Curve(&[
+        Color::rgba(1.0, 1.0, 1.0, 0.6),
+        Color::rgba(1.0, 1.0, 1.0, 0.0),
+    ]),
+};
+
+// kicked up by a hermit walking on the sand
+pub const SAND_PUFF: ParticleEffect = ParticleEffect {
+    lifetime: 0.4..0.7,
+    speed: 0.5..1.2,
+    direction: Vec3::Y,
+    spread: 0.9,
+    acceleration: Vec3::new(0.0, -1.0, 0.0),
+    drag: 3.0,
+    size: Curve(&[0.1, 0.25, 0.3]),
+    color: Curve(&[
+        Color::rgba(0.85, 0.78, 0.6, 0.5),
+        Color::rgba(0.85, 0.78, 0.6, 0.0),
+    ]),
+};
+
+// what a creature bursts into, tinted with its own colour
+pub const DEATH_BURST: ParticleEffect = ParticleEffect {
+    lifetime: 0.4..0.7,
+    speed: 2.5..5.0,
+    direction: Vec3::Y,
+    spread: 1.0,
+    acceleration: Vec3::new(0.0, -6.0, 0.0),
+    drag: 1.5,
+    size: Curve(&[0.2, 0.15, 0.0]),
+    color: Curve(&[Color::WHITE, Color::rgba(1.0, 1.0, 1.0, 0.0)]),
+};
+
+struct Particle {
+    effect: &'static ParticleEffect,
+    position: Vec3,
+    velocity: Vec3,
+    age: f32,
+    lifetime: f32,
+    tint: Color,
+}
+
+// every live particle, they're plain data and all drawn together as one mesh
+#[derive(Resource, Default)]
+pub struct Particles(Vec<Particle>);
+
+impl Particles {
+    // `area` is the half size of the box they start in
+    pub fn burst(
+        &mut self,
+        effect: &'static ParticleEffect,
+        position: Vec3,
+        area: Vec3,
+        count: usize,
+        tint: Color,
+        rng: &mut impl Rng,
+    ) {
+        for _ in 0..count.min(MAX_PARTICLES - self.0.len()) {
+            let offset = Vec3::new(
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+            );
+            let wobble = Vec3::new(
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+                rng.gen_range(-1.0..=1.0),
+            );
+            let direction = (effect.direction + wobble * effect.spread).normalize_or_zero();
+
+            self.0.push(Particle {
+                effect,
+                position: position + offset * area,
+                velocity: direction * rng.gen_range(effect.speed.clone()),
+                age: 0.0,
+                lifetime: rng.gen_range(effect.lifetime.clone()),
+                tint,
+            });
+        }
+    }
+}
+
+// Spawns `rate` particles a second where its entity is. With a minimum speed it only does while
+// the entity moves at least that fast, like sand under a walking hermit.
+#[derive(Component)]
+pub struct ParticleEmitter {
+    pub effect: &'static ParticleEffect,
+    pub rate: f32,
+    pub area: Vec3,
+    pub offset: Vec3,
+    pub min_speed: f32,
+    pub tint: Color,
+    pending: f32,
+    last_position: Option<Vec3>,
+}
+
+impl ParticleEmitter {
+    pub fn new(effect: &'static ParticleEffect, rate: f32) -> Self {
+        Self {
+            effect,
+            rate,
+            area: Vec3::ZERO,
+            offset: Vec3::ZERO,
+            min_speed: 0.0,
+            tint: Color::WHITE,
+            pending: 0.0,
+            last_position: None,
+        }
+    }
+
+    pub fn with_area(mut self, area: Vec3) -> Self {
+        self.area = area;
+        self
+    }
+
+    pub fn with_offset(mut self, offset: Vec3) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn while_moving(mut self, min_speed: f32) -> Self {
+        self.min_speed = min_speed;
+        self
+    }
+}
+
+#[derive(Component)]
+struct ParticleMesh;
+
+#[derive(Component)]
+struct AmbientBubbles;
+
+// a soft disc with a brighter rim, reads as a bubble as well as a puff of sand
+fn particle_texture() -> Image {
+    let size = PARTICLE_TEXTURE_SIZE;
+    let half = size as f32 / 2.0;
+    let data = (0..size * size)
+        .flat_map(|i| {
+            let x = (i % size) as f32 + 0.5 - half;
+            let y = (i / size) as f32 + 0.5 - half;
+            let distance = Vec2::new(x, y).length() / half;
+            let edge = ((1.0 - distance) / 0.15).clamp(0.0, 1.0);
+            let alpha = (0.4 + 0.6 * distance.powi(4)) * edge;
+            [255, 255, 255, (alpha * 255.0) as u8]
+        })
+        .collect();
+
+    Image::new(
+        Extent3d {
+            width: size,
+            height: size,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    // a single invisible quad until there's something to draw
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+    write_quads(&mut mesh, &[], Vec3::X, Vec3::Y);
+
+    commands.spawn((
+        PbrBundle {
+            mesh: meshes.add(mesh),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(images.add(particle_texture())),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        // the particles move around, the mesh's bounds never match them
+        NoFrustumCulling,
+        ParticleMesh,
+    ));
+}
+
+fn spawn_ambient_bubbles(mut commands: Commands) {
+    commands.spawn((
+        TransformBundle::default(),
+        ParticleEmitter::new(&AMBIENT_BUBBLES, 8.0).with_area(Vec3::new(
+            ARENA_DIMENSIONS_METERS[0],
+            0.0,
+            ARENA_DIMENSIONS_METERS[1],
+        )),
+        AmbientBubbles,
+    ));
+}
+
+fn emit_particles(
+    mut particles: ResMut<Particles>,
+    mut emitters: Query<(&mut ParticleEmitter, &GlobalTransform)>,
+    time: Res<Time>,
+) {
+    let delta = time.delta_seconds();
+    if delta <= 0.0 {
+        return;
+    }
+    // purely cosmetic, so it doesn't touch the game's seeded rolls
+    let mut rng = rand::thread_rng();
+
+    for (mut emitter, transform) in &mut emitters {
+        let position = transform.translation();
+        let speed = emitter
+            .last_position
+            .map_or(0.0, |last| last.distance(position) / delta);
+        emitter.last_position = Some(position);
+        if speed < emitter.min_speed {
+            emitter.pending = 0.0;
+            continue;
+        }
+
+        emitter.pending += emitter.rate * delta;
+        let count = emitter.pending as usize;
+        emitter.pending -= count as f32;
+
+        particles.burst(
+            emitter.effect,
+            position + emitter.offset,
+            emitter.area,
+            count,
+            emitter.tint,
+            &mut rng,
+        );
+    }
+}
+
+fn simulate_particles(mut particles: ResMut<Particles>, time: Res<Time>) {
+    let delta = time.delta_seconds();
+    for particle in &mut particles.0 {
+        particle.age += delta;
+        let velocity = particle.velocity * (1.0 - particle.effect.drag * delta).max(0.0)
+            + particle.effect.acceleration * delta;
+        particle.velocity = velocity;
+        particle.position += velocity * delta;
+    }
+    particles
+        .0
+        .retain(|particle| particle.age < particle.lifetime);
+}
+
+// camera facing quads, one per particle
+fn write_quads(mesh: &mut Mesh, particles: &[Particle], right: Vec3, up: Vec3) {
+    let count = particles.len().max(1);
+    let mut positions = Vec::with_capacity(count * 4);
+    let mut colors = Vec::with_capacity(count * 4);
+    let mut uvs = Vec::with_capacity(count * 4);
+    let mut indices = Vec::with_capacity(count * 6);
+    let normal = right.cross(up).normalize_or_zero();
+
+    let mut quads: Vec<(Vec3, f32, Color)> = particles
+        .iter()
+        .map(|particle| {
+            let t = particle.age / particle.lifetime;
+            let size = particle.effect.size.at(t);
+            let color = particle.effect.color.at(t) * particle.tint.as_rgba_f32();
+            (particle.position, size, color)
+        })
+        .collect();
+    if quads.is_empty() {
+        quads.push((Vec3::ZERO, 0.0, Color::NONE));
+    }
+
+    for (i, (position, size, color)) in quads.into_iter().enumerate() {
+        let right = right * size;
+        let up = up * size;
+        positions.extend([
+            (position - right - up).to_array(),
+            (position + right - up).to_array(),
+            (position + right + up).to_array(),
+            (position - right + up).to_array(),
+        ]);
+        colors.extend([color.as_linear_rgba_f32(); 4]);
+        uvs.extend([[0.0, 1.0], [1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]);
+        let first = (i * 4) as u32;
+        indices.extend([first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, vec![normal.to_array(); count * 4]);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    mesh.set_indices(Some(Indices::U32(indices)));
+}
+
+fn build_particle_mesh(
+    particles: Res<Particles>,
+    mut particle_mesh: Query<(&Handle<Mesh>, &mut Visibility), With<ParticleMesh>>,
+    camera: Query<&GlobalTransform, With<HolyCam>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+) {
+    let Ok((handle, mut visibility)) = particle_mesh.get_single_mut() else {
+        return;
+    };
+    let Ok(camera) = camera.get_single() else {
+        return;
+    };
+
+    if particles.0.is_empty() {
+        if *visibility != Visibility::Hidden {
+            *visibility = Visibility::Hidden;
+        }
+        return;
+    }
+    if *visibility != Visibility::Visible {
+        *visibility = Visibility::Visible;
+    }
+
+    if let Some(mesh) = meshes.get_mut(handle) {
+        write_quads(mesh, &particles.0, camera.right(), camera.up());
+    }
+}
+
+fn clear_particles(
+    mut commands: Commands,
+    mut particles: ResMut<Particles>,
+    mut particle_mesh: Query<&mut Visibility, With<ParticleMesh>>,
+    bubbles: Query<Entity, With<AmbientBubbles>>,
+) {
+    particles.0.clear();
+    for mut visibility in &mut particle_mesh {
+        *visibility = Visibility::Hidden;
+    }
+    for entity in &bubbles {
+        commands.entity(entity).despawn();
+    }
+}
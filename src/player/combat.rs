@@ -1,16 +1,25 @@
 use crate::enemy::Enemy;
+use crate::game::GameRng;
 use crate::game_state::GameState;
+use crate::hit_feedback::{EnemyHit, HitSource};
 use bevy::audio::PlaybackMode::{Despawn, Once};
 use bevy::audio::Volume::Relative;
 use bevy::audio::VolumeLevel;
 use bevy::prelude::*;
 use bevy::time::Time;
 use bevy_rapier3d::na::clamp;
+use rand::Rng;
 
-use crate::inventory::Inventory;
+use crate::inventory::InventoryItem;
 use crate::inventory::ItemType::MELEE_WEAPON;
 use crate::inventory::ItemTypeId::WillSword;
-use crate::player::PlayerState;
+use crate::network::{is_simulation_authority, RemotePlayer};
+use crate::player::{
+    is_attack_button_pressed, player_gamepads, uses_keyboard_and_mouse, PlayerControllerState,
+    PlayerState,
+};
+use crate::shop::is_shopping;
+use crate::status_effect::{ApplyStatusEffect, StatusEffects};
 use crate::world_item::WeaponHolder;
 
 pub const BASE_ATTACK_COOLDOWN: f32 = 0.5;
@@ -20,19 +29,30 @@ pub const PLAYER_HEAL_COOLDOWN: f32 = 5.0;
 
 pub const PLAYER_INVICIBILITY_COOLDOWN: f32 = 2.0;
 
+// one hit in ten lands twice as hard
+pub const CRITICAL_CHANCE: f64 = 0.1;
+pub const CRITICAL_MULTIPLIER: i32 = 2;
+
 pub struct PlayerCombatPlugin;
 
 impl Plugin for PlayerCombatPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Update,
-            process_hit.run_if(
-                in_state(GameState::FightingInArena).and_then(in_state(PlayerState::Fighting)),
-            ),
+            process_hit
+                .run_if(
+                    is_simulation_authority
+                        .and_then(in_state(GameState::FightingInArena))
+                        .and_then(in_state(PlayerState::Fighting)),
+                )
+                // clicks on the merchant's buttons aren't attacks
+                .run_if(not(is_shopping)),
         );
         app.add_systems(
             Update,
-            player_heal.run_if(in_state(GameState::FightingInArena)),
+            player_heal
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
         );
     }
 }
@@ -47,21 +67,25 @@ pub struct PlayerCombatState {
     pub last_attack: f32,
     pub last_heal: f32,
     pub last_hit: f32,
+    pub shot_rate_limiter: Option<Timer>,
+    // set by the endless run's modifiers
+    pub damage_multiplier: f32,
+    pub no_healing: bool,
 }
 
-fn player_heal(mut player: Query<&mut PlayerCombatState>, time: Res<Time>) {
-    let mut player = player.single_mut();
+fn player_heal(mut players: Query<&mut PlayerCombatState>, time: Res<Time>) {
+    for mut player in &mut players {
+        if player.current_hp == player.max_hp || player.is_downed() || player.no_healing {
+            continue;
+        }
 
-    if player.current_hp == player.max_hp {
-        return;
-    }
+        if player.last_heal + PLAYER_HEAL_COOLDOWN > time.elapsed_seconds() {
+            continue;
+        }
 
-    if player.last_heal + PLAYER_HEAL_COOLDOWN > time.elapsed_seconds() {
-        return;
+        player.current_hp += 1;
+        player.last_heal = time.elapsed_seconds();
     }
-
-    player.current_hp += 1;
-    player.last_heal = time.elapsed_seconds();
 }
 
 impl PlayerCombatState {
@@ -75,15 +99,29 @@ impl PlayerCombatState {
             last_attack: -10000.0,
             last_heal: -10000.0,
             last_hit: -10000.0,
+            shot_rate_limiter: None,
+            damage_multiplier: 1.0,
+            no_healing: false,
         }
     }
 
-    pub fn compute_from_inventory(&mut self, inv: &Res<Inventory>) {
+    // what one hit with this weapon takes off a creature
+    pub fn hit_damage(&self, weapon: &InventoryItem) -> i32 {
+        let damage = weapon.weapon_damage + self.damage - 1;
+        (damage as f32 * self.damage_multiplier).round() as i32
+    }
+
+    // a downed hermit waits for its teammate to clear the wave
+    pub fn is_downed(&self) -> bool {
+        self.current_hp <= 0
+    }
+
+    pub fn compute_from_inventory(&mut self, items: &[InventoryItem]) {
         self.max_hp = 3;
         self.attack_speed = 1.0;
         self.damage = 1;
 
-        for item in &inv.content {
+        for item in items {
             self.max_hp += item.hp_gain;
             self.attack_speed *= item.attack_speed_gain;
             self.damage += item.attack_damage_gain;
@@ -110,89 +148,117 @@ impl PlayerCombatState {
     }
 }
 
-fn process_hit(
+// the damage a hit really does, and whether it was a critical one
+pub fn roll_critical(damage: i32, rng: &mut impl Rng) -> (i32, bool) {
+    if rng.gen_bool(CRITICAL_CHANCE) {
+        (damage * CRITICAL_MULTIPLIER, true)
+    } else {
+        (damage, false)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn process_hit(
     mut commands: Commands,
     mut asset_server: ResMut<AssetServer>,
-    mut player: Query<(&Transform, &mut PlayerCombatState, &WeaponHolder)>,
-    enemies: Query<(Entity, &Transform), With<Enemy>>,
+    mut players: Query<(
+        &Transform,
+        &mut PlayerCombatState,
+        &WeaponHolder,
+        &PlayerControllerState,
+        Option<&RemotePlayer>,
+        Option<&StatusEffects>,
+    )>,
+    mut enemies: Query<(Entity, &Transform, &mut Enemy)>,
+    mut status_effect_writer: EventWriter<ApplyStatusEffect>,
+    mut hit_writer: EventWriter<EnemyHit>,
+    mut rng: ResMut<GameRng>,
     buttons: Res<Input<MouseButton>>,
     touches: Res<Touches>,
     time: Res<Time>,
     gamepads: Res<Gamepads>,
     gamepad_buttons: Res<Input<GamepadButton>>,
 ) {
-    let mut player = player.single_mut();
-
-    if player.2.current_weapon.is_none() {
-        return;
-    }
+    let player_count = players.iter().filter(|player| player.4.is_none()).count();
 
-    let current_weapon = player.2.current_weapon.clone().unwrap().1;
+    for mut player in &mut players {
+        if player.2.current_weapon.is_none()
+            || player.1.is_downed()
+            || player.5.is_some_and(StatusEffects::is_stunned)
+        {
+            continue;
+        }
 
-    if current_weapon.item_type != MELEE_WEAPON {
-        return;
-    }
+        let current_weapon = player.2.current_weapon.clone().unwrap().1;
 
-    if player.1.last_attack
-        + BASE_ATTACK_COOLDOWN / (player.1.attack_speed * current_weapon.weapon_attack_speed)
-        > time.elapsed_seconds()
-    {
-        return; // too recent to attack again
-    }
+        if current_weapon.item_type != MELEE_WEAPON {
+            continue;
+        }
 
-    let distance_to_kill = if current_weapon.item_type_id == WillSword {
-        1.3
-    } else {
-        1.9
-    };
-
-    let mut gamepad_hit = false;
-
-    for gamepad in gamepads.iter() {
-        if gamepad_buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::South,
-        }) || gamepad_buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightTrigger,
-        }) || gamepad_buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightTrigger2,
-        }) || gamepad_buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightThumb,
-        }) {
-            gamepad_hit = true;
-            break;
+        if player.1.last_attack
+            + BASE_ATTACK_COOLDOWN / (player.1.attack_speed * current_weapon.weapon_attack_speed)
+            > time.elapsed_seconds()
+        {
+            continue; // too recent to attack again
         }
-    }
 
-    if buttons.just_pressed(MouseButton::Left)
-        || (buttons.pressed(MouseButton::Left) && current_weapon.weapon_is_auto)
-        || touches.first_pressed_position() != None
-        || gamepad_hit
-    {
-        commands.spawn(AudioBundle {
-            source: asset_server.load("swing.ogg"),
-            settings: PlaybackSettings {
-                mode: Despawn,
-                volume: Relative(VolumeLevel::new(0.5f32)),
+        let distance_to_kill = if current_weapon.item_type_id == WillSword {
+            1.3
+        } else {
+            1.9
+        };
+
+        let gamepad_hit = player_gamepads(player.3.player_index, player_count, &gamepads)
+            .into_iter()
+            .any(|gamepad| is_attack_button_pressed(&gamepad_buttons, gamepad));
+
+        let pointer_hit = uses_keyboard_and_mouse(player.3.player_index)
+            && (buttons.just_pressed(MouseButton::Left)
+                || (buttons.pressed(MouseButton::Left) && current_weapon.weapon_is_auto)
+                || touches.first_pressed_position() != None);
+
+        // remote hermits swing when their client says so
+        let remote_hit = player.4.is_some() && player.3.is_shoot_pressed;
+
+        if pointer_hit || gamepad_hit || remote_hit {
+            commands.spawn(AudioBundle {
+                source: asset_server.load("swing.ogg"),
+                settings: PlaybackSettings {
+                    mode: Despawn,
+                    volume: Relative(VolumeLevel::new(0.5f32)),
+                    ..default()
+                },
                 ..default()
-            },
-            ..default()
-        });
-        for enemy in enemies.iter() {
-            if enemy
-                .1
-                .translation
-                .distance_squared(player.0.translation + player.0.forward() * 1.0)
-                < distance_to_kill * distance_to_kill
-            {
-                commands.entity(enemy.0).despawn();
+            });
+            let damage = player.1.hit_damage(&current_weapon);
+            for (enemy_entity, enemy_transform, mut enemy) in &mut enemies {
+                if enemy_transform
+                    .translation
+                    .distance_squared(player.0.translation + player.0.forward() * 1.0)
+                    >= distance_to_kill * distance_to_kill
+                {
+                    continue;
+                }
+
+                let (damage, critical) = roll_critical(damage, rng.as_mut());
+                let killed = enemy.take_damage(damage);
+                hit_writer.send(EnemyHit {
+                    position: enemy_transform.translation,
+                    damage,
+                    critical,
+                    enemy_type: enemy.enemy_type,
+                    killed,
+                    source: HitSource::Melee,
+                });
+                if killed {
+                    commands.entity(enemy_entity).despawn();
+                } else if let Some(kind) = current_weapon.status_effect {
+                    status_effect_writer.send(ApplyStatusEffect::new(enemy_entity, kind));
+                }
             }
-        }
 
-        player.1.last_attack = time.elapsed_seconds();
-        player.1.current_weapon_attack_speed = current_weapon.weapon_attack_speed;
+            player.1.last_attack = time.elapsed_seconds();
+            player.1.current_weapon_attack_speed = current_weapon.weapon_attack_speed;
+        }
     }
 }
@@ -0,0 +1,331 @@
+use bevy::audio::PlaybackMode::Despawn;
+use bevy::audio::Volume::Relative;
+use bevy::audio::VolumeLevel;
+use bevy::log;
+use bevy::prelude::*;
+use bevy::utils::HashSet;
+
+use crate::enemy::Enemy;
+use crate::game_state::GameState;
+use crate::hit_feedback::{EnemyHit, HitSource};
+use crate::inventory::{Inventory, ItemType, ItemTypeId};
+use crate::network::{is_client, is_simulation_authority, NetworkProxy, RemotePlayer};
+use crate::player::combat::PlayerCombatState;
+use crate::player::{player_gamepads, uses_keyboard_and_mouse, PlayerControllerState};
+use crate::status_effect::{ApplyStatusEffect, StatusEffectKind};
+
+pub const HEAL_AMOUNT: i32 = 2;
+pub const SPEED_BOOST_DURATION: f32 = 5.0;
+pub const SHIELD_DURATION: f32 = 4.0;
+pub const BOMB_RADIUS: f32 = 4.0;
+
+pub struct PlayerConsumablesPlugin;
+
+impl Plugin for PlayerConsumablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ConsumablePressed>();
+        app.init_resource::<ConsumablesUsed>();
+        app.add_systems(
+            Update,
+            (read_consumable_presses, use_consumables, tick_shields)
+                .chain()
+                .run_if(is_simulation_authority.and_then(in_state(GameState::FightingInArena))),
+        );
+        app.add_systems(
+            Update,
+            spend_consumables_online
+                .run_if(is_client.and_then(in_state(GameState::FightingInArena))),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clear_shields);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ConsumableEffect {
+    Heal,
+    SpeedBoost,
+    Bomb,
+    Shield,
+}
+
+impl ConsumableEffect {
+    pub const ALL: [ConsumableEffect; 4] = [
+        ConsumableEffect::Heal,
+        ConsumableEffect::SpeedBoost,
+        ConsumableEffect::Bomb,
+        ConsumableEffect::Shield,
+    ];
+
+    pub fn of(item_type_id: ItemTypeId) -> Option<Self> {
+        match item_type_id {
+            ItemTypeId::HealPotion => Some(ConsumableEffect::Heal),
+            ItemTypeId::SpeedPotion => Some(ConsumableEffect::SpeedBoost),
+            ItemTypeId::Bomb => Some(ConsumableEffect::Bomb),
+            ItemTypeId::Shield => Some(ConsumableEffect::Shield),
+            _ => None,
+        }
+    }
+
+    // player one's keys, Z to V along the bottom row
+    pub fn key(&self) -> KeyCode {
+        match self {
+            ConsumableEffect::Heal => KeyCode::Z,
+            ConsumableEffect::SpeedBoost => KeyCode::X,
+            ConsumableEffect::Bomb => KeyCode::C,
+            ConsumableEffect::Shield => KeyCode::V,
+        }
+    }
+
+    pub fn gamepad_button(&self) -> GamepadButtonType {
+        match self {
+            ConsumableEffect::Heal => GamepadButtonType::DPadUp,
+            ConsumableEffect::SpeedBoost => GamepadButtonType::DPadRight,
+            ConsumableEffect::Bomb => GamepadButtonType::DPadDown,
+            ConsumableEffect::Shield => GamepadButtonType::DPadLeft,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            ConsumableEffect::Heal => "Heal",
+            ConsumableEffect::SpeedBoost => "Speed",
+            ConsumableEffect::Bomb => "Bomb",
+            ConsumableEffect::Shield => "Shield",
+        }
+    }
+}
+
+// A hermit reaching for a consumable. The ones playing here press their keys and pads, the
+// online ones send how many they used with their input.
+#[derive(Event, Copy, Clone)]
+pub struct ConsumablePressed {
+    pub player_index: usize,
+    pub effect: ConsumableEffect,
+}
+
+// client side: how many of each our hermit used, in the order of ConsumableEffect::ALL. The
+// counts wrap and go out with every input, so a lost one doesn't lose the use.
+#[derive(Resource, Default)]
+pub struct ConsumablesUsed(pub [u8; 4]);
+
+// hits don't land until the timer runs out
+#[derive(Component)]
+pub struct Shield(pub Timer);
+
+// how many of each consumable a hermit has packed
+pub fn charges(inventory: &Inventory, player_index: usize, effect: ConsumableEffect) -> usize {
+    inventory
+        .items_of(player_index)
+        .iter()
+        .filter(|item| {
+            item.item_type == ItemType::CONSUMABLE
+                && ConsumableEffect::of(item.item_type_id) == Some(effect)
+        })
+        .count()
+}
+
+// the item leaves the shell, so its voxels are free the next time the hermit packs
+fn take_consumable(
+    inventory: &mut Inventory,
+    player_index: usize,
+    effect: ConsumableEffect,
+) -> bool {
+    let items = inventory.items_of_mut(player_index);
+    let Some(index) = items.iter().position(|item| {
+        item.item_type == ItemType::CONSUMABLE
+            && ConsumableEffect::of(item.item_type_id) == Some(effect)
+    }) else {
+        return false;
+    };
+    items.remove(index);
+    true
+}
+
+fn is_pressed(
+    effect: ConsumableEffect,
+    player_index: usize,
+    player_count: usize,
+    keyboard: &Input<KeyCode>,
+    gamepads: &Gamepads,
+    gamepad_buttons: &Input<GamepadButton>,
+) -> bool {
+    let key_pressed = uses_keyboard_and_mouse(player_index) && keyboard.just_pressed(effect.key());
+    key_pressed
+        || player_gamepads(player_index, player_count, gamepads)
+            .iter()
+            .any(|gamepad| {
+                gamepad_buttons.just_pressed(GamepadButton {
+                    gamepad: *gamepad,
+                    button_type: effect.gamepad_button(),
+                })
+            })
+}
+
+fn read_consumable_presses(
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    players: Query<&PlayerControllerState, Without<RemotePlayer>>,
+    mut pressed_writer: EventWriter<ConsumablePressed>,
+) {
+    let player_count = players.iter().count();
+
+    for controller in &players {
+        for effect in ConsumableEffect::ALL {
+            if is_pressed(
+                effect,
+                controller.player_index,
+                player_count,
+                &keyboard,
+                &gamepads,
+                &gamepad_buttons,
+            ) {
+                pressed_writer.send(ConsumablePressed {
+                    player_index: controller.player_index,
+                    effect,
+                });
+            }
+        }
+    }
+}
+
+// The host's hermit is the one that counts, ours only takes the item out of the shell so it's
+// gone from what we sync back.
+fn spend_consumables_online(
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut inventory: ResMut<Inventory>,
+    mut used: ResMut<ConsumablesUsed>,
+    players: Query<(&PlayerControllerState, &PlayerCombatState), Without<NetworkProxy>>,
+) {
+    let Some((controller, combat_state)) = players
+        .iter()
+        .find(|(controller, _)| controller.player_index == 0)
+    else {
+        return;
+    };
+    if combat_state.is_downed() {
+        return;
+    }
+
+    for (index, effect) in ConsumableEffect::ALL.into_iter().enumerate() {
+        let refused = effect == ConsumableEffect::Heal && combat_state.no_healing;
+        if is_pressed(effect, 0, 1, &keyboard, &gamepads, &gamepad_buttons)
+            && !refused
+            && take_consumable(&mut inventory, controller.player_index, effect)
+        {
+            used.0[index] = used.0[index].wrapping_add(1);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn use_consumables(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut pressed_reader: EventReader<ConsumablePressed>,
+    mut inventory: ResMut<Inventory>,
+    mut players: Query<(
+        Entity,
+        &Transform,
+        &PlayerControllerState,
+        &mut PlayerCombatState,
+    )>,
+    enemies: Query<(Entity, &Transform, &Enemy)>,
+    mut status_effect_writer: EventWriter<ApplyStatusEffect>,
+    mut hit_writer: EventWriter<EnemyHit>,
+) {
+    // two bombs going off together only take a creature down once
+    let mut bombed = HashSet::new();
+
+    for pressed in pressed_reader.iter() {
+        let effect = pressed.effect;
+        let Some((entity, transform, controller, mut combat_state)) = players
+            .iter_mut()
+            .find(|(_, _, controller, _)| controller.player_index == pressed.player_index)
+        else {
+            continue;
+        };
+        if combat_state.is_downed() {
+            continue;
+        }
+
+        // a potion is kept for later when it can't heal
+        let refused = effect == ConsumableEffect::Heal && combat_state.no_healing;
+        if refused || !take_consumable(&mut inventory, controller.player_index, effect) {
+            continue;
+        }
+
+        log::info!("Player {} used {:?}", controller.player_index + 1, effect);
+        match effect {
+            ConsumableEffect::Heal => {
+                combat_state.current_hp =
+                    (combat_state.current_hp + HEAL_AMOUNT).min(combat_state.max_hp);
+            }
+            ConsumableEffect::SpeedBoost => {
+                status_effect_writer.send(ApplyStatusEffect {
+                    target: entity,
+                    kind: StatusEffectKind::Haste,
+                    seconds: SPEED_BOOST_DURATION,
+                });
+            }
+            ConsumableEffect::Bomb => {
+                for (enemy_entity, enemy_transform, enemy) in &enemies {
+                    if enemy_transform.translation.distance(transform.translation) < BOMB_RADIUS
+                        && bombed.insert(enemy_entity)
+                    {
+                        hit_writer.send(EnemyHit {
+                            position: enemy_transform.translation,
+                            damage: enemy.hp,
+                            critical: false,
+                            enemy_type: enemy.enemy_type,
+                            killed: true,
+                            source: HitSource::Bomb,
+                        });
+                        commands.entity(enemy_entity).despawn();
+                    }
+                }
+            }
+            ConsumableEffect::Shield => {
+                commands.entity(entity).insert(Shield(Timer::from_seconds(
+                    SHIELD_DURATION,
+                    TimerMode::Once,
+                )));
+            }
+        }
+
+        commands.spawn(AudioBundle {
+            source: asset_server.load(if effect == ConsumableEffect::Bomb {
+                "shoot.ogg"
+            } else {
+                "swing.ogg"
+            }),
+            settings: PlaybackSettings {
+                mode: Despawn,
+                volume: Relative(VolumeLevel::new(0.5f32)),
+                ..default()
+            },
+            ..default()
+        });
+    }
+}
+
+fn tick_shields(
+    mut commands: Commands,
+    mut shields: Query<(Entity, &mut Shield)>,
+    time: Res<Time>,
+) {
+    for (entity, mut shield) in &mut shields {
+        if shield.0.tick(time.delta()).finished() {
+            commands.entity(entity).remove::<Shield>();
+        }
+    }
+}
+
+// shields don't carry over into the packing screen or the next run
+fn clear_shields(mut commands: Commands, players: Query<Entity, With<PlayerControllerState>>) {
+    for entity in &players {
+        commands.entity(entity).remove::<Shield>();
+    }
+}
@@ -1,4 +1,5 @@
 pub(crate) mod combat;
+pub(crate) mod consumables;
 
 use crate::collectable::Collectable;
 use bevy::audio::PlaybackMode::Despawn;
@@ -14,20 +15,29 @@ use bevy::{log, prelude::*};
 use bevy_rapier3d::prelude::*;
 use queues::queue;
 use queues::{IsQueue, Queue};
+use std::time::Duration;
 
 use crate::config::{
     COLLISION_GROUP_ENEMIES, COLLISION_GROUP_PLAYER, COLLISION_GROUP_PROJECTILES,
-    COLLISION_GROUP_TERRAIN, COLLISION_GROUP_WALLS,
+    COLLISION_GROUP_TERRAIN, COLLISION_GROUP_WALLS, MAX_LOCAL_PLAYERS,
 };
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::difficulty::DifficultyModifiers;
 use crate::enemy::{Enemy, EnemyType, ENEMY_COLLIDER_RADIUS};
 use crate::game::HolyCam;
 use crate::game_camera_controller::GameCameraControllerPlugin;
 use crate::game_state::GameState;
 use crate::inventory::{Inventory, InventoryItem, ItemType};
-use crate::item_spawner::{create_heart, create_sword};
+use crate::item_spawner::StartingItems;
+use crate::level_loader::LevelMetadata;
+use crate::network::{is_simulation_authority, RemotePlayer};
+use crate::particles::{ParticleEmitter, PROJECTILE_TRAIL, SAND_PUFF};
 use crate::player::combat::PlayerCombatState;
 use crate::player::combat::{PlayerCombatPlugin, PLAYER_INVICIBILITY_COOLDOWN};
+use crate::player::consumables::{PlayerConsumablesPlugin, Shield};
 use crate::projectile::{Projectile, ProjectileBundle};
+use crate::shop::is_shopping;
+use crate::status_effect::{ApplyStatusEffect, StatusEffects};
 use crate::wave_manager::{Wave, WaveState};
 use crate::world_item::WeaponHolder;
 
@@ -48,55 +58,85 @@ pub enum PlayerState {
 
 #[derive(Component)]
 pub struct PlayerControllerState {
+    pub player_index: usize,
+
     is_forward_pressed: bool,
     is_backward_pressed: bool,
     is_left_pressed: bool,
     is_right_pressed: bool,
 
-    is_shoot_pressed: bool,
+    pub is_shoot_pressed: bool,
+
+    // planar velocity and facing the hermit asks for this frame
+    pub movement: Vec3,
+    pub aim: Option<Vec3>,
 
     pub velocity: Vec3,
 }
 
 #[derive(Event)]
-pub struct PlayerHitEvent(Enemy);
+pub struct PlayerHitEvent(Entity, Enemy);
+
+// how many hermits join the next run, picked on the title screen
+#[derive(Resource)]
+pub struct LocalPlayerCount(pub usize);
+
+// hermits shrug off every hit, toggled with the `god` console command
+#[derive(Resource)]
+pub struct GodMode(pub bool);
 
 #[derive(Resource)]
 struct DeathTimer(Timer);
 
 #[derive(Resource)]
 struct PlayerShootingState {
-    rate_limiter: Option<Timer>,
     mesh_material_handle: Option<(Handle<Mesh>, Handle<StandardMaterial>)>,
 }
 
 impl Plugin for PlayerPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Startup, setup);
-        app.add_systems(OnEnter(GameState::FightingInArena), set_player_active);
+        app.add_systems(
+            OnEnter(GameState::FightingInArena),
+            (set_player_active, sync_player_count),
+        );
+        app.add_systems(OnEnter(WaveState::WAVE_END), revive_downed_players);
+        app.add_systems(
+            Update,
+            place_players_at_level_start.run_if(resource_changed::<LevelMetadata>()),
+        );
         app.add_systems(Update, process_inputs);
         app.add_systems(
             Update,
-            player_movement.run_if(
+            (gather_movement_input, player_movement).chain().run_if(
                 in_state(GameState::FightingInArena).and_then(in_state(PlayerState::Fighting)),
             ),
         );
         app.add_systems(
             Update,
-            player_shooting.run_if(
-                in_state(GameState::FightingInArena).and_then(in_state(PlayerState::Fighting)),
-            ),
+            player_shooting
+                .run_if(
+                    is_simulation_authority
+                        .and_then(in_state(GameState::FightingInArena))
+                        .and_then(in_state(PlayerState::Fighting)),
+                )
+                // clicks on the merchant's buttons aren't attacks
+                .run_if(not(is_shopping)),
         );
         app.add_systems(
             Update,
             detect_player_hit.run_if(
-                in_state(GameState::FightingInArena).and_then(in_state(PlayerState::Fighting)),
+                is_simulation_authority
+                    .and_then(in_state(GameState::FightingInArena))
+                    .and_then(in_state(PlayerState::Fighting)),
             ),
         );
         app.add_systems(
             Update,
             handle_player_hit.run_if(
-                in_state(GameState::FightingInArena).and_then(in_state(PlayerState::Fighting)),
+                is_simulation_authority
+                    .and_then(in_state(GameState::FightingInArena))
+                    .and_then(in_state(PlayerState::Fighting)),
             ),
         );
         app.add_systems(
@@ -108,21 +148,83 @@ impl Plugin for PlayerPlugin {
         app.add_state::<PlayerState>();
         app.add_event::<PlayerHitEvent>();
         app.insert_resource(DeathTimer(Timer::from_seconds(2.0, TimerMode::Once)));
-        app.add_plugins((GameCameraControllerPlugin, PlayerCombatPlugin));
+        app.add_plugins((
+            GameCameraControllerPlugin,
+            PlayerCombatPlugin,
+            PlayerConsumablesPlugin,
+        ));
+        app.insert_resource(LocalPlayerCount(1));
+        app.insert_resource(GodMode(false));
+        app.add_console_command(
+            ConsoleCommand::new("god", god_command).help("toggle invincibility for every hermit"),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("hp", hp_command)
+                .usage("<hp>")
+                .help("set the health of every hermit, raising their max if needed"),
+        );
         app.insert_resource(PlayerShootingState {
-            rate_limiter: None,
             mesh_material_handle: None,
         });
     }
 }
 
+fn god_command(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let mut god_mode = world.resource_mut::<GodMode>();
+    god_mode.0 = !god_mode.0;
+    let status = if god_mode.0 { "on" } else { "off" };
+    Ok(format!("god mode {status}"))
+}
+
+fn hp_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let hp: i32 = parse_arg(args, 0, "hp")?;
+    if hp < 1 {
+        return Err("hp must be at least 1".to_string());
+    }
+
+    let mut players = world.query::<&mut PlayerCombatState>();
+    for mut state in players.iter_mut(world) {
+        state.current_hp = hp;
+        state.max_hp = state.max_hp.max(hp);
+    }
+    Ok(format!("hp set to {hp}"))
+}
+
 fn setup(
     mut commands: Commands,
     asset_server: ResMut<AssetServer>,
     mut shooting_state: ResMut<PlayerShootingState>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    level: Res<LevelMetadata>,
 ) {
+    spawn_player(&mut commands, &asset_server, 0, start_position(&level, 0));
+
+    let bubble: Mesh = Mesh::try_from(shape::Icosphere {
+        radius: PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
+        subdivisions: 3,
+    })
+    .unwrap();
+    shooting_state.mesh_material_handle = Some((
+        meshes.add(bubble),
+        materials.add(Color::rgb(0.05, 0.4, 0.9).into()),
+    ));
+}
+
+// the level's `player_start` markers, or side by side in the middle of the arena
+pub fn start_position(level: &LevelMetadata, player_index: usize) -> Vec3 {
+    level
+        .player_start(player_index)
+        .map(|position| position + Vec3::Y)
+        .unwrap_or(vec3(2.0 - 4.0 * player_index as f32, 1.0, 0.0))
+}
+
+pub fn spawn_player(
+    commands: &mut Commands,
+    asset_server: &AssetServer,
+    player_index: usize,
+    position: Vec3,
+) -> Entity {
     commands
         .spawn(Collider::capsule_y(0.3, 0.5))
         .insert(SceneBundle {
@@ -148,31 +250,123 @@ fn setup(
             }),
             ..default()
         })
-        .insert(PlayerControllerState::new())
+        .insert(PlayerControllerState::new(player_index))
         .insert(PlayerCombatState::new())
+        .insert(StatusEffects::default())
+        // sand puffs at the bottom of the capsule while walking
+        .insert(
+            ParticleEmitter::new(&SAND_PUFF, 14.0)
+                .with_area(Vec3::new(0.2, 0.0, 0.2))
+                .with_offset(Vec3::new(0.0, -0.75, 0.0))
+                .while_moving(1.0),
+        )
         .insert(WeaponHolder {
             current_weapon: None,
         })
-        .insert(TransformBundle::from(Transform::from_xyz(2.0, 1.0, 0.0)));
+        .insert(TransformBundle::from(Transform::from_translation(position)))
+        .id()
+}
 
-    let bubble: Mesh = Mesh::try_from(shape::Icosphere {
-        radius: PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
-        subdivisions: 3,
-    })
-    .unwrap();
-    shooting_state.mesh_material_handle = Some((
-        meshes.add(bubble),
-        materials.add(Color::rgb(0.05, 0.4, 0.9).into()),
-    ));
+// a freshly loaded level puts everyone on its start markers
+fn place_players_at_level_start(
+    level: Res<LevelMetadata>,
+    mut players: Query<(&mut Transform, &PlayerControllerState)>,
+) {
+    if level.player_starts.is_empty() {
+        return;
+    }
+
+    for (mut transform, controller) in &mut players {
+        transform.translation = start_position(&level, controller.player_index);
+    }
 }
 
 fn set_player_active(mut next_state: ResMut<NextState<PlayerState>>) {
     next_state.set(PlayerState::Fighting);
 }
 
+// spawns or removes hermits so the arena matches the player count chosen on the title screen
+fn sync_player_count(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    player_count: Res<LocalPlayerCount>,
+    players: Query<(Entity, &PlayerControllerState), Without<RemotePlayer>>,
+    level: Res<LevelMetadata>,
+) {
+    let wanted = player_count.0.clamp(1, MAX_LOCAL_PLAYERS);
+    let mut present = [false; MAX_LOCAL_PLAYERS];
+
+    for (entity, controller) in &players {
+        if controller.player_index >= wanted {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            present[controller.player_index] = true;
+        }
+    }
+
+    for player_index in 0..wanted {
+        if !present[player_index] {
+            log::info!("Player {} joined", player_index + 1);
+            let position = start_position(&level, player_index);
+            spawn_player(&mut commands, &asset_server, player_index, position);
+        }
+    }
+}
+
+// downed teammates get back up once the wave is cleared
+fn revive_downed_players(mut players: Query<&mut PlayerCombatState>, time: Res<Time>) {
+    for mut state in &mut players {
+        if state.is_downed() {
+            state.current_hp = 1;
+            state.last_heal = time.elapsed_seconds();
+        }
+    }
+}
+
+// player one keeps the keyboard, mouse and touch screen
+pub fn uses_keyboard_and_mouse(player_index: usize) -> bool {
+    player_index == 0
+}
+
+// Solo play listens to every connected gamepad. In co-op each hermit gets its own pad, and when
+// there are fewer pads than players, player one stays on the keyboard and the rest shift down.
+pub fn player_gamepads(player_index: usize, player_count: usize, gamepads: &Gamepads) -> Vec<Gamepad> {
+    let mut pads: Vec<Gamepad> = gamepads.iter().collect();
+    pads.sort_by_key(|gamepad| gamepad.id);
+
+    if player_count <= 1 {
+        return pads;
+    }
+
+    let offset = if pads.len() >= player_count { 0 } else { 1 };
+    if player_index < offset {
+        return Vec::new();
+    }
+
+    pads.get(player_index - offset).copied().into_iter().collect()
+}
+
+pub fn is_attack_button_pressed(buttons: &Input<GamepadButton>, gamepad: Gamepad) -> bool {
+    buttons.pressed(GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::South,
+    }) || buttons.pressed(GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::RightTrigger,
+    }) || buttons.pressed(GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::RightTrigger2,
+    }) || buttons.pressed(GamepadButton {
+        gamepad,
+        button_type: GamepadButtonType::RightThumb,
+    })
+}
+
 impl PlayerControllerState {
-    fn new() -> Self {
+    fn new(player_index: usize) -> Self {
         Self {
+            player_index,
+
             is_forward_pressed: false,
             is_backward_pressed: false,
             is_left_pressed: false,
@@ -180,6 +374,9 @@ impl PlayerControllerState {
 
             is_shoot_pressed: false,
 
+            movement: Vec3::ZERO,
+            aim: None,
+
             velocity: vec3(0.0, 0.0, 0.0),
         }
     }
@@ -191,163 +388,194 @@ fn process_inputs(
     mut mouse_input_events: EventReader<MouseButtonInput>,
     touches: Res<Touches>,
     mouse: Res<Input<MouseButton>>,
-    mut state: Query<&mut PlayerControllerState>,
+    mut players: Query<&mut PlayerControllerState, Without<RemotePlayer>>,
     buttons: Res<Input<GamepadButton>>,
 ) {
-    let mut state = state.single_mut();
-    for event in keyboard_input_events.iter() {
-        match event.key_code {
-            Some(KeyCode::W) => {
-                state.is_forward_pressed = event.state.is_pressed();
-            }
-            Some(KeyCode::S) => {
-                state.is_backward_pressed = event.state.is_pressed();
-            }
-            Some(KeyCode::A) => {
-                state.is_left_pressed = event.state.is_pressed();
-            }
-            Some(KeyCode::D) => {
-                state.is_right_pressed = event.state.is_pressed();
+    let keyboard_events: Vec<&KeyboardInput> = keyboard_input_events.iter().collect();
+    let player_count = players.iter().count();
+
+    for mut state in &mut players {
+        if uses_keyboard_and_mouse(state.player_index) {
+            for event in &keyboard_events {
+                match event.key_code {
+                    Some(KeyCode::W) => {
+                        state.is_forward_pressed = event.state.is_pressed();
+                    }
+                    Some(KeyCode::S) => {
+                        state.is_backward_pressed = event.state.is_pressed();
+                    }
+                    Some(KeyCode::A) => {
+                        state.is_left_pressed = event.state.is_pressed();
+                    }
+                    Some(KeyCode::D) => {
+                        state.is_right_pressed = event.state.is_pressed();
+                    }
+                    _ => {}
+                }
             }
-            _ => {}
+
+            state.is_shoot_pressed =
+                mouse.pressed(MouseButton::Left) || touches.first_pressed_position() != None;
+        } else {
+            state.is_shoot_pressed = false;
         }
-    }
 
-    state.is_shoot_pressed =
-        mouse.pressed(MouseButton::Left) || touches.first_pressed_position() != None;
-
-    for gamepad in gamepads.iter() {
-        if buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::South,
-        }) || buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightTrigger,
-        }) || buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightTrigger2,
-        }) || buttons.pressed(GamepadButton {
-            gamepad,
-            button_type: GamepadButtonType::RightThumb,
-        }) {
-            state.is_shoot_pressed |= true;
+        for gamepad in player_gamepads(state.player_index, player_count, &gamepads) {
+            if is_attack_button_pressed(&buttons, gamepad) {
+                state.is_shoot_pressed |= true;
+            }
         }
     }
 }
 
-fn player_movement(
+// turns keyboard, gamepad, mouse and touch input into the movement and aim of local hermits,
+// remote hermits get theirs from the network instead
+fn gather_movement_input(
     gamepads: Res<Gamepads>,
-    mut controllers: Query<&mut KinematicCharacterController, With<PlayerControllerState>>,
-    time: Res<Time>,
-    mut state: Query<&mut PlayerControllerState>,
+    mut players: Query<(&Transform, &mut PlayerControllerState), Without<RemotePlayer>>,
     windows: Query<&Window, With<PrimaryWindow>>,
     touches: Res<Touches>,
     camera_q: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
     axes: Res<Axis<GamepadAxis>>,
-    mut transform: Query<&mut Transform, With<PlayerControllerState>>,
 ) {
-    let mut state = state.single_mut();
-    let mut transform = transform.single_mut();
-
-    let mut current_frame_movement = Vec3::ZERO;
-    current_frame_movement.y -= 9.81 * time.delta_seconds();
-    // current_frame_movement += to_player_unit_vector * speed * time.delta_seconds();
-
-    // state.velocity.y -= 9.81 * time.delta_seconds();
-    // state.velocity.x /= 1.5;
-    // state.velocity.z /= 1.5;
-    if state.is_forward_pressed {
-        current_frame_movement.z -= 6.0;
-        // state.velocity.z = -6.0;
-    }
+    let player_count = players.iter().count();
+    let (camera, camera_transform) = camera_q.single();
 
-    if state.is_backward_pressed {
-        current_frame_movement.z += 6.0;
-        // state.velocity.z = 6.0;
-    }
+    'players: for (transform, mut state) in &mut players {
+        let mut current_frame_movement = Vec3::ZERO;
+        state.aim = None;
 
-    if state.is_left_pressed {
-        current_frame_movement.x -= 6.0;
-        // state.velocity.x = -6.0;
-    }
+        // state.velocity.y -= 9.81 * time.delta_seconds();
+        // state.velocity.x /= 1.5;
+        // state.velocity.z /= 1.5;
+        if state.is_forward_pressed {
+            current_frame_movement.z -= 6.0;
+            // state.velocity.z = -6.0;
+        }
 
-    if state.is_right_pressed {
-        current_frame_movement.x += 6.0;
-        // state.velocity.x = 6.0;
-    }
+        if state.is_backward_pressed {
+            current_frame_movement.z += 6.0;
+            // state.velocity.z = 6.0;
+        }
 
-    for gamepad in gamepads.iter() {
-        let axis_lx = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::LeftStickX,
-        };
-        let axis_ly = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::LeftStickY,
-        };
-        if let (Some(x), Some(y)) = (axes.get(axis_lx), axes.get(axis_ly)) {
-            // combine X and Y into one vector
-            let left_stick_pos = Vec2::new(x, y);
-
-            // Example: check if the stick is pushed up
-            if left_stick_pos.length() > 0.5 {
-                current_frame_movement.x = left_stick_pos.x * 6.0;
-                current_frame_movement.z = -left_stick_pos.y * 6.0;
-            }
+        if state.is_left_pressed {
+            current_frame_movement.x -= 6.0;
+            // state.velocity.x = -6.0;
         }
-    }
 
-    if touches.first_pressed_position() != None {
-        let mut vec = transform.forward() * 6.0;
-        vec.y = current_frame_movement.y;
-        current_frame_movement = vec;
-    }
+        if state.is_right_pressed {
+            current_frame_movement.x += 6.0;
+            // state.velocity.x = 6.0;
+        }
 
-    controllers.single_mut().translation = Some(current_frame_movement * time.delta_seconds());
+        let player_pads = player_gamepads(state.player_index, player_count, &gamepads);
+
+        for gamepad in player_pads.iter().copied() {
+            let axis_lx = GamepadAxis {
+                gamepad,
+                axis_type: GamepadAxisType::LeftStickX,
+            };
+            let axis_ly = GamepadAxis {
+                gamepad,
+                axis_type: GamepadAxisType::LeftStickY,
+            };
+            if let (Some(x), Some(y)) = (axes.get(axis_lx), axes.get(axis_ly)) {
+                // combine X and Y into one vector
+                let left_stick_pos = Vec2::new(x, y);
+
+                // Example: check if the stick is pushed up
+                if left_stick_pos.length() > 0.5 {
+                    current_frame_movement.x = left_stick_pos.x * 6.0;
+                    current_frame_movement.z = -left_stick_pos.y * 6.0;
+                }
+            }
+        }
 
-    let (camera, camera_transform) = camera_q.single();
+        let is_keyboard_player = uses_keyboard_and_mouse(state.player_index);
 
-    for gamepad in gamepads.iter() {
-        let axis_rx = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::RightStickX,
-        };
-        let axis_ry = GamepadAxis {
-            gamepad,
-            axis_type: GamepadAxisType::RightStickY,
-        };
+        if is_keyboard_player && touches.first_pressed_position() != None {
+            current_frame_movement = transform.forward() * 6.0;
+            current_frame_movement.y = 0.0;
+        }
+
+        state.movement = current_frame_movement;
+
+        for gamepad in player_pads.iter().copied() {
+            let axis_rx = GamepadAxis {
+                gamepad,
+                axis_type: GamepadAxisType::RightStickX,
+            };
+            let axis_ry = GamepadAxis {
+                gamepad,
+                axis_type: GamepadAxisType::RightStickY,
+            };
+
+            if let (Some(x), Some(y)) = (axes.get(axis_rx), axes.get(axis_ry)) {
+                // combine X and Y into one vector
+                let right_stick_pos = Vec2::new(x, y);
+
+                // Example: check if the stick is pushed up
+                if right_stick_pos.length() > 0.1 {
+                    state.aim = Some(vec3(right_stick_pos.x, 0.0, -right_stick_pos.y));
+                    continue 'players;
+                }
+            }
+        }
 
-        if let (Some(x), Some(y)) = (axes.get(axis_rx), axes.get(axis_ry)) {
-            // combine X and Y into one vector
-            let right_stick_pos = Vec2::new(x, y);
+        if !is_keyboard_player {
+            continue;
+        }
 
-            // Example: check if the stick is pushed up
-            if right_stick_pos.length() > 0.1 {
-                transform.look_to(vec3(right_stick_pos.x, 0.0, -right_stick_pos.y), Vec3::Y);
-                return;
+        if let Some(position) = windows
+            .single()
+            .cursor_position()
+            .or(touches.first_pressed_position())
+        {
+            let ray: Ray = camera
+                .viewport_to_world(camera_transform, position)
+                .unwrap();
+            if let Some(distance) =
+                ray.intersect_plane(vec3(0.0, transform.translation.y, 0.0), vec3(0.0, 1.0, 0.0))
+            {
+                let pos = ray.get_point(distance);
+                state.aim = Some(pos - transform.translation);
             }
         }
     }
+}
 
-    if let Some(position) = windows.single().cursor_position() {
-        let ray: Ray = camera
-            .viewport_to_world(camera_transform, position)
-            .unwrap();
-        if let Some(distance) =
-            ray.intersect_plane(vec3(0.0, transform.translation.y, 0.0), vec3(0.0, 1.0, 0.0))
-        {
-            let pos = ray.get_point(distance);
-            transform.look_at(pos, Vec3::Y);
+// moves every hermit from its controller state, whether that came from local devices,
+// a remote client or client-side prediction
+pub fn player_movement(
+    mut players: Query<(
+        &mut KinematicCharacterController,
+        &mut Transform,
+        &PlayerControllerState,
+        &PlayerCombatState,
+        Option<&StatusEffects>,
+    )>,
+    time: Res<Time>,
+) {
+    for (mut controller, mut transform, state, combat_state, effects) in &mut players {
+        let mut current_frame_movement = Vec3::ZERO;
+        current_frame_movement.y -= 9.81 * time.delta_seconds();
+        // current_frame_movement += to_player_unit_vector * speed * time.delta_seconds();
+
+        if combat_state.is_downed() || effects.is_some_and(StatusEffects::is_stunned) {
+            controller.translation = Some(current_frame_movement * time.delta_seconds());
+            continue;
         }
-    } else if let Some(position) = touches.first_pressed_position() {
-        let ray: Ray = camera
-            .viewport_to_world(camera_transform, position)
-            .unwrap();
-        if let Some(distance) =
-            ray.intersect_plane(vec3(0.0, transform.translation.y, 0.0), vec3(0.0, 1.0, 0.0))
-        {
-            let pos = ray.get_point(distance);
-            transform.look_at(pos, Vec3::Y);
+
+        let speed = effects.map_or(1.0, StatusEffects::speed_multiplier);
+        current_frame_movement.x = state.movement.x * speed;
+        current_frame_movement.z = state.movement.z * speed;
+
+        controller.translation = Some(current_frame_movement * time.delta_seconds());
+
+        if let Some(aim) = state.aim {
+            if aim.x != 0.0 || aim.z != 0.0 {
+                transform.look_to(vec3(aim.x, 0.0, aim.z), Vec3::Y);
+            }
         }
     }
 }
@@ -355,40 +583,61 @@ fn player_movement(
 fn player_shooting(
     mut commands: Commands,
     mut asset_server: ResMut<AssetServer>,
-    mut shooting_state: ResMut<PlayerShootingState>,
-    player_transform_query: Query<(
+    shooting_state: Res<PlayerShootingState>,
+    mut players: Query<(
         &Transform,
         &PlayerControllerState,
-        &PlayerCombatState,
+        &mut PlayerCombatState,
         &WeaponHolder,
+        Option<&StatusEffects>,
     )>,
     time: Res<Time>,
 ) {
-    let (player_transform, player_controller_state, player_combat_state, weapon_holder_state) =
-        player_transform_query.single();
+    for (
+        player_transform,
+        player_controller_state,
+        mut player_combat_state,
+        weapon_holder_state,
+        effects,
+    ) in &mut players
+    {
+        // letting go starts the next burst at the current rate
+        if !player_controller_state.is_shoot_pressed {
+            player_combat_state.shot_rate_limiter = None;
+            continue;
+        }
+        if player_combat_state.is_downed() || effects.is_some_and(StatusEffects::is_stunned) {
+            continue;
+        }
 
-    if !player_controller_state.is_shoot_pressed {
-        return;
-    }
+        if weapon_holder_state.current_weapon.is_none() {
+            continue;
+        }
 
-    if weapon_holder_state.current_weapon.is_none() {
-        return;
-    }
+        let (_, current_weapon) = weapon_holder_state.current_weapon.as_ref().unwrap();
 
-    let (_, current_weapon) = weapon_holder_state.current_weapon.as_ref().unwrap();
+        if current_weapon.item_type != ItemType::RANGED_WEAPON {
+            continue;
+        }
 
-    if current_weapon.item_type != ItemType::RANGED_WEAPON {
-        return;
-    }
+        let point_in_front_of_player =
+            player_transform.translation + player_transform.forward().normalize() * 1.0;
 
-    let point_in_front_of_player =
-        player_transform.translation + player_transform.forward().normalize() * 1.0;
+        // another weapon or attack speed heart changes the rate, which starts it over
+        let shot_period = BASE_ATTACK_COOLDOWN
+            / (player_combat_state.attack_speed * current_weapon.weapon_attack_speed);
+        if player_combat_state
+            .shot_rate_limiter
+            .as_ref()
+            .is_some_and(|timer| timer.duration() != Duration::from_secs_f32(shot_period))
+        {
+            player_combat_state.shot_rate_limiter = None;
+        }
 
-    //  Timer::from_seconds(PLAYER_SHOOTING_RATE_PERIOD, TimerMode::Repeating)
-    if player_controller_state.is_shoot_pressed && weapon_holder_state.current_weapon.is_some() {
-        if shooting_state.rate_limiter.is_none()
-            || shooting_state
-                .rate_limiter
+        //  Timer::from_seconds(PLAYER_SHOOTING_RATE_PERIOD, TimerMode::Repeating)
+        if player_combat_state.shot_rate_limiter.is_none()
+            || player_combat_state
+                .shot_rate_limiter
                 .as_mut()
                 .unwrap()
                 .tick(time.delta())
@@ -403,53 +652,51 @@ fn player_shooting(
                 },
                 ..default()
             });
-            commands.spawn(ProjectileBundle {
-                pbr: PbrBundle {
-                    mesh: shooting_state
-                        .mesh_material_handle
-                        .as_ref()
-                        .unwrap()
-                        .0
-                        .clone(),
-                    material: shooting_state
-                        .mesh_material_handle
-                        .as_ref()
-                        .unwrap()
-                        .1
-                        .clone(),
-                    transform: Transform::from_translation(Vec3::new(
-                        point_in_front_of_player.x,
-                        ENEMY_COLLIDER_RADIUS,
-                        point_in_front_of_player.z,
-                    )),
-                    ..default()
-                },
-                projectile: Projectile {
-                    speed: current_weapon.projectile_speed,
-                    direction: player_transform.forward(),
-                    source_weapon: current_weapon.clone(),
-                },
-                collider: Collider::cuboid(
-                    PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
-                    PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
-                    PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
-                ),
-                collision_groups: CollisionGroups {
-                    memberships: COLLISION_GROUP_PROJECTILES,
-                    filters: COLLISION_GROUP_ENEMIES,
-                },
-            });
+            commands
+                .spawn(ProjectileBundle {
+                    pbr: PbrBundle {
+                        mesh: shooting_state
+                            .mesh_material_handle
+                            .as_ref()
+                            .unwrap()
+                            .0
+                            .clone(),
+                        material: shooting_state
+                            .mesh_material_handle
+                            .as_ref()
+                            .unwrap()
+                            .1
+                            .clone(),
+                        transform: Transform::from_translation(Vec3::new(
+                            point_in_front_of_player.x,
+                            ENEMY_COLLIDER_RADIUS,
+                            point_in_front_of_player.z,
+                        )),
+                        ..default()
+                    },
+                    projectile: Projectile {
+                        speed: current_weapon.projectile_speed,
+                        direction: player_transform.forward(),
+                        source_weapon: current_weapon.clone(),
+                        damage: player_combat_state.hit_damage(current_weapon),
+                    },
+                    collider: Collider::cuboid(
+                        PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
+                        PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
+                        PLAYER_SHOOTING_PROJECTILE_CUBE_HALF_SIZE * 2.0,
+                    ),
+                    collision_groups: CollisionGroups {
+                        memberships: COLLISION_GROUP_PROJECTILES,
+                        filters: COLLISION_GROUP_ENEMIES,
+                    },
+                })
+                .insert(ParticleEmitter::new(&PROJECTILE_TRAIL, 40.0));
         }
 
-        if shooting_state.rate_limiter.is_none() {
-            shooting_state.rate_limiter = Some(Timer::from_seconds(
-                BASE_ATTACK_COOLDOWN
-                    / (player_combat_state.attack_speed * current_weapon.weapon_attack_speed),
-                TimerMode::Repeating,
-            ))
+        if player_combat_state.shot_rate_limiter.is_none() {
+            player_combat_state.shot_rate_limiter =
+                Some(Timer::from_seconds(shot_period, TimerMode::Repeating))
         }
-    } else {
-        shooting_state.rate_limiter = None;
     }
 }
 
@@ -468,29 +715,37 @@ fn vertical_vel_reset(
 }
 
 fn detect_player_hit(
-    player_controller_output_query: Query<(&Transform, &Collider), With<PlayerControllerState>>,
+    player_query: Query<
+        (Entity, &Transform, &Collider, &PlayerCombatState),
+        With<PlayerControllerState>,
+    >,
     enemy_entity_query: Query<(Entity, &Transform, &Collider, &Enemy), With<Enemy>>,
     mut player_hit_event_writer: EventWriter<PlayerHitEvent>,
 ) {
-    let (player_transform, player_collider) = player_controller_output_query.single();
-
-    for (enemy_entity, enemy_transform, enemy_collider, enemy) in &enemy_entity_query {
-        let mut hit_radius_multipler = 1.5;
-        match enemy.enemy_type {
-            EnemyType::Jellyfish => hit_radius_multipler = 1.5,
-            EnemyType::Urchin => hit_radius_multipler = 1.5,
-            EnemyType::Shrimp => hit_radius_multipler = 4.0,
+    for (player_entity, player_transform, player_collider, player_combat_state) in &player_query {
+        if player_combat_state.is_downed() {
+            continue;
         }
 
-        let total_radius = player_collider.as_capsule().unwrap().radius() * 1.2
-            + enemy_collider.as_ball().unwrap().radius() * hit_radius_multipler;
+        for (enemy_entity, enemy_transform, enemy_collider, enemy) in &enemy_entity_query {
+            let mut hit_radius_multipler = 1.5;
+            match enemy.enemy_type {
+                EnemyType::Jellyfish => hit_radius_multipler = 1.5,
+                EnemyType::Urchin => hit_radius_multipler = 1.5,
+                EnemyType::Shrimp => hit_radius_multipler = 4.0,
+            }
+
+            let total_radius = player_collider.as_capsule().unwrap().radius() * 1.2
+                + enemy_collider.as_ball().unwrap().radius() * hit_radius_multipler;
 
-        // dbg!(
-        //     (player_transform.translation - enemy_transform.translation).length(),
-        //     total_radius
-        // );
-        if (player_transform.translation - enemy_transform.translation).length() < total_radius {
-            player_hit_event_writer.send(PlayerHitEvent((*enemy).clone()));
+            // dbg!(
+            //     (player_transform.translation - enemy_transform.translation).length(),
+            //     total_radius
+            // );
+            if (player_transform.translation - enemy_transform.translation).length() < total_radius
+            {
+                player_hit_event_writer.send(PlayerHitEvent(player_entity, (*enemy).clone()));
+            }
         }
     }
 
@@ -501,37 +756,44 @@ fn detect_player_hit(
     // }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn handle_player_hit(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
-    mut player_state: Query<&mut PlayerCombatState>,
+    mut player_state: Query<(&mut PlayerCombatState, Has<Shield>)>,
     mut player_hit_event_reader: EventReader<PlayerHitEvent>,
+    mut status_effect_writer: EventWriter<ApplyStatusEffect>,
     mut next_player_state: ResMut<NextState<PlayerState>>,
     time: Res<Time>,
+    god_mode: Res<GodMode>,
+    modifiers: Res<DifficultyModifiers>,
 ) {
-    let mut state = player_state.single_mut();
+    for PlayerHitEvent(player_entity, enemy) in &mut player_hit_event_reader {
+        // log::info!("Player hit by enemy: {:?}", enemy);
 
-    if state.last_hit + PLAYER_INVICIBILITY_COOLDOWN > time.elapsed_seconds() {
-        return;
-    }
-
-    for player_hit_event in &mut player_hit_event_reader {
-        // log::info!("Player hit by enemy: {:?}", player_hit_event.0);
+        let Ok((mut state, shielded)) = player_state.get_mut(*player_entity) else {
+            continue;
+        };
 
-        match player_hit_event.0.enemy_type {
-            EnemyType::Jellyfish => {
-                state.current_hp -= 1;
-            }
-            EnemyType::Urchin => {
-                state.current_hp -= 2;
-            }
-            EnemyType::Shrimp => {
-                state.current_hp -= 3;
-            }
+        if god_mode.0
+            || shielded
+            || state.is_downed()
+            || state.last_hit + PLAYER_INVICIBILITY_COOLDOWN > time.elapsed_seconds()
+        {
+            continue;
         }
+
+        state.current_hp -= modifiers.damage(enemy.enemy_type.damage());
         state.last_hit = time.elapsed_seconds();
         state.last_heal = time.elapsed_seconds();
 
+        let (kind, seconds) = enemy.enemy_type.attack_effect();
+        status_effect_writer.send(ApplyStatusEffect {
+            target: *player_entity,
+            kind,
+            seconds,
+        });
+
         commands.spawn(AudioBundle {
             source: asset_server.load("ouch.ogg"),
             settings: PlaybackSettings {
@@ -541,13 +803,11 @@ fn handle_player_hit(
             },
             ..default()
         });
+    }
 
-        if state.current_hp <= 0 {
-            next_player_state.set(PlayerState::Dying);
-            break;
-        } else {
-            break;
-        }
+    // the run only ends once every hermit is down
+    if !player_state.is_empty() && player_state.iter().all(|(state, _)| state.is_downed()) {
+        next_player_state.set(PlayerState::Dying);
     }
 }
 
@@ -560,10 +820,17 @@ fn tick_death_timer(
     mut wave: ResMut<Wave>,
     mut next_wave_state: ResMut<NextState<WaveState>>,
     mut inventory: ResMut<Inventory>,
-    mut player_state: Query<&mut PlayerCombatState>,
-    collectables: Query<(Entity, &Transform, &Collectable, &InventoryItem)>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut player_state: Query<(
+        &mut PlayerCombatState,
+        &mut Transform,
+        &PlayerControllerState,
+    )>,
+    collectables: Query<
+        (Entity, &Transform, &Collectable, &InventoryItem),
+        Without<PlayerControllerState>,
+    >,
+    level: Res<LevelMetadata>,
+    mut starting_items: ResMut<StartingItems>,
 ) {
     if death_timer.0.tick(time.delta()).just_finished() {
         for enemy in &enemy_query {
@@ -573,8 +840,11 @@ fn tick_death_timer(
         next_game_state.set(GameState::TitleScreen);
         wave.count = 0;
         next_wave_state.set(WaveState::WAVE_END);
-        inventory.content = Vec::new();
-        *player_state.single_mut() = PlayerCombatState::new();
+        *inventory = Inventory::new();
+        for (mut state, mut transform, controller) in &mut player_state {
+            *state = PlayerCombatState::new();
+            transform.translation = start_position(&level, controller.player_index);
+        }
 
         for collectable in collectables.iter() {
             if collectable.2 .0 {
@@ -582,25 +852,7 @@ fn tick_death_timer(
             }
         }
 
-        create_sword(
-            &mut commands,
-            &mut meshes,
-            &mut materials,
-            Vec3 {
-                x: -3.0,
-                y: 0.5,
-                z: 0.0,
-            },
-        );
-        create_heart(
-            &mut commands,
-            &mut meshes,
-            &mut materials,
-            Vec3 {
-                x: 5.0,
-                y: 0.5,
-                z: -8.0,
-            },
-        );
+        // the next run starts with a fresh set
+        starting_items.pending = true;
     }
 }
@@ -1,83 +1,103 @@
+use std::num::NonZeroU64;
+
 use bevy::{
-    core_pipeline::{
-        clear_color::ClearColorConfig, core_3d,
-        fullscreen_vertex_shader::fullscreen_shader_vertex_state,
-    },
+    core_pipeline::{core_3d, fullscreen_vertex_shader::fullscreen_shader_vertex_state},
     ecs::query::QueryItem,
     prelude::*,
     render::{
         extract_component::{
-            ComponentUniforms, ExtractComponent, ExtractComponentPlugin, UniformComponentPlugin,
+            ComponentUniforms, DynamicUniformIndex, ExtractComponent, ExtractComponentPlugin,
+            UniformComponentPlugin,
         },
+        extract_resource::{ExtractResource, ExtractResourcePlugin},
+        render_asset::RenderAssets,
         render_graph::{
             NodeRunError, RenderGraphApp, RenderGraphContext, ViewNode, ViewNodeRunner,
         },
         render_resource::{
-            BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
-            BindGroupLayoutEntry, BindingResource, BindingType, CachedRenderPipelineId,
-            ColorTargetState, ColorWrites, FragmentState, MultisampleState, Operations,
-            PipelineCache, PrimitiveState, RenderPassColorAttachment, RenderPassDescriptor,
-            RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor, ShaderStages,
-            ShaderType, TextureFormat, TextureSampleType, TextureViewDimension,
+            AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+            BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+            BufferBindingType, CachedRenderPipelineId, ColorTargetState, ColorWrites, Extent3d,
+            FilterMode, FragmentState, MultisampleState, Operations, PipelineCache, PrimitiveState,
+            RenderPassColorAttachment, RenderPassDescriptor, RenderPipelineDescriptor, Sampler,
+            SamplerBindingType, SamplerDescriptor, ShaderStages, ShaderType, TextureDimension,
+            TextureFormat, TextureSampleType, TextureViewDimension,
         },
         renderer::{RenderContext, RenderDevice},
         texture::BevyDefault,
         view::ViewTarget,
         RenderApp,
     },
+    utils::HashMap,
 };
 
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::game::HolyCam;
 use crate::game_state::GameState;
+use crate::network::RemotePlayer;
+use crate::player::combat::PlayerCombatState;
+
+// the look the single water shader used to have, a tenth of the way to sea blue
+const LUT_SIZE: u32 = 16;
+const WATER_TINT: Vec3 = Vec3::new(0.0, 0.8, 1.0);
+const WATER_TINT_AMOUNT: f32 = 0.1;
+
+// below this share of their hearts the hermits start losing colour
+const LOW_HEALTH: f32 = 0.5;
+const DESATURATION_SPEED: f32 = 2.0;
+// heartbeats per second once it's getting grey
+const HEARTBEAT_RATE: f32 = 1.2;
 
 pub struct PostProcessingPlugin;
 
 impl Plugin for PostProcessingPlugin {
     fn build(&self, app: &mut App) {
+        // Every pass has its own settings component on the camera, extracted to the render world
+        // and uploaded as a uniform each frame. The stack says which of them run and in what order.
         app.add_plugins((
-            // The settings will be a component that lives in the main world but will
-            // be extracted to the render world every frame.
-            // This makes it possible to control the effect from the main world.
-            // This plugin will take care of extracting it automatically.
-            // It's important to derive [`ExtractComponent`] on [`PostProcessingSettings`]
-            // for this plugin to work correctly.
-            ExtractComponentPlugin::<PostProcessSettings>::default(),
-            // The settings will also be the data used in the shader.
-            // This plugin will prepare the component for the GPU by creating a uniform buffer
-            // and writing the data to that buffer every frame.
-            UniformComponentPlugin::<PostProcessSettings>::default(),
+            ExtractComponentPlugin::<PostProcessStack>::default(),
+            ExtractComponentPlugin::<WaterDistortion>::default(),
+            UniformComponentPlugin::<WaterDistortion>::default(),
+            ExtractComponentPlugin::<ChromaticAberration>::default(),
+            UniformComponentPlugin::<ChromaticAberration>::default(),
+            ExtractComponentPlugin::<LowHealthDesaturation>::default(),
+            UniformComponentPlugin::<LowHealthDesaturation>::default(),
+            ExtractComponentPlugin::<ColorGrading>::default(),
+            UniformComponentPlugin::<ColorGrading>::default(),
+            ExtractComponentPlugin::<Vignette>::default(),
+            UniformComponentPlugin::<Vignette>::default(),
+            ExtractResourcePlugin::<ColorGradingLut>::default(),
         ));
 
-        app.add_systems(Update, update_settings);
+        app.add_systems(Startup, setup_lut);
+        app.add_systems(
+            Update,
+            (
+                update_stack,
+                update_water_distortion,
+                flash_aberration_on_hurt,
+                desaturate_on_low_health,
+            ),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("postfx", postfx_command)
+                .usage("[pass] [on|off|<position>]")
+                .help("show the post processing passes, toggle one or move it in the order")
+                .arguments(PostProcessPass::ALL.map(PostProcessPass::name)),
+        );
 
-        // We need to get the render app from the main app
         let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
-        return;
-    };
+            return;
+        };
 
+        // one node runs the whole stack, so the order can change without touching the graph
         render_app
-            // Bevy's renderer uses a render graph which is a collection of nodes in a directed acyclic graph.
-            // It currently runs on each view/camera and executes each node in the specified order.
-            // It will make sure that any node that needs a dependency from another node
-            // only runs when that dependency is done.
-            //
-            // Each node can execute arbitrary work, but it generally runs at least one render pass.
-            // A node only has access to the render world, so if you need data from the main world
-            // you need to extract it manually or with the plugin like above.
-            // Add a [`Node`] to the [`RenderGraph`]
-            // The Node needs to impl FromWorld
-            //
-            // The [`ViewNodeRunner`] is a special [`Node`] that will automatically run the node for each view
-            // matching the [`ViewQuery`]
             .add_render_graph_node::<ViewNodeRunner<PostProcessNode>>(
-                // Specify the name of the graph, in this case we want the graph for 3d
                 core_3d::graph::NAME,
-                // It also needs the name of the node
                 PostProcessNode::NAME,
             )
             .add_render_graph_edges(
                 core_3d::graph::NAME,
-                // Specify the node ordering.
-                // This will automatically create all required node edges to enforce the given ordering.
                 &[
                     core_3d::graph::node::TONEMAPPING,
                     PostProcessNode::NAME,
@@ -87,305 +107,643 @@ impl Plugin for PostProcessingPlugin {
     }
 
     fn finish(&self, app: &mut App) {
-        // We need to get the render app from the main app
         let Ok(render_app) = app.get_sub_app_mut(RenderApp) else {
-          return;
-      };
+            return;
+        };
 
-        render_app
-            // Initialize the pipeline
-            .init_resource::<PostProcessPipeline>();
+        render_app.init_resource::<PostProcessPipelines>();
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PostProcessPass {
+    WaterDistortion,
+    ChromaticAberration,
+    LowHealthDesaturation,
+    ColorGrading,
+    Vignette,
+}
+
+impl PostProcessPass {
+    pub const ALL: [PostProcessPass; 5] = [
+        PostProcessPass::WaterDistortion,
+        PostProcessPass::ChromaticAberration,
+        PostProcessPass::LowHealthDesaturation,
+        PostProcessPass::ColorGrading,
+        PostProcessPass::Vignette,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PostProcessPass::WaterDistortion => "water",
+            PostProcessPass::ChromaticAberration => "aberration",
+            PostProcessPass::LowHealthDesaturation => "desaturation",
+            PostProcessPass::ColorGrading => "grading",
+            PostProcessPass::Vignette => "vignette",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|pass| pass.name() == name)
+    }
+
+    fn shader(self) -> &'static str {
+        match self {
+            PostProcessPass::WaterDistortion => "post_processing/water_distortion.wgsl",
+            PostProcessPass::ChromaticAberration => "post_processing/chromatic_aberration.wgsl",
+            PostProcessPass::LowHealthDesaturation => {
+                "post_processing/low_health_desaturation.wgsl"
+            }
+            PostProcessPass::ColorGrading => "post_processing/color_grading.wgsl",
+            PostProcessPass::Vignette => "post_processing/vignette.wgsl",
+        }
+    }
+
+    fn min_uniform_size(self) -> NonZeroU64 {
+        match self {
+            PostProcessPass::WaterDistortion => WaterDistortion::min_size(),
+            PostProcessPass::ChromaticAberration => ChromaticAberration::min_size(),
+            PostProcessPass::LowHealthDesaturation => LowHealthDesaturation::min_size(),
+            PostProcessPass::ColorGrading => ColorGrading::min_size(),
+            PostProcessPass::Vignette => Vignette::min_size(),
+        }
+    }
+}
+
+// Which passes run on a camera and in what order. A camera without one, like the item icon
+// cameras, gets no post processing at all.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct PostProcessStack {
+    // everything is off while the bag is open, the inventory is drawn plain
+    pub active: bool,
+    passes: Vec<(PostProcessPass, bool)>,
+}
+
+impl Default for PostProcessStack {
+    fn default() -> Self {
+        Self {
+            active: true,
+            passes: PostProcessPass::ALL.map(|pass| (pass, true)).to_vec(),
+        }
+    }
+}
+
+impl PostProcessStack {
+    pub fn passes(&self) -> impl Iterator<Item = (PostProcessPass, bool)> + '_ {
+        self.passes.iter().copied()
+    }
+
+    pub fn is_enabled(&self, pass: PostProcessPass) -> bool {
+        self.passes
+            .iter()
+            .any(|(other, enabled)| *other == pass && *enabled)
+    }
+
+    pub fn set_enabled(&mut self, pass: PostProcessPass, enabled: bool) {
+        for (other, other_enabled) in &mut self.passes {
+            if *other == pass {
+                *other_enabled = enabled;
+            }
+        }
+    }
+
+    // a position past the end puts it last
+    pub fn move_to(&mut self, pass: PostProcessPass, position: usize) {
+        let Some(index) = self.passes.iter().position(|(other, _)| *other == pass) else {
+            return;
+        };
+        let entry = self.passes.remove(index);
+        self.passes.insert(position.min(self.passes.len()), entry);
+    }
+}
+
+// The uniforms are all four floats, WebGL wants them padded to 16 bytes anyway.
+
+// https://www.shadertoy.com/view/4tG3WR
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct WaterDistortion {
+    pub time: f32,
+    // how far the picture gets pushed around, in screen fractions
+    pub strength: f32,
+    // how many waves fit across the screen
+    pub frequency: f32,
+    // the middle of the screen stays sharp out to here
+    pub clear_radius: f32,
+}
+
+impl Default for WaterDistortion {
+    fn default() -> Self {
+        Self {
+            time: 0.0,
+            strength: 0.01,
+            frequency: 12.5,
+            clear_radius: 0.2,
+        }
+    }
+}
+
+// The colours split apart towards the edges when a hermit gets hurt and come back together
+// over a moment.
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct ChromaticAberration {
+    // 0 to 1, set to 1 on a hit
+    pub intensity: f32,
+    // how far red and blue are pulled apart at the edges at full intensity
+    pub max_offset: f32,
+    // intensity lost per second
+    pub decay: f32,
+    // where it settles between hits
+    pub rest: f32,
+}
+
+impl Default for ChromaticAberration {
+    fn default() -> Self {
+        Self {
+            intensity: 0.0,
+            max_offset: 0.012,
+            decay: 2.5,
+            rest: 0.0,
+        }
     }
 }
 
-// The post process node used for the render graph
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct LowHealthDesaturation {
+    // 0 at full health, 1 with no hearts left
+    pub amount: f32,
+    pub max_saturation_loss: f32,
+    // how much darker it gets on each heartbeat
+    pub darken: f32,
+    pub pulse: f32,
+}
+
+impl Default for LowHealthDesaturation {
+    fn default() -> Self {
+        Self {
+            amount: 0.0,
+            max_saturation_loss: 0.8,
+            darken: 0.25,
+            pulse: 0.0,
+        }
+    }
+}
+
+// Exposure, contrast and saturation are applied first, then the colour is looked up in the
+// `ColorGradingLut`.
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct ColorGrading {
+    // how much of the lookup table's colour is used
+    pub strength: f32,
+    // in stops
+    pub exposure: f32,
+    pub contrast: f32,
+    pub saturation: f32,
+}
+
+impl Default for ColorGrading {
+    fn default() -> Self {
+        Self {
+            strength: 1.0,
+            exposure: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+#[derive(Component, Clone, Copy, ExtractComponent, ShaderType)]
+pub struct Vignette {
+    pub intensity: f32,
+    // where the darkening starts, 1 is the middle of the screen's edges
+    pub radius: f32,
+    pub softness: f32,
+    // 0 follows the shape of the screen, 1 is a circle
+    pub roundness: f32,
+}
+
+impl Default for Vignette {
+    fn default() -> Self {
+        Self {
+            intensity: 0.35,
+            radius: 0.75,
+            softness: 0.6,
+            roundness: 0.5,
+        }
+    }
+}
+
+#[derive(Bundle, Default)]
+pub struct PostProcessBundle {
+    pub stack: PostProcessStack,
+    pub water_distortion: WaterDistortion,
+    pub chromatic_aberration: ChromaticAberration,
+    pub low_health_desaturation: LowHealthDesaturation,
+    pub color_grading: ColorGrading,
+    pub vignette: Vignette,
+}
+
+// A 3D lookup table indexed by gamma encoded colour. Swap the handle for another table of any
+// size to change the grading.
+#[derive(Resource, Clone, ExtractResource)]
+pub struct ColorGradingLut(pub Handle<Image>);
+
+fn setup_lut(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.insert_resource(ColorGradingLut(images.add(underwater_lut())));
+}
+
+fn underwater_lut() -> Image {
+    let mut data = Vec::with_capacity((LUT_SIZE.pow(3) * 4) as usize);
+    for b in 0..LUT_SIZE {
+        for g in 0..LUT_SIZE {
+            for r in 0..LUT_SIZE {
+                let encoded = Vec3::new(r as f32, g as f32, b as f32) / (LUT_SIZE - 1) as f32;
+                let color = encoded.powf(2.2).lerp(WATER_TINT, WATER_TINT_AMOUNT);
+                data.extend_from_slice(&Color::rgb_linear(color.x, color.y, color.z).as_rgba_u8());
+            }
+        }
+    }
+
+    Image::new(
+        Extent3d {
+            width: LUT_SIZE,
+            height: LUT_SIZE,
+            depth_or_array_layers: LUT_SIZE,
+        },
+        TextureDimension::D3,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+    )
+}
+
+fn update_stack(mut stacks: Query<&mut PostProcessStack>, game_state: Res<State<GameState>>) {
+    let active = *game_state.get() != GameState::ManagingInventory;
+    for mut stack in &mut stacks {
+        if stack.active != active {
+            stack.active = active;
+        }
+    }
+}
+
+fn update_water_distortion(mut settings: Query<&mut WaterDistortion>, time: Res<Time>) {
+    for mut setting in &mut settings {
+        setting.time = time.elapsed_seconds();
+    }
+}
+
+// any hearts lost by the hermits on this screen, healing doesn't count
+fn flash_aberration_on_hurt(
+    players: Query<&PlayerCombatState, Without<RemotePlayer>>,
+    mut settings: Query<&mut ChromaticAberration>,
+    mut last_hp: Local<Option<i32>>,
+    time: Res<Time>,
+) {
+    let hp = (!players.is_empty()).then(|| players.iter().map(|player| player.current_hp).sum());
+    let hurt = matches!((*last_hp, hp), (Some(last), Some(hp)) if hp < last);
+    *last_hp = hp;
+
+    for mut setting in &mut settings {
+        setting.intensity = if hurt {
+            1.0
+        } else {
+            (setting.intensity - setting.decay * time.delta_seconds()).max(setting.rest)
+        };
+    }
+}
+
+// goes by whoever on this screen is closest to going down
+fn desaturate_on_low_health(
+    players: Query<&PlayerCombatState, Without<RemotePlayer>>,
+    mut settings: Query<&mut LowHealthDesaturation>,
+    time: Res<Time>,
+) {
+    let health = players
+        .iter()
+        .map(|player| player.current_hp.max(0) as f32 / player.max_hp.max(1) as f32)
+        .reduce(f32::min)
+        .unwrap_or(1.0);
+    let target = ((LOW_HEALTH - health) / LOW_HEALTH).clamp(0.0, 1.0);
+    let heartbeat = (time.elapsed_seconds() * HEARTBEAT_RATE * std::f32::consts::TAU).sin();
+
+    for mut setting in &mut settings {
+        let step = DESATURATION_SPEED * time.delta_seconds();
+        setting.amount += (target - setting.amount).clamp(-step, step);
+        setting.pulse = heartbeat.max(0.0).powi(4);
+    }
+}
+
+fn postfx_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut stacks = world.query_filtered::<&mut PostProcessStack, With<HolyCam>>();
+    let mut stack = stacks
+        .get_single_mut(world)
+        .map_err(|_| "there's no game camera".to_string())?;
+
+    if args.is_empty() {
+        return Ok(stack
+            .passes()
+            .map(|(pass, enabled)| {
+                format!("{} {}", pass.name(), if enabled { "on" } else { "off" })
+            })
+            .collect::<Vec<_>>()
+            .join(", "));
+    }
+
+    let name: String = parse_arg(args, 0, "pass")?;
+    let pass =
+        PostProcessPass::from_name(&name).ok_or_else(|| format!("no pass called `{name}`"))?;
+    match args.get(1) {
+        Some(&"on") => stack.set_enabled(pass, true),
+        Some(&"off") => stack.set_enabled(pass, false),
+        Some(_) => {
+            let position: usize = parse_arg(args, 1, "position")?;
+            stack.move_to(pass, position);
+            return Ok(format!("{name} moved to {position}"));
+        }
+        None => {
+            let enabled = stack.is_enabled(pass);
+            stack.set_enabled(pass, !enabled);
+        }
+    }
+    Ok(format!(
+        "{name} {}",
+        if stack.is_enabled(pass) { "on" } else { "off" }
+    ))
+}
+
 #[derive(Default)]
 struct PostProcessNode;
+
 impl PostProcessNode {
     pub const NAME: &str = "post_process";
 }
 
-// The ViewNode trait is required by the ViewNodeRunner
 impl ViewNode for PostProcessNode {
-    // The node needs a query to gather data from the ECS in order to do its rendering,
-    // but it's not a normal system so we need to define it manually.
-    //
-    // This query will only run on the view entity
-    type ViewQuery = &'static ViewTarget;
-
-    // Runs the node logic
-    // This is where you encode draw commands.
-    //
-    // This will run on every view on which the graph is running.
-    // If you don't want your effect to run on every camera,
-    // you'll need to make sure you have a marker component as part of [`ViewQuery`]
-    // to identify which camera(s) should run the effect.
+    // only cameras with a stack match, the settings of a pass are optional and the pass is
+    // skipped without them
+    type ViewQuery = (
+        &'static ViewTarget,
+        &'static PostProcessStack,
+        Option<&'static DynamicUniformIndex<WaterDistortion>>,
+        Option<&'static DynamicUniformIndex<ChromaticAberration>>,
+        Option<&'static DynamicUniformIndex<LowHealthDesaturation>>,
+        Option<&'static DynamicUniformIndex<ColorGrading>>,
+        Option<&'static DynamicUniformIndex<Vignette>>,
+    );
+
     fn run(
         &self,
         _graph: &mut RenderGraphContext,
         render_context: &mut RenderContext,
-        view_target: QueryItem<Self::ViewQuery>,
+        (view_target, stack, water, aberration, desaturation, grading, vignette): QueryItem<
+            Self::ViewQuery,
+        >,
         world: &World,
     ) -> Result<(), NodeRunError> {
-        // Get the pipeline resource that contains the global data we need
-        // to create the render pipeline
-        let post_process_pipeline = world.resource::<PostProcessPipeline>();
-
-        // The pipeline cache is a cache of all previously created pipelines.
-        // It is required to avoid creating a new pipeline each frame,
-        // which is expensive due to shader compilation.
-        let pipeline_cache = world.resource::<PipelineCache>();
-
-        // Get the pipeline from the cache
-        let Some(pipeline) = pipeline_cache.get_render_pipeline(post_process_pipeline.pipeline_id)
-        else {
+        if !stack.active {
             return Ok(());
-        };
+        }
 
-        // Get the settings uniform binding
-        let settings_uniforms = world.resource::<ComponentUniforms<PostProcessSettings>>();
-        let Some(settings_binding) = settings_uniforms.uniforms().binding() else {
-            return Ok(());
-        };
+        let pipelines = world.resource::<PostProcessPipelines>();
+        let pipeline_cache = world.resource::<PipelineCache>();
 
-        // This will start a new "post process write", obtaining two texture
-        // views from the view target - a `source` and a `destination`.
-        // `source` is the "current" main texture and you _must_ write into
-        // `destination` because calling `post_process_write()` on the
-        // [`ViewTarget`] will internally flip the [`ViewTarget`]'s main
-        // texture to the `destination` texture. Failing to do so will cause
-        // the current main texture information to be lost.
-        let post_process = view_target.post_process_write();
-
-        // The bind_group gets created each frame.
-        //
-        // Normally, you would create a bind_group in the Queue set,
-        // but this doesn't work with the post_process_write().
-        // The reason it doesn't work is because each post_process_write will alternate the source/destination.
-        // The only way to have the correct source/destination for the bind_group
-        // is to make sure you get it during the node execution.
-        let bind_group = render_context
-            .render_device()
-            .create_bind_group(&BindGroupDescriptor {
-                label: Some("post_process_bind_group"),
-                layout: &post_process_pipeline.layout,
-                // It's important for this to match the BindGroupLayout defined in the PostProcessPipeline
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        // Make sure to use the source view
-                        resource: BindingResource::TextureView(post_process.source),
-                    },
-                    BindGroupEntry {
-                        binding: 1,
-                        // Use the sampler created for the pipeline
-                        resource: BindingResource::Sampler(&post_process_pipeline.sampler),
-                    },
-                    BindGroupEntry {
-                        binding: 2,
-                        // Set the settings binding
-                        resource: settings_binding.clone(),
-                    },
-                ],
+        for (pass, enabled) in stack.passes() {
+            if !enabled {
+                continue;
+            }
+
+            // the settings buffer and where this camera's settings are in it
+            let uniform = match pass {
+                PostProcessPass::WaterDistortion => world
+                    .resource::<ComponentUniforms<WaterDistortion>>()
+                    .uniforms()
+                    .binding()
+                    .zip(water.map(|index| index.index())),
+                PostProcessPass::ChromaticAberration => world
+                    .resource::<ComponentUniforms<ChromaticAberration>>()
+                    .uniforms()
+                    .binding()
+                    .zip(aberration.map(|index| index.index())),
+                PostProcessPass::LowHealthDesaturation => world
+                    .resource::<ComponentUniforms<LowHealthDesaturation>>()
+                    .uniforms()
+                    .binding()
+                    .zip(desaturation.map(|index| index.index())),
+                PostProcessPass::ColorGrading => world
+                    .resource::<ComponentUniforms<ColorGrading>>()
+                    .uniforms()
+                    .binding()
+                    .zip(grading.map(|index| index.index())),
+                PostProcessPass::Vignette => world
+                    .resource::<ComponentUniforms<Vignette>>()
+                    .uniforms()
+                    .binding()
+                    .zip(vignette.map(|index| index.index())),
+            };
+            let Some((settings_binding, offset)) = uniform else {
+                continue;
+            };
+
+            // grading waits for its lookup table to be on the gpu
+            let lut = if pass == PostProcessPass::ColorGrading {
+                let Some(lut) = world
+                    .get_resource::<ColorGradingLut>()
+                    .and_then(|lut| world.resource::<RenderAssets<Image>>().get(&lut.0))
+                else {
+                    continue;
+                };
+                Some(&lut.texture_view)
+            } else {
+                None
+            };
+
+            let pass_pipeline = &pipelines.passes[&pass];
+            let Some(pipeline) = pipeline_cache.get_render_pipeline(pass_pipeline.pipeline_id)
+            else {
+                continue;
+            };
+
+            // Every pass reads what the last one wrote. The bind group has to be made here, each
+            // post_process_write swaps the source and destination.
+            let post_process = view_target.post_process_write();
+
+            let mut entries = vec![
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(post_process.source),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&pipelines.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: settings_binding,
+                },
+            ];
+            if let Some(lut) = lut {
+                entries.push(BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(lut),
+                });
+                entries.push(BindGroupEntry {
+                    binding: 4,
+                    resource: BindingResource::Sampler(&pipelines.lut_sampler),
+                });
+            }
+
+            let bind_group =
+                render_context
+                    .render_device()
+                    .create_bind_group(&BindGroupDescriptor {
+                        label: Some("post_process_bind_group"),
+                        layout: &pass_pipeline.layout,
+                        entries: &entries,
+                    });
+
+            let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+                label: Some(pass.name()),
+                color_attachments: &[Some(RenderPassColorAttachment {
+                    view: post_process.destination,
+                    resolve_target: None,
+                    ops: Operations::default(),
+                })],
+                depth_stencil_attachment: None,
             });
 
-        // Begin the render pass
-        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
-            label: Some("post_process_pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                // We need to specify the post process destination view here
-                // to make sure we write to the appropriate texture.
-                view: post_process.destination,
-                resolve_target: None,
-                ops: Operations::default(),
-            })],
-            depth_stencil_attachment: None,
-        });
-
-        // This is mostly just wgpu boilerplate for drawing a fullscreen triangle,
-        // using the pipeline/bind_group created above
-        render_pass.set_render_pipeline(pipeline);
-        render_pass.set_bind_group(0, &bind_group, &[]);
-        render_pass.draw(0..3, 0..1);
+            render_pass.set_render_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[offset]);
+            render_pass.draw(0..3, 0..1);
+        }
 
         Ok(())
     }
 }
 
-// This contains global data used by the render pipeline. This will be created once on startup.
-#[derive(Resource)]
-struct PostProcessPipeline {
+struct PassPipeline {
     layout: BindGroupLayout,
-    sampler: Sampler,
     pipeline_id: CachedRenderPipelineId,
 }
 
-impl FromWorld for PostProcessPipeline {
+// created once on startup, one pipeline per pass
+#[derive(Resource)]
+struct PostProcessPipelines {
+    sampler: Sampler,
+    lut_sampler: Sampler,
+    passes: HashMap<PostProcessPass, PassPipeline>,
+}
+
+impl FromWorld for PostProcessPipelines {
     fn from_world(world: &mut World) -> Self {
         let render_device = world.resource::<RenderDevice>();
 
-        // We need to define the bind group layout used for our pipeline
-        let layout = render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-            label: Some("post_process_bind_group_layout"),
-            entries: &[
-                // The screen texture
-                BindGroupLayoutEntry {
-                    binding: 0,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Texture {
-                        sample_type: TextureSampleType::Float { filterable: true },
-                        view_dimension: TextureViewDimension::D2,
-                        multisampled: false,
-                    },
-                    count: None,
-                },
-                // The sampler that will be used to sample the screen texture
-                BindGroupLayoutEntry {
-                    binding: 1,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                    count: None,
-                },
-                // The settings uniform that will control the effect
-                BindGroupLayoutEntry {
-                    binding: 2,
-                    visibility: ShaderStages::FRAGMENT,
-                    ty: BindingType::Buffer {
-                        ty: bevy::render::render_resource::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: Some(PostProcessSettings::min_size()),
-                    },
-                    count: None,
-                },
-            ],
-        });
-
-        // We can create the sampler here since it won't change at runtime and doesn't depend on the view
         let sampler = render_device.create_sampler(&SamplerDescriptor::default());
+        // the table is blended between its entries and never wraps around
+        let lut_sampler = render_device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..default()
+        });
 
-        // Get the shader handle
-        let shader = world.resource::<AssetServer>().load("post_processing.wgsl");
-
-        let pipeline_id = world
-            .resource_mut::<PipelineCache>()
-            // This will add the pipeline to the cache and queue it's creation
-            .queue_render_pipeline(RenderPipelineDescriptor {
-                label: Some("post_process_pipeline".into()),
-                layout: vec![layout.clone()],
-                // This will setup a fullscreen triangle for the vertex state
-                vertex: fullscreen_shader_vertex_state(),
-                fragment: Some(FragmentState {
-                    shader,
-                    shader_defs: vec![],
-                    // Make sure this matches the entry point of your shader.
-                    // It can be anything as long as it matches here and in the shader.
-                    entry_point: "fragment".into(),
-                    targets: vec![Some(ColorTargetState {
-                        format: TextureFormat::bevy_default(),
-                        blend: None,
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                // All of the following properties are not important for this effect so just use the default values.
-                // This struct doesn't have the Default trait implemented because not all field can have a default value.
-                primitive: PrimitiveState::default(),
-                depth_stencil: None,
-                multisample: MultisampleState::default(),
-                push_constant_ranges: vec![],
-            });
+        let layouts: Vec<_> = PostProcessPass::ALL
+            .into_iter()
+            .map(|pass| (pass, pass_layout(render_device, pass)))
+            .collect();
+
+        let asset_server = world.resource::<AssetServer>().clone();
+        let mut pipeline_cache = world.resource_mut::<PipelineCache>();
+        let passes = layouts
+            .into_iter()
+            .map(|(pass, layout)| {
+                let pipeline_id = pipeline_cache.queue_render_pipeline(RenderPipelineDescriptor {
+                    label: Some(pass.name().into()),
+                    layout: vec![layout.clone()],
+                    vertex: fullscreen_shader_vertex_state(),
+                    fragment: Some(FragmentState {
+                        shader: asset_server.load(pass.shader()),
+                        shader_defs: vec![],
+                        entry_point: "fragment".into(),
+                        targets: vec![Some(ColorTargetState {
+                            format: TextureFormat::bevy_default(),
+                            blend: None,
+                            write_mask: ColorWrites::ALL,
+                        })],
+                    }),
+                    primitive: PrimitiveState::default(),
+                    depth_stencil: None,
+                    multisample: MultisampleState::default(),
+                    push_constant_ranges: vec![],
+                });
+                (
+                    pass,
+                    PassPipeline {
+                        layout,
+                        pipeline_id,
+                    },
+                )
+            })
+            .collect();
 
         Self {
-            layout,
             sampler,
-            pipeline_id,
+            lut_sampler,
+            passes,
         }
     }
 }
 
-// This is the component that will get passed to the shader
-#[derive(Component, Default, Clone, Copy, ExtractComponent, ShaderType)]
-pub struct PostProcessSettings {
-    pub time: f32,
-    pub enable: f32,
-    pub padding: Vec2,
-}
-
-// /// Set up a simple 3D scene
-// fn setup(
-//     mut commands: Commands,
-//     mut meshes: ResMut<Assets<Mesh>>,
-//     mut materials: ResMut<Assets<StandardMaterial>>,
-// ) {
-//     // camera
-//     commands.spawn((
-//         Camera3dBundle {
-//             transform: Transform::from_translation(Vec3::new(0.0, 0.0, 5.0))
-//                 .looking_at(Vec3::default(), Vec3::Y),
-//             camera_3d: Camera3d {
-//                 clear_color: ClearColorConfig::Custom(Color::WHITE),
-//                 ..default()
-//             },
-//             ..default()
-//         },
-//         // Add the setting to the camera.
-//         // This component is also used to determine on which camera to run the post processing effect.
-//         PostProcessSettings {
-//             intensity: 0.02,
-//             color_adjuster: Vec3::new(1.0, 1.0, 1.0),
-//         },
-//     ));
-
-//     // cube
-//     commands.spawn((
-//         PbrBundle {
-//             mesh: meshes.add(Mesh::from(shape::Cube { size: 1.0 })),
-//             material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
-//             transform: Transform::from_xyz(0.0, 0.5, 0.0),
-//             ..default()
-//         },
-//         Rotates,
-//     ));
-//     // light
-//     commands.spawn(PointLightBundle {
-//         transform: Transform::from_translation(Vec3::new(0.0, 0.0, 10.0)),
-//         ..default()
-//     });
-// }
-
-#[derive(Component)]
-struct Rotates;
-
-/// Rotates any entity around the x and y axis
-fn rotate(time: Res<Time>, mut query: Query<&mut Transform, With<Rotates>>) {
-    for mut transform in &mut query {
-        transform.rotate_x(0.55 * time.delta_seconds());
-        transform.rotate_z(0.15 * time.delta_seconds());
+// the screen texture, its sampler and the pass's settings, plus the lookup table for grading
+fn pass_layout(render_device: &RenderDevice, pass: PostProcessPass) -> BindGroupLayout {
+    let mut entries = vec![
+        BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        },
+        BindGroupLayoutEntry {
+            binding: 1,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        },
+        BindGroupLayoutEntry {
+            binding: 2,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: Some(pass.min_uniform_size()),
+            },
+            count: None,
+        },
+    ];
+    if pass == PostProcessPass::ColorGrading {
+        entries.push(BindGroupLayoutEntry {
+            binding: 3,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Texture {
+                sample_type: TextureSampleType::Float { filterable: true },
+                view_dimension: TextureViewDimension::D3,
+                multisampled: false,
+            },
+            count: None,
+        });
+        entries.push(BindGroupLayoutEntry {
+            binding: 4,
+            visibility: ShaderStages::FRAGMENT,
+            ty: BindingType::Sampler(SamplerBindingType::Filtering),
+            count: None,
+        });
     }
-}
 
-// Change the intensity over time to show that the effect is controlled from the main world
-fn update_settings(
-    mut settings: Query<&mut PostProcessSettings>,
-    time: Res<Time>,
-    game_state: Res<State<GameState>>,
-) {
-    for mut setting in &mut settings {
-        // let mut intensity = time.elapsed_seconds().sin();
-        // // Make it loop periodically
-        // intensity = intensity.sin();
-        // // Remap it to 0..1 because the intensity can't be negative
-        // intensity = intensity * 0.5 + 0.5;
-        // // Scale it to a more reasonable level
-        // intensity *= 0.015;
-
-        // // Set the intensity.
-        // // This will then be extracted to the render world and uploaded to the gpu automatically by the [`UniformComponentPlugin`]
-        // setting.time = intensity;
-        setting.time = time.elapsed_seconds();
-        setting.enable = if (*game_state.get()) == GameState::ManagingInventory {
-            0.0
-        } else {
-            1.0
-        };
-    }
+    render_device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+        label: Some(pass.name()),
+        entries: &entries,
+    })
 }
@@ -0,0 +1,219 @@
+use bevy::ui::PositionType::Absolute;
+use bevy::{log, prelude::*};
+
+use crate::hit_feedback::EnemyHit;
+use crate::inventory::PackClosed;
+use crate::profile::{Profile, ProfileStats};
+use crate::wave_manager::{Wave, WaveCleared, WaveState};
+
+// a pack only counts as perfect if there was something to pack
+const PERFECT_PACK_MIN_ITEMS: usize = 4;
+const TOAST_SECS: f32 = 4.0;
+
+pub struct AchievementsPlugin;
+
+impl Plugin for AchievementsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>();
+        app.add_systems(OnEnter(WaveState::WAVE_END), reset_run_stats);
+        app.add_systems(
+            Update,
+            (count_kills, count_waves, count_packs, check_achievements).chain(),
+        );
+        app.add_systems(Update, fade_toasts);
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Achievement {
+    FirstBlood,
+    ReefCleaner,
+    Survivor,
+    Marathon,
+    NeatFreak,
+    MasterPacker,
+}
+
+impl Achievement {
+    pub const ALL: [Achievement; 6] = [
+        Achievement::FirstBlood,
+        Achievement::ReefCleaner,
+        Achievement::Survivor,
+        Achievement::Marathon,
+        Achievement::NeatFreak,
+        Achievement::MasterPacker,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Achievement::FirstBlood => "firstblood",
+            Achievement::ReefCleaner => "reefcleaner",
+            Achievement::Survivor => "survivor",
+            Achievement::Marathon => "marathon",
+            Achievement::NeatFreak => "neatfreak",
+            Achievement::MasterPacker => "masterpacker",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|achievement| achievement.name() == name)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Achievement::FirstBlood => "First Blood",
+            Achievement::ReefCleaner => "Reef Cleaner",
+            Achievement::Survivor => "Survivor",
+            Achievement::Marathon => "Marathon",
+            Achievement::NeatFreak => "Neat Freak",
+            Achievement::MasterPacker => "Master Packer",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Achievement::FirstBlood => "Take down a creature",
+            Achievement::ReefCleaner => "Take down 500 creatures",
+            Achievement::Survivor => "Clear 10 waves in one run",
+            Achievement::Marathon => "Clear 100 waves in total",
+            Achievement::NeatFreak => "Close the shell with everything fitting",
+            Achievement::MasterPacker => "Pack perfectly 25 times",
+        }
+    }
+
+    // pearls paid out when it's earned
+    pub fn reward(self) -> u32 {
+        match self {
+            Achievement::FirstBlood => 5,
+            Achievement::ReefCleaner => 50,
+            Achievement::Survivor => 40,
+            Achievement::Marathon => 75,
+            Achievement::NeatFreak => 10,
+            Achievement::MasterPacker => 60,
+        }
+    }
+
+    // the lifetime stats don't have the current run in them until it's banked
+    fn earned(self, stats: &ProfileStats, run: &RunStats) -> bool {
+        match self {
+            Achievement::FirstBlood => stats.kills + run.kills >= 1,
+            Achievement::ReefCleaner => stats.kills + run.kills >= 500,
+            Achievement::Survivor => run.waves >= 10,
+            Achievement::Marathon => stats.waves + run.waves >= 100,
+            Achievement::NeatFreak => stats.perfect_packs + run.perfect_packs >= 1,
+            Achievement::MasterPacker => stats.perfect_packs + run.perfect_packs >= 25,
+        }
+    }
+}
+
+// what the current run has done so far, it goes into the profile once the run is banked
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub kills: u32,
+    pub waves: u32,
+    pub perfect_packs: u32,
+}
+
+#[derive(Component)]
+struct AchievementToast(Timer);
+
+// a run that was left for the title screen never gets banked, a new one starts from nothing
+fn reset_run_stats(wave: Res<Wave>, mut run: ResMut<RunStats>) {
+    if wave.count == 0 && (run.kills > 0 || run.waves > 0 || run.perfect_packs > 0) {
+        *run = RunStats::default();
+    }
+}
+
+fn count_kills(mut enemy_hit_reader: EventReader<EnemyHit>, mut run: ResMut<RunStats>) {
+    for hit in enemy_hit_reader.iter() {
+        if hit.killed {
+            run.kills += 1;
+        }
+    }
+}
+
+fn count_waves(mut wave_cleared_reader: EventReader<WaveCleared>, mut run: ResMut<RunStats>) {
+    for _ in wave_cleared_reader.iter() {
+        run.waves += 1;
+    }
+}
+
+fn count_packs(mut pack_closed_reader: EventReader<PackClosed>, mut run: ResMut<RunStats>) {
+    for pack in pack_closed_reader.iter() {
+        if pack.items >= PERFECT_PACK_MIN_ITEMS && pack.kept == pack.items {
+            run.perfect_packs += 1;
+        }
+    }
+}
+
+pub fn check_achievements(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    run: Res<RunStats>,
+    mut profile: ResMut<Profile>,
+    toasts: Query<(), With<AchievementToast>>,
+) {
+    if !run.is_changed() {
+        return;
+    }
+
+    let mut shown = toasts.iter().count();
+    for achievement in Achievement::ALL {
+        if profile.has_achievement(achievement) || !achievement.earned(&profile.stats, &run) {
+            continue;
+        }
+
+        profile.achievements.push(achievement);
+        profile.pearls += achievement.reward();
+        log::info!(
+            "Achievement {}, {} pearls",
+            achievement.title(),
+            achievement.reward()
+        );
+
+        // stacked under each other while several are up
+        commands.spawn((
+            TextBundle::from_section(
+                format!(
+                    "Achievement: {} (+{} pearls)",
+                    achievement.title(),
+                    achievement.reward()
+                ),
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 30.0,
+                    color: Color::rgb(1.0, 0.85, 0.2),
+                },
+            )
+            .with_style(Style {
+                position_type: Absolute,
+                left: Val::Px(16.0),
+                bottom: Val::Px(16.0 + shown as f32 * 36.0),
+                ..default()
+            }),
+            AchievementToast(Timer::from_seconds(TOAST_SECS, TimerMode::Once)),
+        ));
+        shown += 1;
+    }
+}
+
+// fades out over its last second
+fn fade_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut AchievementToast, &mut Text)>,
+) {
+    for (entity, mut toast, mut text) in &mut toasts {
+        toast.0.tick(time.delta());
+        if toast.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = toast.0.remaining_secs().min(1.0);
+        for section in &mut text.sections {
+            section.style.color.set_a(alpha);
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use bevy::{log, prelude::*};
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::inventory::{BagSize, ItemTypeId};
+use crate::levels::LevelCleared;
+use crate::player::PlayerState;
+use crate::profile::achievements::{check_achievements, Achievement, RunStats};
+use crate::storage::{read_text, write_text};
+
+mod achievements;
+mod shop;
+
+// one fact per line, `pearls 120`, `unlock biggerbag`, `achievement firstblood`
+const PROFILE_FILE: &str = "profile.txt";
+
+const PEARLS_PER_WAVE: u32 = 5;
+const KILLS_PER_PEARL: u32 = 10;
+const LEVEL_CLEAR_PEARLS: u32 = 25;
+
+pub struct ProfilePlugin;
+
+impl Plugin for ProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Profile::load());
+        app.add_plugins((achievements::AchievementsPlugin, shop::UnlockShopPlugin));
+        app.add_systems(OnEnter(PlayerState::Dying), bank_defeat);
+        app.add_systems(Update, bank_level_clear.after(check_achievements));
+        app.add_systems(
+            Update,
+            (apply_bag_size, save_profile).run_if(resource_changed::<Profile>()),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("profile", profile_command)
+                .help("show the pearls, lifetime stats, unlocks and achievements"),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("pearls", pearls_command)
+                .usage("<amount>")
+                .help("set the pearls in the profile"),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("unlock", unlock_command)
+                .usage("<unlock>")
+                .help("buy an unlock with the profile's pearls")
+                .arguments(Unlock::ALL.map(Unlock::name)),
+        );
+    }
+}
+
+// bought with pearls on the title screen, kept for good
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Unlock {
+    StartingPotion,
+    StartingMidSword,
+    StartingHandGun,
+    BiggerBag,
+    BoomerangDrops,
+    SuperGunDrops,
+}
+
+impl Unlock {
+    pub const ALL: [Unlock; 6] = [
+        Unlock::StartingPotion,
+        Unlock::StartingMidSword,
+        Unlock::StartingHandGun,
+        Unlock::BiggerBag,
+        Unlock::BoomerangDrops,
+        Unlock::SuperGunDrops,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unlock::StartingPotion => "potion",
+            Unlock::StartingMidSword => "midsword",
+            Unlock::StartingHandGun => "handgun",
+            Unlock::BiggerBag => "biggerbag",
+            Unlock::BoomerangDrops => "boomerangs",
+            Unlock::SuperGunDrops => "superguns",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|unlock| unlock.name() == name)
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            Unlock::StartingPotion => "Packed Potion",
+            Unlock::StartingMidSword => "Mid Sword Start",
+            Unlock::StartingHandGun => "Handgun Start",
+            Unlock::BiggerBag => "Roomier Shell",
+            Unlock::BoomerangDrops => "Boomerang Drops",
+            Unlock::SuperGunDrops => "Super Gun Drops",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Unlock::StartingPotion => "Start runs with a heal potion",
+            Unlock::StartingMidSword => "Start runs with the mid sword",
+            Unlock::StartingHandGun => "Start runs with the handgun",
+            Unlock::BiggerBag => "A third layer in the shell",
+            Unlock::BoomerangDrops => "Boomerangs in lucky loot",
+            Unlock::SuperGunDrops => "Super guns in any lucky loot",
+        }
+    }
+
+    pub fn cost(self) -> u32 {
+        match self {
+            Unlock::StartingPotion => 30,
+            Unlock::StartingMidSword => 60,
+            Unlock::StartingHandGun => 90,
+            Unlock::BiggerBag => 150,
+            Unlock::BoomerangDrops => 80,
+            Unlock::SuperGunDrops => 120,
+        }
+    }
+
+    fn starting_item(self) -> Option<ItemTypeId> {
+        match self {
+            Unlock::StartingPotion => Some(ItemTypeId::HealPotion),
+            Unlock::StartingMidSword => Some(ItemTypeId::MidSword),
+            Unlock::StartingHandGun => Some(ItemTypeId::HandGun),
+            _ => None,
+        }
+    }
+
+    fn dropped_item(self) -> Option<ItemTypeId> {
+        match self {
+            Unlock::BoomerangDrops => Some(ItemTypeId::AlexBoomerang),
+            Unlock::SuperGunDrops => Some(ItemTypeId::SuperGun),
+            _ => None,
+        }
+    }
+}
+
+// added up over every run that was banked
+#[derive(Clone, Default)]
+pub struct ProfileStats {
+    pub runs: u32,
+    pub kills: u32,
+    pub waves: u32,
+    pub perfect_packs: u32,
+}
+
+// Everything that carries over from one run to the next. It's written to profile.txt, or the
+// browser's local storage, whenever it changes. A missing file is a fresh profile.
+#[derive(Resource, Default)]
+pub struct Profile {
+    pub pearls: u32,
+    pub stats: ProfileStats,
+    unlocks: Vec<Unlock>,
+    achievements: Vec<Achievement>,
+}
+
+impl Profile {
+    pub fn has(&self, unlock: Unlock) -> bool {
+        self.unlocks.contains(&unlock)
+    }
+
+    pub fn buy(&mut self, unlock: Unlock) -> Result<(), String> {
+        if self.has(unlock) {
+            return Err(format!("{} is already unlocked", unlock.title()));
+        }
+        if self.pearls < unlock.cost() {
+            return Err(format!(
+                "{} needs {} pearls, there are {}",
+                unlock.title(),
+                unlock.cost(),
+                self.pearls
+            ));
+        }
+
+        self.pearls -= unlock.cost();
+        self.unlocks.push(unlock);
+        log::info!("Unlocked {}", unlock.title());
+        Ok(())
+    }
+
+    pub fn starting_items(&self) -> Vec<ItemTypeId> {
+        self.unlocks
+            .iter()
+            .filter_map(|unlock| unlock.starting_item())
+            .collect()
+    }
+
+    pub fn dropped_items(&self) -> Vec<ItemTypeId> {
+        self.unlocks
+            .iter()
+            .filter_map(|unlock| unlock.dropped_item())
+            .collect()
+    }
+
+    pub fn bag_size(&self) -> IVec3 {
+        let mut size = BagSize::default().0;
+        if self.has(Unlock::BiggerBag) {
+            size.y += 1;
+        }
+        size
+    }
+
+    pub fn has_achievement(&self, achievement: Achievement) -> bool {
+        self.achievements.contains(&achievement)
+    }
+
+    // folds the run into the lifetime stats and pays out for the waves and creatures it got through
+    fn bank(&mut self, run: &mut RunStats) -> u32 {
+        let pearls = run.waves * PEARLS_PER_WAVE + run.kills / KILLS_PER_PEARL;
+        self.pearls += pearls;
+        self.stats.runs += 1;
+        self.stats.kills += run.kills;
+        self.stats.waves += run.waves;
+        self.stats.perfect_packs += run.perfect_packs;
+        *run = RunStats::default();
+        pearls
+    }
+
+    // lines that don't make sense are skipped
+    fn load() -> Self {
+        let mut profile = Self::default();
+        let Some(text) = read_text(PROFILE_FILE) else {
+            return profile;
+        };
+
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once(' ') else {
+                continue;
+            };
+            let number = value.parse().ok();
+            match key {
+                "pearls" => profile.pearls = number.unwrap_or(profile.pearls),
+                "runs" => profile.stats.runs = number.unwrap_or(0),
+                "kills" => profile.stats.kills = number.unwrap_or(0),
+                "waves" => profile.stats.waves = number.unwrap_or(0),
+                "perfect_packs" => profile.stats.perfect_packs = number.unwrap_or(0),
+                "unlock" => profile.unlocks.extend(Unlock::from_name(value)),
+                "achievement" => profile.achievements.extend(Achievement::from_name(value)),
+                _ => {}
+            }
+        }
+        profile
+    }
+
+    fn save(&self) -> Result<(), String> {
+        let mut text = format!(
+            "pearls {}\nruns {}\nkills {}\nwaves {}\nperfect_packs {}\n",
+            self.pearls,
+            self.stats.runs,
+            self.stats.kills,
+            self.stats.waves,
+            self.stats.perfect_packs
+        );
+        for unlock in &self.unlocks {
+            text += &format!("unlock {}\n", unlock.name());
+        }
+        for achievement in &self.achievements {
+            text += &format!("achievement {}\n", achievement.name());
+        }
+        write_text(PROFILE_FILE, &text)
+    }
+}
+
+fn bank_defeat(mut profile: ResMut<Profile>, mut run: ResMut<RunStats>) {
+    let pearls = profile.bank(&mut run);
+    log::info!("Run over, banked {pearls} pearls");
+}
+
+// the run goes on after a level, only the bonus is paid, the rest waits for bank_defeat
+fn bank_level_clear(
+    mut level_cleared_reader: EventReader<LevelCleared>,
+    mut profile: ResMut<Profile>,
+) {
+    for _ in level_cleared_reader.iter() {
+        profile.pearls += LEVEL_CLEAR_PEARLS;
+        log::info!("Level cleared, banked {LEVEL_CLEAR_PEARLS} pearls");
+    }
+}
+
+fn apply_bag_size(profile: Res<Profile>, mut bag: ResMut<BagSize>) {
+    let size = profile.bag_size();
+    if bag.0 != size {
+        bag.0 = size;
+    }
+}
+
+// the profile starts out as the file, there's nothing to write until it changes
+fn save_profile(profile: Res<Profile>) {
+    if profile.is_added() {
+        return;
+    }
+    if let Err(error) = profile.save() {
+        log::warn!("{error}");
+    }
+}
+
+fn profile_command(world: &mut World, _: &[&str]) -> Result<String, String> {
+    let profile = world.resource::<Profile>();
+    let unlocks: Vec<&str> = profile.unlocks.iter().map(|unlock| unlock.name()).collect();
+    Ok(format!(
+        "{} pearls, {} runs, {} kills, {} waves, {} perfect packs\nunlocks: {}\nachievements: {}/{}",
+        profile.pearls,
+        profile.stats.runs,
+        profile.stats.kills,
+        profile.stats.waves,
+        profile.stats.perfect_packs,
+        if unlocks.is_empty() {
+            "none".to_string()
+        } else {
+            unlocks.join(", ")
+        },
+        profile.achievements.len(),
+        Achievement::ALL.len()
+    ))
+}
+
+fn pearls_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let pearls: u32 = parse_arg(args, 0, "amount")?;
+    world.resource_mut::<Profile>().pearls = pearls;
+    Ok(format!("pearls set to {pearls}"))
+}
+
+fn unlock_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "unlock")?;
+    let unlock = Unlock::from_name(&name).ok_or_else(|| format!("no unlock called `{name}`"))?;
+    let mut profile = world.resource_mut::<Profile>();
+    profile.buy(unlock)?;
+    Ok(format!(
+        "unlocked {}, {} pearls left",
+        unlock.title(),
+        profile.pearls
+    ))
+}
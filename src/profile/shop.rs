@@ -0,0 +1,278 @@
+use bevy::ui::FocusPolicy;
+use bevy::ui::PositionType::Absolute;
+use bevy::{log, prelude::*};
+
+use crate::game_state::GameState;
+use crate::profile::achievements::Achievement;
+use crate::profile::{Profile, Unlock};
+
+pub struct UnlockShopPlugin;
+
+impl Plugin for UnlockShopPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::TitleScreen), setup);
+        app.add_systems(
+            Update,
+            (toggle_shop, buy_unlock, update_labels)
+                .chain()
+                .run_if(in_state(GameState::TitleScreen)),
+        );
+        app.add_systems(OnExit(GameState::TitleScreen), clean);
+    }
+}
+
+// everything here, for the cleanup
+#[derive(Component)]
+struct ShopUi;
+
+#[derive(Component)]
+struct ShopPanel;
+
+#[derive(Component, Copy, Clone, PartialEq)]
+enum ShopButton {
+    Open,
+    Close,
+    Buy(Unlock),
+}
+
+// up in the corner of the title screen, it opens the shop
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(ButtonBundle {
+            style: Style {
+                position_type: Absolute,
+                left: Val::Px(16.0),
+                top: Val::Px(16.0),
+                width: Val::Px(200.0),
+                height: Val::Px(80.0),
+                border: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+            ..default()
+        })
+        .insert((ShopButton::Open, ShopUi))
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 28.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                    },
+                )
+                .with_text_alignment(TextAlignment::Center),
+            );
+        });
+}
+
+fn toggle_shop(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    profile: Res<Profile>,
+    buttons: Query<(&Interaction, &ShopButton), Changed<Interaction>>,
+    panels: Query<Entity, With<ShopPanel>>,
+) {
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            ShopButton::Open if panels.is_empty() => {
+                spawn_panel(&mut commands, &asset_server, &profile)
+            }
+            ShopButton::Open | ShopButton::Close => {
+                for entity in &panels {
+                    commands.entity(entity).despawn_recursive();
+                }
+            }
+            ShopButton::Buy(_) => {}
+        }
+    }
+}
+
+// the unlocks to buy on top, the achievements below them
+fn spawn_panel(commands: &mut Commands, asset_server: &AssetServer, profile: &Profile) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    let button_style = |width: f32, height: f32| Style {
+        width: Val::Px(width),
+        height: Val::Px(height),
+        border: UiRect::all(Val::Px(5.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                flex_direction: FlexDirection::Column,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(15.0),
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.85).into(),
+            // the title screen buttons underneath don't get the clicks
+            focus_policy: FocusPolicy::Block,
+            z_index: ZIndex::Global(1),
+            ..default()
+        })
+        .insert((ShopPanel, ShopUi))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Unlocks",
+                text_style(48.0, Color::rgb(0.9, 0.9, 0.9)),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        max_width: Val::Px(840.0),
+                        flex_wrap: FlexWrap::Wrap,
+                        justify_content: JustifyContent::Center,
+                        row_gap: Val::Px(15.0),
+                        column_gap: Val::Px(15.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for unlock in Unlock::ALL {
+                        parent
+                            .spawn(ButtonBundle {
+                                style: button_style(260.0, 110.0),
+                                background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+                                ..default()
+                            })
+                            .insert(ShopButton::Buy(unlock))
+                            .with_children(|parent| {
+                                parent.spawn(
+                                    TextBundle::from_section(
+                                        "",
+                                        text_style(22.0, Color::rgb(0.9, 0.9, 0.9)),
+                                    )
+                                    .with_text_alignment(TextAlignment::Center),
+                                );
+                            });
+                    }
+                });
+
+            parent.spawn(TextBundle::from_section(
+                "Achievements",
+                text_style(36.0, Color::rgb(0.9, 0.9, 0.9)),
+            ));
+            for achievement in Achievement::ALL {
+                let color = if profile.has_achievement(achievement) {
+                    Color::rgb(1.0, 0.85, 0.2)
+                } else {
+                    Color::GRAY
+                };
+                parent.spawn(TextBundle::from_section(
+                    format!(
+                        "{}: {} ({} pearls)",
+                        achievement.title(),
+                        achievement.description(),
+                        achievement.reward()
+                    ),
+                    text_style(22.0, color),
+                ));
+            }
+
+            parent
+                .spawn(ButtonBundle {
+                    style: button_style(200.0, 65.0),
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+                    ..default()
+                })
+                .insert(ShopButton::Close)
+                .with_children(|parent| {
+                    parent.spawn(TextBundle::from_section(
+                        "Close",
+                        text_style(32.0, Color::rgb(0.9, 0.9, 0.9)),
+                    ));
+                });
+        });
+}
+
+fn buy_unlock(
+    buttons: Query<(&Interaction, &ShopButton), Changed<Interaction>>,
+    mut profile: ResMut<Profile>,
+) {
+    for (interaction, button) in &buttons {
+        if let (Interaction::Pressed, ShopButton::Buy(unlock)) = (interaction, button) {
+            if let Err(error) = profile.buy(*unlock) {
+                log::info!("{error}");
+            }
+        }
+    }
+}
+
+// owned ones go green, the ones that can't be paid for yet grey
+fn update_labels(
+    profile: Res<Profile>,
+    mut buttons: Query<(&ShopButton, &Interaction, &Children, &mut BorderColor)>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, interaction, children, mut border) in &mut buttons {
+        let (label, color) = match button {
+            ShopButton::Open => (
+                format!("Unlocks\n{} pearls", profile.pearls),
+                Color::rgb(0.9, 0.9, 0.9),
+            ),
+            ShopButton::Close => continue,
+            ShopButton::Buy(unlock) => {
+                let (price, color) = if profile.has(*unlock) {
+                    ("Owned".to_string(), Color::rgb(0.4, 0.9, 0.4))
+                } else if profile.pearls >= unlock.cost() {
+                    (
+                        format!("{} pearls", unlock.cost()),
+                        Color::rgb(0.9, 0.9, 0.9),
+                    )
+                } else {
+                    (format!("{} pearls", unlock.cost()), Color::GRAY)
+                };
+                (
+                    format!("{}\n{}\n{price}", unlock.title(), unlock.description()),
+                    color,
+                )
+            }
+        };
+
+        let hovered = *interaction == Interaction::Hovered;
+        let border_color = if hovered {
+            Color::rgb(1.0, 0.85, 0.2)
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 0.0)
+        };
+        if border.0 != border_color {
+            border.0 = border_color;
+        }
+
+        for child in children {
+            let Ok(mut text) = texts.get_mut(*child) else {
+                continue;
+            };
+            if text.sections[0].value != label || text.sections[0].style.color != color {
+                text.sections[0].value = label.clone();
+                text.sections[0].style.color = color;
+            }
+        }
+    }
+}
+
+fn clean(mut commands: Commands, query: Query<Entity, With<ShopUi>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -12,6 +12,7 @@ pub struct Projectile {
     pub speed: f32,
     pub direction: Vec3,
     pub source_weapon: InventoryItem,
+    pub damage: i32,
 }
 
 #[derive(Bundle)]
@@ -0,0 +1,376 @@
+use bevy::{log, prelude::*};
+use rand::Rng;
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::enemy::EnemyType;
+use crate::game::GameRng;
+use crate::game_state::GameState;
+use crate::hit_feedback::EnemyHit;
+use crate::inventory::{InventoryItem, ItemTypeId};
+use crate::network::is_simulation_authority;
+use crate::player::PlayerControllerState;
+use crate::profile::Profile;
+use crate::wave_manager::{Wave, WaveState};
+
+mod ui;
+
+const ITEMS_OFFERED: usize = 3;
+// the first reroll of a visit, every one after it costs that much more again
+const REROLL_COST: u32 = 2;
+// the best tier the merchant can carry, reached at that much luck
+const MAX_TIER: i32 = 3;
+
+const SAND_DOLLAR_HEIGHT: f32 = 0.3;
+const PICKUP_RANGE: f32 = 1.5;
+const SPIN_SPEED: f32 = 2.0;
+
+pub struct ShopPlugin;
+
+impl Plugin for ShopPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Merchant>();
+        app.add_event::<ItemSold>();
+        app.add_systems(Startup, setup_sand_dollar_mesh);
+        app.add_systems(
+            OnEnter(WaveState::WAVE_END),
+            open_shop.run_if(is_simulation_authority),
+        );
+        app.add_systems(
+            Update,
+            (drop_sand_dollars, collect_sand_dollars)
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(
+            Update,
+            spin_sand_dollars.run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_plugins(ui::ShopUiPlugin);
+        app.add_console_command(
+            ConsoleCommand::new("shop", shop_command)
+                .usage("[on|off]")
+                .help("show or toggle the merchant that turns up between waves")
+                .arguments(["on", "off"]),
+        );
+        app.add_console_command(
+            ConsoleCommand::new("sanddollars", sand_dollars_command)
+                .usage("<amount>")
+                .help("set the sand dollars the hermits have to spend"),
+        );
+    }
+}
+
+// what the merchant asks for an item, buying it back goes for half
+pub fn price(item_type_id: ItemTypeId) -> u32 {
+    match item_type_id {
+        ItemTypeId::Heart => 3,
+        ItemTypeId::HealPotion | ItemTypeId::SpeedPotion => 4,
+        ItemTypeId::Bomb | ItemTypeId::Shield => 5,
+        ItemTypeId::Boomerang | ItemTypeId::WillSword => 6,
+        ItemTypeId::MidSword => 10,
+        ItemTypeId::AlexSword => 12,
+        ItemTypeId::HandGun => 14,
+        ItemTypeId::AlexBoomerang => 16,
+        ItemTypeId::SuperGun => 24,
+    }
+}
+
+// a merged weapon is worth all the copies that went into it, two more for every level
+pub fn sell_price(item: &InventoryItem) -> u32 {
+    let copies = 1 << (item.level.max(1) - 1);
+    (price(item.item_type_id) * copies / 2).max(1)
+}
+
+// lines up with the lucky drops, a sword at 1, the handgun at 2 and the super gun at 3
+fn tier(item_type_id: ItemTypeId) -> i32 {
+    match item_type_id {
+        ItemTypeId::MidSword | ItemTypeId::AlexSword => 1,
+        ItemTypeId::HandGun | ItemTypeId::AlexBoomerang => 2,
+        ItemTypeId::SuperGun => 3,
+        _ => 0,
+    }
+}
+
+// only in stock once the profile unlocked them as drops
+const UNLOCKED_ONLY: [ItemTypeId; 1] = [ItemTypeId::AlexBoomerang];
+
+// what the creatures leave behind
+fn sand_dollars(enemy_type: EnemyType) -> u32 {
+    match enemy_type {
+        EnemyType::Jellyfish | EnemyType::Shrimp => 1,
+        EnemyType::Urchin => 2,
+    }
+}
+
+#[derive(Component)]
+struct SandDollar(u32);
+
+// an item sold out of a hermit's shell, the host passes it on when it was an online hermit's
+#[derive(Event)]
+pub struct ItemSold {
+    pub player_index: usize,
+    pub item: InventoryItem,
+}
+
+#[derive(Resource)]
+struct SandDollarMesh {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+// The hermit merchant turns up once a wave is cleared and the next one waits until the hermits
+// are done with it. It sells for the sand dollars the creatures drop, what's bought goes straight
+// into the shell to be packed.
+#[derive(Resource)]
+pub struct Merchant {
+    pub enabled: bool,
+    // shared by the hermits, it's gone with the run
+    pub sand_dollars: u32,
+    open: bool,
+    // None once it's been bought
+    stock: Vec<Option<ItemTypeId>>,
+    rerolls: u32,
+    // the wave's luck and the profile's unlocked drops, kept for the rerolls
+    luck: i32,
+    unlocked: Vec<ItemTypeId>,
+}
+
+impl Default for Merchant {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            sand_dollars: 0,
+            open: false,
+            stock: Vec::new(),
+            rerolls: 0,
+            luck: 0,
+            unlocked: Vec::new(),
+        }
+    }
+}
+
+impl Merchant {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn stock(&self) -> &[Option<ItemTypeId>] {
+        &self.stock
+    }
+
+    pub fn reroll_cost(&self) -> u32 {
+        REROLL_COST * (self.rerolls + 1)
+    }
+
+    fn open(&mut self, luck: i32, unlocked: Vec<ItemTypeId>, rng: &mut impl Rng) {
+        self.open = true;
+        self.rerolls = 0;
+        self.luck = luck;
+        self.unlocked = unlocked;
+        self.roll_stock(rng);
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.stock.clear();
+    }
+
+    // Every item is from a tier between nothing special and the wave's luck, so a lucky wave can
+    // still turn up potions but a plain one never has the super gun.
+    fn roll_stock(&mut self, rng: &mut impl Rng) {
+        let max_tier = self.luck.clamp(0, MAX_TIER);
+        self.stock = (0..ITEMS_OFFERED)
+            .map(|_| {
+                let tier_rolled = rng.gen_range(0..=max_tier);
+                let candidates: Vec<ItemTypeId> = ItemTypeId::ALL
+                    .into_iter()
+                    .filter(|item_type_id| tier(*item_type_id) == tier_rolled)
+                    .filter(|item_type_id| {
+                        !UNLOCKED_ONLY.contains(item_type_id)
+                            || self.unlocked.contains(item_type_id)
+                    })
+                    .collect();
+                Some(candidates[rng.gen_range(0..candidates.len())])
+            })
+            .collect();
+    }
+
+    pub fn buy(&mut self, slot: usize) -> Result<ItemTypeId, String> {
+        let Some(Some(item_type_id)) = self.stock.get(slot).copied() else {
+            return Err("that one is sold out".to_string());
+        };
+        let cost = price(item_type_id);
+        if self.sand_dollars < cost {
+            return Err(format!(
+                "{item_type_id:?} costs {cost} sand dollars, there are {}",
+                self.sand_dollars
+            ));
+        }
+
+        self.sand_dollars -= cost;
+        self.stock[slot] = None;
+        log::info!("Bought {item_type_id:?} for {cost} sand dollars");
+        Ok(item_type_id)
+    }
+
+    pub fn reroll(&mut self, rng: &mut impl Rng) -> Result<(), String> {
+        let cost = self.reroll_cost();
+        if self.sand_dollars < cost {
+            return Err(format!(
+                "a reroll costs {cost} sand dollars, there are {}",
+                self.sand_dollars
+            ));
+        }
+
+        self.sand_dollars -= cost;
+        self.rerolls += 1;
+        self.roll_stock(rng);
+        Ok(())
+    }
+
+    pub fn sell(&mut self, item: &InventoryItem) {
+        let paid = sell_price(item);
+        self.sand_dollars += paid;
+        log::info!("Sold {:?} for {paid} sand dollars", item.item_type_id);
+    }
+}
+
+pub fn is_shopping(merchant: Res<Merchant>) -> bool {
+    merchant.open
+}
+
+fn setup_sand_dollar_mesh(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(SandDollarMesh {
+        mesh: meshes.add(Mesh::from(shape::Cylinder {
+            radius: 0.25,
+            height: 0.06,
+            resolution: 16,
+            segments: 1,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.95, 0.85, 0.6),
+            emissive: Color::rgb(0.3, 0.25, 0.1),
+            ..default()
+        }),
+    });
+}
+
+// A new run starts with an empty purse and a clean floor. After that the merchant shows up
+// after every wave, with better stock the luckier the wave was, and whatever's still lying
+// around is swept into the purse so the last kill can be spent right away.
+fn open_shop(
+    mut commands: Commands,
+    mut merchant: ResMut<Merchant>,
+    wave: Res<Wave>,
+    profile: Res<Profile>,
+    mut rng: ResMut<GameRng>,
+    left_behind: Query<(Entity, &SandDollar)>,
+) {
+    if wave.count == 0 {
+        if merchant.sand_dollars > 0 || merchant.open {
+            merchant.sand_dollars = 0;
+            merchant.close();
+        }
+        for (entity, _) in &left_behind {
+            commands.entity(entity).despawn();
+        }
+        return;
+    }
+    if !merchant.enabled {
+        return;
+    }
+
+    for (entity, sand_dollar) in &left_behind {
+        merchant.sand_dollars += sand_dollar.0;
+        commands.entity(entity).despawn();
+    }
+
+    merchant.open(
+        wave.wave_definition.luck(),
+        profile.dropped_items(),
+        rng.as_mut(),
+    );
+}
+
+fn drop_sand_dollars(
+    mut commands: Commands,
+    mut enemy_hit_reader: EventReader<EnemyHit>,
+    mesh: Res<SandDollarMesh>,
+    mut merchant: ResMut<Merchant>,
+) {
+    for hit in enemy_hit_reader.iter() {
+        if !hit.killed || !merchant.enabled {
+            continue;
+        }
+        // a kill landing once the shop is up goes straight into the purse
+        if merchant.is_open() {
+            merchant.sand_dollars += sand_dollars(hit.enemy_type);
+            continue;
+        }
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.mesh.clone(),
+                material: mesh.material.clone(),
+                transform: Transform::from_xyz(hit.position.x, SAND_DOLLAR_HEIGHT, hit.position.z)
+                    .with_rotation(Quat::from_rotation_x(90.0f32.to_radians())),
+                ..default()
+            },
+            SandDollar(sand_dollars(hit.enemy_type)),
+        ));
+    }
+}
+
+// any hermit walking over one picks it up for everyone
+fn collect_sand_dollars(
+    mut commands: Commands,
+    sand_dollars: Query<(Entity, &Transform, &SandDollar)>,
+    players: Query<&Transform, With<PlayerControllerState>>,
+    mut merchant: ResMut<Merchant>,
+) {
+    for (entity, transform, sand_dollar) in &sand_dollars {
+        let picked_up = players.iter().any(|player| {
+            player.translation.distance_squared(transform.translation) < PICKUP_RANGE * PICKUP_RANGE
+        });
+        if picked_up {
+            merchant.sand_dollars += sand_dollar.0;
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+fn spin_sand_dollars(time: Res<Time>, mut sand_dollars: Query<&mut Transform, With<SandDollar>>) {
+    for mut transform in &mut sand_dollars {
+        transform.rotate_y(SPIN_SPEED * time.delta_seconds());
+    }
+}
+
+fn shop_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let mut merchant = world.resource_mut::<Merchant>();
+    let Some(setting) = args.first() else {
+        return Ok(format!(
+            "shop {}, {} sand dollars",
+            if merchant.enabled { "on" } else { "off" },
+            merchant.sand_dollars
+        ));
+    };
+
+    merchant.enabled = match *setting {
+        "on" => true,
+        "off" => false,
+        other => return Err(format!("expected on or off, got `{other}`")),
+    };
+    if !merchant.enabled {
+        merchant.close();
+    }
+    Ok(format!("shop {setting}"))
+}
+
+fn sand_dollars_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let amount: u32 = parse_arg(args, 0, "amount")?;
+    world.resource_mut::<Merchant>().sand_dollars = amount;
+    Ok(format!("sand dollars set to {amount}"))
+}
@@ -0,0 +1,394 @@
+use bevy::ui::PositionType::Absolute;
+use bevy::{log, prelude::*};
+
+use crate::collectable::ItemCollectEvent;
+use crate::endless::is_choosing_modifier;
+use crate::game::GameRng;
+use crate::game_state::GameState;
+use crate::inventory::Inventory;
+use crate::item_spawner::catalog_item;
+use crate::player::combat::PlayerCombatState;
+use crate::player::PlayerControllerState;
+use crate::shop::{price, sell_price, ItemSold, Merchant};
+use crate::world_item::{refresh_held_weapon, WeaponHolder};
+
+pub struct ShopUiPlugin;
+
+impl Plugin for ShopUiPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Buyer>();
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            (
+                update_purse,
+                show_shop.run_if(not(is_choosing_modifier)),
+                use_shop,
+                update_button_highlight,
+            )
+                .chain()
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+    }
+}
+
+#[derive(Component)]
+struct Purse;
+
+#[derive(Component)]
+struct ShopPanel;
+
+// the hermit whose shell gets what's bought
+#[derive(Resource, Default)]
+struct Buyer(usize);
+
+#[derive(Component, Copy, Clone, PartialEq)]
+enum ShopButton {
+    // the slot in the stock
+    Buy(usize),
+    // picks the hermit buying
+    Buyer(usize),
+    Reroll,
+    // the hermit and the item's index in their shell
+    Sell(usize, usize),
+    Leave,
+}
+
+// along the top, empty with the shop off
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Px(12.0),
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(Purse)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 32.0,
+                    color: Color::BLACK,
+                },
+            ));
+        });
+}
+
+fn update_purse(
+    merchant: Res<Merchant>,
+    purses: Query<&Children, With<Purse>>,
+    mut texts: Query<&mut Text>,
+) {
+    let label = if merchant.enabled {
+        format!("{} sand dollars", merchant.sand_dollars)
+    } else {
+        String::new()
+    };
+
+    for children in &purses {
+        for child in children {
+            if let Ok(mut text) = texts.get_mut(*child) {
+                if text.sections[0].value != label {
+                    text.sections[0].value = label.clone();
+                }
+            }
+        }
+    }
+}
+
+// Built again whenever the stock, the purse, the buyer or a shell changes. An endless modifier
+// pick goes first, the merchant waits for it.
+fn show_shop(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    merchant: Res<Merchant>,
+    inventory: Res<Inventory>,
+    buyer: Res<Buyer>,
+    players: Query<&PlayerControllerState>,
+    panels: Query<Entity, With<ShopPanel>>,
+) {
+    let up_to_date = !panels.is_empty()
+        && !merchant.is_changed()
+        && !inventory.is_changed()
+        && !buyer.is_changed();
+    if merchant.is_open() && up_to_date {
+        return;
+    }
+    for entity in &panels {
+        commands.entity(entity).despawn_recursive();
+    }
+    if !merchant.is_open() {
+        return;
+    }
+
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+    let text_style = |font_size: f32, color: Color| TextStyle {
+        font: font.clone(),
+        font_size,
+        color,
+    };
+    let light = Color::rgb(0.9, 0.9, 0.9);
+    let mut hermits: Vec<usize> = players
+        .iter()
+        .map(|controller| controller.player_index)
+        .collect();
+    hermits.sort();
+    // what can't be paid for yet is greyed out
+    let affordable = |cost: u32| {
+        if merchant.sand_dollars >= cost {
+            light
+        } else {
+            Color::GRAY
+        }
+    };
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                width: Val::Percent(100.0),
+                top: Val::Percent(15.0),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(15.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(ShopPanel)
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "The hermit merchant",
+                text_style(40.0, Color::BLACK),
+            ));
+
+            row(parent, |parent| {
+                for (slot, item_type_id) in merchant.stock().iter().enumerate() {
+                    let (label, color) = match item_type_id {
+                        Some(item_type_id) => (
+                            format!("{item_type_id:?}\n{} sand dollars", price(*item_type_id)),
+                            affordable(price(*item_type_id)),
+                        ),
+                        None => ("Sold".to_string(), Color::GRAY),
+                    };
+                    spawn_button(
+                        parent,
+                        ShopButton::Buy(slot),
+                        (220.0, 110.0),
+                        label,
+                        text_style(28.0, color),
+                    );
+                }
+            });
+
+            // with a single hermit there's nobody else to buy for
+            if hermits.len() > 1 {
+                row(parent, |parent| {
+                    for player_index in &hermits {
+                        let color = if *player_index == buyer.0 {
+                            Color::rgb(1.0, 0.85, 0.2)
+                        } else {
+                            light
+                        };
+                        spawn_button(
+                            parent,
+                            ShopButton::Buyer(*player_index),
+                            (160.0, 50.0),
+                            format!("For hermit {}", player_index + 1),
+                            text_style(20.0, color),
+                        );
+                    }
+                });
+            }
+
+            row(parent, |parent| {
+                spawn_button(
+                    parent,
+                    ShopButton::Reroll,
+                    (220.0, 80.0),
+                    format!("Reroll\n{} sand dollars", merchant.reroll_cost()),
+                    text_style(24.0, affordable(merchant.reroll_cost())),
+                );
+                spawn_button(
+                    parent,
+                    ShopButton::Leave,
+                    (220.0, 80.0),
+                    "Next wave".to_string(),
+                    text_style(28.0, light),
+                );
+            });
+
+            // every hermit's shell, online ones too, sold back for half
+            for player_index in &hermits {
+                let items = inventory.items_of(*player_index);
+                if items.is_empty() {
+                    continue;
+                }
+                parent.spawn(TextBundle::from_section(
+                    format!("Hermit {} sells", player_index + 1),
+                    text_style(28.0, Color::BLACK),
+                ));
+                row(parent, |parent| {
+                    for (index, item) in items.iter().enumerate() {
+                        spawn_button(
+                            parent,
+                            ShopButton::Sell(*player_index, index),
+                            (140.0, 60.0),
+                            format!("{:?}\n+{}", item.item_type_id, sell_price(item)),
+                            text_style(18.0, light),
+                        );
+                    }
+                });
+            }
+        });
+}
+
+fn row(parent: &mut ChildBuilder, children: impl FnOnce(&mut ChildBuilder)) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                max_width: Val::Px(900.0),
+                flex_wrap: FlexWrap::Wrap,
+                justify_content: JustifyContent::Center,
+                column_gap: Val::Px(15.0),
+                row_gap: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(children);
+}
+
+fn spawn_button(
+    parent: &mut ChildBuilder,
+    button: ShopButton,
+    (width, height): (f32, f32),
+    label: String,
+    style: TextStyle,
+) {
+    parent
+        .spawn(ButtonBundle {
+            style: Style {
+                width: Val::Px(width),
+                height: Val::Px(height),
+                border: UiRect::all(Val::Px(5.0)),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+            ..default()
+        })
+        .insert(button)
+        .with_children(|parent| {
+            parent.spawn(
+                TextBundle::from_section(label, style).with_text_alignment(TextAlignment::Center),
+            );
+        });
+}
+
+// A bought item is picked up by the chosen hermit, which takes them to the packing screen like
+// any other pickup. Selling only changes the seller's hand, if the weapon held was the one sold,
+// an online hermit's shell is told about it over the network.
+#[allow(clippy::too_many_arguments)]
+fn use_shop(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    buttons: Query<(&Interaction, &ShopButton), Changed<Interaction>>,
+    mut players: Query<(
+        &mut WeaponHolder,
+        &Transform,
+        &mut PlayerCombatState,
+        &PlayerControllerState,
+    )>,
+    mut merchant: ResMut<Merchant>,
+    mut inventory: ResMut<Inventory>,
+    mut buyer: ResMut<Buyer>,
+    mut rng: ResMut<GameRng>,
+    mut item_collect_writer: EventWriter<ItemCollectEvent>,
+    mut item_sold_writer: EventWriter<ItemSold>,
+) {
+    // the hermit buying left the game
+    if buyer.0 != 0
+        && !players
+            .iter()
+            .any(|(_, _, _, controller)| controller.player_index == buyer.0)
+    {
+        buyer.0 = 0;
+    }
+
+    for (interaction, button) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match button {
+            ShopButton::Buy(slot) => match merchant.buy(*slot) {
+                Ok(item_type_id) => {
+                    item_collect_writer.send(ItemCollectEvent(catalog_item(item_type_id), buyer.0))
+                }
+                Err(error) => log::info!("{error}"),
+            },
+            ShopButton::Buyer(player_index) => buyer.0 = *player_index,
+            ShopButton::Reroll => {
+                if let Err(error) = merchant.reroll(rng.as_mut()) {
+                    log::info!("{error}");
+                }
+            }
+            ShopButton::Sell(player_index, index) => {
+                let items = inventory.items_of_mut(*player_index);
+                if *index >= items.len() {
+                    continue;
+                }
+                let item = items.remove(*index);
+                merchant.sell(&item);
+                item_sold_writer.send(ItemSold {
+                    player_index: *player_index,
+                    item,
+                });
+
+                let seller = players
+                    .iter_mut()
+                    .find(|(_, _, _, controller)| controller.player_index == *player_index);
+                if let Some((mut weapon_holder, transform, mut combat_state, _)) = seller {
+                    refresh_held_weapon(
+                        inventory.items_of(*player_index),
+                        &mut weapon_holder,
+                        transform,
+                        &mut combat_state,
+                        &mut commands,
+                        &mut meshes,
+                        &mut materials,
+                    );
+                }
+            }
+            ShopButton::Leave => merchant.close(),
+        }
+    }
+}
+
+fn update_button_highlight(mut buttons: Query<(&Interaction, &mut BorderColor), With<ShopButton>>) {
+    for (interaction, mut border) in &mut buttons {
+        let color = if *interaction == Interaction::Hovered {
+            Color::rgb(1.0, 0.85, 0.2)
+        } else {
+            Color::rgba(0.0, 0.0, 0.0, 0.0)
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
+
+fn clean(mut commands: Commands, query: Query<Entity, Or<(With<Purse>, With<ShopPanel>)>>) {
+    for entity in &query {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,382 @@
+use bevy::prelude::*;
+
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::enemy::Enemy;
+use crate::game_state::GameState;
+use crate::hit_feedback::{EnemyHit, HitSource};
+use crate::network::is_simulation_authority;
+use crate::player::combat::PlayerCombatState;
+use crate::player::{GodMode, PlayerControllerState};
+
+// how much of an effect's colour shows through the material it tints
+const TINT_STRENGTH: f32 = 0.5;
+
+pub struct StatusEffectPlugin;
+
+impl Plugin for StatusEffectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatusEffect>();
+        app.add_systems(
+            Update,
+            (apply_status_effects, tick_status_effects)
+                .chain()
+                .run_if(is_simulation_authority.and_then(in_state(GameState::FightingInArena))),
+        );
+        app.add_systems(Update, tint_affected.after(tick_status_effects));
+        app.add_systems(OnExit(GameState::FightingInArena), clear_status_effects);
+        app.add_console_command(
+            ConsoleCommand::new("effect", effect_command)
+                .usage("<effect> [seconds]")
+                .help("put a status effect on player one")
+                .arguments(StatusEffectKind::ALL.map(|kind| kind.name())),
+        );
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum StatusEffectKind {
+    Poison,
+    Slow,
+    Burn,
+    Stun,
+    Regen,
+    Haste,
+}
+
+impl StatusEffectKind {
+    pub const ALL: [StatusEffectKind; 6] = [
+        StatusEffectKind::Poison,
+        StatusEffectKind::Slow,
+        StatusEffectKind::Burn,
+        StatusEffectKind::Stun,
+        StatusEffectKind::Regen,
+        StatusEffectKind::Haste,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusEffectKind::Poison => "poison",
+            StatusEffectKind::Slow => "slow",
+            StatusEffectKind::Burn => "burn",
+            StatusEffectKind::Stun => "stun",
+            StatusEffectKind::Regen => "regen",
+            StatusEffectKind::Haste => "haste",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        StatusEffectKind::ALL
+            .into_iter()
+            .find(|kind| kind.name() == name)
+    }
+
+    // how long it lasts when whoever applies it doesn't say
+    pub fn duration(&self) -> f32 {
+        match self {
+            StatusEffectKind::Poison => 4.0,
+            StatusEffectKind::Slow => 2.0,
+            StatusEffectKind::Burn => 3.0,
+            StatusEffectKind::Stun => 0.75,
+            StatusEffectKind::Regen => 5.0,
+            StatusEffectKind::Haste => 5.0,
+        }
+    }
+
+    // seconds between two ticks, for the effects that change hp
+    fn tick_rate(&self) -> Option<f32> {
+        match self {
+            StatusEffectKind::Poison => Some(1.0),
+            StatusEffectKind::Burn => Some(0.5),
+            StatusEffectKind::Regen => Some(1.0),
+            _ => None,
+        }
+    }
+
+    // hp change per tick and per stack
+    fn hp_per_tick(&self) -> i32 {
+        match self {
+            StatusEffectKind::Poison | StatusEffectKind::Burn => -1,
+            StatusEffectKind::Regen => 1,
+            _ => 0,
+        }
+    }
+
+    // poison piles up, everything else only refreshes
+    fn max_stacks(&self) -> u32 {
+        match self {
+            StatusEffectKind::Poison => 3,
+            _ => 1,
+        }
+    }
+
+    fn speed_multiplier(&self) -> f32 {
+        match self {
+            StatusEffectKind::Slow => 0.5,
+            StatusEffectKind::Stun => 0.0,
+            StatusEffectKind::Haste => 1.6,
+            _ => 1.0,
+        }
+    }
+
+    fn tint(&self) -> Color {
+        match self {
+            StatusEffectKind::Poison => Color::rgb(0.3, 1.0, 0.3),
+            StatusEffectKind::Slow => Color::rgb(0.4, 0.6, 1.0),
+            StatusEffectKind::Burn => Color::rgb(1.0, 0.45, 0.1),
+            StatusEffectKind::Stun => Color::rgb(1.0, 1.0, 0.3),
+            StatusEffectKind::Regen => Color::rgb(1.0, 0.5, 0.8),
+            StatusEffectKind::Haste => Color::rgb(0.6, 1.0, 1.0),
+        }
+    }
+}
+
+pub struct StatusEffect {
+    pub kind: StatusEffectKind,
+    pub stacks: u32,
+    duration: Timer,
+    tick: Option<Timer>,
+}
+
+impl StatusEffect {
+    pub fn remaining_secs(&self) -> f32 {
+        self.duration.remaining_secs()
+    }
+}
+
+// every effect running on a hermit or a creature, at most one entry per kind
+#[derive(Component, Default)]
+pub struct StatusEffects(Vec<StatusEffect>);
+
+impl StatusEffects {
+    // Stacking rules: poison adds a stack up to its cap, the rest keep one. Either way the
+    // timer goes to whichever is longer, except for a running stun so it can't be chained.
+    pub fn apply(&mut self, kind: StatusEffectKind, seconds: f32) {
+        if let Some(index) = self.0.iter().position(|effect| effect.kind == kind) {
+            let mut effect = self.0.remove(index);
+            effect.stacks = (effect.stacks + 1).min(kind.max_stacks());
+            if kind != StatusEffectKind::Stun && effect.remaining_secs() < seconds {
+                effect.duration = Timer::from_seconds(seconds, TimerMode::Once);
+            }
+            // the last one applied is the one that tints
+            self.0.push(effect);
+            return;
+        }
+
+        self.0.push(StatusEffect {
+            kind,
+            stacks: 1,
+            duration: Timer::from_seconds(seconds, TimerMode::Once),
+            tick: kind
+                .tick_rate()
+                .map(|rate| Timer::from_seconds(rate, TimerMode::Repeating)),
+        });
+    }
+
+    pub fn has(&self, kind: StatusEffectKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+
+    pub fn is_stunned(&self) -> bool {
+        self.has(StatusEffectKind::Stun)
+    }
+
+    pub fn speed_multiplier(&self) -> f32 {
+        self.0
+            .iter()
+            .map(|effect| effect.kind.speed_multiplier())
+            .product()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &StatusEffect> {
+        self.0.iter()
+    }
+
+    fn tint(&self) -> Option<Color> {
+        self.0.last().map(|effect| effect.kind.tint())
+    }
+
+    // moves every timer forward and gives back the hp change of the ticks that went off
+    fn tick(&mut self, delta: std::time::Duration) -> i32 {
+        let mut hp_change = 0;
+        for effect in &mut self.0 {
+            if let Some(tick) = effect.tick.as_mut() {
+                let ticks = tick.tick(delta).times_finished_this_tick() as i32;
+                hp_change += ticks * effect.kind.hp_per_tick() * effect.stacks as i32;
+            }
+            effect.duration.tick(delta);
+        }
+        self.0.retain(|effect| !effect.duration.finished());
+        hp_change
+    }
+}
+
+// weapons and creatures ask for an effect, it lands at the start of the next tick
+#[derive(Event)]
+pub struct ApplyStatusEffect {
+    pub target: Entity,
+    pub kind: StatusEffectKind,
+    pub seconds: f32,
+}
+
+impl ApplyStatusEffect {
+    pub fn new(target: Entity, kind: StatusEffectKind) -> Self {
+        Self {
+            target,
+            kind,
+            seconds: kind.duration(),
+        }
+    }
+}
+
+// the material a tinted mesh had before, put back once the effects wear off
+#[derive(Component)]
+struct Tinted {
+    original: Handle<StandardMaterial>,
+    color: Color,
+}
+
+fn apply_status_effects(
+    mut events: EventReader<ApplyStatusEffect>,
+    mut targets: Query<&mut StatusEffects>,
+) {
+    for event in &mut events {
+        if let Ok(mut effects) = targets.get_mut(event.target) {
+            effects.apply(event.kind, event.seconds);
+        }
+    }
+}
+
+// Damage over time never takes the last heart of a hermit, only hits can down them. Creatures
+// have no such luck.
+pub fn tick_status_effects(
+    mut commands: Commands,
+    mut affected: Query<(
+        Entity,
+        &Transform,
+        &mut StatusEffects,
+        Option<&mut PlayerCombatState>,
+        Option<&mut Enemy>,
+    )>,
+    mut hit_writer: EventWriter<EnemyHit>,
+    god_mode: Res<GodMode>,
+    time: Res<Time>,
+) {
+    for (entity, transform, mut effects, player, enemy) in &mut affected {
+        let hp_change = effects.tick(time.delta());
+        if hp_change == 0 {
+            continue;
+        }
+
+        if let Some(mut player) = player {
+            if player.is_downed() || (hp_change < 0 && god_mode.0) {
+                continue;
+            }
+            let floor = player.current_hp.min(1);
+            player.current_hp = (player.current_hp + hp_change).clamp(floor, player.max_hp);
+        } else if let Some(mut enemy) = enemy {
+            enemy.hp += hp_change;
+            if hp_change < 0 {
+                hit_writer.send(EnemyHit {
+                    position: transform.translation,
+                    damage: -hp_change,
+                    critical: false,
+                    enemy_type: enemy.enemy_type,
+                    killed: enemy.hp <= 0,
+                    source: HitSource::StatusEffect,
+                });
+            }
+            if enemy.hp <= 0 {
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+fn mix(base: Color, tint: Color) -> Color {
+    let lerp = |a: f32, b: f32| a + (b - a) * TINT_STRENGTH;
+    Color::rgba(
+        lerp(base.r(), tint.r()),
+        lerp(base.g(), tint.g()),
+        lerp(base.b(), tint.b()),
+        base.a(),
+    )
+}
+
+// Tints every mesh of an affected hermit or creature with its latest effect. Creatures share
+// their material, so each tinted mesh gets a copy of its own until the effects are gone.
+fn tint_affected(
+    mut commands: Commands,
+    affected: Query<(Entity, &StatusEffects)>,
+    children: Query<&Children>,
+    mut meshes: Query<(&mut Handle<StandardMaterial>, Option<&Tinted>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, effects) in &affected {
+        let tint = effects.tint();
+        for mesh_entity in std::iter::once(entity).chain(children.iter_descendants(entity)) {
+            let Ok((mut material, tinted)) = meshes.get_mut(mesh_entity) else {
+                continue;
+            };
+
+            match (tint, tinted) {
+                (Some(color), None) => {
+                    let Some(original) = materials.get(&*material).cloned() else {
+                        continue;
+                    };
+                    let base_color = mix(original.base_color, color);
+                    commands.entity(mesh_entity).insert(Tinted {
+                        original: material.clone(),
+                        color,
+                    });
+                    *material = materials.add(StandardMaterial {
+                        base_color,
+                        ..original
+                    });
+                }
+                (Some(color), Some(tinted)) if tinted.color != color => {
+                    let Some(base) = materials.get(&tinted.original).map(|m| m.base_color) else {
+                        continue;
+                    };
+                    if let Some(copy) = materials.get_mut(&*material) {
+                        copy.base_color = mix(base, color);
+                    }
+                    commands.entity(mesh_entity).insert(Tinted {
+                        original: tinted.original.clone(),
+                        color,
+                    });
+                }
+                (None, Some(tinted)) => {
+                    *material = tinted.original.clone();
+                    commands.entity(mesh_entity).remove::<Tinted>();
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// nothing carries over into the packing screen or the next run
+fn clear_status_effects(mut affected: Query<&mut StatusEffects>) {
+    for mut effects in &mut affected {
+        effects.0.clear();
+    }
+}
+
+fn effect_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let name: String = parse_arg(args, 0, "effect")?;
+    let kind =
+        StatusEffectKind::from_name(&name).ok_or_else(|| format!("no effect called `{name}`"))?;
+    let seconds: f32 = if args.len() > 1 {
+        parse_arg(args, 1, "seconds")?
+    } else {
+        kind.duration()
+    };
+
+    let mut players = world.query::<(&PlayerControllerState, &mut StatusEffects)>();
+    let (_, mut effects) = players
+        .iter_mut(world)
+        .find(|(controller, _)| controller.player_index == 0)
+        .ok_or("no hermit to put it on")?;
+    effects.apply(kind, seconds);
+    Ok(format!("{name} for {seconds}s"))
+}
@@ -0,0 +1,30 @@
+// Small text files that outlive the game, like the profile and the leaderboard. On the web there's
+// no file system, so they go into the browser's local storage under the same name.
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn read_text(name: &str) -> Option<String> {
+    std::fs::read_to_string(name).ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn write_text(name: &str, text: &str) -> Result<(), String> {
+    std::fs::write(name, text).map_err(|error| format!("could not write {name}: {error}"))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn read_text(name: &str) -> Option<String> {
+    local_storage()?.get_item(name).ok()?
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn write_text(name: &str, text: &str) -> Result<(), String> {
+    local_storage()
+        .ok_or_else(|| "the browser has no local storage".to_string())?
+        .set_item(name, text)
+        .map_err(|error| format!("could not store {name}: {error:?}"))
+}
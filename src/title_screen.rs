@@ -2,8 +2,10 @@ use bevy::prelude::*;
 
 use crate::{
     asset_loader::{AssetLoaderPlugin, GameAssets},
+    config::MAX_LOCAL_PLAYERS,
     game::HolyCam,
     game_state::GameState,
+    player::LocalPlayerCount,
 };
 
 pub struct TitleScreenPlugin;
@@ -14,6 +16,15 @@ pub struct StartGameButton;
 #[derive(Component)]
 pub struct StartGameButtonText;
 
+#[derive(Component)]
+pub struct PlayerCountButton;
+
+#[derive(Component)]
+pub struct PlayerCountButtonText;
+
+#[derive(Component)]
+pub struct OnlineButton;
+
 #[derive(Component)]
 pub struct TitleScreenUi;
 
@@ -32,6 +43,14 @@ impl Plugin for TitleScreenPlugin {
             Update,
             update_start_game_button_text.run_if(in_state(GameState::TitleScreen)),
         );
+        app.add_systems(
+            Update,
+            player_count_click_handler.run_if(in_state(GameState::TitleScreen)),
+        );
+        app.add_systems(
+            Update,
+            online_click_handler.run_if(in_state(GameState::TitleScreen)),
+        );
         app.add_systems(
             Update,
             move_holy_camera.run_if(in_state(GameState::TitleScreen)),
@@ -110,6 +129,71 @@ fn on_enter(mut commands: Commands, asset_server: Res<AssetServer>) {
                         .insert(TitleScreenUi);
                 });
 
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        top: Val::Px(200.0),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        // horizontally center child text
+                        justify_content: JustifyContent::Center,
+                        // vertically center child text
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+                    ..default()
+                })
+                .insert(PlayerCountButton)
+                .insert(TitleScreenUi)
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 32.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ))
+                        .insert(PlayerCountButtonText)
+                        .insert(TitleScreenUi);
+                });
+
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        border: UiRect::all(Val::Px(5.0)),
+                        top: Val::Px(200.0),
+                        margin: UiRect::left(Val::Px(20.0)),
+                        // horizontally center child text
+                        justify_content: JustifyContent::Center,
+                        // vertically center child text
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 1.0).into(),
+                    ..default()
+                })
+                .insert(OnlineButton)
+                .insert(TitleScreenUi)
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "Online",
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 32.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        ))
+                        .insert(TitleScreenUi);
+                });
+
             parent.spawn((TextBundle::from_section(
                 "Made in Rust!",
                 TextStyle {
@@ -181,7 +265,7 @@ fn start_game_click_handler(
         }
 
         if *interaction == Interaction::Pressed {
-            next_state.set(GameState::FightingInArena);
+            next_state.set(GameState::LevelSelect);
         }
     }
 
@@ -190,7 +274,38 @@ fn start_game_click_handler(
             gamepad,
             button_type: GamepadButtonType::Start,
         }) {
-            next_state.set(GameState::FightingInArena);
+            next_state.set(GameState::LevelSelect);
+        }
+    }
+}
+
+fn player_count_click_handler(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PlayerCountButton>)>,
+    mut text_query: Query<&mut Text, With<PlayerCountButtonText>>,
+    mut player_count: ResMut<LocalPlayerCount>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            player_count.0 = player_count.0 % MAX_LOCAL_PLAYERS + 1;
+        }
+    }
+
+    for mut text in &mut text_query {
+        text.sections[0].value = if player_count.0 > 1 {
+            format!("{} Players", player_count.0)
+        } else {
+            "1 Player".to_string()
+        };
+    }
+}
+
+fn online_click_handler(
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<OnlineButton>)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            next_state.set(GameState::Lobby);
         }
     }
 }
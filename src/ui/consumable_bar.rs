@@ -0,0 +1,123 @@
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+
+use crate::game_state::GameState;
+use crate::inventory::Inventory;
+use crate::player::consumables::{charges, ConsumableEffect, Shield};
+use crate::player::{uses_keyboard_and_mouse, LocalPlayerCount, PlayerControllerState};
+use crate::status_effect::StatusEffects;
+use crate::ui::{hud_column_style, hud_columns};
+
+pub struct ConsumableBarPlugin;
+
+#[derive(Component)]
+struct ConsumableBar;
+
+// the charges line of the hermit with that player index
+#[derive(Component)]
+struct ConsumableBarText(usize);
+
+impl Plugin for ConsumableBarPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            update_consumable_bar.run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+    }
+}
+
+// along the bottom of every local hermit's column
+fn setup(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    player_count: Res<LocalPlayerCount>,
+) {
+    for player_index in hud_columns(&player_count) {
+        commands
+            .spawn(NodeBundle {
+                style: hud_column_style(
+                    player_index,
+                    Style {
+                        position_type: Absolute,
+                        bottom: Val::Px(12.0),
+                        ..default()
+                    },
+                ),
+                ..default()
+            })
+            .insert(ConsumableBar)
+            .with_children(|parent| {
+                parent
+                    .spawn(TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                            font_size: 24.0,
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                        },
+                    ))
+                    .insert(ConsumableBarText(player_index));
+            });
+    }
+}
+
+// a hermit's charges and whatever shield or status effect is still running on them, with the
+// keys or the pad buttons they press
+fn update_consumable_bar(
+    inventory: Res<Inventory>,
+    players: Query<(
+        &PlayerControllerState,
+        Option<&StatusEffects>,
+        Option<&Shield>,
+    )>,
+    mut text_query: Query<(&mut Text, &ConsumableBarText)>,
+) {
+    for (mut text, ConsumableBarText(player_index)) in &mut text_query {
+        let Some((_, effects, shield)) = players
+            .iter()
+            .find(|(controller, _, _)| controller.player_index == *player_index)
+        else {
+            continue;
+        };
+
+        let mut line = ConsumableEffect::ALL
+            .iter()
+            .map(|effect| {
+                let button = if uses_keyboard_and_mouse(*player_index) {
+                    format!("{:?}", effect.key())
+                } else {
+                    format!("{:?}", effect.gamepad_button())
+                };
+                format!(
+                    "[{button}] {} x{}",
+                    effect.label(),
+                    charges(&inventory, *player_index, *effect)
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("   ");
+
+        if let Some(Shield(timer)) = shield {
+            line += &format!("   Shielded {:.1}s", timer.remaining_secs());
+        }
+        for effect in effects.iter().flat_map(|effects| effects.iter()) {
+            line += &format!("   {}", effect.kind.name());
+            if effect.stacks > 1 {
+                line += &format!(" x{}", effect.stacks);
+            }
+            line += &format!(" {:.1}s", effect.remaining_secs());
+        }
+
+        if text.sections[0].value != line {
+            text.sections[0].value = line;
+        }
+    }
+}
+
+fn clean(mut commands: Commands, bars: Query<Entity, With<ConsumableBar>>) {
+    for entity in &bars {
+        commands.entity(entity).despawn_recursive();
+    }
+}
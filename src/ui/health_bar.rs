@@ -1,108 +1,118 @@
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
 
-use crate::inventory::ItemType::NON_WEAPON;
-use crate::inventory::{InventoryItem, ItemTypeId};
+use crate::game_state::GameState;
 use crate::player::combat::PlayerCombatState;
-use crate::{game::HolyCam, game_camera_controller, game_state::GameState};
+use crate::player::{LocalPlayerCount, PlayerControllerState};
+use crate::ui::item_icon::{IconSlot, ItemIcons};
+use crate::ui::{hud_column_style, hud_columns};
+
+pub const HEART_SIZE: f32 = 40.0;
+
+const FULL_HEART: Color = Color::WHITE;
+const EMPTY_HEART: Color = Color::rgba(0.3, 0.3, 0.3, 0.6);
 
 pub struct HealthBarPlugin;
 
+// the hearts of the hermit with that player index
+#[derive(Component)]
+struct HealthBar(usize);
+
 #[derive(Component)]
-pub struct UIHeart;
+struct UIHeart;
 
 impl Plugin for HealthBarPlugin {
     fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
         app.add_systems(
             Update,
-            update_ui
-                .run_if(in_state(GameState::FightingInArena))
-                .after(game_camera_controller::set_camera),
+            update_hearts.run_if(in_state(GameState::FightingInArena)),
         );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
     }
 }
 
-fn update_ui(
+// a row of hearts at the top of every local hermit's column
+fn setup(mut commands: Commands, player_count: Res<LocalPlayerCount>) {
+    for player_index in hud_columns(&player_count) {
+        commands
+            .spawn(NodeBundle {
+                style: hud_column_style(
+                    player_index,
+                    Style {
+                        position_type: Absolute,
+                        top: Val::Px(12.0),
+                        column_gap: Val::Px(4.0),
+                        ..default()
+                    },
+                ),
+                ..default()
+            })
+            .insert(HealthBar(player_index));
+    }
+}
+
+// one heart per max hp, greyed out once lost
+fn update_hearts(
     mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    cam_query: Query<&Transform, With<HolyCam>>,
-    mut heart_query: Query<
-        (
-            &mut Transform,
-            Entity,
-            &UIHeart,
-            &mut Handle<StandardMaterial>,
-        ),
-        Without<HolyCam>,
-    >,
-    player_query: Query<&PlayerCombatState>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    icons: Res<ItemIcons>,
+    players: Query<(&PlayerCombatState, &PlayerControllerState)>,
+    bars: Query<(Entity, &HealthBar, Option<&Children>)>,
+    mut hearts: Query<&mut BackgroundColor, With<UIHeart>>,
 ) {
-    let heart = InventoryItem::from((
-        (0, 0, 0),
-        vec![
-            (0, 0, 0),
-            (0, 0, 1),
-            (-1, 0, 0),
-            (1, 0, 0),
-            (-1, 0, -1),
-            (1, 0, -1),
-        ],
-        Color::rgba(1.0, 0.1, 0.1, 1.0),
-        NON_WEAPON,
-        ItemTypeId::Heart,
-    ));
-
-    let camera_transform = cam_query.single();
-    let ui_entity_position = camera_transform.translation + camera_transform.forward() * 10.0;
-    let window = window_query.single();
-    let resolution = window.resolution.clone();
+    for (bar, HealthBar(player_index), children) in &bars {
+        let Some((state, _)) = players
+            .iter()
+            .find(|(_, controller)| controller.player_index == *player_index)
+        else {
+            continue;
+        };
 
-    let aspect_ratio_vec = Vec2::new(resolution.width(), resolution.height()).normalize();
-    let distance = 1.5;
-    let ui_entity_scale = 0.075 * 0.5;
-    let mut ui_entity_transform = Transform::default()
-        .with_translation(
-            ui_entity_position
-                + camera_transform.left() * aspect_ratio_vec.x * distance * 0.5
-                + camera_transform.up() * aspect_ratio_vec.y * distance,
-        )
-        .with_scale(Vec3::splat(ui_entity_scale));
+        let children: Vec<Entity> = children.map(|c| c.to_vec()).unwrap_or_default();
+        let max_hp = state.max_hp.max(0) as usize;
 
-    let heart_count = player_query.single().max_hp;
-    let healthy_heart_count = player_query.single().current_hp;
+        for (i, heart) in children.iter().enumerate() {
+            if i >= max_hp {
+                commands.entity(*heart).despawn_recursive();
+                continue;
+            }
+            if let Ok(mut color) = hearts.get_mut(*heart) {
+                let wanted = heart_color(i, state.current_hp);
+                if color.0 != wanted {
+                    color.0 = wanted;
+                }
+            }
+        }
 
-    let mut i = 0;
-    for mut heart in heart_query.iter_mut() {
-        *heart.0 = ui_entity_transform;
-        ui_entity_transform.translation += camera_transform.left() * -0.15;
-        materials.get_mut(heart.3.as_ref()).unwrap().base_color = if i < healthy_heart_count {
-            Color::rgba(1.0, 0.1, 0.1, 1.0)
-        } else {
-            Color::rgba(0.7, 0.7, 0.7, 0.5)
-        };
-        i += 1;
+        for i in children.len()..max_hp {
+            let heart = commands
+                .spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(HEART_SIZE),
+                        height: Val::Px(HEART_SIZE),
+                        ..default()
+                    },
+                    image: UiImage::new(icons.image(IconSlot::Heart)),
+                    background_color: heart_color(i, state.current_hp).into(),
+                    ..default()
+                })
+                .insert(UIHeart)
+                .id();
+            commands.entity(bar).add_child(heart);
+        }
     }
+}
 
-    while i < heart_count {
-        ui_entity_transform.translation += camera_transform.left() * -0.15;
-        let mut heart2 = heart.clone();
-        heart2.color = if i < healthy_heart_count {
-            Color::rgba(1.0, 0.1, 0.1, 1.0)
-        } else {
-            Color::rgba(0.7, 0.7, 0.7, 0.5)
-        };
-        heart2.create_ui_entity(
-            ui_entity_transform,
-            false,
-            false,
-            true,
-            &mut commands,
-            &mut meshes,
-            &mut materials,
-            None,
-        );
+fn heart_color(index: usize, current_hp: i32) -> Color {
+    if (index as i32) < current_hp {
+        FULL_HEART
+    } else {
+        EMPTY_HEART
+    }
+}
 
-        i += 1;
+fn clean(mut commands: Commands, bars: Query<Entity, With<HealthBar>>) {
+    for entity in &bars {
+        commands.entity(entity).despawn_recursive();
     }
 }
@@ -0,0 +1,281 @@
+use std::f32::consts::FRAC_PI_4;
+
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::math::vec3;
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::camera::{RenderTarget, ScalingMode};
+use bevy::render::render_resource::{
+    Extent3d, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+};
+use bevy::render::view::RenderLayers;
+
+use crate::config::MAX_LOCAL_PLAYERS;
+use crate::game_state::GameState;
+use crate::inventory::{InventoryItem, ItemTypeId};
+use crate::item_spawner::catalog_item;
+
+pub const ICON_SIZE: u32 = 128;
+
+// one picture per weapon the wheel can hold, same as the number keys
+pub const WHEEL_SLOTS: usize = 9;
+
+// far below the arena, each slot gets its own spot and render layer so the cameras only see theirs
+const ICON_STAGE: Vec3 = vec3(0.0, -500.0, 0.0);
+
+// how much of the camera's 2x2 view the longest side of an item takes
+const ICON_FILL: f32 = 1.5;
+
+pub struct ItemIconPlugin;
+
+impl Plugin for ItemIconPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup);
+        app.add_systems(
+            Update,
+            (update_icon_models, update_icon_cameras).run_if(resource_changed::<ItemIcons>()),
+        );
+        // the pictures are only looked at during a fight
+        app.add_systems(
+            OnEnter(GameState::FightingInArena),
+            set_icons_active::<true>,
+        );
+        app.add_systems(
+            OnExit(GameState::FightingInArena),
+            set_icons_active::<false>,
+        );
+    }
+}
+
+// the voxel items the HUD shows as pictures, the weapons once per local hermit
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum IconSlot {
+    Heart,
+    CurrentWeapon(usize),
+    NextWeapon(usize),
+    Wheel(usize),
+}
+
+impl IconSlot {
+    fn all() -> impl Iterator<Item = IconSlot> {
+        std::iter::once(IconSlot::Heart)
+            .chain((0..MAX_LOCAL_PLAYERS).map(IconSlot::CurrentWeapon))
+            .chain((0..MAX_LOCAL_PLAYERS).map(IconSlot::NextWeapon))
+            .chain((0..WHEEL_SLOTS).map(IconSlot::Wheel))
+    }
+
+    fn index(&self) -> usize {
+        match self {
+            IconSlot::Heart => 0,
+            IconSlot::CurrentWeapon(player_index) => 1 + player_index,
+            IconSlot::NextWeapon(player_index) => 1 + MAX_LOCAL_PLAYERS + player_index,
+            IconSlot::Wheel(i) => 1 + 2 * MAX_LOCAL_PLAYERS + i,
+        }
+    }
+
+    // layer 0 is the game itself
+    fn layer(&self) -> RenderLayers {
+        RenderLayers::layer(1 + self.index() as u8)
+    }
+
+    fn stage(&self) -> Vec3 {
+        ICON_STAGE + Vec3::X * 20.0 * self.index() as f32
+    }
+
+    // weapons point up and to the right, the heart faces the camera the right way up
+    fn rotation(&self) -> Quat {
+        match self {
+            IconSlot::Heart => Quat::from_rotation_x(70.0f32.to_radians()),
+            _ => Quat::from_rotation_z(-FRAC_PI_4) * Quat::from_rotation_x(-70.0f32.to_radians()),
+        }
+    }
+}
+
+struct Icon {
+    image: Handle<Image>,
+    camera: Entity,
+    model: Entity,
+    item: Option<InventoryItem>,
+    changed: bool,
+}
+
+// A camera per slot renders its item into an image that bevy_ui shows like any other picture.
+// Setting an item only rebuilds the mesh when it's a different one, and empty slots don't render.
+#[derive(Resource)]
+pub struct ItemIcons {
+    icons: Vec<Icon>,
+    active: bool,
+}
+
+impl ItemIcons {
+    pub fn image(&self, slot: IconSlot) -> Handle<Image> {
+        self.icons[slot.index()].image.clone()
+    }
+
+    pub fn show(&mut self, slot: IconSlot, item: Option<&InventoryItem>) {
+        let icon = &mut self.icons[slot.index()];
+        if icon.item.as_ref() != item {
+            icon.item = item.cloned();
+            icon.changed = true;
+        }
+    }
+}
+
+#[derive(Component)]
+struct IconCamera;
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    let size = Extent3d {
+        width: ICON_SIZE,
+        height: ICON_SIZE,
+        ..default()
+    };
+
+    let icons = IconSlot::all()
+        .map(|slot| {
+            let mut image = Image {
+                texture_descriptor: TextureDescriptor {
+                    label: None,
+                    size,
+                    dimension: TextureDimension::D2,
+                    format: TextureFormat::Bgra8UnormSrgb,
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    usage: TextureUsages::TEXTURE_BINDING
+                        | TextureUsages::COPY_DST
+                        | TextureUsages::RENDER_ATTACHMENT,
+                    view_formats: &[],
+                },
+                ..default()
+            };
+            image.resize(size);
+            let image = images.add(image);
+
+            let camera = commands
+                .spawn((
+                    Camera3dBundle {
+                        camera_3d: Camera3d {
+                            clear_color: ClearColorConfig::Custom(Color::NONE),
+                            ..default()
+                        },
+                        camera: Camera {
+                            target: RenderTarget::Image(image.clone()),
+                            order: -1,
+                            is_active: false,
+                            ..default()
+                        },
+                        projection: OrthographicProjection {
+                            scaling_mode: ScalingMode::Fixed {
+                                width: 2.0,
+                                height: 2.0,
+                            },
+                            ..default()
+                        }
+                        .into(),
+                        transform: Transform::from_translation(slot.stage() + Vec3::Z * 10.0)
+                            .looking_at(slot.stage(), Vec3::Y),
+                        ..default()
+                    },
+                    // the HUD would draw itself into its own pictures
+                    UiCameraConfig { show_ui: false },
+                    slot.layer(),
+                    IconCamera,
+                ))
+                .id();
+
+            let model = commands
+                .spawn((
+                    // the mesh and material come with the first item shown
+                    PbrBundle {
+                        visibility: Visibility::Hidden,
+                        ..default()
+                    },
+                    NotShadowCaster,
+                    slot.layer(),
+                ))
+                .id();
+
+            Icon {
+                image,
+                camera,
+                model,
+                item: None,
+                changed: false,
+            }
+        })
+        .collect();
+
+    let mut icons = ItemIcons {
+        icons,
+        active: false,
+    };
+    // the heart never changes
+    icons.show(IconSlot::Heart, Some(&catalog_item(ItemTypeId::Heart)));
+    commands.insert_resource(icons);
+}
+
+fn update_icon_models(
+    mut icons: ResMut<ItemIcons>,
+    mut models: Query<(
+        &mut Transform,
+        &mut Handle<Mesh>,
+        &mut Handle<StandardMaterial>,
+        &mut Visibility,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // only the flag is touched here, so this doesn't trigger itself next frame
+    let icons = icons.bypass_change_detection();
+
+    for slot in IconSlot::all() {
+        let icon = &mut icons.icons[slot.index()];
+        if !icon.changed {
+            continue;
+        }
+        icon.changed = false;
+
+        let Ok((mut transform, mut mesh, mut material, mut visibility)) =
+            models.get_mut(icon.model)
+        else {
+            continue;
+        };
+        let Some(item) = icon.item.as_ref() else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+
+        // the mesh is in voxels around the original points, center and fit it
+        let min = item.original_points.iter().copied().reduce(IVec3::min);
+        let max = item.original_points.iter().copied().reduce(IVec3::max);
+        let (Some(min), Some(max)) = (min, max) else {
+            *visibility = Visibility::Hidden;
+            continue;
+        };
+        let center = (min + max).as_vec3() / 2.0;
+        let scale = ICON_FILL / ((max - min).as_vec3() + Vec3::ONE).max_element();
+        let rotation = slot.rotation();
+
+        *transform = Transform::from_translation(slot.stage() - rotation * (center * scale))
+            .with_rotation(rotation)
+            .with_scale(Vec3::splat(scale));
+        *mesh = meshes.add(item.generate_mesh(true));
+        *material = materials.add(item.material());
+        *visibility = Visibility::Visible;
+    }
+}
+
+fn update_icon_cameras(icons: Res<ItemIcons>, mut cameras: Query<&mut Camera, With<IconCamera>>) {
+    for icon in &icons.icons {
+        if let Ok(mut camera) = cameras.get_mut(icon.camera) {
+            let active = icons.active && icon.item.is_some();
+            if camera.is_active != active {
+                camera.is_active = active;
+            }
+        }
+    }
+}
+
+fn set_icons_active<const ACTIVE: bool>(mut icons: ResMut<ItemIcons>) {
+    icons.active = ACTIVE;
+}
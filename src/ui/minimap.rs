@@ -0,0 +1,291 @@
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+use bevy::window::PrimaryWindow;
+
+use crate::collectable::Collectable;
+use crate::enemy::Enemy;
+use crate::game::HolyCam;
+use crate::game_state::GameState;
+use crate::player::PlayerControllerState;
+use crate::wave_manager::ARENA_DIMENSIONS_METERS;
+
+// pixels per metre of arena
+const MINIMAP_SCALE: f32 = 3.0;
+const MINIMAP_WIDTH: f32 = ARENA_DIMENSIONS_METERS[0] * 2.0 * MINIMAP_SCALE;
+const MINIMAP_HEIGHT: f32 = ARENA_DIMENSIONS_METERS[1] * 2.0 * MINIMAP_SCALE;
+const MINIMAP_BORDER: f32 = 2.0;
+
+const MAP_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.35);
+const BORDER_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.5);
+
+const MAX_BLIPS: usize = 96;
+const ITEM_BLIP_SIZE: f32 = 5.0;
+const ENEMY_BLIP_SIZE: f32 = 6.0;
+const PLAYER_BLIP_SIZE: f32 = 9.0;
+const ITEM_COLOR: Color = Color::rgb(1.0, 0.85, 0.2);
+const PLAYER_ONE_COLOR: Color = Color::WHITE;
+const OTHER_PLAYER_COLOR: Color = Color::rgb(0.6, 0.9, 1.0);
+
+// only what's this close to player one gets an arrow on the edge of the screen
+const MAX_INDICATORS: usize = 8;
+const INDICATOR_RANGE: f32 = 30.0;
+const INDICATOR_MARGIN: f32 = 24.0;
+const INDICATOR_SIZE: Vec2 = Vec2::new(20.0, 8.0);
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            (update_minimap, update_offscreen_indicators)
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+    }
+}
+
+// The blips and arrows are spawned once per fight and handed out every frame, the ones left
+// over are hidden. Blips are drawn in the order they're handed out, so players go last and end
+// up on top.
+#[derive(Resource)]
+struct MinimapPool {
+    blips: Vec<Entity>,
+    indicators: Vec<Entity>,
+}
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct MinimapBlip;
+
+#[derive(Component)]
+struct OffscreenIndicator;
+
+// bottom right corner, the top of the map is the far end of the arena like on screen
+fn setup(mut commands: Commands) {
+    let mut blips = Vec::with_capacity(MAX_BLIPS);
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                right: Val::Px(16.0),
+                bottom: Val::Px(12.0),
+                width: Val::Px(MINIMAP_WIDTH + 2.0 * MINIMAP_BORDER),
+                height: Val::Px(MINIMAP_HEIGHT + 2.0 * MINIMAP_BORDER),
+                border: UiRect::all(Val::Px(MINIMAP_BORDER)),
+                ..default()
+            },
+            background_color: MAP_COLOR.into(),
+            border_color: BORDER_COLOR.into(),
+            ..default()
+        })
+        .insert(Minimap)
+        .with_children(|parent| {
+            for _ in 0..MAX_BLIPS {
+                blips.push(
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                position_type: Absolute,
+                                ..default()
+                            },
+                            visibility: Visibility::Hidden,
+                            ..default()
+                        })
+                        .insert(MinimapBlip)
+                        .id(),
+                );
+            }
+        });
+
+    let indicators = (0..MAX_INDICATORS)
+        .map(|_| {
+            commands
+                .spawn(NodeBundle {
+                    style: Style {
+                        position_type: Absolute,
+                        width: Val::Px(INDICATOR_SIZE.x),
+                        height: Val::Px(INDICATOR_SIZE.y),
+                        ..default()
+                    },
+                    visibility: Visibility::Hidden,
+                    ..default()
+                })
+                .insert(OffscreenIndicator)
+                .id()
+        })
+        .collect();
+
+    commands.insert_resource(MinimapPool { blips, indicators });
+}
+
+// where a spot in the arena is on the map, from its top left corner
+fn map_position(position: Vec3) -> Vec2 {
+    let x = (position.x / (2.0 * ARENA_DIMENSIONS_METERS[0]) + 0.5).clamp(0.0, 1.0);
+    let y = (position.z / (2.0 * ARENA_DIMENSIONS_METERS[1]) + 0.5).clamp(0.0, 1.0);
+    Vec2::new(x * MINIMAP_WIDTH, y * MINIMAP_HEIGHT)
+}
+
+fn update_minimap(
+    pool: Option<Res<MinimapPool>>,
+    players: Query<(&GlobalTransform, &PlayerControllerState)>,
+    enemies: Query<(&GlobalTransform, &Enemy)>,
+    items: Query<(&GlobalTransform, &Collectable)>,
+    mut blips: Query<(&mut Style, &mut BackgroundColor, &mut Visibility), With<MinimapBlip>>,
+) {
+    let Some(pool) = pool else {
+        return;
+    };
+
+    // weapons in a hermit's hand aren't collectable and don't show up
+    let marks: Vec<(Vec3, f32, Color)> = items
+        .iter()
+        .filter(|(_, collectable)| collectable.0)
+        .map(|(transform, _)| (transform.translation(), ITEM_BLIP_SIZE, ITEM_COLOR))
+        .chain(enemies.iter().map(|(transform, enemy)| {
+            (
+                transform.translation(),
+                ENEMY_BLIP_SIZE,
+                enemy.enemy_type.color(),
+            )
+        }))
+        .chain(players.iter().map(|(transform, controller)| {
+            let color = if controller.player_index == 0 {
+                PLAYER_ONE_COLOR
+            } else {
+                OTHER_PLAYER_COLOR
+            };
+            (transform.translation(), PLAYER_BLIP_SIZE, color)
+        }))
+        .collect();
+    // with too many, the items are the first to go
+    let skipped = marks.len().saturating_sub(pool.blips.len());
+
+    for (i, entity) in pool.blips.iter().enumerate() {
+        let Ok((mut style, mut color, mut visibility)) = blips.get_mut(*entity) else {
+            continue;
+        };
+        let Some((position, size, mark_color)) = marks.get(skipped + i) else {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        };
+
+        let spot = map_position(*position);
+        style.left = Val::Px(spot.x - size / 2.0);
+        style.top = Val::Px(spot.y - size / 2.0);
+        style.width = Val::Px(*size);
+        style.height = Val::Px(*size);
+        color.0 = *mark_color;
+        if *visibility != Visibility::Inherited {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+// Creatures and loot near player one that the camera doesn't see get an arrow on the edge of
+// the screen, pointing their way and fading with distance.
+fn update_offscreen_indicators(
+    pool: Option<Res<MinimapPool>>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<HolyCam>>,
+    players: Query<(&GlobalTransform, &PlayerControllerState)>,
+    enemies: Query<(&GlobalTransform, &Enemy)>,
+    items: Query<(&GlobalTransform, &Collectable)>,
+    mut indicators: Query<
+        (
+            &mut Style,
+            &mut BackgroundColor,
+            &mut Transform,
+            &mut Visibility,
+        ),
+        With<OffscreenIndicator>,
+    >,
+) {
+    let Some(pool) = pool else {
+        return;
+    };
+    let (Ok(window), Ok((camera, camera_transform))) = (windows.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    let player = players
+        .iter()
+        .find(|(_, controller)| controller.player_index == 0)
+        .map(|(transform, _)| transform.translation());
+
+    let mut targets: Vec<(Vec3, Color, f32)> = Vec::new();
+    if let Some(player) = player {
+        targets = enemies
+            .iter()
+            .map(|(transform, enemy)| (transform.translation(), enemy.enemy_type.color()))
+            .chain(
+                items
+                    .iter()
+                    .filter(|(_, collectable)| collectable.0)
+                    .map(|(transform, _)| (transform.translation(), ITEM_COLOR)),
+            )
+            .map(|(position, color)| (position, color, position.distance(player)))
+            .filter(|(_, _, distance)| *distance < INDICATOR_RANGE)
+            .collect();
+        targets.sort_by(|a, b| a.2.total_cmp(&b.2));
+    }
+
+    let screen_size = Vec2::new(window.width(), window.height());
+    let center = screen_size / 2.0;
+    let mut offscreen = targets
+        .into_iter()
+        .filter_map(|(position, color, distance)| {
+            let screen = camera.world_to_viewport(camera_transform, position)?;
+            let visible = screen.cmpge(Vec2::ZERO).all() && screen.cmple(screen_size).all();
+            (!visible).then_some((screen, color, distance))
+        });
+
+    for entity in &pool.indicators {
+        let Ok((mut style, mut color, mut transform, mut visibility)) = indicators.get_mut(*entity)
+        else {
+            continue;
+        };
+        let Some((screen, target_color, distance)) = offscreen.next() else {
+            if *visibility != Visibility::Hidden {
+                *visibility = Visibility::Hidden;
+            }
+            continue;
+        };
+
+        // from the middle of the screen towards the target, until it hits the margin
+        let direction = (screen - center).normalize_or_zero();
+        let reach = center - Vec2::splat(INDICATOR_MARGIN);
+        let spot =
+            center + direction * (reach.x / direction.x.abs()).min(reach.y / direction.y.abs());
+
+        style.left = Val::Px(spot.x - INDICATOR_SIZE.x / 2.0);
+        style.top = Val::Px(spot.y - INDICATOR_SIZE.y / 2.0);
+        // the layout only moves nodes around, the rotation is left alone
+        transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+        color.0 = target_color.with_a(1.0 - 0.7 * distance / INDICATOR_RANGE);
+        if *visibility != Visibility::Visible {
+            *visibility = Visibility::Visible;
+        }
+    }
+}
+
+fn clean(
+    mut commands: Commands,
+    pool: Option<Res<MinimapPool>>,
+    minimaps: Query<Entity, With<Minimap>>,
+) {
+    for entity in &minimaps {
+        commands.entity(entity).despawn_recursive();
+    }
+    if let Some(pool) = pool {
+        for entity in &pool.indicators {
+            commands.entity(*entity).despawn_recursive();
+        }
+        commands.remove_resource::<MinimapPool>();
+    }
+}
@@ -1,2 +1,54 @@
+use std::ops::Range;
+
+use bevy::prelude::*;
+
+use crate::config::MAX_LOCAL_PLAYERS;
+use crate::player::LocalPlayerCount;
+use crate::ui::consumable_bar::ConsumableBarPlugin;
+use crate::ui::health_bar::HealthBarPlugin;
+use crate::ui::item_icon::ItemIconPlugin;
+use crate::ui::minimap::MinimapPlugin;
+use crate::ui::wave_counter::WaveCounterPlugin;
+use crate::ui::weapon_selector::WeaponSelectorPlugin;
+use crate::ui::weapon_wheel::WeaponWheelPlugin;
+
+pub mod consumable_bar;
 pub mod health_bar;
+pub mod item_icon;
+pub mod minimap;
+pub mod wave_counter;
 pub mod weapon_selector;
+pub mod weapon_wheel;
+
+const HUD_COLUMN_WIDTH: f32 = 480.0;
+
+// everything drawn over the arena during a fight
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            ItemIconPlugin,
+            HealthBarPlugin,
+            WeaponSelectorPlugin,
+            WeaponWheelPlugin,
+            WaveCounterPlugin,
+            ConsumableBarPlugin,
+            MinimapPlugin,
+        ));
+    }
+}
+
+// the player indices of the hermits playing on this screen, each gets a HUD column
+pub fn hud_columns(player_count: &LocalPlayerCount) -> Range<usize> {
+    0..player_count.0.clamp(1, MAX_LOCAL_PLAYERS)
+}
+
+// side by side from the left edge, the wave counter and the minimap keep the right one
+pub fn hud_column_style(player_index: usize, style: Style) -> Style {
+    Style {
+        left: Val::Px(16.0 + HUD_COLUMN_WIDTH * player_index as f32),
+        max_width: Val::Px(HUD_COLUMN_WIDTH - 16.0),
+        ..style
+    }
+}
@@ -0,0 +1,77 @@
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+
+use crate::game_state::GameState;
+use crate::wave_manager::{Wave, WaveState};
+
+pub struct WaveCounterPlugin;
+
+#[derive(Component)]
+struct WaveCounter;
+
+#[derive(Component)]
+struct WaveCounterText;
+
+impl Plugin for WaveCounterPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            update_wave_counter.run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
+    }
+}
+
+// top right corner
+fn setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                right: Val::Px(16.0),
+                top: Val::Px(12.0),
+                ..default()
+            },
+            ..default()
+        })
+        .insert(WaveCounter)
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 48.0,
+                        color: Color::BLACK,
+                    },
+                ))
+                .insert(WaveCounterText);
+        });
+}
+
+// `count` is the number of waves cleared, it goes up as soon as the last creature is gone
+fn update_wave_counter(
+    wave: Res<Wave>,
+    wave_state: Res<State<WaveState>>,
+    mut text_query: Query<&mut Text, With<WaveCounterText>>,
+) {
+    let label = match wave_state.get() {
+        WaveState::ACTIVE_WAVE_SPAWNING | WaveState::ACTIVE_WAVE => {
+            format!("Wave {}", wave.count + 1)
+        }
+        WaveState::WAVE_START | WaveState::WAVE_END => format!("Wave {} incoming", wave.count + 1),
+    };
+
+    for mut text in &mut text_query {
+        if text.sections[0].value != label {
+            text.sections[0].value = label.clone();
+        }
+    }
+}
+
+fn clean(mut commands: Commands, counters: Query<Entity, With<WaveCounter>>) {
+    for entity in &counters {
+        commands.entity(entity).despawn_recursive();
+    }
+}
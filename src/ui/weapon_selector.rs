@@ -1,294 +1,439 @@
-use bevy::input::keyboard::KeyboardInput;
-use bevy::{log, prelude::*, window::PrimaryWindow};
-
-use crate::inventory::{Inventory, InventoryItem};
-use crate::{
-    game::HolyCam,
-    game_camera_controller,
-    game_state::GameState,
-    inventory::ItemType::NON_WEAPON,
-    player::{combat::PlayerCombatState, PlayerControllerState},
-    world_item::WeaponHolder,
+use bevy::ui::PositionType::Absolute;
+use bevy::{log, prelude::*};
+
+use crate::game_state::GameState;
+use crate::inventory::{Inventory, InventoryItem, ItemType};
+use crate::network::RemotePlayer;
+use crate::player::combat::{PlayerCombatState, BASE_ATTACK_COOLDOWN};
+use crate::player::{
+    player_gamepads, uses_keyboard_and_mouse, LocalPlayerCount, PlayerControllerState,
 };
+use crate::ui::health_bar::HEART_SIZE;
+use crate::ui::item_icon::{IconSlot, ItemIcons, WHEEL_SLOTS};
+use crate::ui::{hud_column_style, hud_columns};
+use crate::world_item::WeaponHolder;
+
+const CURRENT_WEAPON_SIZE: f32 = 96.0;
+const NEXT_WEAPON_SIZE: f32 = 64.0;
+
+const SLOT_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.25);
+const HOVERED_SLOT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.3);
+const COOLDOWN_COLOR: Color = Color::rgb(0.9, 0.9, 0.9);
+const READY_COLOR: Color = Color::rgb(0.3, 0.9, 0.4);
 
 pub struct WeaponSelectorPlugin;
 
-#[derive(Resource)]
-pub struct NextWeapon {
-    value: Option<(Entity, InventoryItem)>,
-}
+// the held weapon and the one Tab or a click switches to, under each hermit's hearts
+#[derive(Component)]
+struct WeaponSelector;
+
+// the rest are keyed by the player index of the hermit whose column they're in
+#[derive(Component)]
+struct CurrentWeaponIcon(usize);
+
+#[derive(Component)]
+struct NextWeaponButton(usize);
+
+// fills up as the held weapon gets ready to attack again
+#[derive(Component)]
+struct CooldownFill(usize);
 
 impl Plugin for WeaponSelectorPlugin {
     fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
         app.add_systems(
             Update,
-            update_next_weapon
-                .run_if(in_state(GameState::FightingInArena))
-                .after(game_camera_controller::set_camera),
-        );
-
-        app.add_systems(
-            Update,
-            highlight_item_on_hover
-                .run_if(in_state(GameState::FightingInArena))
-                .after(update_next_weapon),
-        );
-        app.add_systems(
-            Update,
-            switch_weapon_on_tab
-                .run_if(in_state(GameState::FightingInArena))
-                .after(update_next_weapon),
+            (
+                update_weapon_icons,
+                update_cooldown,
+                switch_weapon_on_input,
+                switch_weapon_on_click,
+            )
+                .run_if(in_state(GameState::FightingInArena)),
         );
-
-        app.insert_resource(NextWeapon { value: None });
+        app.add_systems(OnExit(GameState::FightingInArena), clean);
     }
 }
 
-fn update_next_weapon(
+fn setup(
     mut commands: Commands,
-    mut next_weapon: ResMut<NextWeapon>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    mut param_set: ParamSet<(
-        Query<(
-            &mut Transform,
-            &mut Handle<Mesh>,
-            &mut Handle<StandardMaterial>,
-        )>,
-        Query<&Transform, With<PlayerControllerState>>,
-        Query<&Transform, With<HolyCam>>,
-    )>,
-    inventory: Res<Inventory>,
-    selected_weapon_query: Query<&WeaponHolder, &PlayerControllerState>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
+    icons: Res<ItemIcons>,
+    asset_server: Res<AssetServer>,
+    player_count: Res<LocalPlayerCount>,
 ) {
-    let new_next_weapon = query_next_weapon(&selected_weapon_query.single(), &inventory);
-
-    if new_next_weapon.is_none() {
-        if let Some((entity, _)) = next_weapon.value.as_mut() {
-            commands.entity(*entity).despawn();
-        }
-
-        next_weapon.value = None;
-        return;
-    }
-
-    let new_next_weapon = new_next_weapon.unwrap();
-
-    let camera_transform_query = param_set.p2();
-    let camera_transform = camera_transform_query.single();
-    let ui_entity_position = camera_transform.translation + camera_transform.forward() * 10.0;
-    let window = window_query.single();
-    // dbg!(window.cursor.);
-    let resolution = window.resolution.clone();
-    // dbg!(
-    //     resolution.width(),
-    //     resolution.height(),
-    //     window.cursor_position()
-    // );
-    // let aspect_ratio = resolution.width() / resolution.height();
-    let aspect_ratio_vec = Vec2::new(resolution.width(), resolution.height()).normalize();
-    let distance = 1.5;
-    let ui_entity_scale = 0.075 * 0.5;
-    let ui_entity_transform = Transform::default()
-        .with_translation(
-            ui_entity_position
-                + camera_transform.left() * aspect_ratio_vec.x * distance
-                + camera_transform.up() * aspect_ratio_vec.y * distance,
-        )
-        .with_scale(Vec3::splat(ui_entity_scale));
-
-    if next_weapon.value.clone().map(|(_, item)| item.item_type_id)
-        == Some(new_next_weapon.item_type_id)
-    {
-        let mut mesh_material_query = param_set.p0();
-        let mut existing_ui_entity = mesh_material_query
-            .get_mut(next_weapon.value.as_mut().unwrap().0)
-            .unwrap();
-        (*existing_ui_entity.0) = ui_entity_transform;
-        return;
+    for player_index in hud_columns(&player_count) {
+        spawn_selector(&mut commands, &icons, &asset_server, player_index);
     }
+}
 
-    let mesh_handle = meshes.add(new_next_weapon.generate_mesh(true));
-    let material_handle = materials.add(StandardMaterial::from(new_next_weapon.color));
+fn spawn_selector(
+    commands: &mut Commands,
+    icons: &ItemIcons,
+    asset_server: &AssetServer,
+    player_index: usize,
+) {
+    // player two switches with the north button
+    let switch_hint = if uses_keyboard_and_mouse(player_index) {
+        "Tab"
+    } else {
+        "Y"
+    };
 
-    let new_value = match next_weapon.value {
-        Some((entity, _)) => {
-            let mut mesh_material_query = param_set.p0();
-            let mut existing_ui_entity = mesh_material_query.get_mut(entity).unwrap();
-            (*existing_ui_entity.0) = ui_entity_transform;
-            (*existing_ui_entity.1) = mesh_handle;
-            (*existing_ui_entity.2) = material_handle;
+    commands
+        .spawn(NodeBundle {
+            style: hud_column_style(
+                player_index,
+                Style {
+                    position_type: Absolute,
+                    top: Val::Px(20.0 + HEART_SIZE),
+                    align_items: AlignItems::End,
+                    column_gap: Val::Px(8.0),
+                    ..default()
+                },
+            ),
+            ..default()
+        })
+        .insert(WeaponSelector)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        row_gap: Val::Px(4.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(ImageBundle {
+                            style: Style {
+                                width: Val::Px(CURRENT_WEAPON_SIZE),
+                                height: Val::Px(CURRENT_WEAPON_SIZE),
+                                ..default()
+                            },
+                            image: UiImage::new(icons.image(IconSlot::CurrentWeapon(player_index))),
+                            ..default()
+                        })
+                        .insert(CurrentWeaponIcon(player_index));
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(CURRENT_WEAPON_SIZE),
+                                height: Val::Px(6.0),
+                                ..default()
+                            },
+                            background_color: SLOT_COLOR.into(),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        width: Val::Percent(100.0),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    background_color: READY_COLOR.into(),
+                                    ..default()
+                                })
+                                .insert(CooldownFill(player_index));
+                        });
+                });
+
+            parent
+                .spawn(ButtonBundle {
+                    style: Style {
+                        width: Val::Px(NEXT_WEAPON_SIZE),
+                        height: Val::Px(NEXT_WEAPON_SIZE),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: SLOT_COLOR.into(),
+                    ..default()
+                })
+                .insert(NextWeaponButton(player_index))
+                .with_children(|parent| {
+                    // an image's background colour tints it, so the icon sits on top of the button
+                    parent.spawn(ImageBundle {
+                        style: Style {
+                            position_type: Absolute,
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        image: UiImage::new(icons.image(IconSlot::NextWeapon(player_index))),
+                        ..default()
+                    });
+                    parent.spawn(
+                        TextBundle::from_section(
+                            switch_hint,
+                            TextStyle {
+                                font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                                font_size: 16.0,
+                                color: Color::rgb(0.9, 0.9, 0.9),
+                            },
+                        )
+                        .with_style(Style {
+                            position_type: Absolute,
+                            bottom: Val::Px(2.0),
+                            ..default()
+                        }),
+                    );
+                });
+        });
+}
 
-            (entity, new_next_weapon)
+// every column shows its own hermit's hand
+fn update_weapon_icons(
+    mut icons: ResMut<ItemIcons>,
+    inventory: Res<Inventory>,
+    players: Query<(&WeaponHolder, &PlayerControllerState)>,
+    mut current_icons: Query<(&CurrentWeaponIcon, &mut Visibility), Without<NextWeaponButton>>,
+    mut next_buttons: Query<(
+        &NextWeaponButton,
+        &mut Visibility,
+        &Interaction,
+        &mut BackgroundColor,
+    )>,
+) {
+    for (CurrentWeaponIcon(player_index), mut visibility) in &mut current_icons {
+        let weapon_holder = players
+            .iter()
+            .find(|(_, controller)| controller.player_index == *player_index)
+            .map(|(weapon_holder, _)| weapon_holder);
+        let current_weapon = weapon_holder
+            .and_then(|weapon_holder| weapon_holder.current_weapon.as_ref())
+            .map(|(_, item)| item);
+        let next_weapon = weapon_holder.and_then(|weapon_holder| {
+            weapon_after(weapon_holder, inventory.items_of(*player_index), 1)
+        });
+        icons.show(IconSlot::CurrentWeapon(*player_index), current_weapon);
+        icons.show(IconSlot::NextWeapon(*player_index), next_weapon.as_ref());
+
+        *visibility = visibility_of(current_weapon.is_some());
+        for (_, mut visibility, interaction, mut color) in next_buttons
+            .iter_mut()
+            .filter(|(button, _, _, _)| button.0 == *player_index)
+        {
+            *visibility = visibility_of(next_weapon.is_some());
+            color.0 = if *interaction == Interaction::None {
+                SLOT_COLOR
+            } else {
+                HOVERED_SLOT_COLOR
+            };
         }
-        None => {
-            let entity = new_next_weapon.create_ui_entity(
-                ui_entity_transform,
-                false,
-                false,
-                false,
-                &mut commands,
-                &mut meshes,
-                &mut materials,
-                None,
-                // Some(Collider::ball(3.0)),
-            );
+    }
+}
 
-            (entity, new_next_weapon)
-        }
-    };
+fn visibility_of(shown: bool) -> Visibility {
+    if shown {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    }
+}
 
-    next_weapon.value = Some(new_value);
+fn update_cooldown(
+    players: Query<(&WeaponHolder, &PlayerCombatState, &PlayerControllerState)>,
+    mut fills: Query<(&CooldownFill, &mut Style, &mut BackgroundColor)>,
+    time: Res<Time>,
+) {
+    for (CooldownFill(player_index), mut style, mut color) in &mut fills {
+        let Some((weapon_holder, state, _)) = players
+            .iter()
+            .find(|(_, _, controller)| controller.player_index == *player_index)
+        else {
+            continue;
+        };
+
+        let readiness = match weapon_holder.current_weapon.as_ref() {
+            Some((_, weapon)) if weapon.item_type == ItemType::RANGED_WEAPON => state
+                .shot_rate_limiter
+                .as_ref()
+                .map_or(1.0, |timer| timer.percent()),
+            Some((_, weapon)) => {
+                let cooldown =
+                    BASE_ATTACK_COOLDOWN / (state.attack_speed * weapon.weapon_attack_speed);
+                ((time.elapsed_seconds() - state.last_attack) / cooldown).clamp(0.0, 1.0)
+            }
+            None => 0.0,
+        };
+
+        style.width = Val::Percent(readiness * 100.0);
+        color.0 = if readiness >= 1.0 {
+            READY_COLOR
+        } else {
+            COOLDOWN_COLOR
+        };
+    }
 }
 
-fn query_next_weapon(
+// every weapon in the shell, in the order the wheel and the number keys use
+pub fn carried_weapons(items: &[InventoryItem]) -> Vec<&InventoryItem> {
+    items
+        .iter()
+        .filter(|item| item.item_type.is_weapon())
+        .collect()
+}
+
+// the weapon `step` places away from the held one, negative steps go backwards
+pub fn weapon_after(
     weapon_holder: &WeaponHolder,
-    inventory: &Res<Inventory>,
+    items: &[InventoryItem],
+    step: isize,
 ) -> Option<InventoryItem> {
-    if inventory.content.len() < 2 {
+    let weapons = carried_weapons(items);
+    if weapons.len() < 2 {
         return None;
     }
 
-    if inventory
-        .content
+    let (_, selected_weapon) = weapon_holder.current_weapon.as_ref()?;
+    let selected_weapon_index = weapons
         .iter()
-        .all(|item| item.item_type == NON_WEAPON)
-    {
-        return None;
-    }
+        .position(|item| item.is_same_item(selected_weapon))?;
+    let next_weapon_index =
+        (selected_weapon_index as isize + step).rem_euclid(weapons.len() as isize);
 
-    let selected_weapon = weapon_holder.current_weapon.clone();
+    Some(weapons[next_weapon_index as usize].clone())
+}
 
-    if selected_weapon.is_none() {
-        return None;
+// swaps the weapon in a hermit's hand, nothing happens if it's already the one held
+pub fn equip_weapon(
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+    player_transform: &Transform,
+    weapon_holder: &mut WeaponHolder,
+    weapon: InventoryItem,
+) {
+    if let Some((entity, held)) = weapon_holder.current_weapon.as_ref() {
+        if held.is_same_item(&weapon) {
+            return;
+        }
+        commands.entity(*entity).despawn();
     }
 
-    let (_, selected_weapon) = selected_weapon.unwrap();
-    let selected_weapon_index = inventory
-        .content
-        .iter()
-        .enumerate()
-        .find_map(|(index, item)| {
-            (item.item_type_id == selected_weapon.item_type_id).then(|| index)
-        });
-    let selected_weapon_index = selected_weapon_index.unwrap();
-    let mut next_weapon_index = (selected_weapon_index + 1) % inventory.content.len();
-
-    while inventory.content[next_weapon_index].item_type == NON_WEAPON {
-        next_weapon_index = (next_weapon_index + 1) % inventory.content.len();
-    }
+    let entity = weapon.create_world_entity(
+        player_transform.translation,
+        true,
+        false,
+        commands,
+        meshes,
+        materials,
+    );
 
-    Some(inventory.content[next_weapon_index].clone())
+    log::info!("Switching to {:?}", weapon.item_type_id);
+    weapon_holder.current_weapon = Some((entity, weapon));
 }
 
-fn switch_weapon_on_tab(
+const HOTBAR_KEYS: [KeyCode; WHEEL_SLOTS] = [
+    KeyCode::Key1,
+    KeyCode::Key2,
+    KeyCode::Key3,
+    KeyCode::Key4,
+    KeyCode::Key5,
+    KeyCode::Key6,
+    KeyCode::Key7,
+    KeyCode::Key8,
+    KeyCode::Key9,
+];
+
+// Tab cycles forward and Shift+Tab back, the number keys pick a weapon straight away. On a
+// gamepad north cycles forward and west back.
+#[allow(clippy::too_many_arguments)]
+fn switch_weapon_on_input(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut keyboard_input_events: EventReader<KeyboardInput>,
-    mut player_query: Query<(Entity, &mut WeaponHolder, &PlayerCombatState)>,
-    mut transform_query: Query<&mut Transform>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    mut player_query: Query<
+        (&Transform, &mut WeaponHolder, &PlayerControllerState),
+        Without<RemotePlayer>,
+    >,
     inventory: Res<Inventory>,
 ) {
-    for event in keyboard_input_events.iter() {
-        match event.key_code {
-            Some(KeyCode::Tab) => {
-                if event.state.is_pressed() {
-                    let (player_entity, mut player_weapon, _) = player_query.single_mut();
-
-                    if let Some(next_weapon) = query_next_weapon(&player_weapon, &inventory) {
-                        let player_transform = transform_query.get(player_entity).unwrap();
-
-                        // despawn current weapon
-                        if let Some((entity, _)) = player_weapon.current_weapon {
-                            commands.entity(entity).despawn();
-                        }
-
-                        let entity = next_weapon.create_world_entity(
-                            player_transform.translation,
-                            true,
-                            false,
-                            &mut commands,
-                            &mut meshes,
-                            &mut materials,
-                        );
-
-                        player_weapon.current_weapon = Some((entity, next_weapon));
-
-                        log::info!("Switching to next weapon");
-                    }
-                }
-            }
-            _ => {}
+    let player_count = player_query.iter().count();
+    let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    for (player_transform, mut weapon_holder, controller) in &mut player_query {
+        let items = inventory.items_of(controller.player_index);
+        let pads = player_gamepads(controller.player_index, player_count, &gamepads);
+        let button_pressed = |button_type| {
+            pads.iter().any(|gamepad| {
+                gamepad_buttons.just_pressed(GamepadButton {
+                    gamepad: *gamepad,
+                    button_type,
+                })
+            })
+        };
+        let keyboard = uses_keyboard_and_mouse(controller.player_index).then_some(&*keyboard);
+
+        let weapon = if keyboard.is_some_and(|keys| keys.just_pressed(KeyCode::Tab)) {
+            weapon_after(&weapon_holder, items, if shift { -1 } else { 1 })
+        } else if button_pressed(GamepadButtonType::North) {
+            weapon_after(&weapon_holder, items, 1)
+        } else if button_pressed(GamepadButtonType::West) {
+            weapon_after(&weapon_holder, items, -1)
+        } else if let Some(slot) =
+            keyboard.and_then(|keys| HOTBAR_KEYS.iter().position(|key| keys.just_pressed(*key)))
+        {
+            carried_weapons(items).get(slot).map(|item| (*item).clone())
+        } else {
+            None
+        };
+
+        if let Some(weapon) = weapon {
+            equip_weapon(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                player_transform,
+                &mut weapon_holder,
+                weapon,
+            );
         }
     }
 }
 
-fn highlight_item_on_hover(
+// clicking the next weapon in a column switches that column's hermit
+fn switch_weapon_on_click(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut transform_query: Query<&mut Transform>,
-    mut player_query: Query<(Entity, &mut WeaponHolder, &PlayerCombatState)>,
-    next_weapon: Res<NextWeapon>,
+    buttons: Query<(&Interaction, &NextWeaponButton), Changed<Interaction>>,
+    mut player_query: Query<(&Transform, &mut WeaponHolder, &PlayerControllerState)>,
     inventory: Res<Inventory>,
-    window_query: Query<&Window, With<PrimaryWindow>>,
-    mouse_button: Res<Input<MouseButton>>,
 ) {
-    let window = window_query.single();
-
-    let cursor_position = window.cursor_position();
-    if cursor_position.is_none() {
-        return;
-    }
-    let cursor_position = cursor_position.unwrap();
-    let window_resolution = window.resolution.clone();
-    let aspect_ratio = window_resolution.width() / window_resolution.height();
+    for (interaction, NextWeaponButton(player_index)) in &buttons {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
 
-    let cursor_pos_percent = Vec2::new(
-        cursor_position.x / window_resolution.width(),
-        cursor_position.y / window_resolution.height(),
-    );
+        let Some((player_transform, mut weapon_holder, _)) = player_query
+            .iter_mut()
+            .find(|(_, _, controller)| controller.player_index == *player_index)
+        else {
+            continue;
+        };
 
-    if next_weapon.value.is_none() {
-        return;
+        if let Some(weapon) = weapon_after(&weapon_holder, inventory.items_of(*player_index), 1) {
+            equip_weapon(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                player_transform,
+                &mut weapon_holder,
+                weapon,
+            );
+        }
     }
-    let (next_weapon_entity, _) = next_weapon.value.as_ref().unwrap().clone();
-
-    if cursor_pos_percent.x < 0.15 && cursor_pos_percent.y < 0.15 * aspect_ratio {
-        if let Ok(mut transform) = transform_query.get_mut(next_weapon_entity) {
-            transform.scale *= if mouse_button.pressed(MouseButton::Left) {
-                1.0
-            } else {
-                1.1
-            };
-
-            if mouse_button.just_pressed(MouseButton::Left) {
-                let (player_entity, mut player_weapon, _) = player_query.single_mut();
-
-                if let Some(next_weapon) = query_next_weapon(&player_weapon, &inventory) {
-                    let player_transform = transform_query.get(player_entity).unwrap();
-
-                    // despawn current weapon
-                    if let Some((entity, _)) = player_weapon.current_weapon {
-                        commands.entity(entity).despawn();
-                    }
-
-                    let entity = next_weapon.create_world_entity(
-                        player_transform.translation,
-                        true,
-                        false,
-                        &mut commands,
-                        &mut meshes,
-                        &mut materials,
-                    );
-
-                    player_weapon.current_weapon = Some((entity, next_weapon));
+}
 
-                    log::info!("Switching to next weapon");
-                }
-            }
-        }
+fn clean(mut commands: Commands, selectors: Query<Entity, With<WeaponSelector>>) {
+    for entity in &selectors {
+        commands.entity(entity).despawn_recursive();
     }
 }
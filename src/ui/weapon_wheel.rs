@@ -0,0 +1,351 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+use bevy::ui::PositionType::Absolute;
+use bevy::window::PrimaryWindow;
+
+use crate::game::TimeScale;
+use crate::game_state::GameState;
+use crate::inventory::Inventory;
+use crate::network::NetworkRole;
+use crate::player::{player_gamepads, PlayerControllerState};
+use crate::ui::item_icon::{IconSlot, ItemIcons, WHEEL_SLOTS};
+use crate::ui::weapon_selector::{carried_weapons, equip_weapon};
+use crate::world_item::WeaponHolder;
+
+// how fast the fight goes on while picking a weapon
+const WHEEL_TIME_SCALE: f32 = 0.2;
+
+const WHEEL_RADIUS: f32 = 160.0;
+const WHEEL_SLOT_SIZE: f32 = 88.0;
+const RING_SIZE: f32 = 2.0 * WHEEL_RADIUS + WHEEL_SLOT_SIZE;
+
+// how far the mouse or the stick has to go from the middle before it picks something
+const MOUSE_DEADZONE: f32 = 40.0;
+const STICK_DEADZONE: f32 = 0.5;
+
+const BACKDROP_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.35);
+const SLOT_COLOR: Color = Color::rgba(0.0, 0.0, 0.0, 0.35);
+const SELECTED_SLOT_COLOR: Color = Color::rgba(1.0, 1.0, 1.0, 0.45);
+
+pub struct WeaponWheelPlugin;
+
+impl Plugin for WeaponWheelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<WheelState>();
+        app.add_systems(OnEnter(GameState::FightingInArena), setup);
+        app.add_systems(
+            Update,
+            (toggle_wheel, select_on_wheel, update_wheel)
+                .chain()
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        app.add_systems(OnExit(GameState::FightingInArena), (close_wheel, clean));
+    }
+}
+
+// Player one holds Q or the left shoulder button to open it. Every carried weapon gets a slot,
+// and letting go puts the one pointed at in their hand.
+#[derive(Resource, Default)]
+struct WheelState {
+    open: bool,
+    slots: usize,
+    selected: Option<usize>,
+}
+
+#[derive(Component)]
+struct WeaponWheel;
+
+#[derive(Component)]
+struct WheelSlot(usize);
+
+#[derive(Component)]
+struct WheelLabel;
+
+fn setup(mut commands: Commands, icons: Res<ItemIcons>, asset_server: Res<AssetServer>) {
+    let font = asset_server.load("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                position_type: Absolute,
+                width: Val::Percent(100.0),
+                height: Val::Percent(100.0),
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            background_color: BACKDROP_COLOR.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        })
+        .insert(WeaponWheel)
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(RING_SIZE),
+                        height: Val::Px(RING_SIZE),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    // the only child in the flow, so it sits in the middle of the ring
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "",
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 32.0,
+                                color: Color::WHITE,
+                            },
+                        ))
+                        .insert(WheelLabel);
+
+                    for i in 0..WHEEL_SLOTS {
+                        parent
+                            .spawn(NodeBundle {
+                                style: Style {
+                                    position_type: Absolute,
+                                    width: Val::Px(WHEEL_SLOT_SIZE),
+                                    height: Val::Px(WHEEL_SLOT_SIZE),
+                                    ..default()
+                                },
+                                background_color: SLOT_COLOR.into(),
+                                ..default()
+                            })
+                            .insert(WheelSlot(i))
+                            .with_children(|parent| {
+                                parent.spawn(ImageBundle {
+                                    style: Style {
+                                        position_type: Absolute,
+                                        width: Val::Percent(100.0),
+                                        height: Val::Percent(100.0),
+                                        ..default()
+                                    },
+                                    image: UiImage::new(icons.image(IconSlot::Wheel(i))),
+                                    ..default()
+                                });
+                                // the number key that picks it without the wheel
+                                parent.spawn(
+                                    TextBundle::from_section(
+                                        format!("{}", i + 1),
+                                        TextStyle {
+                                            font: font.clone(),
+                                            font_size: 18.0,
+                                            color: Color::rgb(0.9, 0.9, 0.9),
+                                        },
+                                    )
+                                    .with_style(Style {
+                                        position_type: Absolute,
+                                        left: Val::Px(4.0),
+                                        bottom: Val::Px(2.0),
+                                        ..default()
+                                    }),
+                                );
+                            });
+                    }
+                });
+        });
+}
+
+fn wheel_pressed(
+    keyboard: &Input<KeyCode>,
+    gamepad_buttons: &Input<GamepadButton>,
+    pads: &[Gamepad],
+) -> bool {
+    keyboard.pressed(KeyCode::Q)
+        || pads.iter().any(|gamepad| {
+            gamepad_buttons.pressed(GamepadButton {
+                gamepad: *gamepad,
+                button_type: GamepadButtonType::LeftTrigger,
+            })
+        })
+}
+
+// Time only slows down when playing alone or on the same screen, the host wouldn't wait for a
+// client picking a weapon.
+#[allow(clippy::too_many_arguments)]
+fn toggle_wheel(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut wheel: ResMut<WheelState>,
+    mut icons: ResMut<ItemIcons>,
+    mut time_scale: ResMut<TimeScale>,
+    keyboard: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    inventory: Res<Inventory>,
+    role: Res<NetworkRole>,
+    mut players: Query<(&Transform, &mut WeaponHolder, &PlayerControllerState)>,
+) {
+    let player_count = players.iter().count();
+    let Some((player_transform, mut weapon_holder, _)) = players
+        .iter_mut()
+        .find(|(_, _, controller)| controller.player_index == 0)
+    else {
+        return;
+    };
+
+    let pads = player_gamepads(0, player_count, &gamepads);
+    let pressed = wheel_pressed(&keyboard, &gamepad_buttons, &pads);
+    if pressed == wheel.open {
+        return;
+    }
+
+    let weapons = carried_weapons(inventory.items_of(0));
+    if pressed {
+        wheel.open = true;
+        wheel.slots = weapons.len().min(WHEEL_SLOTS);
+        wheel.selected = weapon_holder
+            .current_weapon
+            .as_ref()
+            .and_then(|(_, held)| weapons.iter().position(|item| item.is_same_item(held)))
+            .filter(|index| *index < wheel.slots);
+        for i in 0..WHEEL_SLOTS {
+            icons.show(IconSlot::Wheel(i), weapons.get(i).copied());
+        }
+
+        if matches!(*role, NetworkRole::Offline) {
+            time_scale.weapon_wheel = WHEEL_TIME_SCALE;
+        }
+        return;
+    }
+
+    if let Some(weapon) = wheel.selected.and_then(|index| weapons.get(index)) {
+        equip_weapon(
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+            player_transform,
+            &mut weapon_holder,
+            (*weapon).clone(),
+        );
+    }
+    shut(&mut wheel, &mut icons, &mut time_scale);
+}
+
+fn shut(wheel: &mut WheelState, icons: &mut ItemIcons, time_scale: &mut TimeScale) {
+    wheel.open = false;
+    wheel.selected = None;
+    // the empty slots stop rendering
+    for i in 0..WHEEL_SLOTS {
+        icons.show(IconSlot::Wheel(i), None);
+    }
+    time_scale.weapon_wheel = 1.0;
+}
+
+// the slot in the direction pointed at, clockwise from the top like the slots are laid out
+fn slot_towards(direction: Vec2, slots: usize) -> usize {
+    let angle = direction.x.atan2(direction.y).rem_euclid(TAU);
+    let step = TAU / slots as f32;
+    (angle / step).round() as usize % slots
+}
+
+fn select_on_wheel(
+    mut wheel: ResMut<WheelState>,
+    windows: Query<&Window, With<PrimaryWindow>>,
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    players: Query<&PlayerControllerState>,
+) {
+    if !wheel.open || wheel.slots == 0 {
+        return;
+    }
+
+    // the stick wins when it's pushed, otherwise the mouse away from the middle of the screen
+    let pads = player_gamepads(0, players.iter().count(), &gamepads);
+    let stick = pads.iter().find_map(|gamepad| {
+        let x = axes.get(GamepadAxis::new(*gamepad, GamepadAxisType::RightStickX))?;
+        let y = axes.get(GamepadAxis::new(*gamepad, GamepadAxisType::RightStickY))?;
+        let stick = Vec2::new(x, y);
+        (stick.length() > STICK_DEADZONE).then_some(stick)
+    });
+    let mouse = windows.get_single().ok().and_then(|window| {
+        let cursor = window.cursor_position()?;
+        let center = Vec2::new(window.width(), window.height()) / 2.0;
+        // the cursor goes down the screen, the stick goes up
+        let offset = (cursor - center) * Vec2::new(1.0, -1.0);
+        (offset.length() > MOUSE_DEADZONE).then_some(offset)
+    });
+
+    if let Some(direction) = stick.or(mouse) {
+        let selected = Some(slot_towards(direction, wheel.slots));
+        if wheel.selected != selected {
+            wheel.selected = selected;
+        }
+    }
+}
+
+fn update_wheel(
+    wheel: Res<WheelState>,
+    inventory: Res<Inventory>,
+    mut wheels: Query<&mut Visibility, (With<WeaponWheel>, Without<WheelSlot>)>,
+    mut slots: Query<(
+        &WheelSlot,
+        &mut Style,
+        &mut BackgroundColor,
+        &mut Visibility,
+    )>,
+    mut labels: Query<&mut Text, With<WheelLabel>>,
+) {
+    if !wheel.is_changed() {
+        return;
+    }
+
+    for mut visibility in &mut wheels {
+        *visibility = if wheel.open {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+
+    for (slot, mut style, mut color, mut visibility) in &mut slots {
+        if slot.0 >= wheel.slots {
+            *visibility = Visibility::Hidden;
+            continue;
+        }
+        *visibility = Visibility::Inherited;
+
+        let angle = slot.0 as f32 * TAU / wheel.slots as f32;
+        let offset = Vec2::new(angle.sin(), -angle.cos()) * WHEEL_RADIUS;
+        style.left = Val::Px((RING_SIZE - WHEEL_SLOT_SIZE) / 2.0 + offset.x);
+        style.top = Val::Px((RING_SIZE - WHEEL_SLOT_SIZE) / 2.0 + offset.y);
+        color.0 = if wheel.selected == Some(slot.0) {
+            SELECTED_SLOT_COLOR
+        } else {
+            SLOT_COLOR
+        };
+    }
+
+    let label = wheel
+        .selected
+        .and_then(|index| carried_weapons(inventory.items_of(0)).get(index).copied())
+        .map(|item| format!("{:?}", item.item_type_id))
+        .unwrap_or_default();
+    for mut text in &mut labels {
+        text.sections[0].value = label.clone();
+    }
+}
+
+// leaving the fight with the wheel open gives the normal speed back
+fn close_wheel(
+    mut wheel: ResMut<WheelState>,
+    mut icons: ResMut<ItemIcons>,
+    mut time_scale: ResMut<TimeScale>,
+) {
+    if wheel.open {
+        shut(&mut wheel, &mut icons, &mut time_scale);
+    }
+}
+
+fn clean(mut commands: Commands, wheels: Query<Entity, With<WeaponWheel>>) {
+    for entity in &wheels {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -1,19 +1,38 @@
 use std::time::Duration;
 
+use bevy::ecs::system::SystemState;
 use bevy::{log, prelude::*};
 use bevy_rapier3d::prelude::*;
 use queues::{IsQueue, Queue};
-use rand::random;
 use rand::Rng;
 
-use crate::asset_loader::GameAssets;
+use crate::arena_generator::ArenaLayout;
 use crate::config::SPAWN_ENEMIES;
-use crate::enemy::{Enemy, EnemyBundle, EnemyType};
+use crate::console::{parse_arg, AddConsoleCommand, ConsoleCommand};
+use crate::difficulty::{DifficultyDirector, DifficultyModifiers};
+use crate::endless::{is_choosing_modifier, EndlessRun};
+use crate::enemy::{Enemy, EnemyType};
+use crate::game::GameRng;
 use crate::game_state::GameState;
+use crate::inventory::ItemTypeId;
 use crate::item_spawner::spawn_random_item;
+use crate::level_loader::LevelMetadata;
+use crate::levels::{CurrentLevel, LevelCleared, LevelRegistry};
+use crate::network::is_simulation_authority;
 use crate::player::PlayerControllerState;
-use crate::wave_manager::waves::{wave_generation, DEFINED_WAVES};
-
+use crate::profile::Profile;
+use crate::shop::is_shopping;
+use crate::wave_manager::spawning::{
+    find_spawn_point, grow_telegraphs, place_telegraph, relaxed_spawn_point, setup_telegraph_mesh,
+    TelegraphMesh,
+};
+pub(crate) use crate::wave_manager::spawning::{
+    LineOfSight, SpawnRules, SpawnTelegraph, SpawnZone,
+};
+use crate::wave_manager::waves::{endless_wave, wave_generation};
+pub(crate) use crate::wave_manager::waves::{CORAL_MAZE_WAVES, DEFINED_WAVES, TRENCH_WAVES};
+
+mod spawning;
 mod waves;
 
 pub const ARENA_DIMENSIONS_METERS: [f32; 2] = [24.0, 30.0];
@@ -43,6 +62,42 @@ pub struct WaveDefinition {
     drop_item_count: i32,
 }
 
+impl WaveDefinition {
+    pub fn enemy_count(&self) -> i32 {
+        self.jellyfish_count + self.urchin_count + self.shrimp_count
+    }
+
+    pub fn spawn_interval(&self) -> f32 {
+        self.spawn_rate
+    }
+
+    // more or fewer of every creature, a kind that was in the wave stays in it
+    pub fn scale_enemies(&mut self, factor: f32) {
+        for count in [
+            &mut self.jellyfish_count,
+            &mut self.urchin_count,
+            &mut self.shrimp_count,
+        ] {
+            if *count > 0 {
+                *count = ((*count as f32 * factor).round() as i32).max(1);
+            }
+        }
+    }
+
+    // above 1 the creatures come in quicker
+    pub fn scale_spawn_rate(&mut self, factor: f32) {
+        self.spawn_rate /= factor;
+    }
+
+    pub fn luck(&self) -> i32 {
+        self.luck
+    }
+
+    pub fn add_luck(&mut self, luck: i32) {
+        self.luck += luck;
+    }
+}
+
 #[derive(Resource)]
 pub struct Wave {
     pub count: i32,
@@ -59,6 +114,12 @@ impl Wave {
     }
 }
 
+// sent as soon as the last creature of a wave is gone, `wave` counts from 1
+#[derive(Event)]
+pub struct WaveCleared {
+    pub wave: i32,
+}
+
 #[derive(Resource)]
 struct SpawnTimer(Timer);
 
@@ -68,43 +129,56 @@ struct WaveStartDelayTimer(Timer);
 impl Plugin for WaveManagerPlugin {
     fn build(&self, app: &mut App) {
         app.add_state::<WaveState>();
+        app.add_event::<WaveCleared>();
 
         app.add_systems(
             Update,
             wait_for_wave_start
+                .run_if(is_simulation_authority)
                 .run_if(in_state(GameState::FightingInArena))
                 .run_if(in_state(WaveState::WAVE_START)),
         );
         app.add_systems(
             Update,
             spawn_enemies
+                .run_if(is_simulation_authority)
                 .run_if(in_state(GameState::FightingInArena))
                 .run_if(in_state(WaveState::ACTIVE_WAVE_SPAWNING)),
         );
+        // markers already down still turn into creatures when the spawning is over
+        app.add_systems(
+            Update,
+            grow_telegraphs
+                .run_if(is_simulation_authority)
+                .run_if(in_state(GameState::FightingInArena)),
+        );
+        // the packing screen only pauses them like the creatures, a run that's over drops them
+        app.add_systems(OnEnter(GameState::TitleScreen), clear_telegraphs);
         app.add_systems(
             Update,
             check_for_wave_end
+                .run_if(is_simulation_authority)
                 .run_if(in_state(GameState::FightingInArena))
                 .run_if(in_state(WaveState::ACTIVE_WAVE)),
         );
         app.add_systems(
             Update,
             prepare_next_wave
+                .run_if(is_simulation_authority)
                 .run_if(in_state(GameState::FightingInArena))
-                .run_if(in_state(WaveState::WAVE_END)),
-        );
-
-        app.add_systems(
-            OnEnter(GameState::FightingInArena),
-            show_ui.run_if(in_state(WaveState::ACTIVE_WAVE)),
-        );
-        app.add_systems(
-            OnEnter(GameState::FightingInArena),
-            show_ui.run_if(in_state(WaveState::ACTIVE_WAVE_SPAWNING)),
+                .run_if(in_state(WaveState::WAVE_END))
+                // the next wave waits for the endless run's pick and the merchant
+                .run_if(not(is_choosing_modifier))
+                .run_if(not(is_shopping)),
         );
-        app.add_systems(OnExit(GameState::FightingInArena), hide_ui);
 
+        app.add_systems(Startup, setup_telegraph_mesh);
         app.insert_resource(Wave::new());
+        app.add_console_command(
+            ConsoleCommand::new("wave", wave_command)
+                .usage("<number>")
+                .help("clear the arena and jump to a wave"),
+        );
 
         app.insert_resource(WaveStartDelayTimer(Timer::from_seconds(
             2.0,
@@ -117,64 +191,67 @@ impl Plugin for WaveManagerPlugin {
     }
 }
 
+fn wave_command(world: &mut World, args: &[&str]) -> Result<String, String> {
+    let number: i32 = parse_arg(args, 0, "number")?;
+    if number < 1 {
+        return Err("waves start at 1".to_string());
+    }
+
+    let mut state: SystemState<(
+        Commands,
+        ResMut<Wave>,
+        ResMut<NextState<WaveState>>,
+        Query<Entity, Or<(With<Enemy>, With<SpawnTelegraph>)>>,
+    )> = SystemState::new(world);
+    let (mut commands, mut current_wave, mut next_state, to_clear) = state.get_mut(world);
+
+    for entity in &to_clear {
+        commands.entity(entity).despawn_recursive();
+    }
+    // prepare_next_wave picks the definition up from here
+    current_wave.count = number - 1;
+    next_state.set(WaveState::WAVE_END);
+
+    state.apply(world);
+    Ok(format!("skipping to wave {number}"))
+}
+
 fn wait_for_wave_start(
     mut start_delay_timer: ResMut<WaveStartDelayTimer>,
     time: Res<Time>,
     mut next_state: ResMut<NextState<WaveState>>,
     current_wave: ResMut<Wave>,
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
 ) {
     if start_delay_timer.0.tick(time.delta()).finished() {
         log::info!("Starting wave: {}", current_wave.count);
 
         next_state.set(WaveState::ACTIVE_WAVE_SPAWNING);
         start_delay_timer.0.reset();
-
-        commands
-            .spawn(NodeBundle {
-                style: Style {
-                    top: Val::Percent(0.0),
-                    left: Val::Percent(0.0),
-                    width: Val::Percent(100.0),
-                    height: Val::Percent(100.0),
-                    align_items: AlignItems::Center,
-                    justify_content: JustifyContent::Center,
-                    ..default()
-                },
-                ..default()
-            })
-            .insert(WaveUI)
-            .with_children(|parent| {
-                parent.spawn(
-                    (TextBundle::from_section(
-                        format!("Wave {:?}", current_wave.count + 1),
-                        TextStyle {
-                            font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                            font_size: 48.0,
-                            color: Color::BLACK,
-                        },
-                    )
-                    .with_text_alignment(TextAlignment::Center)
-                    .with_style(Style {
-                        position_type: PositionType::Absolute,
-                        bottom: Val::Px(5.0),
-                        right: Val::Px(15.0),
-                        ..default()
-                    })),
-                );
-            });
     }
 }
 
+// A spot is only used if it passes the level's spawn rules. A creature that doesn't find one is
+// tried again the next frame instead of waiting out another spawn interval, until it's been
+// tried for as many frames as the rules try spots and goes wherever the relaxed rules allow.
+// Either way it first shows up as a marker growing on the floor.
+#[allow(clippy::too_many_arguments)]
 fn spawn_enemies(
     mut commands: Commands,
+    mut materials: ResMut<Assets<StandardMaterial>>,
     mut spawn_timer: ResMut<SpawnTimer>,
+    // frames the current creature has gone without a spot
+    mut retries: Local<usize>,
     player_transform_query: Query<&Transform, With<PlayerControllerState>>,
-    game_assets: Res<GameAssets>,
+    telegraph_mesh: Res<TelegraphMesh>,
     time: Res<Time>,
     mut current_wave: ResMut<Wave>,
     mut next_state: ResMut<NextState<WaveState>>,
+    mut rng: ResMut<GameRng>,
+    arena: Option<Res<ArenaLayout>>,
+    level: Res<LevelMetadata>,
+    levels: Res<LevelRegistry>,
+    current_level: Res<CurrentLevel>,
+    rapier: Res<RapierContext>,
 ) {
     if !SPAWN_ENEMIES {
         return;
@@ -184,13 +261,12 @@ fn spawn_enemies(
         && current_wave.wave_definition.urchin_count <= 0
         && current_wave.wave_definition.shrimp_count <= 0
     {
+        *retries = 0;
         next_state.set(WaveState::ACTIVE_WAVE);
         return;
     }
 
-    if spawn_timer.0.tick(time.delta()).just_finished() {
-        let player_transform = player_transform_query.single();
-
+    if spawn_timer.0.tick(time.delta()).just_finished() || *retries > 0 {
         let mut enemy_type = EnemyType::Jellyfish;
         let mut enemy_count = &mut current_wave.wave_definition.jellyfish_count;
 
@@ -199,7 +275,6 @@ fn spawn_enemies(
         while !selected && attempts < 10 {
             attempts += 1;
 
-            let mut rng = rand::thread_rng();
             let spawnTypeId = rng.gen_range(0..3);
             match spawnTypeId {
                 0 => {
@@ -222,122 +297,169 @@ fn spawn_enemies(
             }
         }
 
-        attempts = 0;
-
-        // Spawn enemy
-        let rand_x = random::<f32>() - 0.5;
-        let rand_y = random::<f32>() - 0.5;
-
-        let position = Vec3::new(
-            ((rand_x) * ARENA_DIMENSIONS_METERS[0]) * 2.0,
-            1.0,
-            ((rand_y) * ARENA_DIMENSIONS_METERS[1]) * 2.0,
-        );
-        if (player_transform.translation - position).length() > 3.0 {
-            commands.spawn(EnemyBundle::new(position, &game_assets, enemy_type));
-
-            *enemy_count -= 1;
+        let level_definition = levels.level(current_level.0);
+        let players: Vec<Vec3> = player_transform_query
+            .iter()
+            .map(|player_transform| player_transform.translation)
+            .collect();
+        let rules = &level_definition.spawn_rules;
+        let position = if *retries < rules.max_attempts {
+            find_spawn_point(
+                rng.as_mut(),
+                enemy_type,
+                rules,
+                &level_definition.spawn_zones,
+                arena.as_deref(),
+                &level,
+                &players,
+                &rapier,
+            )
+        } else {
+            log::debug!(
+                "No spot to spawn a {}, relaxing the rules",
+                enemy_type.name()
+            );
+            Some(relaxed_spawn_point(
+                rng.as_mut(),
+                enemy_type,
+                rules,
+                &level_definition.spawn_zones,
+                arena.as_deref(),
+                &level,
+                &players,
+                &rapier,
+            ))
+        };
+
+        match position {
+            Some(position) => {
+                place_telegraph(
+                    &mut commands,
+                    &mut materials,
+                    &telegraph_mesh,
+                    position,
+                    enemy_type,
+                    rules,
+                );
+                *enemy_count -= 1;
+                *retries = 0;
+                spawn_timer.0.reset();
+            }
+            None => {
+                if *retries == 0 {
+                    log::debug!("No spot to spawn a {} yet, retrying", enemy_type.name());
+                }
+                *retries += 1;
+            }
         }
-
-        spawn_timer.0.reset();
-    }
-}
-
-fn hide_ui(mut commands: Commands, wave_ui_query: Query<Entity, With<WaveUI>>) {
-    // println!("Hiding Wave UI");
-    for e in wave_ui_query.iter() {
-        commands.entity(e).despawn();
     }
 }
 
-fn show_ui(mut commands: Commands, asset_server: Res<AssetServer>, current_wave: Res<Wave>) {
-    // println!("Showing Wave UI");
-    commands
-        .spawn(NodeBundle {
-            style: Style {
-                top: Val::Percent(0.0),
-                left: Val::Percent(0.0),
-                width: Val::Percent(100.0),
-                height: Val::Percent(100.0),
-                align_items: AlignItems::Center,
-                justify_content: JustifyContent::Center,
-                ..default()
-            },
-            ..default()
-        })
-        .insert(WaveUI)
-        .with_children(|parent| {
-            parent.spawn(
-                (TextBundle::from_section(
-                    format!("Wave {:?}", current_wave.count + 1),
-                    TextStyle {
-                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
-                        font_size: 48.0,
-                        color: Color::BLACK,
-                    },
-                )
-                .with_text_alignment(TextAlignment::Center)
-                .with_style(Style {
-                    position_type: PositionType::Absolute,
-                    bottom: Val::Px(5.0),
-                    right: Val::Px(15.0),
-                    ..default()
-                })),
-            );
-        });
-}
-
+#[allow(clippy::too_many_arguments)]
 fn check_for_wave_end(
     enemy_entity_query: Query<Entity, With<Enemy>>,
+    telegraphs: Query<(), With<SpawnTelegraph>>,
     mut current_wave: ResMut<Wave>,
     mut next_state: ResMut<NextState<WaveState>>,
 
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    wave_ui_query: Query<Entity, With<WaveUI>>,
+    mut rng: ResMut<GameRng>,
+    arena: Option<Res<ArenaLayout>>,
+    level: Res<LevelMetadata>,
+    levels: Res<LevelRegistry>,
+    current_level: Res<CurrentLevel>,
+    mut level_cleared_writer: EventWriter<LevelCleared>,
+    mut wave_cleared_writer: EventWriter<WaveCleared>,
+    endless: Res<EndlessRun>,
+    profile: Res<Profile>,
 ) {
-    if enemy_entity_query.iter().len() <= 0 {
+    if enemy_entity_query.iter().len() <= 0 && telegraphs.is_empty() {
         log::info!("Ending wave: {}", current_wave.count);
 
         next_state.set(WaveState::WAVE_END);
         current_wave.count += 1;
+        wave_cleared_writer.send(WaveCleared {
+            wave: current_wave.count,
+        });
 
-        for e in wave_ui_query.iter() {
-            commands.entity(e).despawn();
+        // that was the level's last wave, endless runs go on until the hermits are down
+        if !endless.enabled
+            && current_wave.count as usize == levels.level(current_level.0).waves.len()
+        {
+            level_cleared_writer.send(LevelCleared {
+                level: current_level.0,
+            });
         }
-        drop_items(&mut commands, meshes, materials, current_wave);
+
+        drop_items(
+            &mut commands,
+            meshes,
+            materials,
+            current_wave,
+            1 + endless.effects().extra_drops,
+            &profile.dropped_items(),
+            &mut rng,
+            arena.as_deref(),
+            &level,
+        );
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn drop_items(
     commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut current_wave: ResMut<Wave>,
+    count: i32,
+    unlocked: &[ItemTypeId],
+    rng: &mut GameRng,
+    arena: Option<&ArenaLayout>,
+    level: &LevelMetadata,
 ) {
-    spawn_random_item(
-        current_wave.wave_definition.luck,
-        commands,
-        &mut meshes,
-        &mut materials,
-    );
+    for _ in 0..count {
+        spawn_random_item(
+            current_wave.wave_definition.luck,
+            unlocked,
+            rng,
+            arena,
+            level,
+            commands,
+            &mut meshes,
+            &mut materials,
+        );
+    }
 }
-#[derive(Component)]
-struct WaveUI;
-
+#[allow(clippy::too_many_arguments)]
 fn prepare_next_wave(
     mut start_delay_timer: ResMut<WaveStartDelayTimer>,
     mut spawn_timer: ResMut<SpawnTimer>,
     mut current_wave: ResMut<Wave>,
     mut next_state: ResMut<NextState<WaveState>>,
+    mut rng: ResMut<GameRng>,
+    levels: Res<LevelRegistry>,
+    current_level: Res<CurrentLevel>,
+    modifiers: Res<DifficultyModifiers>,
+    mut director: ResMut<DifficultyDirector>,
+    endless: Res<EndlessRun>,
 ) {
-    if current_wave.count < (DEFINED_WAVES.len() as i32) {
-        // Wave count is within defined waves
-        current_wave.wave_definition = DEFINED_WAVES[current_wave.count as usize].clone();
+    let waves = &levels.level(current_level.0).waves;
+    let mut definition = if endless.enabled {
+        endless_wave(current_wave.count, rng.as_mut())
+    } else if current_wave.count < (waves.len() as i32) {
+        // Wave count is within the level's waves
+        waves[current_wave.count as usize].clone()
     } else {
-        current_wave.wave_definition = wave_generation(current_wave.count);
-    }
+        wave_generation(current_wave.count, rng.as_mut())
+    };
+    director.nudge(current_wave.count, &mut definition);
+    let effects = endless.effects();
+    definition.scale_enemies(effects.enemy_count);
+    definition.add_luck(effects.luck);
+    definition.scale_spawn_rate(modifiers.spawn_rate);
+    current_wave.wave_definition = definition;
 
     // set delay before next wave
     start_delay_timer.0.set_duration(Duration::from_secs_f32(
@@ -350,3 +472,9 @@ fn prepare_next_wave(
 
     next_state.set(WaveState::WAVE_START);
 }
+
+fn clear_telegraphs(mut commands: Commands, telegraphs: Query<Entity, With<SpawnTelegraph>>) {
+    for entity in &telegraphs {
+        commands.entity(entity).despawn_recursive();
+    }
+}
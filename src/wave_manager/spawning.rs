@@ -0,0 +1,263 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use rand::Rng;
+
+use crate::arena_generator::ArenaLayout;
+use crate::asset_loader::GameAssets;
+use crate::config::{COLLISION_GROUP_PLAYER, COLLISION_GROUP_TERRAIN, COLLISION_GROUP_WALLS};
+use crate::enemy::{EnemyBundle, EnemyType};
+use crate::level_loader::LevelMetadata;
+use crate::wave_manager::ARENA_DIMENSIONS_METERS;
+
+const TELEGRAPH_RADIUS: f32 = 1.0;
+// just above the floor so it doesn't flicker with it
+const TELEGRAPH_HEIGHT: f32 = 0.05;
+const TELEGRAPH_START_SCALE: f32 = 0.2;
+
+// what the hermits have to be able to see of a spot for something to spawn there
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LineOfSight {
+    Any,
+    // at least one hermit sees it, nothing appears sealed off behind a rock
+    Visible,
+    // none of them do, creatures come out from behind the scenery
+    Hidden,
+}
+
+#[derive(Clone)]
+pub struct SpawnRules {
+    // measured along the floor
+    pub min_player_distance: f32,
+    pub line_of_sight: LineOfSight,
+    // spots tried for one creature before trying again next frame, and frames tried before the
+    // rules are relaxed
+    pub max_attempts: usize,
+    // how long the marker on the floor grows before the creature shows up
+    pub telegraph_secs: f32,
+}
+
+impl Default for SpawnRules {
+    fn default() -> Self {
+        Self {
+            min_player_distance: 3.0,
+            line_of_sight: LineOfSight::Any,
+            max_attempts: 10,
+            telegraph_secs: 1.0,
+        }
+    }
+}
+
+// A rectangle of floor a level lets creatures spawn in. A level with zones only spawns in them,
+// zones without a type take any creature.
+#[derive(Clone)]
+pub struct SpawnZone {
+    pub center: Vec3,
+    // along x and z
+    pub half_extents: Vec2,
+    pub enemy_type: Option<EnemyType>,
+}
+
+impl SpawnZone {
+    pub fn takes(&self, enemy_type: EnemyType) -> bool {
+        self.enemy_type.map_or(true, |wanted| wanted == enemy_type)
+    }
+
+    fn random_point(&self, rng: &mut impl Rng) -> Vec3 {
+        self.center
+            + Vec3::new(
+                rng.gen_range(-1.0..=1.0) * self.half_extents.x,
+                0.0,
+                rng.gen_range(-1.0..=1.0) * self.half_extents.y,
+            )
+    }
+}
+
+// Where a creature about to spawn was put down, it's spawned once the marker is done growing.
+// The wave isn't over while one is still on the floor.
+#[derive(Component)]
+pub struct SpawnTelegraph {
+    enemy_type: EnemyType,
+    position: Vec3,
+    timer: Timer,
+}
+
+#[derive(Resource)]
+pub(super) struct TelegraphMesh(Handle<Mesh>);
+
+pub(super) fn setup_telegraph_mesh(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(TelegraphMesh(meshes.add(Mesh::from(shape::Cylinder {
+        radius: TELEGRAPH_RADIUS,
+        height: 0.02,
+        resolution: 24,
+        segments: 1,
+    }))));
+}
+
+// The level's zones first, otherwise the generated floor or the level's markers, otherwise
+// anywhere in the arena. Every spot has to pass the rules, the result is on the floor.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn find_spawn_point(
+    rng: &mut impl Rng,
+    enemy_type: EnemyType,
+    rules: &SpawnRules,
+    zones: &[SpawnZone],
+    arena: Option<&ArenaLayout>,
+    level: &LevelMetadata,
+    players: &[Vec3],
+    rapier: &RapierContext,
+) -> Option<Vec3> {
+    let zones: Vec<&SpawnZone> = zones.iter().filter(|zone| zone.takes(enemy_type)).collect();
+
+    (0..rules.max_attempts).find_map(|_| {
+        let point = if !zones.is_empty() {
+            let point = zones[rng.gen_range(0..zones.len())].random_point(rng);
+            // a generated arena might have a pit or a rock right there
+            if !arena.map_or(true, |arena| arena.is_reachable(point)) {
+                return None;
+            }
+            point
+        } else if let Some(arena) = arena {
+            arena.spawn_point(rng, players, rules.min_player_distance)?
+        } else if let Some(point) = level.enemy_spawn_point(rng, enemy_type) {
+            point
+        } else {
+            Vec3::new(
+                (rng.gen::<f32>() - 0.5) * ARENA_DIMENSIONS_METERS[0] * 2.0,
+                0.0,
+                (rng.gen::<f32>() - 0.5) * ARENA_DIMENSIONS_METERS[1] * 2.0,
+            )
+        };
+
+        allowed(point, rules, players, rapier).then_some(point)
+    })
+}
+
+// Nothing passed the level's rules for a while, so the line of sight and the distance to the
+// hermits are dropped. If even that finds nothing the creature comes out in the middle of a zone
+// that takes it, or of the arena.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn relaxed_spawn_point(
+    rng: &mut impl Rng,
+    enemy_type: EnemyType,
+    rules: &SpawnRules,
+    zones: &[SpawnZone],
+    arena: Option<&ArenaLayout>,
+    level: &LevelMetadata,
+    players: &[Vec3],
+    rapier: &RapierContext,
+) -> Vec3 {
+    let relaxed = SpawnRules {
+        min_player_distance: 0.0,
+        line_of_sight: LineOfSight::Any,
+        ..rules.clone()
+    };
+    find_spawn_point(
+        rng, enemy_type, &relaxed, zones, arena, level, players, rapier,
+    )
+    .or_else(|| {
+        zones
+            .iter()
+            .find(|zone| zone.takes(enemy_type))
+            .map(|zone| zone.center)
+    })
+    .unwrap_or(Vec3::ZERO)
+}
+
+fn allowed(point: Vec3, rules: &SpawnRules, players: &[Vec3], rapier: &RapierContext) -> bool {
+    let far_enough = players.iter().all(|player| {
+        Vec2::new(player.x - point.x, player.z - point.z).length() >= rules.min_player_distance
+    });
+    if !far_enough {
+        return false;
+    }
+
+    // from the hermit to where the creature's middle will be
+    let seen = || {
+        players
+            .iter()
+            .any(|player| in_line_of_sight(rapier, *player, point + Vec3::Y))
+    };
+    match rules.line_of_sight {
+        LineOfSight::Any => true,
+        LineOfSight::Visible => players.is_empty() || seen(),
+        LineOfSight::Hidden => !seen(),
+    }
+}
+
+// only the level's terrain and walls are in the way, not creatures or other hermits
+fn in_line_of_sight(rapier: &RapierContext, from: Vec3, to: Vec3) -> bool {
+    let ray = to - from;
+    let filter = QueryFilter::new()
+        .exclude_sensors()
+        .groups(CollisionGroups::new(
+            COLLISION_GROUP_PLAYER,
+            COLLISION_GROUP_TERRAIN | COLLISION_GROUP_WALLS,
+        ));
+    rapier
+        .cast_ray(from, ray.normalize_or_zero(), ray.length(), true, filter)
+        .is_none()
+}
+
+pub(super) fn place_telegraph(
+    commands: &mut Commands,
+    materials: &mut Assets<StandardMaterial>,
+    mesh: &TelegraphMesh,
+    position: Vec3,
+    enemy_type: EnemyType,
+    rules: &SpawnRules,
+) {
+    commands.spawn((
+        PbrBundle {
+            mesh: mesh.0.clone(),
+            material: materials.add(StandardMaterial {
+                base_color: enemy_type.color().with_a(0.2),
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                ..default()
+            }),
+            transform: Transform::from_translation(position + Vec3::Y * TELEGRAPH_HEIGHT)
+                .with_scale(Vec3::splat(TELEGRAPH_START_SCALE)),
+            ..default()
+        },
+        SpawnTelegraph {
+            enemy_type,
+            position,
+            timer: Timer::from_seconds(rules.telegraph_secs, TimerMode::Once),
+        },
+    ));
+}
+
+// the marker grows and gets more solid, then makes way for the creature
+pub(super) fn grow_telegraphs(
+    mut commands: Commands,
+    mut telegraphs: Query<(
+        Entity,
+        &mut SpawnTelegraph,
+        &mut Transform,
+        &Handle<StandardMaterial>,
+    )>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    game_assets: Res<GameAssets>,
+    time: Res<Time>,
+) {
+    for (entity, mut telegraph, mut transform, material) in &mut telegraphs {
+        telegraph.timer.tick(time.delta());
+        let progress = telegraph.timer.percent();
+
+        if telegraph.timer.finished() {
+            commands.entity(entity).despawn_recursive();
+            commands.spawn(EnemyBundle::new(
+                telegraph.position + Vec3::Y,
+                &game_assets,
+                telegraph.enemy_type,
+            ));
+            continue;
+        }
+
+        transform.scale =
+            Vec3::splat(TELEGRAPH_START_SCALE + (1.0 - TELEGRAPH_START_SCALE) * progress);
+        if let Some(material) = materials.get_mut(material) {
+            material.base_color.set_a(0.2 + 0.5 * progress);
+        }
+    }
+}
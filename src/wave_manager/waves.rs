@@ -69,9 +69,123 @@ pub(crate) const DEFINED_WAVES: [WaveDefinition; 5] = [
     },
 ];
 
-pub fn wave_generation(wave_count: i32) -> WaveDefinition {
-    let mut rng = rand::thread_rng();
+// more walls, fewer holes: lots of urchins cornering you
+pub(crate) const CORAL_MAZE_WAVES: [WaveDefinition; 4] = [
+    WaveDefinition {
+        start_delay: 5.0,
+        spawn_rate: 0.5,
+
+        jellyfish_count: 4,
+        urchin_count: 6,
+        shrimp_count: 0,
+
+        luck: 1,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 5.0,
+        spawn_rate: 0.5,
+
+        jellyfish_count: 8,
+        urchin_count: 12,
+        shrimp_count: 1,
+
+        luck: 2,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 7.0,
+        spawn_rate: 0.75,
+
+        jellyfish_count: 10,
+        urchin_count: 20,
+        shrimp_count: 2,
+
+        luck: 0,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 7.0,
+        spawn_rate: 0.5,
+
+        jellyfish_count: 15,
+        urchin_count: 30,
+        shrimp_count: 4,
+
+        luck: 3,
+
+        drop_item_count: 10,
+    },
+];
+
+// dark and full of holes, the shrimps come out
+pub(crate) const TRENCH_WAVES: [WaveDefinition; 5] = [
+    WaveDefinition {
+        start_delay: 5.0,
+        spawn_rate: 0.75,
+
+        jellyfish_count: 6,
+        urchin_count: 2,
+        shrimp_count: 2,
+
+        luck: 2,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 5.0,
+        spawn_rate: 0.5,
+
+        jellyfish_count: 10,
+        urchin_count: 5,
+        shrimp_count: 4,
 
+        luck: 0,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 7.0,
+        spawn_rate: 0.5,
+
+        jellyfish_count: 15,
+        urchin_count: 10,
+        shrimp_count: 6,
+
+        luck: 3,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 7.0,
+        spawn_rate: 0.25,
+
+        jellyfish_count: 20,
+        urchin_count: 15,
+        shrimp_count: 8,
+
+        luck: 0,
+
+        drop_item_count: 10,
+    },
+    WaveDefinition {
+        start_delay: 7.0,
+        spawn_rate: 0.25,
+
+        jellyfish_count: 30,
+        urchin_count: 20,
+        shrimp_count: 12,
+
+        luck: 3,
+
+        drop_item_count: 10,
+    },
+];
+
+pub fn wave_generation(wave_count: i32, rng: &mut impl Rng) -> WaveDefinition {
     let mut total_enemy_count = wave_count * 4;
 
     let mut jellyfish = 0;
@@ -118,3 +232,35 @@ pub fn wave_generation(wave_count: i32) -> WaveDefinition {
         drop_item_count: 10,
     };
 }
+
+// Endless mode's waves keep growing and come in quicker, with more urchins and shrimps mixed in
+// as they go. The luck goes up for good every five waves.
+pub fn endless_wave(wave_count: i32, rng: &mut impl Rng) -> WaveDefinition {
+    let tough_share = (0.2 + wave_count as f32 * 0.04).min(0.6);
+
+    let mut jellyfish = 0;
+    let mut urchin = 0;
+    let mut shrimp = 0;
+    for _ in 0..6 + wave_count * 3 {
+        if rng.gen::<f32>() >= tough_share {
+            jellyfish += 1;
+        } else if rng.gen_bool(0.5) {
+            urchin += 1;
+        } else {
+            shrimp += 1;
+        }
+    }
+
+    WaveDefinition {
+        start_delay: 3.0,
+        spawn_rate: (1.0 - wave_count as f32 * 0.05).max(0.3),
+
+        jellyfish_count: jellyfish,
+        urchin_count: urchin,
+        shrimp_count: shrimp,
+
+        luck: 1 + wave_count / 5,
+
+        drop_item_count: 10,
+    }
+}
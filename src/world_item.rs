@@ -1,14 +1,11 @@
-use bevy::ecs::system::EntityCommands;
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::Collider;
 
 use crate::collectable::Collectable;
 use crate::game_state::GameState;
 use crate::inventory::ItemType::MELEE_WEAPON;
 use crate::inventory::{Inventory, InventoryItem};
 use crate::player::combat::PlayerCombatState;
-use crate::player::PlayerState;
-use crate::ui::health_bar::UIHeart;
+use crate::player::{PlayerControllerState, PlayerState};
 
 pub const VOXEL_SIZE_IN_WORLD: f32 = 0.2;
 
@@ -38,48 +35,16 @@ impl InventoryItem {
             ))
             .insert(PbrBundle {
                 mesh: meshes.add(self.generate_mesh(true)),
-                material: materials.add(self.color.clone().into()),
+                material: materials.add(self.material()),
                 ..default()
             })
             .insert(TransformBundle::from(
-                Transform::from_translation(location).with_scale(Vec3::splat(VOXEL_SIZE_IN_WORLD)),
+                Transform::from_translation(location)
+                    .with_rotation(self.orientation.quat())
+                    .with_scale(Vec3::splat(VOXEL_SIZE_IN_WORLD)),
             ))
             .id();
     }
-
-    pub fn create_ui_entity(
-        &self,
-        transform: Transform,
-        on_player: bool,
-        collectable: bool,
-        ui_heart: bool,
-        commands: &mut Commands,
-        meshes: &mut ResMut<Assets<Mesh>>,
-        materials: &mut ResMut<Assets<StandardMaterial>>,
-        collider: Option<Collider>,
-    ) -> Entity {
-        let mut e_commands = commands.spawn((
-            AttachedToPlayer(on_player),
-            Collectable(collectable),
-            self.clone(),
-        ));
-        e_commands.insert(PbrBundle {
-            mesh: meshes.add(self.generate_mesh(true)),
-            material: materials.add(self.color.clone().into()),
-            transform,
-            ..default()
-        });
-
-        if let Some(collider) = collider {
-            e_commands.insert(collider);
-        }
-
-        if ui_heart {
-            e_commands.insert(UIHeart);
-        }
-
-        return e_commands.id();
-    }
 }
 
 pub struct ItemAttachmentPlugin;
@@ -106,16 +71,15 @@ pub fn item_attachment_update(
     time: Res<Time>,
 ) {
     let binding = param_set.p0();
-    let player_transform = binding.single().0.clone();
-    let entity = binding.single().1.current_weapon.clone().map(|x| x.0);
-    let current_weapon = binding
-        .single()
-        .1
-        .current_weapon
-        .clone()
-        .map(|x| x.1)
-        .clone();
-    let state = binding.single().2.clone();
+    let holders: Vec<(Transform, Entity, InventoryItem, PlayerCombatState)> = binding
+        .iter()
+        .filter_map(|(transform, holder, state)| {
+            holder
+                .current_weapon
+                .clone()
+                .map(|(entity, weapon)| (transform.clone(), entity, weapon, state.clone()))
+        })
+        .collect();
     drop(binding);
     let mut query = param_set.p1();
     for mut item in query.iter_mut() {
@@ -123,20 +87,25 @@ pub fn item_attachment_update(
             // dbg!("not attached?");
             continue;
         }
-        if entity != Some(item.0) {
-            // dbg!("get fked", entity, item.0);
+
+        let Some((player_transform, _, current_weapon, state)) =
+            holders.iter().find(|holder| holder.1 == item.0)
+        else {
+            // dbg!("get fked", item.0);
             commands.entity(item.0).despawn();
             continue;
-        }
+        };
 
         // dbg!("ok wtf");
 
         item.1.translation = player_transform.translation + player_transform.forward() * 0.5;
         item.1.rotation = player_transform.rotation;
         item.1.rotate_y(180.0f32.to_radians());
-        if current_weapon.clone().unwrap().item_type == MELEE_WEAPON {
+        if current_weapon.item_type == MELEE_WEAPON {
             item.1.rotate_y(state.get_weapon_angle(&time));
         }
+        // held the way it was packed
+        item.1.rotation *= current_weapon.orientation.quat();
     }
 }
 
@@ -144,41 +113,64 @@ fn equip_update(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut player_query: Query<(&mut WeaponHolder, &Transform, &mut PlayerCombatState)>,
+    mut player_query: Query<(
+        &mut WeaponHolder,
+        &Transform,
+        &mut PlayerCombatState,
+        &PlayerControllerState,
+    )>,
     inventory: Res<Inventory>,
 ) {
-    let (mut player_weapon, player_transform, mut combat_state) = player_query.single_mut();
-
-    combat_state.compute_from_inventory(&inventory);
-
-    if !player_weapon.current_weapon.is_none() {
-        let player_weapon_id = player_weapon.current_weapon.clone().unwrap().1.item_type_id;
-
-        let mut found_item = false;
+    for (mut player_weapon, player_transform, mut combat_state, controller) in &mut player_query {
+        refresh_held_weapon(
+            inventory.items_of(controller.player_index),
+            &mut player_weapon,
+            player_transform,
+            &mut combat_state,
+            &mut commands,
+            &mut meshes,
+            &mut materials,
+        );
+    }
+}
 
-        for item in &inventory.content {
-            if item.item_type_id == player_weapon_id {
-                found_item = true;
-                break;
+// puts what's in a hermit's shell in their hand, after packing or selling to the merchant
+pub fn refresh_held_weapon(
+    items: &[InventoryItem],
+    player_weapon: &mut WeaponHolder,
+    player_transform: &Transform,
+    combat_state: &mut PlayerCombatState,
+    commands: &mut Commands,
+    meshes: &mut ResMut<Assets<Mesh>>,
+    materials: &mut ResMut<Assets<StandardMaterial>>,
+) {
+    combat_state.compute_from_inventory(items);
+
+    // A merge, a turn or a move in the shell changes the weapon, so the held copy is swapped for
+    // the packed one. Without any of its kind left the hand is emptied.
+    let mut repacked = None;
+    if let Some((_, held)) = &player_weapon.current_weapon {
+        if !items.iter().any(|item| item.is_same_item(held)) {
+            repacked = items
+                .iter()
+                .find(|item| item.item_type_id == held.item_type_id);
+            if repacked.is_none() {
+                dbg!("Deleting current weapon because no longer in inventory");
             }
-        }
-
-        if !found_item {
-            dbg!("Deleting current weapon because no longer in inventory");
             player_weapon.current_weapon = None;
         }
     }
 
-    let item = inventory.first_weapon();
+    let item = repacked.or_else(|| items.iter().find(|i| i.item_type.is_weapon()));
     if player_weapon.current_weapon.is_none() && item != None {
         let item = item.unwrap();
         let entity = item.create_world_entity(
             player_transform.translation,
             true,
             false,
-            &mut commands,
-            &mut meshes,
-            &mut materials,
+            commands,
+            meshes,
+            materials,
         );
 
         dbg!("adding current weapon!");